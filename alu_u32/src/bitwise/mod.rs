@@ -6,7 +6,9 @@ use columns::{Bitwise32Cols, COL_MAP, NUM_BITWISE_COLS};
 use core::mem::transmute;
 use valida_bus::MachineWithGeneralBus;
 use valida_cpu::MachineWithCpuChip;
-use valida_machine::{instructions, Chip, Instruction, Interaction, Operands, Word};
+use valida_machine::{
+    instructions, Chip, CountBound, Instruction, Interaction, InteractionAir, Operands, Word,
+};
 use valida_opcodes::{AND32, OR32, XOR32};
 
 use p3_air::VirtualPairCol;
@@ -31,28 +33,11 @@ pub struct Bitwise32Chip {
     pub operations: Vec<Operation>,
 }
 
-impl<M, SC> Chip<M, SC> for Bitwise32Chip
+impl<M, SC> InteractionAir<M, SC> for Bitwise32Chip
 where
     M: MachineWithGeneralBus<SC::Val>,
     SC: StarkConfig,
 {
-    fn generate_trace(&self, _machine: &M) -> RowMajorMatrix<SC::Val> {
-        let rows = self
-            .operations
-            .par_iter()
-            .map(|op| self.op_to_row(op))
-            .collect::<Vec<_>>();
-
-        let mut trace = RowMajorMatrix::new(
-            rows.into_iter().flatten().collect::<Vec<_>>(),
-            NUM_BITWISE_COLS,
-        );
-
-        pad_to_power_of_two::<NUM_BITWISE_COLS, SC::Val>(&mut trace.values);
-
-        trace
-    }
-
     fn global_receives(&self, machine: &M) -> Vec<Interaction<SC::Val>> {
         let opcode = VirtualPairCol::new_main(
             vec![
@@ -77,11 +62,38 @@ where
             fields,
             count: is_real,
             argument_index: machine.general_bus(),
+            rotations: vec![],
+            count_bound: CountBound::Unbounded,
+            is_real: None,
+            limb_order: None,
         };
         vec![receive]
     }
 }
 
+impl<M, SC> Chip<M, SC> for Bitwise32Chip
+where
+    M: MachineWithGeneralBus<SC::Val>,
+    SC: StarkConfig,
+{
+    fn generate_trace(&self, _machine: &M) -> RowMajorMatrix<SC::Val> {
+        let rows = self
+            .operations
+            .par_iter()
+            .map(|op| self.op_to_row(op))
+            .collect::<Vec<_>>();
+
+        let mut trace = RowMajorMatrix::new(
+            rows.into_iter().flatten().collect::<Vec<_>>(),
+            NUM_BITWISE_COLS,
+        );
+
+        pad_to_power_of_two::<NUM_BITWISE_COLS, SC::Val>(&mut trace.values);
+
+        trace
+    }
+}
+
 impl Bitwise32Chip {
     fn op_to_row<F>(&self, op: &Operation) -> [F; NUM_BITWISE_COLS]
     where
@@ -9,7 +9,8 @@ use valida_bus::MachineWithGeneralBus;
 use valida_cpu::MachineWithCpuChip;
 use valida_machine::StarkConfig;
 use valida_machine::{
-    instructions, Chip, Instruction, Interaction, Operands, Word, MEMORY_CELL_BYTES,
+    instructions, Chip, CountBound, Instruction, Interaction, InteractionAir, Operands, Word,
+    MEMORY_CELL_BYTES,
 };
 use valida_opcodes::{EQ32, NE32};
 
@@ -33,26 +34,11 @@ pub struct Com32Chip {
     pub operations: Vec<Operation>,
 }
 
-impl<M, SC> Chip<M, SC> for Com32Chip
+impl<M, SC> InteractionAir<M, SC> for Com32Chip
 where
     M: MachineWithGeneralBus<SC::Val>,
     SC: StarkConfig,
 {
-    fn generate_trace(&self, _machine: &M) -> RowMajorMatrix<SC::Val> {
-        let rows = self
-            .operations
-            .par_iter()
-            .map(|op| self.op_to_row(op))
-            .collect::<Vec<_>>();
-
-        let mut trace =
-            RowMajorMatrix::new(rows.into_iter().flatten().collect::<Vec<_>>(), NUM_COM_COLS);
-
-        pad_to_power_of_two::<NUM_COM_COLS, SC::Val>(&mut trace.values);
-
-        trace
-    }
-
     fn global_receives(&self, machine: &M) -> Vec<Interaction<SC::Val>> {
         let opcode = VirtualPairCol::new_main(
             vec![
@@ -78,11 +64,36 @@ where
             fields,
             count: is_real,
             argument_index: machine.general_bus(),
+            rotations: vec![],
+            count_bound: CountBound::Unbounded,
+            is_real: None,
+            limb_order: None,
         };
         vec![receive]
     }
 }
 
+impl<M, SC> Chip<M, SC> for Com32Chip
+where
+    M: MachineWithGeneralBus<SC::Val>,
+    SC: StarkConfig,
+{
+    fn generate_trace(&self, _machine: &M) -> RowMajorMatrix<SC::Val> {
+        let rows = self
+            .operations
+            .par_iter()
+            .map(|op| self.op_to_row(op))
+            .collect::<Vec<_>>();
+
+        let mut trace =
+            RowMajorMatrix::new(rows.into_iter().flatten().collect::<Vec<_>>(), NUM_COM_COLS);
+
+        pad_to_power_of_two::<NUM_COM_COLS, SC::Val>(&mut trace.values);
+
+        trace
+    }
+}
+
 impl Com32Chip {
     fn op_to_row<F>(&self, op: &Operation) -> [F; NUM_COM_COLS]
     where
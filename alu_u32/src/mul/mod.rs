@@ -5,7 +5,10 @@ use alloc::vec::Vec;
 use columns::{Mul32Cols, MUL_COL_MAP, NUM_MUL_COLS};
 use valida_bus::MachineWithGeneralBus;
 use valida_cpu::MachineWithCpuChip;
-use valida_machine::{instructions, Chip, Instruction, Interaction, Mulhs, Mulhu, Operands, Word};
+use valida_machine::{
+    instructions, Chip, CountBound, Instruction, Interaction, InteractionAir, Mulhs, Mulhu,
+    Operands, Word,
+};
 use valida_opcodes::{MUL32, MULHS32, MULHU32};
 use valida_range::MachineWithRangeChip;
 
@@ -30,39 +33,11 @@ pub struct Mul32Chip {
     pub operations: Vec<Operation>,
 }
 
-impl<M, SC> Chip<M, SC> for Mul32Chip
+impl<M, SC> InteractionAir<M, SC> for Mul32Chip
 where
     M: MachineWithGeneralBus<SC::Val>,
     SC: StarkConfig,
 {
-    fn generate_trace(&self, _machine: &M) -> RowMajorMatrix<SC::Val> {
-        const MIN_LENGTH: usize = 1 << 10; // for the range check counter
-
-        let num_ops = self.operations.len();
-        let num_padded_ops = num_ops.next_power_of_two().max(MIN_LENGTH);
-        let mut values = vec![SC::Val::zero(); num_padded_ops * NUM_MUL_COLS];
-
-        // Encode the real operations.
-        for (i, op) in self.operations.iter().enumerate() {
-            let row = &mut values[i * NUM_MUL_COLS..(i + 1) * NUM_MUL_COLS];
-            let cols: &mut Mul32Cols<SC::Val> = row.borrow_mut();
-            cols.counter = SC::Val::from_canonical_usize(i + 1);
-            self.op_to_row(op, cols);
-        }
-
-        // Encode dummy operations as needed to pad the trace.
-        for i in num_ops..num_padded_ops {
-            let row = &mut values[i * NUM_MUL_COLS..(i + 1) * NUM_MUL_COLS];
-            let cols: &mut Mul32Cols<SC::Val> = row.borrow_mut();
-            cols.counter = SC::Val::from_canonical_usize(i + 1);
-        }
-
-        RowMajorMatrix {
-            values,
-            width: NUM_MUL_COLS,
-        }
-    }
-
     fn global_receives(&self, machine: &M) -> Vec<Interaction<SC::Val>> {
         let opcode = VirtualPairCol::new_main(
             vec![
@@ -91,6 +66,10 @@ where
             fields,
             count: is_real,
             argument_index: machine.general_bus(),
+            rotations: vec![],
+            count_bound: CountBound::Unbounded,
+            is_real: None,
+            limb_order: None,
         };
         vec![receive]
     }
@@ -101,6 +80,40 @@ where
     }
 }
 
+impl<M, SC> Chip<M, SC> for Mul32Chip
+where
+    M: MachineWithGeneralBus<SC::Val>,
+    SC: StarkConfig,
+{
+    fn generate_trace(&self, _machine: &M) -> RowMajorMatrix<SC::Val> {
+        const MIN_LENGTH: usize = 1 << 10; // for the range check counter
+
+        let num_ops = self.operations.len();
+        let num_padded_ops = num_ops.next_power_of_two().max(MIN_LENGTH);
+        let mut values = vec![SC::Val::zero(); num_padded_ops * NUM_MUL_COLS];
+
+        // Encode the real operations.
+        for (i, op) in self.operations.iter().enumerate() {
+            let row = &mut values[i * NUM_MUL_COLS..(i + 1) * NUM_MUL_COLS];
+            let cols: &mut Mul32Cols<SC::Val> = row.borrow_mut();
+            cols.counter = SC::Val::from_canonical_usize(i + 1);
+            self.op_to_row(op, cols);
+        }
+
+        // Encode dummy operations as needed to pad the trace.
+        for i in num_ops..num_padded_ops {
+            let row = &mut values[i * NUM_MUL_COLS..(i + 1) * NUM_MUL_COLS];
+            let cols: &mut Mul32Cols<SC::Val> = row.borrow_mut();
+            cols.counter = SC::Val::from_canonical_usize(i + 1);
+        }
+
+        RowMajorMatrix {
+            values,
+            width: NUM_MUL_COLS,
+        }
+    }
+}
+
 impl Mul32Chip {
     fn op_to_row<F>(&self, op: &Operation, cols: &mut Mul32Cols<F>)
     where
@@ -8,7 +8,9 @@ use columns::{Shift32Cols, COL_MAP, NUM_SHIFT_COLS};
 use core::mem::transmute;
 use valida_bus::{MachineWithGeneralBus, MachineWithRangeBus8};
 use valida_cpu::MachineWithCpuChip;
-use valida_machine::{instructions, Chip, Instruction, Interaction, Operands, Sra, Word};
+use valida_machine::{
+    instructions, Chip, CountBound, Instruction, Interaction, InteractionAir, Operands, Sra, Word,
+};
 use valida_opcodes::{DIV32, MUL32, SDIV32, SHL32, SHR32, SRA32};
 
 use p3_air::VirtualPairCol;
@@ -33,28 +35,11 @@ pub struct Shift32Chip {
     pub operations: Vec<Operation>,
 }
 
-impl<M, SC> Chip<M, SC> for Shift32Chip
+impl<M, SC> InteractionAir<M, SC> for Shift32Chip
 where
     M: MachineWithGeneralBus<SC::Val> + MachineWithRangeBus8<SC::Val>,
     SC: StarkConfig,
 {
-    fn generate_trace(&self, _machine: &M) -> RowMajorMatrix<SC::Val> {
-        let rows = self
-            .operations
-            .par_iter()
-            .map(|op| self.op_to_row(op))
-            .collect::<Vec<_>>();
-
-        let mut trace = RowMajorMatrix::new(
-            rows.into_iter().flatten().collect::<Vec<_>>(),
-            NUM_SHIFT_COLS,
-        );
-
-        pad_to_power_of_two::<NUM_SHIFT_COLS, SC::Val>(&mut trace.values);
-
-        trace
-    }
-
     fn global_sends(&self, machine: &M) -> Vec<Interaction<SC::Val>> {
         let opcode = VirtualPairCol::new_main(
             vec![
@@ -80,6 +65,10 @@ where
             fields,
             count: is_real,
             argument_index: machine.general_bus(),
+            rotations: vec![],
+            count_bound: CountBound::Unbounded,
+            is_real: None,
+            limb_order: None,
         };
 
         vec![send]
@@ -110,11 +99,38 @@ where
             fields,
             count: is_real,
             argument_index: machine.general_bus(),
+            rotations: vec![],
+            count_bound: CountBound::Unbounded,
+            is_real: None,
+            limb_order: None,
         };
         vec![receive]
     }
 }
 
+impl<M, SC> Chip<M, SC> for Shift32Chip
+where
+    M: MachineWithGeneralBus<SC::Val> + MachineWithRangeBus8<SC::Val>,
+    SC: StarkConfig,
+{
+    fn generate_trace(&self, _machine: &M) -> RowMajorMatrix<SC::Val> {
+        let rows = self
+            .operations
+            .par_iter()
+            .map(|op| self.op_to_row(op))
+            .collect::<Vec<_>>();
+
+        let mut trace = RowMajorMatrix::new(
+            rows.into_iter().flatten().collect::<Vec<_>>(),
+            NUM_SHIFT_COLS,
+        );
+
+        pad_to_power_of_two::<NUM_SHIFT_COLS, SC::Val>(&mut trace.values);
+
+        trace
+    }
+}
+
 impl Shift32Chip {
     fn op_to_row<F>(&self, op: &Operation) -> [F; NUM_SHIFT_COLS]
     where
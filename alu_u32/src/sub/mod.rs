@@ -6,7 +6,9 @@ use columns::{Sub32Cols, NUM_SUB_COLS, SUB_COL_MAP};
 use core::mem::transmute;
 use valida_bus::{MachineWithGeneralBus, MachineWithRangeBus8};
 use valida_cpu::MachineWithCpuChip;
-use valida_machine::{instructions, Chip, Instruction, Interaction, Operands, Word};
+use valida_machine::{
+    instructions, Chip, CountBound, Instruction, Interaction, InteractionAir, Operands, Word,
+};
 use valida_opcodes::SUB32;
 use valida_range::MachineWithRangeChip;
 
@@ -30,37 +32,21 @@ pub struct Sub32Chip {
     pub operations: Vec<Operation>,
 }
 
-impl<M, SC> Chip<M, SC> for Sub32Chip
+impl<M, SC> InteractionAir<M, SC> for Sub32Chip
 where
     M: MachineWithGeneralBus<SC::Val> + MachineWithRangeBus8<SC::Val>,
     SC: StarkConfig,
 {
-    fn generate_trace(&self, _machine: &M) -> RowMajorMatrix<SC::Val> {
-        let rows = self
-            .operations
-            .par_iter()
-            .map(|op| self.op_to_row(op))
-            .collect::<Vec<_>>();
-
-        let mut trace =
-            RowMajorMatrix::new(rows.into_iter().flatten().collect::<Vec<_>>(), NUM_SUB_COLS);
-
-        pad_to_power_of_two::<NUM_SUB_COLS, SC::Val>(&mut trace.values);
-
-        trace
-    }
-
     fn global_sends(&self, machine: &M) -> Vec<Interaction<SC::Val>> {
         let sends = SUB_COL_MAP
             .output
             .0
             .map(|field| {
-                let output = VirtualPairCol::single_main(field);
-                Interaction {
-                    fields: vec![output],
-                    count: VirtualPairCol::single_main(SUB_COL_MAP.is_real),
-                    argument_index: machine.range_bus(),
-                }
+                Interaction::range_check(
+                    machine.range_bus(),
+                    field,
+                    VirtualPairCol::single_main(SUB_COL_MAP.is_real),
+                )
             })
             .into_iter()
             .collect::<Vec<_>>();
@@ -82,11 +68,36 @@ where
             fields,
             count: VirtualPairCol::single_main(SUB_COL_MAP.is_real),
             argument_index: machine.general_bus(),
+            rotations: vec![],
+            count_bound: CountBound::Unbounded,
+            is_real: None,
+            limb_order: None,
         };
         vec![receive]
     }
 }
 
+impl<M, SC> Chip<M, SC> for Sub32Chip
+where
+    M: MachineWithGeneralBus<SC::Val> + MachineWithRangeBus8<SC::Val>,
+    SC: StarkConfig,
+{
+    fn generate_trace(&self, _machine: &M) -> RowMajorMatrix<SC::Val> {
+        let rows = self
+            .operations
+            .par_iter()
+            .map(|op| self.op_to_row(op))
+            .collect::<Vec<_>>();
+
+        let mut trace =
+            RowMajorMatrix::new(rows.into_iter().flatten().collect::<Vec<_>>(), NUM_SUB_COLS);
+
+        pad_to_power_of_two::<NUM_SUB_COLS, SC::Val>(&mut trace.values);
+
+        trace
+    }
+}
+
 impl Sub32Chip {
     fn op_to_row<F>(&self, op: &Operation) -> [F; NUM_SUB_COLS]
     where
@@ -0,0 +1,187 @@
+//! Permutation trace generation over long traces. Run with `--features parallel` to compare the
+//! rayon path of `generate_permutation_trace` with the serial one.
+//!
+//! `beta powers` compares reducing rows of a 16 field interaction with the powers of beta computed
+//! once, as `generate_permutation_trace` does, against rederiving them on every row.
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use p3_air::{Air, AirBuilder, BaseAir, VirtualPairCol};
+use p3_baby_bear::BabyBear;
+use p3_challenger::DuplexChallenger;
+use p3_dft::Radix2Bowers;
+use p3_field::extension::BinomialExtensionField;
+use p3_field::{AbstractField, Field};
+use p3_matrix::dense::RowMajorMatrix;
+use p3_matrix::{Matrix, MatrixRowSlices};
+use p3_mds::coset_mds::CosetMds;
+use p3_poseidon::Poseidon;
+use valida_machine::test_util::MockMachine;
+use valida_machine::{
+    generate_permutation_trace, BusArgument, Chip, Interaction, InteractionAir, MockPcs,
+    StarkConfig, StarkConfigImpl,
+};
+
+type Val = BabyBear;
+type Challenge = BinomialExtensionField<Val, 5>;
+type PackedChallenge = BinomialExtensionField<<Val as Field>::Packing, 5>;
+type Mds16 = CosetMds<Val, 16>;
+type Perm16 = Poseidon<Val, Mds16, 16, 5>;
+type Challenger = DuplexChallenger<Val, Perm16, 16>;
+type MyConfig =
+    StarkConfigImpl<Val, Challenge, PackedChallenge, MockPcs<Val, Radix2Bowers>, Challenger>;
+
+const LOG_HEIGHT: usize = 20;
+const WIDE_LOG_HEIGHT: usize = 16;
+const WIDE_FIELDS: usize = 16;
+
+/// Columns `a, b`: sends `a` and receives `b` on a local bus, with `b = 3 a` modulo the height so
+/// that the bus balances.
+struct SendReceiveChip;
+
+impl<F> BaseAir<F> for SendReceiveChip {
+    fn width(&self) -> usize {
+        2
+    }
+}
+
+impl<AB: AirBuilder> Air<AB> for SendReceiveChip {
+    fn eval(&self, _builder: &mut AB) {}
+}
+
+impl<SC: StarkConfig> InteractionAir<MockMachine<SC::Val>, SC> for SendReceiveChip {
+    fn local_sends(&self) -> Vec<Interaction<SC::Val>> {
+        vec![Interaction::send_columns(
+            BusArgument::Local(0),
+            &[0],
+            VirtualPairCol::one(),
+        )]
+    }
+
+    fn local_receives(&self) -> Vec<Interaction<SC::Val>> {
+        vec![Interaction::receive_always(BusArgument::Local(0), &[1])]
+    }
+}
+
+impl<SC: StarkConfig> Chip<MockMachine<SC::Val>, SC> for SendReceiveChip {
+    fn generate_trace(&self, _machine: &MockMachine<SC::Val>) -> RowMajorMatrix<SC::Val> {
+        let height = 1 << LOG_HEIGHT;
+        let values = (0..height)
+            .flat_map(|a| [a, 3 * a % height].map(SC::Val::from_canonical_usize))
+            .collect();
+        RowMajorMatrix::new(values, 2)
+    }
+}
+
+/// `WIDE_FIELDS` columns sent as one interaction on a local bus and received as another, so that
+/// every row reduces two interactions of `WIDE_FIELDS` fields.
+struct WideChip;
+
+impl<F> BaseAir<F> for WideChip {
+    fn width(&self) -> usize {
+        WIDE_FIELDS
+    }
+}
+
+impl<AB: AirBuilder> Air<AB> for WideChip {
+    fn eval(&self, _builder: &mut AB) {}
+}
+
+impl<SC: StarkConfig> InteractionAir<MockMachine<SC::Val>, SC> for WideChip {
+    fn local_sends(&self) -> Vec<Interaction<SC::Val>> {
+        let cols = (0..WIDE_FIELDS).collect::<Vec<_>>();
+        vec![Interaction::send_columns(
+            BusArgument::Local(0),
+            &cols,
+            VirtualPairCol::one(),
+        )]
+    }
+
+    fn local_receives(&self) -> Vec<Interaction<SC::Val>> {
+        let cols = (0..WIDE_FIELDS).collect::<Vec<_>>();
+        vec![Interaction::receive_always(BusArgument::Local(0), &cols)]
+    }
+}
+
+impl<SC: StarkConfig> Chip<MockMachine<SC::Val>, SC> for WideChip {
+    fn generate_trace(&self, _machine: &MockMachine<SC::Val>) -> RowMajorMatrix<SC::Val> {
+        let values = (0..(1 << WIDE_LOG_HEIGHT) * WIDE_FIELDS)
+            .map(SC::Val::from_canonical_usize)
+            .collect();
+        RowMajorMatrix::new(values, WIDE_FIELDS)
+    }
+}
+
+fn random_elements() -> Vec<Challenge> {
+    [3, 5, 7].map(Challenge::from_canonical_u32).to_vec()
+}
+
+fn permutation_trace(c: &mut Criterion) {
+    let machine = MockMachine::default();
+    let chip = SendReceiveChip;
+    let main = Chip::<_, MyConfig>::generate_trace(&chip, &machine);
+
+    let mut group = c.benchmark_group("permutation trace");
+    group.sample_size(10);
+    group.bench_function("2^20 rows", |b| {
+        b.iter_batched(
+            random_elements,
+            |random_elements| {
+                generate_permutation_trace::<_, MyConfig>(&machine, &chip, &main, random_elements)
+            },
+            BatchSize::SmallInput,
+        )
+    });
+    group.finish();
+}
+
+fn beta_powers(c: &mut Criterion) {
+    let machine = MockMachine::default();
+    let main = Chip::<_, MyConfig>::generate_trace(&WideChip, &machine);
+    let [alpha, _, beta] = random_elements().try_into().unwrap();
+
+    let mut group = c.benchmark_group("beta powers");
+    group.sample_size(10);
+    group.bench_function("precomputed", |b| {
+        b.iter(|| {
+            let betas = beta.powers().take(WIDE_FIELDS).collect::<Vec<_>>();
+            (0..main.height())
+                .map(|n| {
+                    let row = main.row_slice(n);
+                    row.iter()
+                        .zip(&betas)
+                        .fold(alpha, |rlc, (&f, &beta)| rlc + beta * f)
+                })
+                .sum::<Challenge>()
+        })
+    });
+    group.bench_function("rederived per row", |b| {
+        b.iter(|| {
+            (0..main.height())
+                .map(|n| {
+                    let row = main.row_slice(n);
+                    row.iter()
+                        .zip(beta.powers())
+                        .fold(alpha, |rlc, (&f, beta)| rlc + beta * f)
+                })
+                .sum::<Challenge>()
+        })
+    });
+    group.bench_function("permutation trace", |b| {
+        b.iter_batched(
+            random_elements,
+            |random_elements| {
+                generate_permutation_trace::<_, MyConfig>(
+                    &machine,
+                    &WideChip,
+                    &main,
+                    random_elements,
+                )
+            },
+            BatchSize::SmallInput,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(benches, permutation_trace, beta_powers);
+criterion_main!(benches);
@@ -42,13 +42,12 @@ use valida_cpu::{
 };
 use valida_cpu::{CpuChip, MachineWithCpuChip};
 use valida_machine::__internal::p3_challenger::{CanObserve, FieldChallenger};
-use valida_machine::__internal::{
-    check_constraints, check_cumulative_sums, get_log_quotient_degree, quotient,
-};
+use valida_machine::__internal::{check_constraints, get_chip_log_quotient_degree, quotient};
 use valida_machine::{
-    generate_permutation_trace, verify_constraints, AdviceProvider, BusArgument, Chip, ChipProof,
-    Commitments, Instruction, Machine, MachineProof, OpenedValues, ProgramROM, StoppingFlag,
-    ValidaAirBuilder,
+    cumulative_sum_from_perm, num_permutation_challenges, permutation_challenge_field,
+    permutation_trace_width, sign_conventions, verify_aggregate_cumulative_sum, verify_constraints,
+    AdviceProvider, BusArgument, Chip, ChipProof, Commitments, Instruction, Machine, MachineProof,
+    OpenedValues, ProgramROM, StoppingFlag, ValidaAirBuilder,
 };
 use valida_memory::{MachineWithMemoryChip, MemoryChip};
 use valida_output::{MachineWithOutputChip, OutputChip, WriteInstruction};
@@ -163,20 +162,20 @@ impl<F: PrimeField32 + TwoAdicField> Machine<F> for BasicMachine<F> {
         ];
 
         let log_quotient_degrees: [usize; NUM_CHIPS] = [
-            get_log_quotient_degree::<Self, SC, _>(self, self.cpu()),
-            get_log_quotient_degree::<Self, SC, _>(self, self.program()),
-            get_log_quotient_degree::<Self, SC, _>(self, self.mem()),
-            get_log_quotient_degree::<Self, SC, _>(self, self.add_u32()),
-            get_log_quotient_degree::<Self, SC, _>(self, self.sub_u32()),
-            get_log_quotient_degree::<Self, SC, _>(self, self.mul_u32()),
-            get_log_quotient_degree::<Self, SC, _>(self, self.div_u32()),
-            get_log_quotient_degree::<Self, SC, _>(self, self.shift_u32()),
-            get_log_quotient_degree::<Self, SC, _>(self, self.lt_u32()),
-            get_log_quotient_degree::<Self, SC, _>(self, self.com_u32()),
-            get_log_quotient_degree::<Self, SC, _>(self, self.bitwise_u32()),
-            get_log_quotient_degree::<Self, SC, _>(self, self.output()),
-            get_log_quotient_degree::<Self, SC, _>(self, self.range()),
-            get_log_quotient_degree::<Self, SC, _>(self, self.static_data()),
+            get_chip_log_quotient_degree::<Self, SC, _>(self, self.cpu()),
+            get_chip_log_quotient_degree::<Self, SC, _>(self, self.program()),
+            get_chip_log_quotient_degree::<Self, SC, _>(self, self.mem()),
+            get_chip_log_quotient_degree::<Self, SC, _>(self, self.add_u32()),
+            get_chip_log_quotient_degree::<Self, SC, _>(self, self.sub_u32()),
+            get_chip_log_quotient_degree::<Self, SC, _>(self, self.mul_u32()),
+            get_chip_log_quotient_degree::<Self, SC, _>(self, self.div_u32()),
+            get_chip_log_quotient_degree::<Self, SC, _>(self, self.shift_u32()),
+            get_chip_log_quotient_degree::<Self, SC, _>(self, self.lt_u32()),
+            get_chip_log_quotient_degree::<Self, SC, _>(self, self.com_u32()),
+            get_chip_log_quotient_degree::<Self, SC, _>(self, self.bitwise_u32()),
+            get_chip_log_quotient_degree::<Self, SC, _>(self, self.output()),
+            get_chip_log_quotient_degree::<Self, SC, _>(self, self.range()),
+            get_chip_log_quotient_degree::<Self, SC, _>(self, self.static_data()),
         ];
 
         let mut challenger = config.challenger();
@@ -201,7 +200,11 @@ impl<F: PrimeField32 + TwoAdicField> Machine<F> for BasicMachine<F> {
             tracing::info_span!("generate main trace").in_scope(|| {
                 chips
                     .par_iter()
-                    .map(|chip| chip.generate_trace(self))
+                    .map(|chip| {
+                        let mut trace = chip.generate_main_trace(self);
+                        chip.pad_trace(self, &mut trace);
+                        trace
+                    })
                     .collect::<Vec<_>>()
                     .try_into()
                     .unwrap()
@@ -218,33 +221,22 @@ impl<F: PrimeField32 + TwoAdicField> Machine<F> for BasicMachine<F> {
 
         let (main_commit, main_data) = tracing::info_span!("commit to main traces")
             .in_scope(|| pcs.commit_batches(main_traces.to_vec()));
-        challenger.observe(main_commit.clone());
         let mut main_trace_ldes = pcs.get_ldes(&main_data);
 
-        let mut perm_challenges = Vec::new();
-        for _ in 0..3 {
-            perm_challenges.push(challenger.sample_ext_element());
-        }
-
-        let perm_traces = tracing::info_span!("generate permutation traces").in_scope(|| {
-            chips
-                .into_par_iter()
-                .enumerate()
-                .map(|(i, chip)| {
-                    generate_permutation_trace(
-                        self,
-                        *chip,
-                        &main_traces[i],
-                        perm_challenges.clone(),
-                    )
-                })
-                .collect::<Vec<_>>()
-        });
+        let perm_challenges: Vec<SC::Challenge> = self.derive_permutation_challenges(
+            &mut challenger,
+            &[main_commit.clone()],
+            num_permutation_challenges(self, chips.iter().map(|chip| **chip)),
+            permutation_challenge_field(chips.iter().map(|chip| **chip)),
+        );
 
-        let cumulative_sums = perm_traces
-            .iter()
-            .map(|trace| trace.row_slice(trace.height() - 1).last().unwrap().clone())
-            .collect::<Vec<_>>();
+        let (perm_traces, cumulative_sums): (Vec<_>, Vec<_>) =
+            tracing::info_span!("generate permutation traces").in_scope(|| {
+                let chips = chips.iter().map(|chip| **chip).collect::<Vec<_>>();
+                self.generate_all_permutation_traces(&chips, &main_traces, &perm_challenges)
+                    .into_iter()
+                    .unzip()
+            });
 
         let (perm_commit, perm_data) = tracing::info_span!("commit to permutation traces")
             .in_scope(|| {
@@ -597,9 +589,6 @@ impl<F: PrimeField32 + TwoAdicField> Machine<F> for BasicMachine<F> {
 
         challenger.observe(quotient_commit.clone());
 
-        #[cfg(debug_assertions)]
-        check_cumulative_sums(&perm_traces[..]);
-
         let zeta: SC::Challenge = challenger.sample_ext_element();
         let zeta_and_next: [Vec<SC::Challenge>; NUM_CHIPS] =
             g_subgroups.map(|g| vec![zeta, zeta * g]);
@@ -651,11 +640,7 @@ impl<F: PrimeField32 + TwoAdicField> Machine<F> for BasicMachine<F> {
                     quotient_chunks,
                 };
 
-                let cumulative_sum = perm_trace
-                    .row_slice(perm_trace.height() - 1)
-                    .last()
-                    .unwrap()
-                    .clone();
+                let cumulative_sum = cumulative_sum_from_perm(&perm_trace);
                 ChipProof {
                     log_degree: *log_degree,
                     opened_values,
@@ -693,31 +678,26 @@ impl<F: PrimeField32 + TwoAdicField> Machine<F> for BasicMachine<F> {
         ];
 
         let log_quotient_degrees: [usize; NUM_CHIPS] = [
-            get_log_quotient_degree::<Self, SC, _>(self, self.cpu()),
-            get_log_quotient_degree::<Self, SC, _>(self, self.program()),
-            get_log_quotient_degree::<Self, SC, _>(self, self.mem()),
-            get_log_quotient_degree::<Self, SC, _>(self, self.add_u32()),
-            get_log_quotient_degree::<Self, SC, _>(self, self.sub_u32()),
-            get_log_quotient_degree::<Self, SC, _>(self, self.mul_u32()),
-            get_log_quotient_degree::<Self, SC, _>(self, self.div_u32()),
-            get_log_quotient_degree::<Self, SC, _>(self, self.shift_u32()),
-            get_log_quotient_degree::<Self, SC, _>(self, self.lt_u32()),
-            get_log_quotient_degree::<Self, SC, _>(self, self.com_u32()),
-            get_log_quotient_degree::<Self, SC, _>(self, self.bitwise_u32()),
-            get_log_quotient_degree::<Self, SC, _>(self, self.output()),
-            get_log_quotient_degree::<Self, SC, _>(self, self.range()),
-            get_log_quotient_degree::<Self, SC, _>(self, self.static_data()),
+            get_chip_log_quotient_degree::<Self, SC, _>(self, self.cpu()),
+            get_chip_log_quotient_degree::<Self, SC, _>(self, self.program()),
+            get_chip_log_quotient_degree::<Self, SC, _>(self, self.mem()),
+            get_chip_log_quotient_degree::<Self, SC, _>(self, self.add_u32()),
+            get_chip_log_quotient_degree::<Self, SC, _>(self, self.sub_u32()),
+            get_chip_log_quotient_degree::<Self, SC, _>(self, self.mul_u32()),
+            get_chip_log_quotient_degree::<Self, SC, _>(self, self.div_u32()),
+            get_chip_log_quotient_degree::<Self, SC, _>(self, self.shift_u32()),
+            get_chip_log_quotient_degree::<Self, SC, _>(self, self.lt_u32()),
+            get_chip_log_quotient_degree::<Self, SC, _>(self, self.com_u32()),
+            get_chip_log_quotient_degree::<Self, SC, _>(self, self.bitwise_u32()),
+            get_chip_log_quotient_degree::<Self, SC, _>(self, self.output()),
+            get_chip_log_quotient_degree::<Self, SC, _>(self, self.range()),
+            get_chip_log_quotient_degree::<Self, SC, _>(self, self.static_data()),
         ];
 
         let mut challenger = config.challenger();
         // TODO: Seed challenger with digest of all constraints & trace lengths.
         let pcs = config.pcs();
 
-        let chips_interactions = chips
-            .iter()
-            .map(|chip| chip.all_interactions(self))
-            .collect::<Vec<_>>();
-
         let dims = &[
             chips
                 .iter()
@@ -727,11 +707,11 @@ impl<F: PrimeField32 + TwoAdicField> Machine<F> for BasicMachine<F> {
                     height: 1 << chip_proof.log_degree,
                 })
                 .collect::<Vec<_>>(),
-            chips_interactions
+            chips
                 .iter()
                 .zip(proof.chip_proofs.iter())
-                .map(|(interactions, chip_proof)| Dimensions {
-                    width: (interactions.len() + 1) * SC::Challenge::D,
+                .map(|(chip, chip_proof)| Dimensions {
+                    width: permutation_trace_width::<Self, SC, _>(self, **chip) * SC::Challenge::D,
                     height: 1 << chip_proof.log_degree,
                 })
                 .collect::<Vec<_>>(),
@@ -740,7 +720,7 @@ impl<F: PrimeField32 + TwoAdicField> Machine<F> for BasicMachine<F> {
                 .iter()
                 .zip(log_quotient_degrees)
                 .map(|(chip_proof, log_quotient_deg)| Dimensions {
-                    width: log_quotient_deg << SC::Challenge::D,
+                    width: (1 << log_quotient_deg) * SC::Challenge::D,
                     height: 1 << chip_proof.log_degree,
                 })
                 .collect::<Vec<_>>(),
@@ -800,12 +780,12 @@ impl<F: PrimeField32 + TwoAdicField> Machine<F> for BasicMachine<F> {
 
         challenger.observe(preprocessed_commit.clone());
 
-        challenger.observe(main_trace.clone());
-
-        let mut perm_challenges = Vec::new();
-        for _ in 0..3 {
-            perm_challenges.push(challenger.sample_ext_element::<SC::Challenge>());
-        }
+        let perm_challenges: Vec<SC::Challenge> = self.derive_permutation_challenges(
+            &mut challenger,
+            &[main_trace.clone()],
+            num_permutation_challenges(self, chips.iter().map(|chip| **chip)),
+            permutation_challenge_field(chips.iter().map(|chip| **chip)),
+        );
 
         challenger.observe(perm_trace.clone());
 
@@ -1047,15 +1027,13 @@ impl<F: PrimeField32 + TwoAdicField> Machine<F> for BasicMachine<F> {
         i += 1;
 
         // Verify that the cumulative_sum sums add up to zero.
-        let sum: SC::Challenge = proof
+        let cumulative_sums = proof
             .chip_proofs
             .iter()
-            .map(|chip_proof| chip_proof.cumulative_sum)
-            .sum();
-
-        if sum != SC::Challenge::zero() {
-            return Err(());
-        }
+            .zip(sign_conventions(chips.iter().map(|chip| **chip)))
+            .map(|(chip_proof, sign_convention)| sign_convention.orient(chip_proof.cumulative_sum))
+            .collect::<Vec<_>>();
+        verify_aggregate_cumulative_sum(self, &cumulative_sums).map_err(|_| ())?;
 
         Ok(())
     }
@@ -1176,6 +1154,20 @@ impl<F: PrimeField32 + TwoAdicField> Machine<F> for BasicMachine<F> {
             StoppingFlag::DidNotStop
         }
     }
+
+    fn bus_label(&self, bus: BusArgument) -> Option<&str> {
+        if bus == self.general_bus() {
+            Some("general")
+        } else if bus == self.program_bus() {
+            Some("program")
+        } else if bus == self.mem_bus() {
+            Some("memory")
+        } else if bus == self.range_bus() {
+            Some("range")
+        } else {
+            None
+        }
+    }
 }
 
 impl<F: PrimeField32 + TwoAdicField> MachineWithGeneralBus<F> for BasicMachine<F> {
@@ -0,0 +1,169 @@
+//! Heap allocations made while generating permutation traces, counted by a global allocator. The
+//! buffers of a permutation trace are allocated up front, so the count must not grow with the
+//! trace height.
+
+use p3_air::{Air, AirBuilder, BaseAir, VirtualPairCol};
+use p3_baby_bear::BabyBear;
+use p3_challenger::DuplexChallenger;
+use p3_dft::Radix2Bowers;
+use p3_field::extension::BinomialExtensionField;
+use p3_field::{AbstractField, Field};
+use p3_matrix::dense::RowMajorMatrix;
+use p3_matrix::{Matrix, MatrixRowSlices};
+use p3_mds::coset_mds::CosetMds;
+use p3_poseidon::Poseidon;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use valida_machine::test_util::MockMachine;
+use valida_machine::{
+    generate_permutation_trace, generate_permutation_trace_streaming, BusArgument, Chip,
+    Interaction, InteractionAir, MockPcs, PermutationTraceBuilder, StarkConfig, StarkConfigImpl,
+};
+
+type Val = BabyBear;
+type Challenge = BinomialExtensionField<Val, 5>;
+type PackedChallenge = BinomialExtensionField<<Val as Field>::Packing, 5>;
+type Mds16 = CosetMds<Val, 16>;
+type Perm16 = Poseidon<Val, Mds16, 16, 5>;
+type Challenger = DuplexChallenger<Val, Perm16, 16>;
+type MyConfig =
+    StarkConfigImpl<Val, Challenge, PackedChallenge, MockPcs<Val, Radix2Bowers>, Challenger>;
+
+const LOG_HEIGHT: usize = 18;
+
+/// Counts the allocations of each thread, so that tests running in parallel do not interfere.
+struct CountingAllocator;
+
+std::thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+/// Run `f`, returning its result and the allocations it made on this thread.
+fn count_allocations<R>(f: impl FnOnce() -> R) -> (R, usize) {
+    let before = ALLOCATIONS.with(Cell::get);
+    let result = f();
+    let after = ALLOCATIONS.with(Cell::get);
+    (result, after - before)
+}
+
+/// Columns `a, b`: sends `(a, b)` on a global bus on every row and receives `(b, a + b)` on
+/// another, so that the permutation trace has two reciprocal columns.
+struct FibonacciChip;
+
+impl<F> BaseAir<F> for FibonacciChip {
+    fn width(&self) -> usize {
+        2
+    }
+}
+
+impl<AB: AirBuilder> Air<AB> for FibonacciChip {
+    fn eval(&self, _builder: &mut AB) {}
+}
+
+impl<SC: StarkConfig> InteractionAir<MockMachine<SC::Val>, SC> for FibonacciChip {
+    fn global_sends(&self, _machine: &MockMachine<SC::Val>) -> Vec<Interaction<SC::Val>> {
+        vec![Interaction::send_columns(
+            BusArgument::Global(0),
+            &[0, 1],
+            VirtualPairCol::one(),
+        )]
+    }
+
+    fn global_receives(&self, _machine: &MockMachine<SC::Val>) -> Vec<Interaction<SC::Val>> {
+        vec![Interaction::with_multiplicity_col(
+            vec![
+                VirtualPairCol::single_main(1),
+                VirtualPairCol::sum_main(vec![0, 1]),
+            ],
+            1,
+            BusArgument::Global(1),
+        )]
+    }
+}
+
+impl<SC: StarkConfig> Chip<MockMachine<SC::Val>, SC> for FibonacciChip {
+    fn generate_trace(&self, _machine: &MockMachine<SC::Val>) -> RowMajorMatrix<SC::Val> {
+        let (mut a, mut b) = (SC::Val::zero(), SC::Val::one());
+        let mut values = Vec::with_capacity(2 << LOG_HEIGHT);
+        for _ in 0..1 << LOG_HEIGHT {
+            values.extend([a, b]);
+            (a, b) = (b, a + b);
+        }
+        RowMajorMatrix::new(values, 2)
+    }
+}
+
+fn random_elements() -> Vec<Challenge> {
+    [3, 5, 7].map(Challenge::from_canonical_u32).to_vec()
+}
+
+/// Far fewer than one allocation per row.
+fn assert_few_allocations(allocations: usize) {
+    assert!(
+        allocations < (1 << LOG_HEIGHT) / 64,
+        "{} allocations for {} rows",
+        allocations,
+        1 << LOG_HEIGHT
+    );
+}
+
+#[test]
+fn permutation_trace_allocations() {
+    let machine = MockMachine::default();
+    let chip = FibonacciChip;
+    let main = Chip::<_, MyConfig>::generate_trace(&chip, &machine);
+
+    let (perm, allocations) = count_allocations(|| {
+        generate_permutation_trace::<_, MyConfig>(&machine, &chip, &main, random_elements())
+    });
+    assert_eq!(perm.height(), 1 << LOG_HEIGHT);
+    assert_few_allocations(allocations);
+}
+
+#[test]
+fn streaming_permutation_trace_allocations() {
+    let machine = MockMachine::default();
+    let chip = FibonacciChip;
+    let main = Chip::<_, MyConfig>::generate_trace(&chip, &machine);
+    let expected =
+        generate_permutation_trace::<_, MyConfig>(&machine, &chip, &main, random_elements());
+
+    let mut blocks = vec![];
+    let (_, allocations) = count_allocations(|| {
+        generate_permutation_trace_streaming::<_, MyConfig, _, _>(
+            &machine,
+            &chip,
+            [main.clone()],
+            random_elements(),
+            |block| blocks.push(block),
+        )
+    });
+    assert_few_allocations(allocations);
+    assert_eq!(blocks.len(), 1);
+    assert_eq!(blocks[0].values, expected.values);
+
+    let (perm, allocations) = count_allocations(|| {
+        let mut builder =
+            PermutationTraceBuilder::<MyConfig>::new(&machine, &chip, &random_elements());
+        for n in 0..main.height() {
+            builder.push_row(main.row_slice(n));
+        }
+        builder.finish()
+    });
+    assert_few_allocations(allocations);
+    assert_eq!(perm.values, expected.values);
+}
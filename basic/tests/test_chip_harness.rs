@@ -0,0 +1,3875 @@
+use p3_air::{
+    Air, AirBuilder, BaseAir, ExtensionBuilder, PairBuilder, PermutationAirBuilder,
+    TwoRowMatrixView, VirtualPairCol,
+};
+use p3_baby_bear::BabyBear;
+use p3_challenger::DuplexChallenger;
+use p3_dft::Radix2Bowers;
+use p3_field::extension::BinomialExtensionField;
+use p3_field::{AbstractExtensionField, AbstractField, Field, PackedField};
+use p3_matrix::dense::RowMajorMatrix;
+use p3_matrix::{Matrix, MatrixRowSlices};
+use p3_mds::coset_mds::CosetMds;
+use p3_poseidon::Poseidon;
+use rand::{thread_rng, Rng};
+use std::any::type_name;
+use std::collections::BTreeSet;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use valida_machine::__internal::{
+    check_constraints, find_failing_constraint, get_chip_log_quotient_degree,
+    get_symbolic_permutation_constraints, try_check_constraints, SymbolicExpression, Trace,
+};
+use valida_machine::test_util::{
+    check_layout_consistency, prove_and_verify_chip, prove_chip, prove_chips, run_chip_permutation,
+    sample_permutation_challenges, verify_chip, verify_chips, ChallengeSource, MockMachine,
+    SeededChallenges,
+};
+use valida_machine::{
+    align_domain_traces, assert_machine_balanced, bundling_savings, check_provided_once,
+    check_running_sum_degree, cumulative_sum_from_perm, diff_constraint_systems, eval_payload_hash,
+    eval_permutation_constraints, generate_payload_hash, generate_permutation_trace,
+    generate_permutation_trace_packed, generate_permutation_trace_streaming,
+    generate_permutation_trace_with_layout, generate_permutation_trace_with_trace,
+    generate_permutation_trace_with_zero_policy, generate_scoped_permutation_trace,
+    generate_segment_permutation_traces, measure_cost, permutation_challenge_field,
+    permutation_columns, permutation_trace_width, record_interaction_log, replay_permutation_trace,
+    running_sum_degree, segment_cumulative_sum, sign_conventions, soundness_error,
+    symbolic_running_sum, verify_aggregate_cumulative_sum, verify_cumulative_sums,
+    write_external_multiplicities, BusArgument, Chip, Composite, ConstraintDiff, ConstraintFailure,
+    ConstraintKind, ConstraintProvenance, CostReport, CountBound, DomainId, DuplicateEntry,
+    Interaction, InteractionAir, InteractionScope, InteractionType, LayoutMismatch, LocalImbalance,
+    LookupBackend, LookupField, Machine, MissingCumulativeSum, MockPcs, PermutationAir,
+    PermutationLayout, PermutationTraceBuilder, RadixPayloadHash, RunningSumTerm, SignConvention,
+    StarkConfig, StarkConfigImpl, SymbolicRunningSum, ValidaAirBuilder, VerificationError,
+    ZeroPolicy, LOOKUP_DEGREE_BOUND,
+};
+use valida_memory::sorted::{Access, SortedMemoryChip};
+use valida_range::bits::{range_send, RangeCheckChip};
+
+type Val = BabyBear;
+type Challenge = BinomialExtensionField<Val, 5>;
+type PackedChallenge = BinomialExtensionField<<Val as Field>::Packing, 5>;
+type Mds16 = CosetMds<Val, 16>;
+type Perm16 = Poseidon<Val, Mds16, 16, 5>;
+type Challenger = DuplexChallenger<Val, Perm16, 16>;
+type MyConfig =
+    StarkConfigImpl<Val, Challenge, PackedChallenge, MockPcs<Val, Radix2Bowers>, Challenger>;
+
+/// Sends column 0 and receives column 1 on a local bus, so it balances exactly when column 1 is
+/// a permutation of column 0.
+struct SendReceiveChip {
+    rows: Vec<[u32; 2]>,
+}
+
+impl<F> BaseAir<F> for SendReceiveChip {
+    fn width(&self) -> usize {
+        2
+    }
+}
+
+impl<AB: AirBuilder> Air<AB> for SendReceiveChip {
+    fn eval(&self, _builder: &mut AB) {}
+}
+
+impl<SC: StarkConfig> InteractionAir<MockMachine<SC::Val>, SC> for SendReceiveChip {
+    fn local_sends(&self) -> Vec<Interaction<SC::Val>> {
+        vec![Interaction {
+            fields: vec![VirtualPairCol::single_main(0)],
+            count: VirtualPairCol::one(),
+            argument_index: BusArgument::Local(0),
+            rotations: vec![],
+            count_bound: CountBound::Unbounded,
+            is_real: None,
+            limb_order: None,
+        }]
+    }
+
+    fn local_receives(&self) -> Vec<Interaction<SC::Val>> {
+        vec![Interaction {
+            fields: vec![VirtualPairCol::single_main(1)],
+            count: VirtualPairCol::one(),
+            argument_index: BusArgument::Local(0),
+            rotations: vec![],
+            count_bound: CountBound::Unbounded,
+            is_real: None,
+            limb_order: None,
+        }]
+    }
+}
+
+impl<SC: StarkConfig> Chip<MockMachine<SC::Val>, SC> for SendReceiveChip {
+    fn generate_trace(&self, _machine: &MockMachine<SC::Val>) -> RowMajorMatrix<SC::Val> {
+        let values = self
+            .rows
+            .iter()
+            .flatten()
+            .map(|&x| SC::Val::from_canonical_u32(x))
+            .collect();
+        RowMajorMatrix::new(values, 2)
+    }
+}
+
+/// A fixed table of `0..8` in a preprocessed column, receiving each entry as many times as main
+/// column 1 says, and a lookup of main column 0 into it on every row.
+struct FixedTableChip {
+    rows: Vec<[u32; 2]>,
+}
+
+impl<F: Field> BaseAir<F> for FixedTableChip {
+    fn width(&self) -> usize {
+        2
+    }
+
+    fn preprocessed_trace(&self) -> Option<RowMajorMatrix<F>> {
+        Some(RowMajorMatrix::new_col(
+            (0..8).map(F::from_canonical_u32).collect(),
+        ))
+    }
+}
+
+impl<AB: AirBuilder> Air<AB> for FixedTableChip {
+    fn eval(&self, _builder: &mut AB) {}
+}
+
+impl<SC: StarkConfig> InteractionAir<MockMachine<SC::Val>, SC> for FixedTableChip {
+    fn local_sends(&self) -> Vec<Interaction<SC::Val>> {
+        vec![Interaction::send_columns(
+            BusArgument::Local(0),
+            &[0],
+            VirtualPairCol::one(),
+        )]
+    }
+
+    fn local_receives(&self) -> Vec<Interaction<SC::Val>> {
+        vec![Interaction::with_multiplicity_col(
+            vec![VirtualPairCol::single_preprocessed(0)],
+            1,
+            BusArgument::Local(0),
+        )]
+    }
+}
+
+impl<SC: StarkConfig> Chip<MockMachine<SC::Val>, SC> for FixedTableChip {
+    fn generate_trace(&self, _machine: &MockMachine<SC::Val>) -> RowMajorMatrix<SC::Val> {
+        let values = self
+            .rows
+            .iter()
+            .flatten()
+            .map(|&x| SC::Val::from_canonical_u32(x))
+            .collect();
+        RowMajorMatrix::new(values, 2)
+    }
+}
+
+/// Columns `a, b, product, mult, is_real`: constrains `a b = product` and sends `(a, b, product)`
+/// on a global bus `mult` times on each real row.
+struct MulChip;
+
+impl<F> BaseAir<F> for MulChip {
+    fn width(&self) -> usize {
+        5
+    }
+}
+
+impl<AB: AirBuilder> Air<AB> for MulChip {
+    fn eval(&self, builder: &mut AB) {
+        let main = builder.main();
+        let local = main.row_slice(0);
+        builder.assert_eq(local[0] * local[1], local[2]);
+    }
+}
+
+impl<SC: StarkConfig> InteractionAir<MockMachine<SC::Val>, SC> for MulChip {
+    fn global_sends(&self, _machine: &MockMachine<SC::Val>) -> Vec<Interaction<SC::Val>> {
+        vec![Interaction::send_columns(
+            BusArgument::Global(0),
+            &[0, 1, 2],
+            VirtualPairCol::single_main(3),
+        )]
+    }
+
+    fn active_selector(&self) -> Option<usize> {
+        Some(4)
+    }
+}
+
+impl<SC: StarkConfig> Chip<MockMachine<SC::Val>, SC> for MulChip {
+    fn generate_trace(&self, _machine: &MockMachine<SC::Val>) -> RowMajorMatrix<SC::Val> {
+        RowMajorMatrix::new(vec![SC::Val::zero(); 5], 5)
+    }
+}
+
+/// Columns `value, is_real`: sends `value` on a global bus on each real row.
+struct GatedSendChip {
+    values: Vec<u32>,
+}
+
+impl<F> BaseAir<F> for GatedSendChip {
+    fn width(&self) -> usize {
+        2
+    }
+}
+
+impl<AB: AirBuilder> Air<AB> for GatedSendChip {
+    fn eval(&self, _builder: &mut AB) {}
+}
+
+impl<SC: StarkConfig> InteractionAir<MockMachine<SC::Val>, SC> for GatedSendChip {
+    fn global_sends(&self, _machine: &MockMachine<SC::Val>) -> Vec<Interaction<SC::Val>> {
+        vec![Interaction::send_columns(
+            BusArgument::Global(0),
+            &[0],
+            VirtualPairCol::one(),
+        )]
+    }
+
+    fn active_selector(&self) -> Option<usize> {
+        Some(1)
+    }
+}
+
+impl<SC: StarkConfig> Chip<MockMachine<SC::Val>, SC> for GatedSendChip {
+    fn generate_trace(&self, _machine: &MockMachine<SC::Val>) -> RowMajorMatrix<SC::Val> {
+        let values = self
+            .values
+            .iter()
+            .flat_map(|&x| [SC::Val::from_canonical_u32(x), SC::Val::one()])
+            .collect();
+        RowMajorMatrix::new(values, 2)
+    }
+}
+
+/// Columns `value, mult, is_real`: sends `value` on a global bus `mult` times on each real row,
+/// so that its count `mult \cdot is_real` is quadratic. With `budget`, its permutation degree
+/// budget is `budget` rather than `LOOKUP_DEGREE_BOUND`.
+struct GatedMultiplicityChip {
+    rows: Vec<[u32; 2]>,
+    budget: Option<usize>,
+}
+
+impl<F> BaseAir<F> for GatedMultiplicityChip {
+    fn width(&self) -> usize {
+        3
+    }
+}
+
+impl<AB: AirBuilder> Air<AB> for GatedMultiplicityChip {
+    fn eval(&self, _builder: &mut AB) {}
+}
+
+impl<SC: StarkConfig> InteractionAir<MockMachine<SC::Val>, SC> for GatedMultiplicityChip {
+    fn global_sends(&self, _machine: &MockMachine<SC::Val>) -> Vec<Interaction<SC::Val>> {
+        vec![Interaction::send_columns(
+            BusArgument::Global(0),
+            &[0],
+            VirtualPairCol::single_main(1),
+        )]
+    }
+
+    fn active_selector(&self) -> Option<usize> {
+        Some(2)
+    }
+}
+
+impl<SC: StarkConfig> Chip<MockMachine<SC::Val>, SC> for GatedMultiplicityChip {
+    fn generate_trace(&self, _machine: &MockMachine<SC::Val>) -> RowMajorMatrix<SC::Val> {
+        let values = self
+            .rows
+            .iter()
+            .flat_map(|&[value, mult]| [value, mult, 1])
+            .map(SC::Val::from_canonical_u32)
+            .collect();
+        RowMajorMatrix::new(values, 3)
+    }
+
+    fn max_permutation_degree(&self) -> usize {
+        self.budget.unwrap_or(LOOKUP_DEGREE_BOUND)
+    }
+}
+
+/// Columns `a, b, c, d`: sends `a` and `b` and receives `c` and `d` on a local bus, bundling its
+/// reciprocals under `bundle_degree` if it is given.
+struct DoubleSendReceiveChip {
+    rows: Vec<[u32; 4]>,
+    bundle_degree: Option<usize>,
+}
+
+impl<F> BaseAir<F> for DoubleSendReceiveChip {
+    fn width(&self) -> usize {
+        4
+    }
+}
+
+impl<AB: AirBuilder> Air<AB> for DoubleSendReceiveChip {
+    fn eval(&self, _builder: &mut AB) {}
+}
+
+impl<SC: StarkConfig> InteractionAir<MockMachine<SC::Val>, SC> for DoubleSendReceiveChip {
+    fn local_sends(&self) -> Vec<Interaction<SC::Val>> {
+        [0, 1]
+            .map(|col| {
+                Interaction::send_columns(BusArgument::Local(0), &[col], VirtualPairCol::one())
+            })
+            .to_vec()
+    }
+
+    fn local_receives(&self) -> Vec<Interaction<SC::Val>> {
+        [2, 3]
+            .map(|col| {
+                Interaction::send_columns(BusArgument::Local(0), &[col], VirtualPairCol::one())
+            })
+            .to_vec()
+    }
+
+    fn reciprocal_bundle_degree(&self) -> Option<usize> {
+        self.bundle_degree
+    }
+}
+
+impl<SC: StarkConfig> Chip<MockMachine<SC::Val>, SC> for DoubleSendReceiveChip {
+    fn generate_trace(&self, _machine: &MockMachine<SC::Val>) -> RowMajorMatrix<SC::Val> {
+        let values = self
+            .rows
+            .iter()
+            .flatten()
+            .map(|&x| SC::Val::from_canonical_u32(x))
+            .collect();
+        RowMajorMatrix::new(values, 4)
+    }
+}
+
+/// Sends column 0 and `Main(3) - Main(3) + 7` on a global bus, the second of which is constant
+/// once its weights cancel out.
+struct CancellingFieldChip;
+
+impl<F> BaseAir<F> for CancellingFieldChip {
+    fn width(&self) -> usize {
+        4
+    }
+}
+
+impl<AB: AirBuilder> Air<AB> for CancellingFieldChip {
+    fn eval(&self, _builder: &mut AB) {}
+}
+
+impl<SC: StarkConfig> InteractionAir<MockMachine<SC::Val>, SC> for CancellingFieldChip {
+    fn global_sends(&self, _machine: &MockMachine<SC::Val>) -> Vec<Interaction<SC::Val>> {
+        let cancelling = VirtualPairCol::new_main(
+            vec![(3, SC::Val::one()), (3, SC::Val::neg_one())],
+            SC::Val::from_canonical_u32(7),
+        );
+        vec![
+            Interaction::send_columns(BusArgument::Global(0), &[0], VirtualPairCol::one()),
+            Interaction {
+                fields: vec![cancelling],
+                count: VirtualPairCol::one(),
+                argument_index: BusArgument::Global(0),
+                rotations: vec![],
+                count_bound: CountBound::Unbounded,
+                is_real: None,
+                limb_order: None,
+            },
+        ]
+    }
+}
+
+impl<SC: StarkConfig> Chip<MockMachine<SC::Val>, SC> for CancellingFieldChip {
+    fn generate_trace(&self, _machine: &MockMachine<SC::Val>) -> RowMajorMatrix<SC::Val> {
+        RowMajorMatrix::new((0..16).map(SC::Val::from_canonical_u32).collect(), 4)
+    }
+}
+
+/// A table providing each of its keys once, receiving the key of every row, or with `rotated` the
+/// key of every row paired with the key of the next row.
+struct KeyTableChip {
+    keys: Vec<u32>,
+    rotated: bool,
+}
+
+impl<F> BaseAir<F> for KeyTableChip {
+    fn width(&self) -> usize {
+        2
+    }
+}
+
+impl<AB: AirBuilder> Air<AB> for KeyTableChip {
+    fn eval(&self, _builder: &mut AB) {}
+}
+
+impl<SC: StarkConfig> InteractionAir<MockMachine<SC::Val>, SC> for KeyTableChip {
+    fn local_receives(&self) -> Vec<Interaction<SC::Val>> {
+        let receive = Interaction::with_multiplicity_col(
+            vec![VirtualPairCol::single_main(0)],
+            1,
+            BusArgument::Local(0),
+        );
+        if self.rotated {
+            vec![receive.field_rotated(0, 1)]
+        } else {
+            vec![receive]
+        }
+    }
+}
+
+impl<SC: StarkConfig> Chip<MockMachine<SC::Val>, SC> for KeyTableChip {
+    fn generate_trace(&self, _machine: &MockMachine<SC::Val>) -> RowMajorMatrix<SC::Val> {
+        let values = self
+            .keys
+            .iter()
+            .flat_map(|&key| [SC::Val::from_canonical_u32(key), SC::Val::one()])
+            .collect();
+        RowMajorMatrix::new(values, 2)
+    }
+
+    fn provides_once(&self) -> bool {
+        true
+    }
+}
+
+/// A chip of two unconstrained columns blinded with `seed`.
+struct BlindedChip {
+    seed: u64,
+}
+
+impl<F> BaseAir<F> for BlindedChip {
+    fn width(&self) -> usize {
+        2
+    }
+}
+
+impl<AB: AirBuilder> Air<AB> for BlindedChip {
+    fn eval(&self, _builder: &mut AB) {}
+}
+
+impl<SC: StarkConfig> InteractionAir<MockMachine<SC::Val>, SC> for BlindedChip {}
+
+impl<SC: StarkConfig> Chip<MockMachine<SC::Val>, SC> for BlindedChip {
+    fn generate_trace(&self, _machine: &MockMachine<SC::Val>) -> RowMajorMatrix<SC::Val> {
+        RowMajorMatrix::new(vec![SC::Val::zero(); 2], 2)
+    }
+
+    fn blinding_seed(&self) -> Option<u64> {
+        Some(self.seed)
+    }
+}
+
+/// A `tracing` subscriber recording the name of every span created under it.
+struct SpanNames(Arc<Mutex<Vec<&'static str>>>);
+
+impl tracing::Subscriber for SpanNames {
+    fn enabled(&self, _metadata: &tracing::Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, span: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+        let mut names = self.0.lock().unwrap();
+        names.push(span.metadata().name());
+        tracing::span::Id::from_u64(names.len() as u64)
+    }
+
+    fn record(&self, _span: &tracing::span::Id, _values: &tracing::span::Record<'_>) {}
+
+    fn record_follows_from(&self, _span: &tracing::span::Id, _follows: &tracing::span::Id) {}
+
+    fn event(&self, _event: &tracing::Event<'_>) {}
+
+    fn enter(&self, _span: &tracing::span::Id) {}
+
+    fn exit(&self, _span: &tracing::span::Id) {}
+}
+
+/// Columns `a, square`: constrains `r (a^2 - square) = 0` with a fourth permutation challenge
+/// `r` of its own, and sends and receives `a` on a local bus.
+struct OwnChallengeChip {
+    values: Vec<u32>,
+}
+
+impl<F> BaseAir<F> for OwnChallengeChip {
+    fn width(&self) -> usize {
+        2
+    }
+}
+
+impl<AB: PermutationAirBuilder> Air<AB> for OwnChallengeChip {
+    fn eval(&self, builder: &mut AB) {
+        let main = builder.main();
+        let local = main.row_slice(0);
+        let residue = local[0] * local[0] - local[1];
+        let r = AB::ExprEF::from_f(builder.permutation_randomness()[3]);
+        builder.assert_zero_ext(r * residue);
+    }
+}
+
+impl<SC: StarkConfig> InteractionAir<MockMachine<SC::Val>, SC> for OwnChallengeChip {
+    fn local_sends(&self) -> Vec<Interaction<SC::Val>> {
+        vec![Interaction::send_columns(
+            BusArgument::Local(0),
+            &[0],
+            VirtualPairCol::one(),
+        )]
+    }
+
+    fn local_receives(&self) -> Vec<Interaction<SC::Val>> {
+        vec![Interaction::receive_always(BusArgument::Local(0), &[0])]
+    }
+
+    fn num_permutation_challenges(&self, _machine: &MockMachine<SC::Val>) -> usize {
+        4
+    }
+}
+
+impl<SC: StarkConfig> Chip<MockMachine<SC::Val>, SC> for OwnChallengeChip {
+    fn generate_trace(&self, _machine: &MockMachine<SC::Val>) -> RowMajorMatrix<SC::Val> {
+        let values = self
+            .values
+            .iter()
+            .flat_map(|&a| [a, a * a].map(SC::Val::from_canonical_u32))
+            .collect();
+        RowMajorMatrix::new(values, 2)
+    }
+}
+
+/// Sends each of its six columns on a local bus on every row.
+struct SixSendChip;
+
+impl<F> BaseAir<F> for SixSendChip {
+    fn width(&self) -> usize {
+        6
+    }
+}
+
+impl<AB: AirBuilder> Air<AB> for SixSendChip {
+    fn eval(&self, _builder: &mut AB) {}
+}
+
+impl<SC: StarkConfig> InteractionAir<MockMachine<SC::Val>, SC> for SixSendChip {
+    fn local_sends(&self) -> Vec<Interaction<SC::Val>> {
+        (0..6)
+            .map(|col| {
+                Interaction::send_columns(BusArgument::Local(0), &[col], VirtualPairCol::one())
+            })
+            .collect()
+    }
+}
+
+impl<SC: StarkConfig> Chip<MockMachine<SC::Val>, SC> for SixSendChip {
+    fn generate_trace(&self, _machine: &MockMachine<SC::Val>) -> RowMajorMatrix<SC::Val> {
+        RowMajorMatrix::new(vec![SC::Val::zero(); 6], 6)
+    }
+}
+
+/// Columns `a, b, a', b'`: sends `a` on global bus 0 and `b` on global bus 1, and receives `a'`
+/// and `b'` on the same buses, with its reciprocal columns placed by `layout`.
+struct TwoBusChip {
+    rows: Vec<[u32; 4]>,
+    layout: PermutationLayout,
+}
+
+impl<F> BaseAir<F> for TwoBusChip {
+    fn width(&self) -> usize {
+        4
+    }
+}
+
+impl<AB: AirBuilder> Air<AB> for TwoBusChip {
+    fn eval(&self, _builder: &mut AB) {}
+}
+
+impl<SC: StarkConfig> InteractionAir<MockMachine<SC::Val>, SC> for TwoBusChip {
+    fn global_sends(&self, _machine: &MockMachine<SC::Val>) -> Vec<Interaction<SC::Val>> {
+        (0..2)
+            .map(|bus| {
+                Interaction::send_columns(BusArgument::Global(bus), &[bus], VirtualPairCol::one())
+            })
+            .collect()
+    }
+
+    fn global_receives(&self, _machine: &MockMachine<SC::Val>) -> Vec<Interaction<SC::Val>> {
+        (0..2)
+            .map(|bus| Interaction::receive_always(BusArgument::Global(bus), &[2 + bus]))
+            .collect()
+    }
+
+    fn permutation_layout(&self) -> PermutationLayout {
+        self.layout.clone()
+    }
+}
+
+impl<SC: StarkConfig> Chip<MockMachine<SC::Val>, SC> for TwoBusChip {
+    fn generate_trace(&self, _machine: &MockMachine<SC::Val>) -> RowMajorMatrix<SC::Val> {
+        let values = self
+            .rows
+            .iter()
+            .flatten()
+            .map(|&x| SC::Val::from_canonical_u32(x))
+            .collect();
+        RowMajorMatrix::new(values, 4)
+    }
+}
+
+/// Columns `x_0, x_1, x_2, hash(x), y_0, y_1, y_2, hash(y)`: sends the record `x` and receives the
+/// record `y` on a local bus, each collapsed to its `RadixPayloadHash`.
+struct HashedRecordChip {
+    rows: Vec<([u32; 3], [u32; 3])>,
+}
+
+impl HashedRecordChip {
+    fn hasher() -> RadixPayloadHash<Val> {
+        RadixPayloadHash {
+            radix: Val::from_canonical_u32(256),
+        }
+    }
+
+    fn payload<F: Field>(start: usize) -> Vec<VirtualPairCol<F>> {
+        (start..start + 3)
+            .map(VirtualPairCol::single_main)
+            .collect()
+    }
+}
+
+impl<F> BaseAir<F> for HashedRecordChip {
+    fn width(&self) -> usize {
+        8
+    }
+}
+
+impl<AB: PairBuilder<F = Val>> Air<AB> for HashedRecordChip {
+    fn eval(&self, builder: &mut AB) {
+        eval_payload_hash(builder, &Self::hasher(), &Self::payload(0), 3);
+        eval_payload_hash(builder, &Self::hasher(), &Self::payload(4), 7);
+    }
+}
+
+impl<SC: StarkConfig<Val = Val>> InteractionAir<MockMachine<Val>, SC> for HashedRecordChip {
+    fn local_sends(&self) -> Vec<Interaction<Val>> {
+        vec![
+            Interaction::send_columns(BusArgument::Local(0), &[0, 1, 2], VirtualPairCol::one())
+                .with_hashed_payload(3),
+        ]
+    }
+
+    fn local_receives(&self) -> Vec<Interaction<Val>> {
+        vec![Interaction::receive_always(BusArgument::Local(0), &[4, 5, 6]).with_hashed_payload(7)]
+    }
+}
+
+impl<SC: StarkConfig<Val = Val>> Chip<MockMachine<Val>, SC> for HashedRecordChip {
+    fn generate_trace(&self, _machine: &MockMachine<Val>) -> RowMajorMatrix<Val> {
+        let mut values = vec![];
+        for (x, y) in &self.rows {
+            for record in [x, y] {
+                let record = record.map(Val::from_canonical_u32);
+                let hash = generate_payload_hash(&Self::hasher(), &Self::payload(0), &[], &record);
+                values.extend(record);
+                values.push(hash);
+            }
+        }
+        RowMajorMatrix::new(values, 8)
+    }
+}
+
+/// Columns `value, seven`: sends `value` and `seven` on a global bus, with `seven` holding 7 on
+/// every row, so that it sends what `CancellingFieldChip` does but with a reciprocal column for
+/// each interaction.
+struct SevenColumnChip;
+
+impl<F> BaseAir<F> for SevenColumnChip {
+    fn width(&self) -> usize {
+        2
+    }
+}
+
+impl<AB: AirBuilder> Air<AB> for SevenColumnChip {
+    fn eval(&self, _builder: &mut AB) {}
+}
+
+impl<SC: StarkConfig> InteractionAir<MockMachine<SC::Val>, SC> for SevenColumnChip {
+    fn global_sends(&self, _machine: &MockMachine<SC::Val>) -> Vec<Interaction<SC::Val>> {
+        vec![
+            Interaction::send_columns(BusArgument::Global(0), &[0], VirtualPairCol::one()),
+            Interaction::send_columns(BusArgument::Global(0), &[1], VirtualPairCol::one()),
+        ]
+    }
+}
+
+impl<SC: StarkConfig> Chip<MockMachine<SC::Val>, SC> for SevenColumnChip {
+    fn generate_trace(&self, _machine: &MockMachine<SC::Val>) -> RowMajorMatrix<SC::Val> {
+        let values = (0..4)
+            .flat_map(|row| {
+                [
+                    SC::Val::from_canonical_u32(4 * row),
+                    SC::Val::from_canonical_u32(7),
+                ]
+            })
+            .collect();
+        RowMajorMatrix::new(values, 2)
+    }
+}
+
+/// Columns `a, b`: sends `a` and receives `b` on a local bus, and sends `a` on a global bus.
+struct MixedScopeChip {
+    rows: Vec<[u32; 2]>,
+}
+
+impl<F> BaseAir<F> for MixedScopeChip {
+    fn width(&self) -> usize {
+        2
+    }
+}
+
+impl<AB: AirBuilder> Air<AB> for MixedScopeChip {
+    fn eval(&self, _builder: &mut AB) {}
+}
+
+impl<SC: StarkConfig> InteractionAir<MockMachine<SC::Val>, SC> for MixedScopeChip {
+    fn local_sends(&self) -> Vec<Interaction<SC::Val>> {
+        vec![Interaction::send_columns(
+            BusArgument::Local(0),
+            &[0],
+            VirtualPairCol::one(),
+        )]
+    }
+
+    fn local_receives(&self) -> Vec<Interaction<SC::Val>> {
+        vec![Interaction::receive_always(BusArgument::Local(0), &[1])]
+    }
+
+    fn global_sends(&self, _machine: &MockMachine<SC::Val>) -> Vec<Interaction<SC::Val>> {
+        vec![Interaction::send_columns(
+            BusArgument::Global(0),
+            &[0],
+            VirtualPairCol::one(),
+        )]
+    }
+}
+
+impl<SC: StarkConfig> Chip<MockMachine<SC::Val>, SC> for MixedScopeChip {
+    fn generate_trace(&self, _machine: &MockMachine<SC::Val>) -> RowMajorMatrix<SC::Val> {
+        let values = self
+            .rows
+            .iter()
+            .flatten()
+            .map(|&x| SC::Val::from_canonical_u32(x))
+            .collect();
+        RowMajorMatrix::new(values, 2)
+    }
+}
+
+/// Columns `a, b`: sends `(a, b)` on global bus 1, then `a` on global bus 0.
+struct PairSendChip;
+
+impl<F> BaseAir<F> for PairSendChip {
+    fn width(&self) -> usize {
+        2
+    }
+}
+
+impl<AB: AirBuilder> Air<AB> for PairSendChip {
+    fn eval(&self, _builder: &mut AB) {}
+}
+
+impl<SC: StarkConfig> InteractionAir<MockMachine<SC::Val>, SC> for PairSendChip {
+    fn global_sends(&self, _machine: &MockMachine<SC::Val>) -> Vec<Interaction<SC::Val>> {
+        vec![
+            Interaction::send_columns(BusArgument::Global(1), &[0, 1], VirtualPairCol::one()),
+            Interaction::send_columns(BusArgument::Global(0), &[0], VirtualPairCol::one()),
+        ]
+    }
+}
+
+impl<SC: StarkConfig> Chip<MockMachine<SC::Val>, SC> for PairSendChip {
+    fn generate_trace(&self, _machine: &MockMachine<SC::Val>) -> RowMajorMatrix<SC::Val> {
+        RowMajorMatrix::new((0..8).map(SC::Val::from_canonical_u32).collect(), 2)
+    }
+}
+
+/// A builder over the first two rows of a chip's traces, counting the constraints asserted on it
+/// whether or not they hold.
+struct CountingBuilder<'a> {
+    machine: &'a MockMachine<Val>,
+    main: TwoRowMatrixView<'a, Val>,
+    preprocessed: TwoRowMatrixView<'a, Val>,
+    perm: TwoRowMatrixView<'a, Challenge>,
+    random_elements: &'a [Challenge],
+    constraints: usize,
+}
+
+impl<'a> AirBuilder for CountingBuilder<'a> {
+    type F = Val;
+    type Expr = Val;
+    type Var = Val;
+    type M = TwoRowMatrixView<'a, Val>;
+
+    fn main(&self) -> Self::M {
+        self.main
+    }
+
+    fn is_first_row(&self) -> Self::Expr {
+        Val::one()
+    }
+
+    fn is_last_row(&self) -> Self::Expr {
+        Val::zero()
+    }
+
+    fn is_transition_window(&self, size: usize) -> Self::Expr {
+        assert_eq!(size, 2);
+        Val::one()
+    }
+
+    fn assert_zero<I: Into<Self::Expr>>(&mut self, _x: I) {
+        self.constraints += 1;
+    }
+}
+
+impl PairBuilder for CountingBuilder<'_> {
+    fn preprocessed(&self) -> Self::M {
+        self.preprocessed
+    }
+}
+
+impl ExtensionBuilder for CountingBuilder<'_> {
+    type EF = Challenge;
+    type ExprEF = Challenge;
+    type VarEF = Challenge;
+
+    fn assert_zero_ext<I: Into<Self::ExprEF>>(&mut self, _x: I) {
+        self.constraints += 1;
+    }
+}
+
+impl<'a> PermutationAirBuilder for CountingBuilder<'a> {
+    type MP = TwoRowMatrixView<'a, Challenge>;
+
+    fn permutation(&self) -> Self::MP {
+        self.perm
+    }
+
+    fn permutation_randomness(&self) -> &[Self::EF] {
+        self.random_elements
+    }
+}
+
+impl ValidaAirBuilder for CountingBuilder<'_> {
+    type Machine = MockMachine<Val>;
+
+    fn machine(&self) -> &Self::Machine {
+        self.machine
+    }
+}
+
+/// The number of constraints `eval` asserts on a `CountingBuilder` over the traces of `chip`.
+fn count_constraints(
+    machine: &MockMachine<Val>,
+    chip: &dyn Chip<MockMachine<Val>, MyConfig>,
+    eval: impl FnOnce(&mut CountingBuilder),
+) -> usize {
+    fn two_rows<T: Clone + Send + Sync>(trace: &RowMajorMatrix<T>) -> TwoRowMatrixView<'_, T> {
+        TwoRowMatrixView {
+            local: trace.row_slice(0),
+            next: trace.row_slice(1),
+        }
+    }
+    let main = chip.generate_trace(machine);
+    let preprocessed = chip.preprocessed_trace();
+    let perm = generate_permutation_trace(machine, chip, &main, random_elements());
+    let random_elements = random_elements();
+    let mut builder = CountingBuilder {
+        machine,
+        main: two_rows(&main),
+        preprocessed: preprocessed.as_ref().map_or(
+            TwoRowMatrixView {
+                local: &[],
+                next: &[],
+            },
+            two_rows,
+        ),
+        perm: two_rows(&perm),
+        random_elements: &random_elements,
+        constraints: 0,
+    };
+    eval(&mut builder);
+    builder.constraints
+}
+
+/// Column `a`: sends `(a, a)` on a local bus, optionally requiring distinct fields.
+struct DuplicateFieldChip {
+    require_distinct: bool,
+}
+
+impl<F> BaseAir<F> for DuplicateFieldChip {
+    fn width(&self) -> usize {
+        1
+    }
+}
+
+impl<AB: AirBuilder> Air<AB> for DuplicateFieldChip {
+    fn eval(&self, _builder: &mut AB) {}
+}
+
+impl<SC: StarkConfig> InteractionAir<MockMachine<SC::Val>, SC> for DuplicateFieldChip {
+    fn local_sends(&self) -> Vec<Interaction<SC::Val>> {
+        vec![Interaction::send_columns(
+            BusArgument::Local(0),
+            &[0, 0],
+            VirtualPairCol::one(),
+        )]
+    }
+}
+
+impl<SC: StarkConfig> Chip<MockMachine<SC::Val>, SC> for DuplicateFieldChip {
+    fn generate_trace(&self, _machine: &MockMachine<SC::Val>) -> RowMajorMatrix<SC::Val> {
+        RowMajorMatrix::new_col((1..=4).map(SC::Val::from_canonical_u32).collect())
+    }
+
+    fn require_distinct_fields(&self) -> bool {
+        self.require_distinct
+    }
+}
+
+/// Columns `lookup, key, mult`: sends `lookup` on a local bus on every row and receives the
+/// `key`th entry of the table `0..n` with multiplicity `mult`. `generate_trace` leaves `mult` at
+/// zero and takes the counts from `counts` through `Chip::external_multiplicities`.
+struct ExternalCountChip {
+    lookups: Vec<u32>,
+    counts: Vec<u32>,
+}
+
+impl<F> BaseAir<F> for ExternalCountChip {
+    fn width(&self) -> usize {
+        3
+    }
+}
+
+impl<AB: AirBuilder> Air<AB> for ExternalCountChip {
+    fn eval(&self, _builder: &mut AB) {}
+}
+
+impl<SC: StarkConfig> InteractionAir<MockMachine<SC::Val>, SC> for ExternalCountChip {
+    fn local_sends(&self) -> Vec<Interaction<SC::Val>> {
+        vec![Interaction::send_columns(
+            BusArgument::Local(0),
+            &[0],
+            VirtualPairCol::constant(SC::Val::one()),
+        )]
+    }
+
+    fn local_receives(&self) -> Vec<Interaction<SC::Val>> {
+        vec![Interaction::with_multiplicity_col(
+            vec![VirtualPairCol::single_main(1)],
+            2,
+            BusArgument::Local(0),
+        )]
+    }
+}
+
+impl<SC: StarkConfig> Chip<MockMachine<SC::Val>, SC> for ExternalCountChip {
+    fn generate_trace(&self, _machine: &MockMachine<SC::Val>) -> RowMajorMatrix<SC::Val> {
+        let values = self
+            .lookups
+            .iter()
+            .enumerate()
+            .flat_map(|(key, &lookup)| {
+                [
+                    SC::Val::from_canonical_u32(lookup),
+                    SC::Val::from_canonical_usize(key),
+                    SC::Val::zero(),
+                ]
+            })
+            .collect();
+        RowMajorMatrix::new(values, 3)
+    }
+
+    fn external_multiplicities(
+        &self,
+        _machine: &MockMachine<SC::Val>,
+    ) -> Option<Vec<Option<Vec<SC::Val>>>> {
+        Some(vec![
+            None,
+            Some(
+                self.counts
+                    .iter()
+                    .map(|&count| SC::Val::from_canonical_u32(count))
+                    .collect(),
+            ),
+        ])
+    }
+}
+
+/// `SendReceiveChip` with its permutation trace computed over the base field.
+struct BaseFieldChip {
+    rows: Vec<[u32; 2]>,
+}
+
+impl<F> BaseAir<F> for BaseFieldChip {
+    fn width(&self) -> usize {
+        2
+    }
+}
+
+impl<AB: AirBuilder> Air<AB> for BaseFieldChip {
+    fn eval(&self, _builder: &mut AB) {}
+}
+
+impl<SC: StarkConfig> InteractionAir<MockMachine<SC::Val>, SC> for BaseFieldChip {
+    fn local_sends(&self) -> Vec<Interaction<SC::Val>> {
+        vec![Interaction::receive_always(BusArgument::Local(0), &[0])]
+    }
+
+    fn local_receives(&self) -> Vec<Interaction<SC::Val>> {
+        vec![Interaction::receive_always(BusArgument::Local(0), &[1])]
+    }
+}
+
+impl<SC: StarkConfig> Chip<MockMachine<SC::Val>, SC> for BaseFieldChip {
+    fn generate_trace(&self, machine: &MockMachine<SC::Val>) -> RowMajorMatrix<SC::Val> {
+        Chip::<_, SC>::generate_trace(
+            &SendReceiveChip {
+                rows: self.rows.clone(),
+            },
+            machine,
+        )
+    }
+
+    fn lookup_field(&self) -> LookupField {
+        LookupField::Base
+    }
+}
+
+/// Column `value`: sends `value` on a global bus, or with an `upstream` chip receives it there and
+/// reads the upstream chip's cumulative sum, requiring its own to cancel it.
+struct UpstreamSumChip {
+    values: Vec<u32>,
+    upstream: Option<usize>,
+}
+
+impl<F> BaseAir<F> for UpstreamSumChip {
+    fn width(&self) -> usize {
+        1
+    }
+}
+
+impl<AB: AirBuilder> Air<AB> for UpstreamSumChip {
+    fn eval(&self, _builder: &mut AB) {}
+}
+
+impl<SC: StarkConfig> InteractionAir<MockMachine<SC::Val>, SC> for UpstreamSumChip {
+    fn global_sends(&self, _machine: &MockMachine<SC::Val>) -> Vec<Interaction<SC::Val>> {
+        match self.upstream {
+            None => vec![Interaction::send_columns(
+                BusArgument::Global(0),
+                &[0],
+                VirtualPairCol::one(),
+            )],
+            Some(_) => vec![],
+        }
+    }
+
+    fn global_receives(&self, _machine: &MockMachine<SC::Val>) -> Vec<Interaction<SC::Val>> {
+        match self.upstream {
+            None => vec![],
+            Some(_) => vec![Interaction::receive_always(BusArgument::Global(0), &[0])],
+        }
+    }
+}
+
+impl<SC: StarkConfig> Chip<MockMachine<SC::Val>, SC> for UpstreamSumChip {
+    fn generate_trace(&self, _machine: &MockMachine<SC::Val>) -> RowMajorMatrix<SC::Val> {
+        let values = self
+            .values
+            .iter()
+            .map(|&x| SC::Val::from_canonical_u32(x))
+            .collect();
+        RowMajorMatrix::new(values, 1)
+    }
+
+    fn cumulative_sum_dependencies(&self) -> Vec<usize> {
+        self.upstream.into_iter().collect()
+    }
+
+    fn generate_dependent_permutation_trace(
+        &self,
+        machine: &MockMachine<SC::Val>,
+        main: &RowMajorMatrix<SC::Val>,
+        random_elements: &[SC::Challenge],
+        upstream_sums: &[SC::Challenge],
+    ) -> Option<RowMajorMatrix<SC::Challenge>> {
+        let perm =
+            generate_permutation_trace::<_, SC>(machine, self, main, random_elements.to_vec());
+        assert!(
+            (cumulative_sum_from_perm(&perm) + upstream_sums[0]).is_zero(),
+            "the cumulative sum does not cancel the upstream cumulative sum"
+        );
+        Some(perm)
+    }
+}
+
+/// Column `value`: each row receives its own value on a local bus and sends the value of the next
+/// row, wrapping around on the last row, so every value is sent and received once.
+struct NextRowChip {
+    values: Vec<u32>,
+}
+
+impl<F> BaseAir<F> for NextRowChip {
+    fn width(&self) -> usize {
+        1
+    }
+}
+
+impl<AB: AirBuilder> Air<AB> for NextRowChip {
+    fn eval(&self, _builder: &mut AB) {}
+}
+
+impl<SC: StarkConfig> InteractionAir<MockMachine<SC::Val>, SC> for NextRowChip {
+    fn local_sends(&self) -> Vec<Interaction<SC::Val>> {
+        vec![
+            Interaction::send_columns(BusArgument::Local(0), &[], VirtualPairCol::one())
+                .field_rotated(0, 1),
+        ]
+    }
+
+    fn local_receives(&self) -> Vec<Interaction<SC::Val>> {
+        vec![Interaction::receive_always(BusArgument::Local(0), &[0])]
+    }
+}
+
+impl<SC: StarkConfig> Chip<MockMachine<SC::Val>, SC> for NextRowChip {
+    fn generate_trace(&self, _machine: &MockMachine<SC::Val>) -> RowMajorMatrix<SC::Val> {
+        let values = self
+            .values
+            .iter()
+            .map(|&x| SC::Val::from_canonical_u32(x))
+            .collect();
+        RowMajorMatrix::new(values, 1)
+    }
+}
+
+/// Columns `value, is_real`: sends `value` on a global bus on each real row, if the first public
+/// value is one.
+struct PublicGatedSendChip {
+    values: Vec<u32>,
+}
+
+impl<F> BaseAir<F> for PublicGatedSendChip {
+    fn width(&self) -> usize {
+        2
+    }
+}
+
+impl<AB: AirBuilder> Air<AB> for PublicGatedSendChip {
+    fn eval(&self, _builder: &mut AB) {}
+}
+
+impl<SC: StarkConfig> InteractionAir<MockMachine<SC::Val>, SC> for PublicGatedSendChip {
+    fn global_sends(&self, machine: &MockMachine<SC::Val>) -> Vec<Interaction<SC::Val>> {
+        let gate = machine.public_values()[0];
+        vec![Interaction::send_columns(
+            BusArgument::Global(0),
+            &[0],
+            VirtualPairCol::new_main(vec![(1, gate)], SC::Val::zero()),
+        )]
+    }
+}
+
+impl<SC: StarkConfig> Chip<MockMachine<SC::Val>, SC> for PublicGatedSendChip {
+    fn generate_trace(&self, machine: &MockMachine<SC::Val>) -> RowMajorMatrix<SC::Val> {
+        Chip::<_, SC>::generate_trace(
+            &GatedSendChip {
+                values: self.values.clone(),
+            },
+            machine,
+        )
+    }
+}
+
+/// Receives column 0 on the global bus `GatedSendChip` sends on, once per row.
+struct GlobalReceiveChip {
+    values: Vec<u32>,
+}
+
+impl<F> BaseAir<F> for GlobalReceiveChip {
+    fn width(&self) -> usize {
+        1
+    }
+}
+
+impl<AB: AirBuilder> Air<AB> for GlobalReceiveChip {
+    fn eval(&self, _builder: &mut AB) {}
+}
+
+impl<SC: StarkConfig> InteractionAir<MockMachine<SC::Val>, SC> for GlobalReceiveChip {
+    fn global_receives(&self, _machine: &MockMachine<SC::Val>) -> Vec<Interaction<SC::Val>> {
+        vec![Interaction::receive_always(BusArgument::Global(0), &[0])]
+    }
+}
+
+impl<SC: StarkConfig> Chip<MockMachine<SC::Val>, SC> for GlobalReceiveChip {
+    fn generate_trace(&self, _machine: &MockMachine<SC::Val>) -> RowMajorMatrix<SC::Val> {
+        RowMajorMatrix::new_col(
+            self.values
+                .iter()
+                .map(|&x| SC::Val::from_canonical_u32(x))
+                .collect(),
+        )
+    }
+}
+
+/// Columns `value, count`: sends `value` on a global bus `count` times, declaring `bound`.
+struct BoundedCountChip {
+    rows: Vec<[u32; 2]>,
+    bound: CountBound,
+}
+
+impl<F> BaseAir<F> for BoundedCountChip {
+    fn width(&self) -> usize {
+        2
+    }
+}
+
+impl<AB: AirBuilder> Air<AB> for BoundedCountChip {
+    fn eval(&self, _builder: &mut AB) {}
+}
+
+impl<SC: StarkConfig> InteractionAir<MockMachine<SC::Val>, SC> for BoundedCountChip {
+    fn global_sends(&self, _machine: &MockMachine<SC::Val>) -> Vec<Interaction<SC::Val>> {
+        vec![Interaction {
+            count_bound: self.bound,
+            is_real: None,
+            limb_order: None,
+            ..Interaction::send_columns(
+                BusArgument::Global(0),
+                &[0],
+                VirtualPairCol::single_main(1),
+            )
+        }]
+    }
+}
+
+impl<SC: StarkConfig> Chip<MockMachine<SC::Val>, SC> for BoundedCountChip {
+    fn generate_trace(&self, _machine: &MockMachine<SC::Val>) -> RowMajorMatrix<SC::Val> {
+        let values = self
+            .rows
+            .iter()
+            .flatten()
+            .map(|&x| SC::Val::from_canonical_u32(x))
+            .collect();
+        RowMajorMatrix::new(values, 2)
+    }
+}
+
+/// A chip with no interactions which counts how often its trace is generated.
+#[derive(Default)]
+struct CountingChip {
+    generations: AtomicUsize,
+}
+
+impl<F> BaseAir<F> for CountingChip {
+    fn width(&self) -> usize {
+        1
+    }
+}
+
+impl<AB: AirBuilder> Air<AB> for CountingChip {
+    fn eval(&self, _builder: &mut AB) {}
+}
+
+impl<SC: StarkConfig> InteractionAir<MockMachine<SC::Val>, SC> for CountingChip {}
+
+impl<SC: StarkConfig> Chip<MockMachine<SC::Val>, SC> for CountingChip {
+    fn generate_trace(&self, _machine: &MockMachine<SC::Val>) -> RowMajorMatrix<SC::Val> {
+        self.generations.fetch_add(1, Ordering::Relaxed);
+        RowMajorMatrix::new_col((1..=4).map(SC::Val::from_canonical_u32).collect())
+    }
+}
+
+/// Sends memory accesses `(addr, timestamp, value, is_write)` in execution order, as a CPU would
+/// to a `SortedMemoryChip`.
+struct AccessLogChip {
+    accesses: Vec<Access>,
+}
+
+impl<F> BaseAir<F> for AccessLogChip {
+    fn width(&self) -> usize {
+        4
+    }
+}
+
+impl<AB: AirBuilder> Air<AB> for AccessLogChip {
+    fn eval(&self, _builder: &mut AB) {}
+}
+
+impl<SC: StarkConfig> InteractionAir<MockMachine<SC::Val>, SC> for AccessLogChip {
+    fn global_sends(&self, _machine: &MockMachine<SC::Val>) -> Vec<Interaction<SC::Val>> {
+        vec![Interaction::send_columns(
+            BusArgument::Global(0),
+            &[0, 1, 2, 3],
+            VirtualPairCol::one(),
+        )]
+    }
+}
+
+impl<SC: StarkConfig> Chip<MockMachine<SC::Val>, SC> for AccessLogChip {
+    fn generate_trace(&self, _machine: &MockMachine<SC::Val>) -> RowMajorMatrix<SC::Val> {
+        let values = self
+            .accesses
+            .iter()
+            .flat_map(|access| {
+                [
+                    access.addr,
+                    access.timestamp,
+                    access.value,
+                    access.is_write as u32,
+                ]
+            })
+            .map(SC::Val::from_canonical_u32)
+            .collect();
+        RowMajorMatrix::new(values, 4)
+    }
+}
+
+/// Range checks each value in its single column on global bus 0.
+struct RangeSendChip {
+    values: Vec<u32>,
+}
+
+impl<F> BaseAir<F> for RangeSendChip {
+    fn width(&self) -> usize {
+        1
+    }
+}
+
+impl<AB: AirBuilder> Air<AB> for RangeSendChip {
+    fn eval(&self, _builder: &mut AB) {}
+}
+
+impl<SC: StarkConfig> InteractionAir<MockMachine<SC::Val>, SC> for RangeSendChip {
+    fn global_sends(&self, _machine: &MockMachine<SC::Val>) -> Vec<Interaction<SC::Val>> {
+        vec![range_send(BusArgument::Global(0), 0)]
+    }
+}
+
+impl<SC: StarkConfig> Chip<MockMachine<SC::Val>, SC> for RangeSendChip {
+    fn generate_trace(&self, _machine: &MockMachine<SC::Val>) -> RowMajorMatrix<SC::Val> {
+        RowMajorMatrix::new_col(
+            self.values
+                .iter()
+                .map(|&x| SC::Val::from_canonical_u32(x))
+                .collect(),
+        )
+    }
+}
+
+/// Sends column 0 on global bus 0 on the rows where column 1, its `is_real` selector, is one.
+struct MaskedSendChip {
+    rows: Vec<[u32; 2]>,
+}
+
+impl<F> BaseAir<F> for MaskedSendChip {
+    fn width(&self) -> usize {
+        2
+    }
+}
+
+impl<AB: AirBuilder> Air<AB> for MaskedSendChip {
+    fn eval(&self, _builder: &mut AB) {}
+}
+
+impl<SC: StarkConfig> InteractionAir<MockMachine<SC::Val>, SC> for MaskedSendChip {
+    fn global_sends(&self, _machine: &MockMachine<SC::Val>) -> Vec<Interaction<SC::Val>> {
+        vec![
+            Interaction::send_columns(BusArgument::Global(0), &[0], VirtualPairCol::one())
+                .with_is_real(VirtualPairCol::single_main(1)),
+        ]
+    }
+}
+
+impl<SC: StarkConfig> Chip<MockMachine<SC::Val>, SC> for MaskedSendChip {
+    fn generate_trace(&self, _machine: &MockMachine<SC::Val>) -> RowMajorMatrix<SC::Val> {
+        let values = self
+            .rows
+            .iter()
+            .flatten()
+            .map(|&x| SC::Val::from_canonical_u32(x))
+            .collect();
+        RowMajorMatrix::new(values, 2)
+    }
+}
+
+/// A chip of two segments: `DomainId(0)` sends each value of its single column on global bus 0,
+/// and the shorter `DomainId(1)` receives each `(value, multiplicity)` row.
+struct SegmentedChip {
+    sent: Vec<u32>,
+    table: Vec<[u32; 2]>,
+}
+
+impl<F> BaseAir<F> for SegmentedChip {
+    fn width(&self) -> usize {
+        3
+    }
+}
+
+impl<AB: AirBuilder> Air<AB> for SegmentedChip {
+    fn eval(&self, _builder: &mut AB) {}
+}
+
+impl<SC: StarkConfig> InteractionAir<MockMachine<SC::Val>, SC> for SegmentedChip {}
+
+impl<SC: StarkConfig> Chip<MockMachine<SC::Val>, SC> for SegmentedChip {
+    fn generate_trace(&self, machine: &MockMachine<SC::Val>) -> RowMajorMatrix<SC::Val> {
+        let traces = Chip::<_, SC>::generate_traces(self, machine);
+        align_domain_traces(&traces).0
+    }
+
+    fn generate_traces(
+        &self,
+        _machine: &MockMachine<SC::Val>,
+    ) -> Vec<(DomainId, RowMajorMatrix<SC::Val>)> {
+        let sent = self.sent.iter().map(|&x| SC::Val::from_canonical_u32(x));
+        let table = self
+            .table
+            .iter()
+            .flatten()
+            .map(|&x| SC::Val::from_canonical_u32(x));
+        vec![
+            (DomainId(0), RowMajorMatrix::new_col(sent.collect())),
+            (DomainId(1), RowMajorMatrix::new(table.collect(), 2)),
+        ]
+    }
+
+    fn segment_interactions(
+        &self,
+        _machine: &MockMachine<SC::Val>,
+        domain: DomainId,
+    ) -> Vec<(Interaction<SC::Val>, InteractionType)> {
+        let bus = BusArgument::Global(0);
+        match domain {
+            DomainId(0) => vec![(
+                Interaction::send_columns(bus, &[0], VirtualPairCol::one()),
+                InteractionType::GlobalSend,
+            )],
+            _ => vec![(
+                Interaction::with_multiplicity_col(vec![VirtualPairCol::single_main(0)], 1, bus),
+                InteractionType::GlobalReceive,
+            )],
+        }
+    }
+}
+
+/// The verifier's view of `SendReceiveChip`: the same bus interface, with no trace generation.
+struct SendReceiveLayout;
+
+impl<F> BaseAir<F> for SendReceiveLayout {
+    fn width(&self) -> usize {
+        2
+    }
+}
+
+impl<AB: AirBuilder> Air<AB> for SendReceiveLayout {
+    fn eval(&self, _builder: &mut AB) {}
+}
+
+impl<SC: StarkConfig> InteractionAir<MockMachine<SC::Val>, SC> for SendReceiveLayout {
+    fn local_sends(&self) -> Vec<Interaction<SC::Val>> {
+        vec![Interaction::send_columns(
+            BusArgument::Local(0),
+            &[0],
+            VirtualPairCol::one(),
+        )]
+    }
+
+    fn local_receives(&self) -> Vec<Interaction<SC::Val>> {
+        vec![Interaction::send_columns(
+            BusArgument::Local(0),
+            &[1],
+            VirtualPairCol::one(),
+        )]
+    }
+}
+
+/// Columns `value, count`: receives `value` on the global bus `GatedSendChip` sends on, `count`
+/// times, with receives counted positively in its running sum.
+struct ReceivesPositiveTableChip {
+    rows: Vec<[u32; 2]>,
+}
+
+impl<F> BaseAir<F> for ReceivesPositiveTableChip {
+    fn width(&self) -> usize {
+        2
+    }
+}
+
+impl<AB: AirBuilder> Air<AB> for ReceivesPositiveTableChip {
+    fn eval(&self, _builder: &mut AB) {}
+}
+
+impl<SC: StarkConfig> InteractionAir<MockMachine<SC::Val>, SC> for ReceivesPositiveTableChip {
+    fn global_receives(&self, _machine: &MockMachine<SC::Val>) -> Vec<Interaction<SC::Val>> {
+        vec![Interaction::with_multiplicity_col(
+            vec![VirtualPairCol::single_main(0)],
+            1,
+            BusArgument::Global(0),
+        )]
+    }
+
+    fn sign_convention(&self) -> SignConvention {
+        SignConvention::ReceivesPositive
+    }
+}
+
+impl<SC: StarkConfig> Chip<MockMachine<SC::Val>, SC> for ReceivesPositiveTableChip {
+    fn generate_trace(&self, _machine: &MockMachine<SC::Val>) -> RowMajorMatrix<SC::Val> {
+        let values = self
+            .rows
+            .iter()
+            .flatten()
+            .map(|&x| SC::Val::from_canonical_u32(x))
+            .collect();
+        RowMajorMatrix::new(values, 2)
+    }
+}
+
+/// A verifier's view of `SendReceiveChip` which lists its receive before its send, as a layout
+/// out of sync with the chip would.
+struct ReorderedSendReceiveLayout;
+
+impl<F> BaseAir<F> for ReorderedSendReceiveLayout {
+    fn width(&self) -> usize {
+        2
+    }
+}
+
+impl<AB: AirBuilder> Air<AB> for ReorderedSendReceiveLayout {
+    fn eval(&self, _builder: &mut AB) {}
+}
+
+impl<SC: StarkConfig> InteractionAir<MockMachine<SC::Val>, SC> for ReorderedSendReceiveLayout {
+    fn local_sends(&self) -> Vec<Interaction<SC::Val>> {
+        InteractionAir::<_, SC>::local_sends(&SendReceiveLayout)
+    }
+
+    fn local_receives(&self) -> Vec<Interaction<SC::Val>> {
+        InteractionAir::<_, SC>::local_receives(&SendReceiveLayout)
+    }
+
+    fn all_interactions(
+        &self,
+        machine: &MockMachine<SC::Val>,
+    ) -> Vec<(Interaction<SC::Val>, InteractionType)> {
+        let mut interactions =
+            InteractionAir::<_, SC>::all_interactions(&SendReceiveLayout, machine);
+        interactions.reverse();
+        interactions
+    }
+}
+
+/// `SendReceiveChip`, generating its permutation trace as if the two columns were swapped, so that
+/// its send's reciprocals are those of the values it receives.
+struct MiswiredSendChip {
+    rows: Vec<[u32; 2]>,
+}
+
+impl<F> BaseAir<F> for MiswiredSendChip {
+    fn width(&self) -> usize {
+        2
+    }
+}
+
+impl<AB: AirBuilder> Air<AB> for MiswiredSendChip {
+    fn eval(&self, _builder: &mut AB) {}
+}
+
+impl<SC: StarkConfig> InteractionAir<MockMachine<SC::Val>, SC> for MiswiredSendChip {
+    fn local_sends(&self) -> Vec<Interaction<SC::Val>> {
+        InteractionAir::<_, SC>::local_sends(&SendReceiveLayout)
+    }
+
+    fn local_receives(&self) -> Vec<Interaction<SC::Val>> {
+        InteractionAir::<_, SC>::local_receives(&SendReceiveLayout)
+    }
+}
+
+impl<SC: StarkConfig> Chip<MockMachine<SC::Val>, SC> for MiswiredSendChip {
+    fn generate_trace(&self, machine: &MockMachine<SC::Val>) -> RowMajorMatrix<SC::Val> {
+        let chip = SendReceiveChip {
+            rows: self.rows.clone(),
+        };
+        Chip::<_, SC>::generate_trace(&chip, machine)
+    }
+
+    fn generate_permutation_trace(
+        &self,
+        machine: &MockMachine<SC::Val>,
+        _main: &RowMajorMatrix<SC::Val>,
+        random_elements: &[SC::Challenge],
+    ) -> Option<RowMajorMatrix<SC::Challenge>> {
+        let swapped = SendReceiveChip {
+            rows: self.rows.iter().map(|&[a, b]| [b, a]).collect(),
+        };
+        let main = Chip::<_, SC>::generate_trace(&swapped, machine);
+        Some(generate_permutation_trace::<_, SC>(
+            machine,
+            &swapped,
+            &main,
+            random_elements.to_vec(),
+        ))
+    }
+}
+
+fn random_elements() -> Vec<Challenge> {
+    [3, 5, 7].map(Challenge::from_canonical_u32).to_vec()
+}
+
+fn config(log_blowup: usize) -> MyConfig {
+    let perm = Perm16::new_from_rng(4, 22, Mds16::default(), &mut thread_rng());
+    MyConfig::new(
+        MockPcs::new(Radix2Bowers::default(), log_blowup),
+        Challenger::new(perm),
+    )
+}
+
+#[test]
+fn send_receive_chip_balances() {
+    let machine = MockMachine::default();
+    let chip = SendReceiveChip {
+        rows: vec![[1, 3], [2, 1], [3, 4], [4, 2]],
+    };
+    let (perm, cumulative_sum) =
+        run_chip_permutation::<_, _, MyConfig>(&machine, &chip, &random_elements());
+    assert_eq!(perm.height(), 4);
+    assert_eq!(cumulative_sum, Challenge::zero());
+}
+
+#[test]
+fn send_receive_chip_imbalance() {
+    let machine = MockMachine::default();
+    let chip = SendReceiveChip {
+        rows: vec![[1, 3], [2, 1], [3, 4], [4, 4]],
+    };
+    let (_, cumulative_sum) =
+        run_chip_permutation::<_, _, MyConfig>(&machine, &chip, &random_elements());
+    assert_ne!(cumulative_sum, Challenge::zero());
+}
+
+#[test]
+fn local_balance_reports_unmatched_tuple() {
+    let machine = MockMachine::<Val>::default();
+    let balanced = SendReceiveChip {
+        rows: vec![[1, 3], [2, 1], [3, 4], [4, 2]],
+    };
+    assert_eq!(
+        Chip::<_, MyConfig>::check_local_balance(&balanced, &machine),
+        Ok(())
+    );
+
+    // 2 is sent but never received, and 4 received twice.
+    let unbalanced = SendReceiveChip {
+        rows: vec![[1, 3], [2, 1], [3, 4], [4, 4]],
+    };
+    assert_eq!(
+        Chip::<_, MyConfig>::check_local_balance(&unbalanced, &machine),
+        Err(LocalImbalance {
+            bus: 0,
+            fields: vec![2],
+            multiplicity: 1,
+        })
+    );
+}
+
+#[test]
+fn fixed_table_chip_balances() {
+    let machine = MockMachine::default();
+    // Looks up 3, 1, 3, 7, 0, 0, 5 and 2, so entry `n` is received as often as it is looked up.
+    let chip = FixedTableChip {
+        rows: vec![
+            [3, 2],
+            [1, 1],
+            [3, 1],
+            [7, 2],
+            [0, 0],
+            [0, 1],
+            [5, 0],
+            [2, 1],
+        ],
+    };
+    let (_, cumulative_sum) =
+        run_chip_permutation::<_, _, MyConfig>(&machine, &chip, &random_elements());
+    assert_eq!(cumulative_sum, Challenge::zero());
+}
+
+#[test]
+fn chip_constraint_degrees() {
+    let machine = MockMachine::default();
+    let send_receive = SendReceiveChip { rows: vec![] };
+    // The reciprocal constraints `(\alpha + \beta x) q = 1` and the first row constraint on the
+    // running sum are quadratic, and the AIR is empty.
+    assert_eq!(
+        Chip::<_, MyConfig>::max_constraint_degree(&send_receive, &machine),
+        2
+    );
+    // The count `mult \cdot is_real` is quadratic, so the first row constraint
+    // `is_first_row (\phi - mult \cdot is_real \cdot q)` has degree 4, above the AIR's 2.
+    assert_eq!(
+        Chip::<_, MyConfig>::max_constraint_degree(&MulChip, &machine),
+        4
+    );
+
+    let chips: [&dyn Chip<_, MyConfig>; 2] = [&send_receive, &MulChip];
+    assert_eq!(machine.max_constraint_degree(&chips), 4);
+}
+
+#[test]
+fn padding_keeps_cumulative_sum() {
+    let machine = MockMachine::default();
+    let chip = GatedSendChip {
+        values: vec![4, 8, 15, 16, 23],
+    };
+    let mut main = Chip::<_, MyConfig>::generate_trace(&chip, &machine);
+    let perm = generate_permutation_trace::<_, MyConfig>(&machine, &chip, &main, random_elements());
+    let unpadded_sum = cumulative_sum_from_perm(&perm);
+
+    Chip::<_, MyConfig>::pad_trace(&chip, &machine, &mut main);
+    assert_eq!(main.height(), 8);
+    let padded_perm =
+        generate_permutation_trace::<_, MyConfig>(&machine, &chip, &main, random_elements());
+    // The running sum is flat across the padding rows.
+    for n in 5..8 {
+        assert_eq!(padded_perm.row_slice(n)[1], unpadded_sum);
+    }
+    assert_eq!(cumulative_sum_from_perm(&padded_perm), unpadded_sum);
+    assert_ne!(unpadded_sum, Challenge::zero());
+}
+
+#[test]
+#[should_panic(expected = "nonzero count on padding row 5")]
+fn padding_rejects_ungated_counts() {
+    let machine = MockMachine::default();
+    // Every row sends and receives, padding rows included.
+    let chip = SendReceiveChip {
+        rows: vec![[1, 1]; 5],
+    };
+    let mut main = Chip::<_, MyConfig>::generate_trace(&chip, &machine);
+    Chip::<_, MyConfig>::pad_trace(&chip, &machine, &mut main);
+}
+
+#[test]
+fn trace_cache_hit_and_miss() {
+    let path = std::env::temp_dir().join(format!("valida-trace-cache-{}", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let chip = CountingChip::default();
+
+    let machine = MockMachine::with_trace_cache_state(vec![1]);
+    let generated = machine.generate_or_load_trace::<MyConfig, _>(&chip, &path);
+    assert_eq!(chip.generations.load(Ordering::Relaxed), 1);
+    let loaded = machine.generate_or_load_trace::<MyConfig, _>(&chip, &path);
+    assert_eq!(chip.generations.load(Ordering::Relaxed), 1);
+    assert_eq!(loaded.values, generated.values);
+
+    // A different machine state must not reuse the cached trace.
+    let machine = MockMachine::with_trace_cache_state(vec![2]);
+    machine.generate_or_load_trace::<MyConfig, _>(&chip, &path);
+    assert_eq!(chip.generations.load(Ordering::Relaxed), 2);
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn logup_backend_matches_reciprocals() {
+    let reciprocals = MockMachine::default();
+    let logup = MockMachine::with_lookup_backend(LookupBackend::LogUp { max_degree: 3 });
+    for rows in [
+        vec![[1, 3], [2, 1], [3, 4], [4, 2]],
+        vec![[1, 3], [2, 1], [3, 4], [4, 4]],
+    ] {
+        let chip = SendReceiveChip { rows };
+        let (perm, cumulative_sum) =
+            run_chip_permutation::<_, _, MyConfig>(&reciprocals, &chip, &random_elements());
+        let (logup_perm, logup_cumulative_sum) =
+            run_chip_permutation::<_, _, MyConfig>(&logup, &chip, &random_elements());
+        assert_eq!(logup_cumulative_sum, cumulative_sum);
+        // The send and receive share one fraction column under LogUp.
+        assert_eq!(perm.width(), 3);
+        assert_eq!(logup_perm.width(), 2);
+    }
+}
+
+fn global_cumulative_sums(sent: Vec<u32>, received: Vec<u32>) -> [Challenge; 2] {
+    let machine = MockMachine::default();
+    let sender = GatedSendChip { values: sent };
+    let receiver = GlobalReceiveChip { values: received };
+    let chips: [&dyn Chip<_, MyConfig>; 2] = [&sender, &receiver];
+    chips.map(|chip| {
+        let main = chip.generate_trace(&machine);
+        let perm = generate_permutation_trace(&machine, chip, &main, random_elements());
+        cumulative_sum_from_perm(&perm)
+    })
+}
+
+#[test]
+fn cumulative_sums_cancel_across_chips() {
+    let machine = MockMachine::<Val>::default();
+    let sums = global_cumulative_sums(vec![4, 8, 15, 16], vec![16, 4, 15, 8]);
+    assert!(sums.iter().all(|sum| !sum.is_zero()));
+    assert_eq!(machine.aggregate_cumulative_sum(&sums), Challenge::zero());
+    assert_eq!(verify_aggregate_cumulative_sum(&machine, &sums), Ok(()));
+
+    let sums = global_cumulative_sums(vec![4, 8, 15, 16], vec![16, 4, 15, 9]);
+    let total = machine.aggregate_cumulative_sum(&sums);
+    assert_ne!(total, Challenge::zero());
+    assert_eq!(verify_aggregate_cumulative_sum(&machine, &sums), Err(total));
+}
+
+#[test]
+fn send_gated_by_public_value() {
+    let verify_with_gate = |gate| {
+        let machine = MockMachine::with_public_values(vec![Val::from_canonical_u32(gate)]);
+        let sender = PublicGatedSendChip {
+            values: vec![4, 8, 15, 16],
+        };
+        let receiver = GlobalReceiveChip {
+            values: vec![16, 4, 15, 8],
+        };
+        let sums = [
+            run_chip_permutation::<_, _, MyConfig>(&machine, &sender, &random_elements()).1,
+            run_chip_permutation::<_, _, MyConfig>(&machine, &receiver, &random_elements()).1,
+        ];
+        verify_aggregate_cumulative_sum(&machine, &sums)
+    };
+    assert_eq!(verify_with_gate(1), Ok(()));
+    assert!(verify_with_gate(0).is_err());
+}
+
+#[test]
+fn composite_matches_separate_chips() {
+    let machine = MockMachine::default();
+    for received in [vec![5, 7, 7, 8], vec![5, 7, 6, 8]] {
+        let sender = BoundedCountChip {
+            rows: vec![[5, 1], [6, 0], [7, 2], [8, 1]],
+            bound: CountBound::AtMost(2),
+        };
+        let receiver = GlobalReceiveChip { values: received };
+        let separate = [
+            run_chip_permutation::<_, _, MyConfig>(&machine, &sender, &random_elements()).1,
+            run_chip_permutation::<_, _, MyConfig>(&machine, &receiver, &random_elements()).1,
+        ];
+
+        let composite = Composite::new(sender, receiver);
+        let interactions = InteractionAir::<_, MyConfig>::all_interactions(&composite, &machine);
+        assert_eq!(interactions.len(), 2);
+        let (_, combined) =
+            run_chip_permutation::<_, _, MyConfig>(&machine, &composite, &random_elements());
+        assert_eq!(combined, machine.aggregate_cumulative_sum(&separate));
+    }
+}
+
+#[test]
+fn challenges_bind_main_commitments() {
+    let machine = MockMachine::<Val>::default();
+    let perm = Perm16::new_from_rng(4, 22, Mds16::default(), &mut thread_rng());
+    let challenger = Challenger::new(perm);
+    let derive = |commitment: [Val; 8]| -> Vec<Challenge> {
+        machine.derive_permutation_challenges(
+            &mut challenger.clone(),
+            &[commitment],
+            3,
+            LookupField::Extension,
+        )
+    };
+
+    let commitment = [1, 2, 3, 4, 5, 6, 7, 8].map(Val::from_canonical_u32);
+    let mut changed = commitment;
+    changed[7] = Val::zero();
+    assert_eq!(derive(commitment), derive(commitment));
+    assert_ne!(derive(commitment), derive(changed));
+}
+
+#[test]
+fn challenges_bind_public_values() {
+    let perm = Perm16::new_from_rng(4, 22, Mds16::default(), &mut thread_rng());
+    let challenger = Challenger::new(perm);
+    let commitment = [1, 2, 3, 4, 5, 6, 7, 8].map(Val::from_canonical_u32);
+    let derive = |public_values: Vec<u32>| -> Vec<Challenge> {
+        let machine = MockMachine::with_public_values(
+            public_values
+                .into_iter()
+                .map(Val::from_canonical_u32)
+                .collect(),
+        );
+        machine.derive_permutation_challenges(
+            &mut challenger.clone(),
+            &[commitment],
+            3,
+            LookupField::Extension,
+        )
+    };
+
+    assert_eq!(derive(vec![1, 0]), derive(vec![1, 0]));
+    assert_ne!(derive(vec![1, 0]), derive(vec![0, 0]));
+}
+
+#[test]
+fn cached_layout_matches_per_call_columns() {
+    let machine = MockMachine::default();
+    let chip = FixedTableChip {
+        rows: vec![
+            [3, 2],
+            [1, 1],
+            [3, 1],
+            [7, 2],
+            [0, 0],
+            [0, 1],
+            [5, 0],
+            [2, 1],
+        ],
+    };
+    let layout = Chip::<_, MyConfig>::interaction_layout(&chip, &machine);
+    assert_eq!(
+        layout.columns,
+        permutation_columns::<_, MyConfig, _>(&machine, &chip)
+    );
+
+    let main = Chip::<_, MyConfig>::generate_trace(&chip, &machine);
+    let cached = generate_permutation_trace_with_layout::<_, MyConfig>(
+        &machine,
+        &chip,
+        &main,
+        random_elements(),
+        &layout,
+    );
+    let perm = generate_permutation_trace::<_, MyConfig>(&machine, &chip, &main, random_elements());
+    assert_eq!(cached.values, perm.values);
+}
+
+#[test]
+fn cost_report_matches_permutation_widths() {
+    let machine = MockMachine::default();
+    let send_receive = SendReceiveChip {
+        rows: vec![[1, 3], [2, 1], [3, 4], [4, 2]],
+    };
+    let table = FixedTableChip {
+        rows: vec![
+            [3, 2],
+            [1, 1],
+            [3, 1],
+            [7, 2],
+            [0, 0],
+            [0, 1],
+            [5, 0],
+            [2, 1],
+        ],
+    };
+    let receiver = GlobalReceiveChip {
+        values: vec![16, 4, 15, 8],
+    };
+    let chips: [&dyn Chip<_, MyConfig>; 3] = [&send_receive, &table, &receiver];
+    let report = machine.permutation_cost_report(&chips);
+
+    assert_eq!(report.chips.len(), chips.len());
+    let mut extension_columns = 0;
+    for (chip, cost) in chips.iter().zip(&report.chips) {
+        let main = chip.generate_trace(&machine);
+        let perm = generate_permutation_trace(&machine, *chip, &main, random_elements());
+        assert_eq!(cost.main_width, main.width());
+        assert_eq!(cost.permutation_width, perm.width());
+        extension_columns += perm.width();
+    }
+    assert_eq!(report.extension_columns(), extension_columns);
+    assert_eq!(
+        report.chips[0]
+            .interactions_per_bus
+            .get(&BusArgument::Local(0)),
+        Some(&2)
+    );
+}
+
+#[test]
+fn sorted_memory_argument_closes() {
+    let machine = MockMachine::default();
+    let access = |timestamp, addr, value, is_write| Access {
+        addr,
+        timestamp,
+        value,
+        is_write,
+    };
+    let accesses = vec![
+        access(1, 1, 10, true),
+        access(2, 3, 7, true),
+        access(3, 1, 10, false),
+        access(4, 1, 11, true),
+        access(5, 3, 7, false),
+        access(6, 1, 11, false),
+        access(7, 3, 8, true),
+        access(8, 3, 8, false),
+    ];
+    let log = AccessLogChip {
+        accesses: accesses.clone(),
+    };
+    let mut memory = SortedMemoryChip::new(BusArgument::Global(0));
+    memory.accesses = accesses;
+
+    let sums = [
+        run_chip_permutation::<_, _, MyConfig>(&machine, &log, &random_elements()).1,
+        run_chip_permutation::<_, _, MyConfig>(&machine, &memory, &random_elements()).1,
+    ];
+    assert!(sums.iter().all(|sum| !sum.is_zero()));
+    assert_eq!(verify_aggregate_cumulative_sum(&machine, &sums), Ok(()));
+}
+
+#[test]
+fn range_check_rejects_out_of_range_values() {
+    let machine = MockMachine::default();
+    let verify_range = |values| {
+        let sender = RangeSendChip { values };
+        let mut table = RangeCheckChip::<8>::new(0);
+        table.record_sends::<_, MyConfig>(&machine, &[&sender]);
+        let sums = [
+            run_chip_permutation::<_, _, MyConfig>(&machine, &sender, &random_elements()).1,
+            run_chip_permutation::<_, _, MyConfig>(&machine, &table, &random_elements()).1,
+        ];
+        verify_aggregate_cumulative_sum(&machine, &sums)
+    };
+    assert_eq!(verify_range(vec![0, 3, 255, 3]), Ok(()));
+    assert!(verify_range(vec![0, 3, 256, 3]).is_err());
+}
+
+#[test]
+fn is_real_selector_masks_rows() {
+    let machine = MockMachine::default();
+    let masked = MaskedSendChip {
+        rows: vec![[4, 1], [9, 0], [8, 1], [2, 0]],
+    };
+    let filtered = MaskedSendChip {
+        rows: masked
+            .rows
+            .iter()
+            .copied()
+            .filter(|&[_, is_real]| is_real == 1)
+            .collect(),
+    };
+    let (_, masked_sum) =
+        run_chip_permutation::<_, _, MyConfig>(&machine, &masked, &random_elements());
+    let (_, filtered_sum) =
+        run_chip_permutation::<_, _, MyConfig>(&machine, &filtered, &random_elements());
+    assert_eq!(masked_sum, filtered_sum);
+}
+
+#[test]
+fn segments_have_their_own_permutation_traces() {
+    let machine = MockMachine::default();
+    let chip = SegmentedChip {
+        sent: vec![4, 9, 4, 9],
+        table: vec![[4, 2], [9, 2]],
+    };
+    let traces = Chip::<_, MyConfig>::generate_traces(&chip, &machine);
+    let perms = generate_segment_permutation_traces::<_, MyConfig>(
+        &machine,
+        &chip,
+        &traces,
+        random_elements(),
+    );
+    assert_eq!(perms.len(), 2);
+    assert_eq!(perms[0].height(), 4);
+    assert_eq!(perms[1].height(), 2);
+
+    // The sending segment matches a standalone chip sending the same values.
+    let standalone = MaskedSendChip {
+        rows: vec![[4, 1], [9, 1], [4, 1], [9, 1]],
+    };
+    let (_, standalone_sum) =
+        run_chip_permutation::<_, _, MyConfig>(&machine, &standalone, &random_elements());
+    assert_eq!(cumulative_sum_from_perm(&perms[0]), standalone_sum);
+    assert_ne!(cumulative_sum_from_perm(&perms[1]), Challenge::zero());
+    assert_eq!(segment_cumulative_sum(&perms), Challenge::zero());
+}
+
+#[test]
+fn layout_only_verifier_accepts_chip_traces() {
+    let machine = MockMachine::default();
+    let chip = SendReceiveChip {
+        rows: vec![[1, 3], [2, 1], [3, 4], [4, 2]],
+    };
+    let main = Chip::<_, MyConfig>::generate_trace(&chip, &machine);
+    let (perm, cumulative_sum) =
+        run_chip_permutation::<_, _, MyConfig>(&machine, &chip, &random_elements());
+    assert_eq!(cumulative_sum, Challenge::zero());
+
+    // `SendReceiveLayout` is no `Chip`, yet checks the full chip's traces from its layout alone.
+    assert_eq!(
+        permutation_columns::<_, MyConfig, _>(&machine, &SendReceiveLayout),
+        permutation_columns::<_, MyConfig, _>(&machine, &chip),
+    );
+    assert_eq!(
+        permutation_trace_width::<_, MyConfig, _>(&machine, &SendReceiveLayout),
+        perm.width()
+    );
+    check_constraints::<_, _, MyConfig>(
+        &machine,
+        &SendReceiveLayout,
+        &main,
+        &perm,
+        &random_elements(),
+    );
+}
+
+#[test]
+fn opposite_sign_conventions_close_the_aggregate() {
+    let machine = MockMachine::<Val>::default();
+    let sender = GatedSendChip {
+        values: vec![4, 8, 15, 8, 4, 8, 16, 15],
+    };
+    let table = ReceivesPositiveTableChip {
+        rows: vec![[4, 2], [8, 3], [15, 2], [16, 1]],
+    };
+    let (_, sent) = run_chip_permutation::<_, _, MyConfig>(&machine, &sender, &random_elements());
+    let (_, received) =
+        run_chip_permutation::<_, _, MyConfig>(&machine, &table, &random_elements());
+    // Each chip counts its own side of the bus positively.
+    assert_ne!(sent, Challenge::zero());
+    assert_eq!(sent, received);
+
+    let chips: [&dyn Chip<_, MyConfig>; 2] = [&sender, &table];
+    let sums = sign_conventions(chips)
+        .into_iter()
+        .zip([sent, received])
+        .map(|(sign_convention, sum)| sign_convention.orient(sum))
+        .collect::<Vec<_>>();
+    assert_eq!(verify_aggregate_cumulative_sum(&machine, &sums), Ok(()));
+}
+
+#[test]
+fn all_permutation_traces_share_challenges() {
+    let machine = MockMachine::<Val>::default();
+    let sender = GatedSendChip {
+        values: vec![4, 8, 15, 8, 4, 8, 16, 15],
+    };
+    let receiver = GlobalReceiveChip {
+        values: vec![8, 15, 16, 4],
+    };
+    let table = ReceivesPositiveTableChip {
+        rows: vec![[4, 1], [8, 2], [15, 1], [16, 0]],
+    };
+    let chips: [&dyn Chip<_, MyConfig>; 3] = [&sender, &receiver, &table];
+    let mains = chips.map(|chip| chip.generate_trace(&machine));
+
+    let perms = machine.generate_all_permutation_traces(&chips, &mains, &random_elements());
+    assert_eq!(perms.len(), 3);
+    for ((chip, main), (perm, cumulative_sum)) in chips.iter().zip(&mains).zip(&perms) {
+        let expected = generate_permutation_trace(&machine, *chip, main, random_elements());
+        assert_eq!(perm.values, expected.values);
+        assert_eq!(*cumulative_sum, cumulative_sum_from_perm(&expected));
+        assert_ne!(*cumulative_sum, Challenge::zero());
+    }
+
+    let sums = sign_conventions(chips)
+        .into_iter()
+        .zip(&perms)
+        .map(|(sign_convention, (_, sum))| sign_convention.orient(*sum))
+        .collect::<Vec<_>>();
+    assert_eq!(verify_aggregate_cumulative_sum(&machine, &sums), Ok(()));
+}
+
+#[test]
+fn row_contributions_add_up_to_running_sum() {
+    let machine = MockMachine::default();
+    let chip = FixedTableChip {
+        rows: vec![
+            [3, 2],
+            [1, 1],
+            [3, 1],
+            [7, 2],
+            [0, 0],
+            [0, 1],
+            [5, 0],
+            [2, 1],
+        ],
+    };
+    let main = Chip::<_, MyConfig>::generate_trace(&chip, &machine);
+    let (perm, contributions) = generate_permutation_trace_with_trace::<_, MyConfig>(
+        &machine,
+        &chip,
+        &main,
+        random_elements(),
+    );
+    assert_eq!(
+        perm.values,
+        generate_permutation_trace::<_, MyConfig>(&machine, &chip, &main, random_elements()).values
+    );
+    assert_eq!(contributions.len(), perm.height());
+
+    let mut phi = Challenge::zero();
+    for (n, contribution) in contributions.iter().enumerate() {
+        assert_eq!(contribution.row, n);
+        assert_eq!(contribution.deltas.len(), 2);
+        phi += contribution.delta();
+        assert_eq!(*perm.row_slice(n).last().unwrap(), phi);
+    }
+    assert_eq!(phi, cumulative_sum_from_perm(&perm));
+    assert!(contributions
+        .iter()
+        .any(|contribution| !contribution.delta().is_zero()));
+}
+
+#[test]
+fn reordered_interactions_break_layout_consistency() {
+    let machine = MockMachine::default();
+    let chip = SendReceiveChip {
+        rows: vec![[1, 3], [2, 1], [3, 4], [4, 2]],
+    };
+    let main = Chip::<_, MyConfig>::generate_trace(&chip, &machine);
+    assert_eq!(
+        check_layout_consistency::<_, _, _, MyConfig>(&machine, &chip, &SendReceiveLayout, &main),
+        Ok(())
+    );
+    assert_eq!(
+        check_layout_consistency::<_, _, _, MyConfig>(
+            &machine,
+            &chip,
+            &ReorderedSendReceiveLayout,
+            &main
+        ),
+        Err(LayoutMismatch::Interaction(0))
+    );
+}
+
+#[test]
+fn interaction_free_chip_needs_no_cumulative_sum() {
+    let machine = MockMachine::default();
+    let chip = CountingChip::default();
+    let main = Chip::<_, MyConfig>::generate_trace(&chip, &machine);
+    let perm = generate_permutation_trace::<_, MyConfig>(&machine, &chip, &main, random_elements());
+    assert_eq!(perm.width(), 1);
+    assert_eq!(
+        try_check_constraints::<_, _, MyConfig>(
+            &machine,
+            &chip,
+            &main,
+            &perm,
+            &random_elements(),
+            None
+        ),
+        Ok(())
+    );
+
+    let chip = SendReceiveChip {
+        rows: vec![[1, 3], [2, 1], [3, 4], [4, 2]],
+    };
+    let main = Chip::<_, MyConfig>::generate_trace(&chip, &machine);
+    let perm = generate_permutation_trace::<_, MyConfig>(&machine, &chip, &main, random_elements());
+    assert_eq!(
+        try_check_constraints::<_, _, MyConfig>(
+            &machine,
+            &chip,
+            &main,
+            &perm,
+            &random_elements(),
+            None
+        ),
+        Err(MissingCumulativeSum {
+            chip: type_name::<SendReceiveChip>()
+        })
+    );
+}
+
+#[test]
+fn prove_and_verify_finds_the_failing_constraint() {
+    let machine = MockMachine::default();
+    let rows = vec![[1, 3], [2, 1], [3, 4], [4, 2]];
+    let chip = SendReceiveChip { rows: rows.clone() };
+    assert_eq!(
+        prove_and_verify_chip::<_, _, MyConfig>(&chip, &machine),
+        Ok(())
+    );
+
+    let chip = MiswiredSendChip { rows };
+    let failure = prove_and_verify_chip::<_, _, MyConfig>(&chip, &machine).unwrap_err();
+    assert_eq!(
+        failure,
+        ConstraintFailure {
+            row: 0,
+            constraint: 0,
+            provenance: Some(ConstraintProvenance {
+                chip: type_name::<MiswiredSendChip>(),
+                interaction_index: Some(0),
+                bus: Some(BusArgument::Local(0)),
+                kind: ConstraintKind::Reciprocal,
+            }),
+        }
+    );
+    assert!(failure
+        .to_string()
+        .starts_with("Reciprocal constraint 0 of "));
+}
+
+#[test]
+fn seeded_challenges_are_reproducible() {
+    let challenges = |seed| {
+        let mut source = <SeededChallenges as ChallengeSource<Val>>::from_seed(seed);
+        sample_permutation_challenges::<Val, Challenge, _>(&mut source, 3)
+    };
+    assert_eq!(challenges(7), challenges(7));
+    assert_ne!(challenges(7), challenges(8));
+
+    let machine = MockMachine::default();
+    let chip = SendReceiveChip {
+        rows: vec![[1, 3], [2, 1], [3, 4], [4, 2]],
+    };
+    let main = Chip::<_, MyConfig>::generate_trace(&chip, &machine);
+    let perm =
+        |seed| generate_permutation_trace::<_, MyConfig>(&machine, &chip, &main, challenges(seed));
+    assert_eq!(perm(7).values, perm(7).values);
+}
+
+#[test]
+fn proved_chip_verifies() {
+    let machine = MockMachine::default();
+    let chip = SendReceiveChip {
+        rows: vec![[1, 3], [2, 1], [3, 4], [4, 2]],
+    };
+    let config = config(1);
+    let proof = prove_chip(&config, &machine, &chip);
+    assert!(verify_chip(&config, &machine, &chip, &proof).is_ok());
+    assert_eq!(proof.chip_proofs[0].cumulative_sum, Challenge::zero());
+
+    // The mock machine's own proof covers no chips, and does not verify.
+    let empty = machine.prove(&config);
+    assert!(empty.chip_proofs.is_empty());
+    assert!(machine.verify(&config, &empty).is_err());
+}
+
+#[test]
+fn degree_four_permutation_constraints_verify() {
+    let machine = MockMachine::default();
+    let chip = GatedMultiplicityChip {
+        rows: vec![[4, 1], [8, 2], [15, 0], [16, 3]],
+        budget: None,
+    };
+    // The first row constraint `is_first_row (\phi - mult \cdot is_real \cdot q)` has degree 4,
+    // above the AIR's, so the quotient needs four chunks rather than two.
+    assert_eq!(
+        Chip::<_, MyConfig>::max_constraint_degree(&chip, &machine),
+        4
+    );
+    assert_eq!(
+        get_chip_log_quotient_degree::<_, MyConfig, _>(&machine, &chip),
+        2
+    );
+    let config = config(2);
+    let proof = prove_chip(&config, &machine, &chip);
+    assert_eq!(
+        proof.chip_proofs[0].opened_values.quotient_chunks.len(),
+        4 * <Challenge as AbstractExtensionField<Val>>::D
+    );
+    assert!(verify_chip(&config, &machine, &chip, &proof).is_ok());
+}
+
+#[test]
+fn bundles_above_degree_three_verify() {
+    let machine = MockMachine::default();
+    let chip = DoubleSendReceiveChip {
+        rows: vec![[1, 2, 3, 4], [3, 4, 1, 2], [5, 6, 6, 5], [7, 8, 8, 7]],
+        bundle_degree: Some(4),
+    };
+    // The first three reciprocals share a column under a degree 4 bundle constraint.
+    assert_eq!(
+        permutation_trace_width::<_, MyConfig, _>(&machine, &chip),
+        3
+    );
+    assert_eq!(
+        Chip::<_, MyConfig>::max_constraint_degree(&chip, &machine),
+        4
+    );
+    let config = config(2);
+    let proof = prove_chip(&config, &machine, &chip);
+    assert!(verify_chip(&config, &machine, &chip, &proof).is_ok());
+    assert_eq!(proof.chip_proofs[0].cumulative_sum, Challenge::zero());
+}
+
+#[test]
+fn logup_backend_above_degree_three_verifies() {
+    let machine = MockMachine::with_lookup_backend(LookupBackend::LogUp { max_degree: 4 });
+    let chip = DoubleSendReceiveChip {
+        rows: vec![[1, 2, 3, 4], [3, 4, 1, 2], [5, 6, 6, 5], [7, 8, 8, 7]],
+        bundle_degree: None,
+    };
+    // The machine's budget bundles the chip, and its degree 4 sizes the quotient.
+    assert_eq!(
+        get_chip_log_quotient_degree::<_, MyConfig, _>(&machine, &chip),
+        2
+    );
+    let config = config(2);
+    let proof = prove_chip(&config, &machine, &chip);
+    assert!(verify_chip(&config, &machine, &chip, &proof).is_ok());
+    assert_eq!(proof.chip_proofs[0].cumulative_sum, Challenge::zero());
+}
+
+#[test]
+fn cancelled_field_has_no_column_on_either_side() {
+    let machine = MockMachine::default();
+    let chip = CancellingFieldChip;
+    // Only the send of column 0 owns a reciprocal column, for the verifier as for the prover.
+    assert_eq!(
+        permutation_columns::<_, MyConfig, _>(&machine, &chip),
+        vec![Some(0), None]
+    );
+    assert_eq!(
+        permutation_trace_width::<_, MyConfig, _>(&machine, &chip),
+        2
+    );
+    let main = Chip::<_, MyConfig>::generate_trace(&chip, &machine);
+    let perm = generate_permutation_trace::<_, MyConfig>(&machine, &chip, &main, random_elements());
+    assert_eq!(perm.width(), 2);
+
+    let config = config(1);
+    let proof = prove_chip(&config, &machine, &chip);
+    assert!(verify_chip(&config, &machine, &chip, &proof).is_ok());
+}
+
+#[test]
+fn machine_balance_reads_interactions_not_columns() {
+    // The bundled chip's reciprocals share its permutation columns, so its buses can only be
+    // summed from the interactions themselves.
+    let machine = MockMachine::with_lookup_backend(LookupBackend::LogUp { max_degree: 4 });
+    let bundled = DoubleSendReceiveChip {
+        rows: vec![[1, 2, 3, 4], [3, 4, 1, 2], [5, 6, 6, 5], [7, 8, 8, 7]],
+        bundle_degree: None,
+    };
+    let report = |received: Vec<u32>| {
+        let sender = GatedSendChip {
+            values: vec![4, 8, 15, 16],
+        };
+        let receiver = GlobalReceiveChip { values: received };
+        let chips: [&dyn Chip<_, MyConfig>; 3] = [&sender, &receiver, &bundled];
+        let mains = chips
+            .iter()
+            .map(|chip| chip.generate_trace(&machine))
+            .collect::<Vec<_>>();
+        assert_machine_balanced(&machine, &chips, &mains, &random_elements())
+    };
+
+    assert_eq!(report(vec![16, 4, 15, 8]), Ok(()));
+    assert_eq!(
+        report(vec![16, 4, 15, 9]),
+        Err(vec![BusArgument::Global(0)])
+    );
+}
+
+#[test]
+fn table_listing_a_key_twice_is_flagged() {
+    let machine = MockMachine::default();
+    let check = |keys: Vec<u32>| {
+        let chip = KeyTableChip {
+            keys,
+            rotated: false,
+        };
+        let main = Chip::<_, MyConfig>::generate_trace(&chip, &machine);
+        check_provided_once::<_, MyConfig>(&machine, &chip, &main)
+    };
+
+    assert_eq!(check(vec![0x10, 0x20, 0x30, 0x40]), Ok(()));
+    assert_eq!(
+        check(vec![0x10, 0x20, 0x10, 0x40]),
+        Err(vec![DuplicateEntry {
+            interaction: 0,
+            first_row: 0,
+            row: 2,
+        }])
+    );
+}
+
+#[test]
+fn provided_once_reads_rotated_fields() {
+    let machine = MockMachine::default();
+    let check = |keys: Vec<u32>| {
+        let chip = KeyTableChip {
+            keys,
+            rotated: true,
+        };
+        let main = Chip::<_, MyConfig>::generate_trace(&chip, &machine);
+        check_provided_once::<_, MyConfig>(&machine, &chip, &main)
+    };
+
+    // The pairs `(1, 1), (1, 2), (2, 3), (3, 1)` are distinct though the key 1 repeats.
+    assert_eq!(check(vec![1, 1, 2, 3]), Ok(()));
+    // The last row pairs its key with the first row's, repeating the pair `(1, 1)` of row 0.
+    assert_eq!(
+        check(vec![1, 1, 5, 1]),
+        Err(vec![DuplicateEntry {
+            interaction: 0,
+            first_row: 0,
+            row: 3,
+        }])
+    );
+}
+
+#[test]
+fn streamed_blocks_match_permutation_trace() {
+    let machine = MockMachine::default();
+    let chip = SendReceiveChip {
+        rows: vec![[1, 3], [2, 1], [3, 4], [4, 2]],
+    };
+    let main = Chip::<_, MyConfig>::generate_trace(&chip, &machine);
+    let blocks = main
+        .values
+        .chunks(2 * 2)
+        .map(|block| RowMajorMatrix::new(block.to_vec(), 2))
+        .collect::<Vec<_>>();
+
+    let mut streamed = vec![];
+    let cumulative_sum = generate_permutation_trace_streaming::<_, MyConfig, _, _>(
+        &machine,
+        &chip,
+        blocks,
+        random_elements(),
+        |block| streamed.extend(block.values),
+    );
+    let perm = generate_permutation_trace::<_, MyConfig>(&machine, &chip, &main, random_elements());
+    assert_eq!(streamed, perm.values);
+    assert_eq!(cumulative_sum, cumulative_sum_from_perm(&perm));
+}
+
+#[test]
+#[should_panic(expected = "streamed permutation traces do not support bundled reciprocals")]
+fn streaming_logup_backend() {
+    let machine = MockMachine::with_lookup_backend(LookupBackend::LogUp { max_degree: 3 });
+    let chip = SendReceiveChip {
+        rows: vec![[1, 2], [2, 1]],
+    };
+    let main = Chip::<_, MyConfig>::generate_trace(&chip, &machine);
+    generate_permutation_trace_streaming::<_, MyConfig, _, _>(
+        &machine,
+        &chip,
+        [main],
+        random_elements(),
+        |_| {},
+    );
+}
+
+#[test]
+#[should_panic(expected = "streamed permutation traces do not support bundled reciprocals")]
+fn streaming_bundled_reciprocals() {
+    let machine = MockMachine::default();
+    let chip = DoubleSendReceiveChip {
+        rows: vec![[1, 2, 3, 4], [3, 4, 1, 2]],
+        bundle_degree: Some(3),
+    };
+    PermutationTraceBuilder::<MyConfig>::new(&machine, &chip, &random_elements());
+}
+
+#[test]
+fn blinding_rows_follow_the_chip_seed() {
+    let blinding_values = |chip: &dyn Chip<MockMachine<Val>, MyConfig>| {
+        chip.blinding_rows(4).map(|rows| {
+            assert_eq!((rows.width(), rows.height()), (2, 4));
+            rows.values
+        })
+    };
+
+    let rows = blinding_values(&BlindedChip { seed: 1 }).unwrap();
+    assert_eq!(
+        blinding_values(&BlindedChip { seed: 1 }),
+        Some(rows.clone())
+    );
+    let other = blinding_values(&BlindedChip { seed: 2 }).unwrap();
+    assert!(rows.iter().zip(&other).all(|(a, b)| a != b));
+
+    let unblinded = SendReceiveChip { rows: vec![[1, 1]] };
+    assert_eq!(blinding_values(&unblinded), None);
+}
+
+#[test]
+fn permutation_trace_phases_emit_spans() {
+    let machine = MockMachine::default();
+    let chip = SendReceiveChip {
+        rows: vec![[1, 2], [2, 1]],
+    };
+    let main = Chip::<_, MyConfig>::generate_trace(&chip, &machine);
+    let names = Arc::new(Mutex::new(vec![]));
+    tracing::subscriber::with_default(SpanNames(names.clone()), || {
+        generate_permutation_trace::<_, MyConfig>(&machine, &chip, &main, random_elements())
+    });
+    assert_eq!(
+        *names.lock().unwrap(),
+        ["reduce rows", "batch inverse", "running sum"]
+    );
+}
+
+#[test]
+fn tampered_cumulative_sum_fails_verification() {
+    let machine = MockMachine::default();
+    let chip = SendReceiveChip {
+        rows: vec![[1, 3], [2, 1], [3, 4], [4, 2]],
+    };
+    let config = config(1);
+    let mut proof = prove_chip(&config, &machine, &chip);
+    assert!(verify_chip(&config, &machine, &chip, &proof).is_ok());
+
+    // The last row constraint binds `phi` to the claimed sum, which no longer matches.
+    proof.chip_proofs[0].cumulative_sum += Challenge::one();
+    assert!(matches!(
+        verify_chip(&config, &machine, &chip, &proof),
+        Err(VerificationError::OodEvaluationMismatch)
+    ));
+}
+
+#[test]
+fn cumulative_sums_are_checked_against_opened_rows() {
+    let machine = MockMachine::default();
+    let send_receive = SendReceiveChip {
+        rows: vec![[1, 3], [2, 1], [3, 4], [4, 2]],
+    };
+    let table = FixedTableChip {
+        rows: vec![
+            [3, 2],
+            [1, 1],
+            [3, 1],
+            [7, 2],
+            [0, 0],
+            [0, 1],
+            [5, 0],
+            [2, 1],
+        ],
+    };
+    let sender = GatedSendChip {
+        values: vec![4, 8, 15, 16],
+    };
+    let chips: [&dyn Chip<_, MyConfig>; 3] = [&send_receive, &table, &sender];
+    let perms = chips
+        .iter()
+        .map(|chip| {
+            let main = chip.generate_trace(&machine);
+            generate_permutation_trace(&machine, *chip, &main, random_elements())
+        })
+        .collect::<Vec<_>>();
+    let last_rows = perms
+        .iter()
+        .map(|perm| perm.row_slice(perm.height() - 1).to_vec())
+        .collect::<Vec<_>>();
+    let mut claimed = perms
+        .iter()
+        .map(cumulative_sum_from_perm)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        verify_cumulative_sums(&chips, &machine, &claimed, &last_rows),
+        Ok(())
+    );
+
+    claimed[1] += Challenge::one();
+    assert_eq!(
+        verify_cumulative_sums(&chips, &machine, &claimed, &last_rows),
+        Err(vec![1])
+    );
+
+    // A row of the wrong width is flagged even though its last entry matches the claim.
+    claimed[1] -= Challenge::one();
+    let mut truncated = last_rows.clone();
+    truncated[2].remove(0);
+    assert_eq!(
+        verify_cumulative_sums(&chips, &machine, &claimed, &truncated),
+        Err(vec![2])
+    );
+}
+
+#[test]
+fn own_permutation_challenge_is_shared_by_prover_and_verifier() {
+    let machine = MockMachine::default();
+    let chip = OwnChallengeChip {
+        values: vec![2, 3, 5, 7],
+    };
+    assert_eq!(
+        Chip::<_, MyConfig>::num_permutation_challenges(&chip, &machine),
+        4
+    );
+    // `a^2 - square` vanishes on the trace but not at the out of domain point, so the verifier
+    // only accepts if it reads the same fourth challenge the prover's quotient was built with.
+    let config = config(1);
+    let proof = prove_chip(&config, &machine, &chip);
+    assert!(verify_chip(&config, &machine, &chip, &proof).is_ok());
+}
+
+#[test]
+fn bundling_pairs_degree_one_interactions() {
+    let machine = MockMachine::default();
+    // Two bundled denominators of degree 1 reach degree 3 with the reciprocal, a third would
+    // exceed the budget, so the six interactions share three columns.
+    assert_eq!(
+        bundling_savings::<_, MyConfig, _>(&machine, &SixSendChip, 3),
+        (7, 4)
+    );
+    assert_eq!(
+        bundling_savings::<_, MyConfig, _>(&machine, &SixSendChip, 2),
+        (7, 7)
+    );
+}
+
+#[test]
+fn rotated_field_reads_the_next_row() {
+    let machine = MockMachine::default();
+    let keys = [4, 9, 2, 6];
+    let chip = KeyTableChip {
+        keys: keys.to_vec(),
+        rotated: true,
+    };
+    let main = Chip::<_, MyConfig>::generate_trace(&chip, &machine);
+    let perm = generate_permutation_trace::<_, MyConfig>(&machine, &chip, &main, random_elements());
+
+    // The receive reduces to `\alpha + key + \beta key'`, with `key'` read from the first row on
+    // the last row.
+    let [alpha, _, beta] = random_elements().try_into().unwrap();
+    for n in 0..keys.len() {
+        let key = Challenge::from_canonical_u32(keys[n]);
+        let next_key = Challenge::from_canonical_u32(keys[(n + 1) % keys.len()]);
+        assert_eq!(
+            perm.row_slice(n)[0],
+            (alpha + key + beta * next_key).inverse()
+        );
+    }
+}
+
+#[test]
+fn next_row_interaction_balances() {
+    let values = [3, 1, 4, 1];
+    let [alpha, _, _] = random_elements().try_into().unwrap();
+    // On the last row, the send reads the value of the first row.
+    let wrapped = (alpha + Challenge::from_canonical_u32(values[0])).inverse();
+    // One reciprocal column per interaction, unbundled and bundled on their own.
+    for backend in [
+        LookupBackend::Reciprocals,
+        LookupBackend::LogUp { max_degree: 2 },
+    ] {
+        let machine = MockMachine::with_lookup_backend(backend);
+        let chip = NextRowChip {
+            values: values.to_vec(),
+        };
+        let (perm, cumulative_sum) =
+            run_chip_permutation::<_, _, MyConfig>(&machine, &chip, &random_elements());
+        assert_eq!(perm.width(), 3);
+        assert_eq!(perm.row_slice(values.len() - 1)[0], wrapped);
+        assert_eq!(cumulative_sum, Challenge::zero());
+    }
+}
+
+#[test]
+fn machine_balance_names_the_unbalanced_bus() {
+    let machine = MockMachine::default();
+    let report = |rows: Vec<[u32; 4]>| {
+        let sender = GatedSendChip { values: vec![4, 8] };
+        let receiver = GlobalReceiveChip { values: vec![8, 4] };
+        let two_bus = TwoBusChip {
+            rows,
+            layout: PermutationLayout::Blocked,
+        };
+        let chips: [&dyn Chip<_, MyConfig>; 3] = [&sender, &receiver, &two_bus];
+        let mains = chips
+            .iter()
+            .map(|chip| chip.generate_trace(&machine))
+            .collect::<Vec<_>>();
+        assert_machine_balanced(&machine, &chips, &mains, &random_elements())
+    };
+
+    assert_eq!(report(vec![[1, 2, 3, 5], [3, 5, 1, 2]]), Ok(()));
+    assert_eq!(
+        report(vec![[1, 2, 3, 5], [3, 5, 1, 6]]),
+        Err(vec![BusArgument::Global(1)])
+    );
+}
+
+#[test]
+fn hashed_records_balance_exactly_when_equal() {
+    let machine = MockMachine::default();
+    let run = |rows: Vec<([u32; 3], [u32; 3])>| {
+        let chip = HashedRecordChip { rows };
+        let main = Chip::<_, MyConfig>::generate_trace(&chip, &machine);
+        let perm =
+            generate_permutation_trace::<_, MyConfig>(&machine, &chip, &main, random_elements());
+        check_constraints::<_, _, MyConfig>(&machine, &chip, &main, &perm, &random_elements());
+        (main, cumulative_sum_from_perm(&perm))
+    };
+
+    // The record sent on row 0 is received on row 1, so its hashes match.
+    let (main, cumulative_sum) = run(vec![([1, 2, 3], [4, 5, 6]), ([4, 5, 6], [1, 2, 3])]);
+    assert_eq!(main.row_slice(0)[3], main.row_slice(1)[7]);
+    assert_eq!(cumulative_sum, Challenge::zero());
+
+    let (main, cumulative_sum) = run(vec![([1, 2, 3], [4, 5, 6]), ([4, 5, 6], [1, 2, 4])]);
+    assert_ne!(main.row_slice(0)[3], main.row_slice(1)[7]);
+    assert_ne!(cumulative_sum, Challenge::zero());
+}
+
+#[test]
+#[should_panic(
+    expected = "interaction 0 on Global(0) has count 2 on row 1, violating its Unit count bound"
+)]
+fn unit_count_evaluating_to_two() {
+    let machine = MockMachine::default();
+    let chip = BoundedCountChip {
+        rows: vec![[5, 1], [6, 2], [7, 1], [8, 1]],
+        bound: CountBound::Unit,
+    };
+    let main = Chip::<_, MyConfig>::generate_trace(&chip, &machine);
+    generate_permutation_trace::<_, MyConfig>(&machine, &chip, &main, random_elements());
+}
+
+#[test]
+fn driver_uses_the_chip_permutation_trace() {
+    let machine = MockMachine::default();
+    let rows = vec![[1, 2], [2, 1], [3, 4], [4, 3]];
+    let chip = MiswiredSendChip { rows: rows.clone() };
+    let main = Chip::<_, MyConfig>::generate_trace(&chip, &machine);
+
+    let swapped = SendReceiveChip {
+        rows: rows.iter().map(|&[a, b]| [b, a]).collect(),
+    };
+    let swapped_main = Chip::<_, MyConfig>::generate_trace(&swapped, &machine);
+    let custom = generate_permutation_trace::<_, MyConfig>(
+        &machine,
+        &swapped,
+        &swapped_main,
+        random_elements(),
+    );
+    let default = generate_permutation_trace::<_, MyConfig>(
+        &machine,
+        &SendReceiveChip { rows },
+        &main,
+        random_elements(),
+    );
+    assert_ne!(custom.values, default.values);
+
+    let perm = generate_permutation_trace::<_, MyConfig>(&machine, &chip, &main, random_elements());
+    assert_eq!(perm.values, custom.values);
+    let chips: [&dyn Chip<_, MyConfig>; 1] = [&chip];
+    let [(perm, _)] = machine
+        .generate_all_permutation_traces(&chips, &[main], &random_elements())
+        .try_into()
+        .unwrap();
+    assert_eq!(perm.values, custom.values);
+}
+
+/// The row selectors and permutation trace cells an expression reads, e.g. `perm[1]'` for the
+/// next row of the second permutation column.
+fn permutation_leaves(expr: &SymbolicExpression<Challenge>) -> BTreeSet<String> {
+    match expr {
+        SymbolicExpression::Variable(v) if matches!(v.trace, Trace::Permutation) => {
+            let next = if v.is_next { "'" } else { "" };
+            BTreeSet::from([format!("perm[{}]{next}", v.column)])
+        }
+        SymbolicExpression::IsFirstRow => BTreeSet::from(["first row".to_string()]),
+        SymbolicExpression::IsLastRow => BTreeSet::from(["last row".to_string()]),
+        SymbolicExpression::IsTransition => BTreeSet::from(["transition".to_string()]),
+        SymbolicExpression::Add { x, y, .. }
+        | SymbolicExpression::Sub { x, y, .. }
+        | SymbolicExpression::Mul { x, y, .. } => {
+            let mut leaves = permutation_leaves(x);
+            leaves.extend(permutation_leaves(y));
+            leaves
+        }
+        SymbolicExpression::Neg { x, .. } => permutation_leaves(x),
+        _ => BTreeSet::new(),
+    }
+}
+
+#[test]
+fn symbolic_permutation_constraints_of_one_interaction() {
+    let machine = MockMachine::default();
+    let chip = GlobalReceiveChip { values: vec![] };
+    let constraints = get_symbolic_permutation_constraints::<_, MyConfig, _>(
+        &machine,
+        &chip,
+        random_elements(),
+        Challenge::zero(),
+    );
+
+    let kinds: Vec<_> = constraints.iter().map(|c| c.provenance.kind).collect();
+    assert_eq!(
+        kinds,
+        [
+            ConstraintKind::Reciprocal,
+            ConstraintKind::RunningSumTransition,
+            ConstraintKind::RunningSumFirstRow,
+            ConstraintKind::RunningSumLastRow,
+        ]
+    );
+    assert_eq!(constraints[0].provenance.interaction_index, Some(0));
+    assert_eq!(constraints[0].provenance.bus, Some(BusArgument::Global(0)));
+
+    // The reciprocal equation is `rlc \cdot q - 1`, over the local reciprocal column only.
+    let SymbolicExpression::Sub { x, y, .. } = &constraints[0].expr else {
+        panic!("expected the reciprocal equation to subtract one");
+    };
+    assert!(matches!(**x, SymbolicExpression::Mul { .. }));
+    assert!(matches!(**y, SymbolicExpression::Constant(c) if c == Challenge::one()));
+
+    let leaves: Vec<Vec<String>> = constraints
+        .iter()
+        .map(|c| permutation_leaves(&c.expr).into_iter().collect())
+        .collect();
+    assert_eq!(leaves[0], ["perm[0]"]);
+    assert_eq!(leaves[1], ["perm[0]'", "perm[1]", "perm[1]'", "transition"]);
+    // The two boundary equations pin the running sum on the first and last rows.
+    assert_eq!(leaves[2], ["first row", "perm[0]", "perm[1]"]);
+    assert_eq!(leaves[3], ["last row", "perm[1]"]);
+}
+
+#[test]
+fn receive_of_multiplicity_three_balances_three_sends() {
+    let machine = MockMachine::default();
+    // Looks up 4 three times, received once with multiplicity 3 on the row of table entry 4.
+    let mut rows = vec![
+        [4, 0],
+        [4, 1],
+        [4, 1],
+        [1, 0],
+        [2, 3],
+        [5, 1],
+        [6, 1],
+        [7, 1],
+    ];
+    let chip = FixedTableChip { rows: rows.clone() };
+    let (_, cumulative_sum) =
+        run_chip_permutation::<_, _, MyConfig>(&machine, &chip, &random_elements());
+    assert_eq!(cumulative_sum, Challenge::zero());
+
+    // Moving one of the three receives to entry 3 leaves a lookup of 4 unmatched.
+    rows[3][1] = 1;
+    rows[4][1] = 2;
+    let chip = FixedTableChip { rows };
+    let (_, cumulative_sum) =
+        run_chip_permutation::<_, _, MyConfig>(&machine, &chip, &random_elements());
+    assert_ne!(cumulative_sum, Challenge::zero());
+}
+
+#[test]
+fn constant_reciprocal_matches_its_full_column() {
+    let machine = MockMachine::default();
+    let (compact, compact_sum) =
+        run_chip_permutation::<_, _, MyConfig>(&machine, &CancellingFieldChip, &random_elements());
+    let (full, full_sum) =
+        run_chip_permutation::<_, _, MyConfig>(&machine, &SevenColumnChip, &random_elements());
+    assert_eq!(compact.width(), 2);
+    assert_eq!(full.width(), 3);
+
+    // Row by row, the running sums agree, and so does the reciprocal of column 0.
+    for row in 0..compact.height() {
+        assert_eq!(compact.row_slice(row)[0], full.row_slice(row)[0]);
+        assert_eq!(compact.row_slice(row)[1], full.row_slice(row)[2]);
+    }
+    assert_eq!(compact_sum, full_sum);
+    assert_ne!(compact_sum, Challenge::zero());
+}
+
+#[test]
+fn symbolic_running_sum_evaluates_to_cumulative_sum() {
+    let machine = MockMachine::default();
+    let chip = GatedMultiplicityChip {
+        rows: vec![[4, 2], [8, 1], [4, 3], [15, 0]],
+        budget: None,
+    };
+    let main = Chip::<_, MyConfig>::generate_trace(&chip, &machine);
+    let sum = symbolic_running_sum::<_, MyConfig>(&machine, &chip, &main);
+    let term = |payload, multiplicity| RunningSumTerm {
+        bus: BusArgument::Global(0),
+        payload: vec![Val::from_canonical_u32(payload)],
+        multiplicity: Val::from_canonical_u32(multiplicity),
+    };
+    assert_eq!(sum.terms, [term(4, 5), term(8, 1)]);
+
+    let perm = generate_permutation_trace::<_, MyConfig>(&machine, &chip, &main, random_elements());
+    assert_eq!(
+        sum.evaluate(&random_elements()),
+        cumulative_sum_from_perm(&perm)
+    );
+
+    // A balanced local bus cancels out entirely.
+    let table = FixedTableChip {
+        rows: vec![
+            [3, 2],
+            [1, 1],
+            [3, 1],
+            [7, 2],
+            [0, 0],
+            [0, 1],
+            [5, 0],
+            [2, 1],
+        ],
+    };
+    let main = Chip::<_, MyConfig>::generate_trace(&table, &machine);
+    let sum = symbolic_running_sum::<_, MyConfig>(&machine, &table, &main);
+    assert!(sum.terms.is_empty());
+    let perm =
+        generate_permutation_trace::<_, MyConfig>(&machine, &table, &main, random_elements());
+    assert_eq!(
+        sum.evaluate(&random_elements()),
+        cumulative_sum_from_perm(&perm)
+    );
+}
+
+#[test]
+fn paused_rows_contribute_nothing() {
+    let machine = MockMachine::default();
+    let chip = GatedSendChip { values: vec![4, 8] };
+    // Rows 1 and 3 are paused, with values that are sent nowhere else.
+    let main = RowMajorMatrix::new(
+        [4, 1, 99, 0, 8, 1, 77, 0]
+            .map(Val::from_canonical_u32)
+            .to_vec(),
+        2,
+    );
+    let perm = generate_permutation_trace::<_, MyConfig>(&machine, &chip, &main, random_elements());
+    check_constraints::<_, _, MyConfig>(&machine, &chip, &main, &perm, &random_elements());
+
+    let phi = |row: usize| perm.row_slice(row)[perm.width() - 1];
+    assert_eq!(phi(1), phi(0));
+    assert_eq!(phi(3), phi(2));
+
+    // The sum is that of the active rows alone.
+    let active = Chip::<_, MyConfig>::generate_trace(&chip, &machine);
+    let active_perm =
+        generate_permutation_trace::<_, MyConfig>(&machine, &chip, &active, random_elements());
+    assert_eq!(
+        cumulative_sum_from_perm(&perm),
+        cumulative_sum_from_perm(&active_perm)
+    );
+    let sum = symbolic_running_sum::<_, MyConfig>(&machine, &chip, &main);
+    let payloads: Vec<_> = sum.terms.iter().map(|term| term.payload[0]).collect();
+    assert_eq!(payloads, [4, 8].map(Val::from_canonical_u32));
+}
+
+#[test]
+fn scoped_sums_add_up_to_combined_sum() {
+    let machine = MockMachine::default();
+    // The local bus is left unbalanced, so that both scopes have a nonzero sum.
+    let chip = MixedScopeChip {
+        rows: vec![[1, 2], [2, 3], [3, 4], [4, 5]],
+    };
+    let main = Chip::<_, MyConfig>::generate_trace(&chip, &machine);
+    let combined =
+        generate_permutation_trace::<_, MyConfig>(&machine, &chip, &main, random_elements());
+    let scoped = |scope| {
+        generate_scoped_permutation_trace::<_, MyConfig>(
+            &machine,
+            &chip,
+            &main,
+            random_elements(),
+            scope,
+        )
+    };
+    let local = scoped(InteractionScope::Local);
+    let global = scoped(InteractionScope::Global);
+    assert_eq!((local.width(), global.width()), (3, 2));
+
+    // Each scoped trace carries its reciprocals of the combined trace.
+    for row in 0..combined.height() {
+        let combined_row = combined.row_slice(row);
+        assert_eq!(local.row_slice(row)[..2], combined_row[..2]);
+        assert_eq!(global.row_slice(row)[0], combined_row[2]);
+    }
+
+    // The scoped sums are the parts of the combined sum on their buses.
+    let sum = symbolic_running_sum::<_, MyConfig>(&machine, &chip, &main);
+    let part = |local: bool| {
+        SymbolicRunningSum {
+            terms: sum
+                .terms
+                .iter()
+                .filter(|term| matches!(term.bus, BusArgument::Local(_)) == local)
+                .cloned()
+                .collect(),
+            ..sum.clone()
+        }
+        .evaluate(&random_elements())
+    };
+    let local_sum = cumulative_sum_from_perm(&local);
+    let global_sum = cumulative_sum_from_perm(&global);
+    assert_eq!(local_sum, part(true));
+    assert_eq!(global_sum, part(false));
+    assert_ne!(local_sum, Challenge::zero());
+    assert_ne!(global_sum, Challenge::zero());
+    assert_eq!(local_sum + global_sum, cumulative_sum_from_perm(&combined));
+}
+
+#[test]
+fn exported_constraints_carry_their_provenance() {
+    let machine = MockMachine::default();
+    let constraints = get_symbolic_permutation_constraints::<_, MyConfig, _>(
+        &machine,
+        &PairSendChip,
+        random_elements(),
+        Challenge::zero(),
+    );
+    let provenance = |interaction_index, bus, kind| ConstraintProvenance {
+        chip: type_name::<PairSendChip>(),
+        interaction_index,
+        bus,
+        kind,
+    };
+    let provenances: Vec<_> = constraints.iter().map(|c| c.provenance).collect();
+    assert_eq!(
+        provenances,
+        [
+            provenance(
+                Some(0),
+                Some(BusArgument::Global(1)),
+                ConstraintKind::Reciprocal
+            ),
+            provenance(
+                Some(1),
+                Some(BusArgument::Global(0)),
+                ConstraintKind::Reciprocal
+            ),
+            provenance(None, None, ConstraintKind::RunningSumTransition),
+            provenance(None, None, ConstraintKind::RunningSumFirstRow),
+            provenance(None, None, ConstraintKind::RunningSumLastRow),
+        ]
+    );
+}
+
+#[test]
+fn soundness_error_matches_hand_calculation() {
+    let machine = MockMachine::default();
+    let sender = GatedSendChip {
+        values: vec![4, 8, 15, 16],
+    };
+    let chips: [&dyn Chip<_, MyConfig>; 2] = [&sender, &PairSendChip];
+    let bound = soundness_error(&machine, &chips, &[2, 2]);
+
+    // 4 rows of one interaction and 4 rows of two give N = 12 fractions. The send on global bus 1
+    // has a denominator of degree d = 2 in the challenges, and the pair of fields only degree 1.
+    // Over the quintic extension of BabyBear, the bound is 2 N d / p^5 = 48 / p^5.
+    let p = 2013265921f64;
+    let expected = 48.0 / p.powi(5);
+    assert!((bound / expected - 1.0).abs() < 1e-12);
+}
+
+#[test]
+fn fingerprints_follow_the_bus_interface() {
+    let machine = MockMachine::default();
+    let fingerprint = |chip: &dyn Chip<_, MyConfig>| chip.interaction_fingerprint(&machine);
+
+    // The same interactions, whatever the trace or how they are declared.
+    let chip = SendReceiveChip {
+        rows: vec![[1, 2], [2, 1]],
+    };
+    let other_rows = SendReceiveChip {
+        rows: vec![[5, 5], [6, 6], [7, 7], [8, 8]],
+    };
+    let miswired = MiswiredSendChip {
+        rows: vec![[1, 2], [2, 1]],
+    };
+    assert_eq!(fingerprint(&chip), fingerprint(&other_rows));
+    assert_eq!(fingerprint(&chip), fingerprint(&miswired));
+
+    // A rotated field, or different buses and fields, change the fingerprint.
+    let table = |rotated| KeyTableChip {
+        keys: vec![1, 2],
+        rotated,
+    };
+    assert_ne!(fingerprint(&table(false)), fingerprint(&table(true)));
+    assert_ne!(fingerprint(&PairSendChip), fingerprint(&SevenColumnChip));
+}
+
+#[test]
+fn quadratic_count_exceeds_a_running_sum_budget_of_two() {
+    let machine = MockMachine::default();
+    let chip = |budget| GatedMultiplicityChip {
+        rows: vec![[4, 1], [8, 2], [15, 0], [16, 3]],
+        budget,
+    };
+    assert_eq!(
+        running_sum_degree::<_, MyConfig, _>(&machine, &chip(None)),
+        3
+    );
+    assert_eq!(
+        check_running_sum_degree::<_, MyConfig, _>(&machine, &chip(None)),
+        Ok(())
+    );
+    assert_eq!(
+        check_running_sum_degree::<_, MyConfig, _>(&machine, &chip(Some(2))),
+        Err(3)
+    );
+}
+
+#[test]
+fn replayed_log_matches_permutation_trace() {
+    let machine = MockMachine::default();
+    let table = FixedTableChip {
+        rows: vec![
+            [3, 2],
+            [1, 1],
+            [3, 1],
+            [7, 2],
+            [0, 0],
+            [0, 1],
+            [5, 0],
+            [2, 1],
+        ],
+    };
+    let gated = GatedMultiplicityChip {
+        rows: vec![[4, 1], [8, 2], [15, 0], [16, 3]],
+        budget: None,
+    };
+    let keys = KeyTableChip {
+        keys: vec![1, 2, 3, 4],
+        rotated: true,
+    };
+    // Preprocessed fields, an active selector, a constant payload and a rotated field.
+    let chips: [&dyn Chip<_, MyConfig>; 4] = [&table, &gated, &CancellingFieldChip, &keys];
+    for chip in chips {
+        let main = chip.generate_trace(&machine);
+        let perm = generate_permutation_trace(&machine, chip, &main, random_elements());
+        let log = record_interaction_log(&machine, chip, &main);
+        let replayed = replay_permutation_trace(&log, &random_elements());
+        assert_eq!(replayed.width(), perm.width());
+        assert_eq!(replayed.values, perm.values);
+    }
+}
+
+#[test]
+fn interleaved_layout_moves_reciprocals_only() {
+    let machine = MockMachine::default();
+    let rows = vec![[1, 2, 3, 4], [5, 6, 7, 8]];
+    let blocked = TwoBusChip {
+        rows: rows.clone(),
+        layout: PermutationLayout::Blocked,
+    };
+    // Each bus's send is followed by its receive.
+    let interleaved = TwoBusChip {
+        rows,
+        layout: PermutationLayout::Interleaved(vec![0, 2, 1, 3]),
+    };
+    assert_eq!(
+        permutation_columns::<_, MyConfig, _>(&machine, &blocked),
+        [Some(0), Some(1), Some(2), Some(3)]
+    );
+    assert_eq!(
+        permutation_columns::<_, MyConfig, _>(&machine, &interleaved),
+        [Some(0), Some(2), Some(1), Some(3)]
+    );
+    assert_eq!(
+        Chip::<_, MyConfig>::interaction_layout(&interleaved, &machine).columns,
+        [Some(0), Some(2), Some(1), Some(3)]
+    );
+
+    let (blocked_perm, blocked_sum) =
+        run_chip_permutation::<_, _, MyConfig>(&machine, &blocked, &random_elements());
+    let (interleaved_perm, interleaved_sum) =
+        run_chip_permutation::<_, _, MyConfig>(&machine, &interleaved, &random_elements());
+    for row in 0..blocked_perm.height() {
+        let blocked_row = blocked_perm.row_slice(row);
+        let interleaved_row = interleaved_perm.row_slice(row);
+        for (i, c) in [0, 2, 1, 3].into_iter().enumerate() {
+            assert_eq!(interleaved_row[c], blocked_row[i]);
+        }
+        assert_eq!(interleaved_row[4], blocked_row[4]);
+    }
+    assert_eq!(interleaved_sum, blocked_sum);
+    assert_ne!(interleaved_sum, Challenge::zero());
+}
+
+#[test]
+fn permutation_air_emits_the_permutation_constraints() {
+    let machine = MockMachine::default();
+    let table = FixedTableChip {
+        rows: vec![
+            [3, 2],
+            [1, 1],
+            [3, 1],
+            [7, 2],
+            [0, 0],
+            [0, 1],
+            [5, 0],
+            [2, 1],
+        ],
+    };
+    let direct = count_constraints(&machine, &table, |builder| {
+        eval_permutation_constraints::<_, _, MyConfig, _>(&table, builder, Challenge::zero())
+    });
+    let wrapped = count_constraints(&machine, &table, |builder| {
+        PermutationAir::<_, MyConfig, _>::new(&table, Challenge::zero()).eval(builder)
+    });
+    // Two reciprocals and the three running sum constraints.
+    assert_eq!(direct, 5);
+    assert_eq!(wrapped, direct);
+
+    let sender = GatedSendChip {
+        values: vec![4, 8, 15, 16],
+    };
+    let direct = count_constraints(&machine, &sender, |builder| {
+        eval_permutation_constraints::<_, _, MyConfig, _>(&sender, builder, Challenge::zero())
+    });
+    let wrapped = count_constraints(&machine, &sender, |builder| {
+        PermutationAir::<_, MyConfig, _>::new(&sender, Challenge::zero()).eval(builder)
+    });
+    // One reciprocal, the boolean active selector and the running sum constraints.
+    assert_eq!(direct, 5);
+    assert_eq!(wrapped, direct);
+}
+
+#[test]
+fn interaction_free_chip_runs_alongside_a_bus() {
+    let machine = MockMachine::default();
+    let counting = CountingChip::default();
+    let sender = GatedSendChip {
+        values: vec![4, 8, 15, 16],
+    };
+    let receiver = GlobalReceiveChip {
+        values: vec![16, 4, 15, 8],
+    };
+    let chips: [&dyn Chip<_, MyConfig>; 3] = [&counting, &sender, &receiver];
+    let mains = chips.map(|chip| chip.generate_trace(&machine));
+    let perms = machine.generate_all_permutation_traces(&chips, &mains, &random_elements());
+
+    let (counting_perm, counting_sum) = &perms[0];
+    assert_eq!(counting_perm.width(), 1);
+    assert!(counting_perm.values.iter().all(|x| x.is_zero()));
+    assert_eq!(*counting_sum, Challenge::zero());
+
+    check_constraints::<_, _, MyConfig>(
+        &machine,
+        &counting,
+        &mains[0],
+        counting_perm,
+        &random_elements(),
+    );
+    check_constraints::<_, _, MyConfig>(
+        &machine,
+        &sender,
+        &mains[1],
+        &perms[1].0,
+        &random_elements(),
+    );
+    check_constraints::<_, _, MyConfig>(
+        &machine,
+        &receiver,
+        &mains[2],
+        &perms[2].0,
+        &random_elements(),
+    );
+    let sums: Vec<_> = perms.iter().map(|(_, sum)| *sum).collect();
+    assert_eq!(verify_aggregate_cumulative_sum(&machine, &sums), Ok(()));
+
+    let config = config(1);
+    let proof = prove_chip(&config, &machine, &counting);
+    assert!(verify_chip(&config, &machine, &counting, &proof).is_ok());
+}
+
+#[test]
+fn packed_denominators_match_scalar_on_a_random_trace() {
+    let machine = MockMachine::default();
+    // Not a multiple of the lane count, so that the scalar tail is covered.
+    let height = 4 * <<Val as Field>::Packing as PackedField>::WIDTH + 3;
+    let mut rng = thread_rng();
+    let keys = KeyTableChip {
+        keys: vec![],
+        rotated: true,
+    };
+    let mixed = MixedScopeChip { rows: vec![] };
+    let mut random_trace =
+        |width| RowMajorMatrix::new((0..height * width).map(|_| rng.gen()).collect(), width);
+    // The table's multiplicity column has to stay within its count bound.
+    let mut keys_main = random_trace(2);
+    for row in keys_main.values.chunks_mut(2) {
+        row[1] = Val::one();
+    }
+    let traces: [(&dyn Chip<_, MyConfig>, _); 3] = [
+        (&keys, keys_main),
+        (&mixed, random_trace(2)),
+        (&CancellingFieldChip, random_trace(4)),
+    ];
+    for (chip, main) in traces {
+        let scalar = generate_permutation_trace(&machine, chip, &main, random_elements());
+        let packed = generate_permutation_trace_packed(&machine, chip, &main, random_elements());
+        assert_eq!(packed.values, scalar.values);
+    }
+}
+
+#[test]
+fn duplicated_field_weighs_both_positions() {
+    let machine = MockMachine::default();
+    let chip = DuplicateFieldChip {
+        require_distinct: false,
+    };
+    let [(interaction, _)] = InteractionAir::<_, MyConfig>::all_interactions(&chip, &machine)
+        .try_into()
+        .unwrap();
+    assert_eq!(interaction.duplicate_fields(0, 1), Some((0, 1)));
+
+    // The send reduces to `\alpha + a + \beta a`.
+    let main = Chip::<_, MyConfig>::generate_trace(&chip, &machine);
+    let perm = generate_permutation_trace::<_, MyConfig>(&machine, &chip, &main, random_elements());
+    let [alpha, _, beta] = random_elements().try_into().unwrap();
+    for n in 0..main.height() {
+        let a = Challenge::from_base(main.row_slice(n)[0]);
+        assert_eq!(perm.row_slice(n)[0], (alpha + a + beta * a).inverse());
+    }
+}
+
+#[test]
+#[should_panic(expected = "interaction 0 repeats field 0 at position 1")]
+fn duplicated_field_is_flagged_when_distinct_fields_are_required() {
+    let machine = MockMachine::default();
+    let chip = DuplicateFieldChip {
+        require_distinct: true,
+    };
+    let main = Chip::<_, MyConfig>::generate_trace(&chip, &machine);
+    generate_permutation_trace::<_, MyConfig>(&machine, &chip, &main, random_elements());
+}
+
+#[test]
+fn last_row_running_sum_is_the_cumulative_sum() {
+    let machine = MockMachine::default();
+    let chip = GatedSendChip {
+        values: vec![4, 8, 15, 16],
+    };
+    let main = Chip::<_, MyConfig>::generate_trace(&chip, &machine);
+    let perm = generate_permutation_trace::<_, MyConfig>(&machine, &chip, &main, random_elements());
+    let cumulative_sum = cumulative_sum_from_perm(&perm);
+    assert_eq!(perm.row_slice(3)[perm.width() - 1], cumulative_sum);
+
+    // A wraparound recurrence from the last row to the first would only hold for a zero sum.
+    assert_ne!(cumulative_sum, Challenge::zero());
+    assert_eq!(
+        try_check_constraints::<_, _, MyConfig>(
+            &machine,
+            &chip,
+            &main,
+            &perm,
+            &random_elements(),
+            Some(cumulative_sum)
+        ),
+        Ok(())
+    );
+}
+
+#[test]
+#[should_panic(expected = "constraints must evaluate to zero")]
+fn last_row_rejects_another_cumulative_sum() {
+    let machine = MockMachine::default();
+    let chip = GatedSendChip {
+        values: vec![4, 8, 15, 16],
+    };
+    let main = Chip::<_, MyConfig>::generate_trace(&chip, &machine);
+    let perm = generate_permutation_trace::<_, MyConfig>(&machine, &chip, &main, random_elements());
+    let claimed = cumulative_sum_from_perm(&perm) + Challenge::one();
+    let _ = try_check_constraints::<_, _, MyConfig>(
+        &machine,
+        &chip,
+        &main,
+        &perm,
+        &random_elements(),
+        Some(claimed),
+    );
+}
+
+#[test]
+fn external_multiplicities_balance_a_bus() {
+    let machine = MockMachine::default();
+    let chip = ExternalCountChip {
+        lookups: vec![2, 0, 2, 3],
+        counts: vec![1, 0, 2, 1],
+    };
+    let main = Chip::<_, MyConfig>::generate_main_trace(&chip, &machine);
+    assert_eq!(
+        (0..4).map(|n| main.row_slice(n)[2]).collect::<Vec<_>>(),
+        [1, 0, 2, 1].map(Val::from_canonical_u32)
+    );
+    assert!(Chip::<_, MyConfig>::check_local_balance(&chip, &machine).is_ok());
+    let (_, cumulative_sum) =
+        run_chip_permutation::<_, _, MyConfig>(&machine, &chip, &random_elements());
+    assert_eq!(cumulative_sum, Challenge::zero());
+
+    let config = config(1);
+    let proof = prove_chip(&config, &machine, &chip);
+    assert!(verify_chip(&config, &machine, &chip, &proof).is_ok());
+    assert_eq!(proof.chip_proofs[0].cumulative_sum, Challenge::zero());
+
+    // The committed counts are what the constraints read, so wrong ones unbalance the bus.
+    let wrong = ExternalCountChip {
+        lookups: vec![2, 0, 2, 3],
+        counts: vec![1, 1, 1, 1],
+    };
+    assert!(Chip::<_, MyConfig>::check_local_balance(&wrong, &machine).is_err());
+    let (_, cumulative_sum) =
+        run_chip_permutation::<_, _, MyConfig>(&machine, &wrong, &random_elements());
+    assert_ne!(cumulative_sum, Challenge::zero());
+}
+
+#[test]
+#[should_panic(expected = "interaction 0 must count a single main column")]
+fn external_multiplicities_need_a_count_column() {
+    let chip = ExternalCountChip {
+        lookups: vec![0],
+        counts: vec![1],
+    };
+    let mut main = Chip::<_, MyConfig>::generate_trace(&chip, &MockMachine::default());
+    let interactions =
+        InteractionAir::<_, MyConfig>::all_interactions(&chip, &MockMachine::default());
+    write_external_multiplicities(&interactions, 0, &mut main, &[Some(vec![Val::one()]), None]);
+}
+
+#[test]
+fn base_field_chip_proves_and_verifies() {
+    let machine = MockMachine::default();
+    let chip = BaseFieldChip {
+        rows: vec![[1, 3], [2, 1], [3, 4], [4, 2]],
+    };
+    let chips: [&dyn Chip<_, MyConfig>; 2] = [&chip, &SendReceiveChip { rows: vec![] }];
+    assert_eq!(permutation_challenge_field(chips), LookupField::Base);
+    assert_eq!(
+        permutation_challenge_field([&SendReceiveChip { rows: vec![] } as &dyn Chip<_, MyConfig>]),
+        LookupField::Extension
+    );
+
+    assert_eq!(
+        prove_and_verify_chip::<_, _, MyConfig>(&chip, &machine),
+        Ok(())
+    );
+    let config = config(1);
+    let proof = prove_chip(&config, &machine, &chip);
+    assert!(verify_chip(&config, &machine, &chip, &proof).is_ok());
+    assert_eq!(proof.chip_proofs[0].cumulative_sum, Challenge::zero());
+}
+
+#[test]
+#[should_panic(expected = "a base field lookup cannot bundle its reciprocals")]
+fn base_field_chip_rejects_logup() {
+    let machine = MockMachine::with_lookup_backend(LookupBackend::LogUp { max_degree: 3 });
+    let chip = BaseFieldChip {
+        rows: vec![[1, 2], [2, 1]],
+    };
+    let main = Chip::<_, MyConfig>::generate_trace(&chip, &machine);
+    generate_permutation_trace::<_, MyConfig>(&machine, &chip, &main, random_elements());
+}
+
+#[test]
+fn zero_policies_match_the_default_trace() {
+    let machine = MockMachine::default();
+    let chip = SendReceiveChip {
+        rows: vec![[1, 3], [2, 1], [3, 4], [4, 2]],
+    };
+    let main = Chip::<_, MyConfig>::generate_trace(&chip, &machine);
+    let expected =
+        generate_permutation_trace::<_, MyConfig>(&machine, &chip, &main, random_elements());
+    for policy in [ZeroPolicy::Panic, ZeroPolicy::SkipAsZero] {
+        let perm = generate_permutation_trace_with_zero_policy::<_, MyConfig>(
+            &machine,
+            &chip,
+            &main,
+            random_elements(),
+            policy,
+        );
+        assert_eq!(perm.values, expected.values);
+    }
+}
+
+/// Challenges whose local alpha `-3` makes the denominators of a `SendReceiveChip` vanish where it
+/// sends or receives `3`.
+fn zero_denominator_challenges() -> Vec<Challenge> {
+    vec![
+        -Challenge::from_canonical_u32(3),
+        Challenge::from_canonical_u32(5),
+        Challenge::from_canonical_u32(7),
+    ]
+}
+
+#[test]
+#[should_panic(expected = "interaction 1 has a zero denominator on row 0")]
+fn panic_policy_reports_a_zero_denominator() {
+    let machine = MockMachine::default();
+    let chip = SendReceiveChip {
+        rows: vec![[1, 3], [2, 1], [3, 4], [4, 2]],
+    };
+    let main = Chip::<_, MyConfig>::generate_trace(&chip, &machine);
+    generate_permutation_trace_with_zero_policy::<_, MyConfig>(
+        &machine,
+        &chip,
+        &main,
+        zero_denominator_challenges(),
+        ZeroPolicy::Panic,
+    );
+}
+
+#[test]
+fn skip_policy_leaves_a_zero_reciprocal() {
+    let machine = MockMachine::default();
+    let chip = SendReceiveChip {
+        rows: vec![[1, 3], [2, 1], [3, 4], [4, 2]],
+    };
+    let main = Chip::<_, MyConfig>::generate_trace(&chip, &machine);
+    // Returned even in debug builds, where `generate_permutation_trace` panics on the zero.
+    let perm = generate_permutation_trace_with_zero_policy::<_, MyConfig>(
+        &machine,
+        &chip,
+        &main,
+        zero_denominator_challenges(),
+        ZeroPolicy::SkipAsZero,
+    );
+    assert_eq!(perm.row_slice(0)[1], Challenge::zero());
+    assert_eq!(perm.row_slice(2)[0], Challenge::zero());
+    assert!(find_failing_constraint::<_, _, MyConfig>(
+        &machine,
+        &chip,
+        &main,
+        &perm,
+        &zero_denominator_challenges()
+    )
+    .is_err());
+}
+
+#[test]
+fn streamed_uneven_blocks_match_the_batch_trace() {
+    let machine = MockMachine::default();
+    let rows = vec![
+        [1, 5],
+        [2, 8],
+        [3, 1],
+        [4, 7],
+        [5, 2],
+        [6, 4],
+        [7, 3],
+        [8, 6],
+    ];
+    let chips: [&dyn Chip<_, MyConfig>; 2] = [
+        &SendReceiveChip { rows: rows.clone() },
+        &BaseFieldChip { rows },
+    ];
+    for chip in chips {
+        let main = chip.generate_trace(&machine);
+        let mut blocks = vec![];
+        let mut start = 0;
+        for height in [3, 4, 1] {
+            let end = start + height * main.width();
+            blocks.push(RowMajorMatrix::new(
+                main.values[start..end].to_vec(),
+                main.width(),
+            ));
+            start = end;
+        }
+
+        let mut streamed = vec![];
+        let cumulative_sum = generate_permutation_trace_streaming(
+            &machine,
+            chip,
+            blocks,
+            random_elements(),
+            |block| streamed.extend(block.values),
+        );
+        let perm = generate_permutation_trace(&machine, chip, &main, random_elements());
+        assert_eq!(streamed, perm.values);
+        assert_eq!(cumulative_sum, cumulative_sum_from_perm(&perm));
+        assert_eq!(cumulative_sum, Challenge::zero());
+        assert_builder_matches(&machine, chip);
+    }
+}
+
+#[test]
+// Counters are per thread, and the parallel path runs on worker threads, see `measure_cost`.
+#[cfg(not(feature = "parallel"))]
+fn measured_cost_matches_the_predicted_cost() {
+    let machine = MockMachine::default();
+    let measure = |chip: &dyn Chip<MockMachine<Val>, MyConfig>| {
+        let main = chip.generate_trace(&machine);
+        let (_, report) =
+            measure_cost(|| generate_permutation_trace(&machine, chip, &main, random_elements()));
+        report
+    };
+
+    // With `n` rows and `k` interactions of one field each, every interaction having a column:
+    // `n k` scalings in `reduce_row`, `3 (n k - 1)` multiplications and one inversion in the batch
+    // inversion of the `n k` nonzero denominators, and `n k` in the running sum.
+    let predicted = |n: usize, k: usize| CostReport {
+        multiplications: n * k + 3 * (n * k - 1) + n * k,
+        inversions: 1,
+    };
+    let send_receive = SendReceiveChip {
+        rows: vec![[1, 3], [2, 1], [3, 4], [4, 2]],
+    };
+    assert_eq!(measure(&send_receive), predicted(4, 2));
+    let double = DoubleSendReceiveChip {
+        rows: vec![[1, 2, 3, 4], [3, 4, 1, 2], [5, 6, 7, 8], [7, 8, 5, 6]],
+        bundle_degree: None,
+    };
+    assert_eq!(measure(&double), predicted(4, 4));
+
+    // The constant payload of `CancellingFieldChip` is reduced and inverted once, so its four rows
+    // cost one scaling and one inversion more than a chip of one column, plus its four terms of
+    // the running sum.
+    let one_column = predicted(4, 1);
+    assert_eq!(
+        measure(&CancellingFieldChip),
+        CostReport {
+            multiplications: one_column.multiplications + 1 + 4,
+            inversions: one_column.inversions + 1,
+        }
+    );
+}
+
+#[test]
+fn constraint_snapshot_diffs() {
+    let machine = MockMachine::default();
+    let export = |rotated| {
+        get_symbolic_permutation_constraints::<_, MyConfig, _>(
+            &machine,
+            &KeyTableChip {
+                keys: vec![],
+                rotated,
+            },
+            random_elements(),
+            Challenge::zero(),
+        )
+    };
+    let snapshot = export(false);
+    assert!(diff_constraint_systems(&snapshot, &export(false)).is_empty());
+
+    // Rotating the received key only changes the reciprocal constraint which reads it.
+    assert_eq!(
+        diff_constraint_systems(&snapshot, &export(true)),
+        vec![ConstraintDiff::Modified(ConstraintProvenance {
+            chip: type_name::<KeyTableChip>(),
+            interaction_index: Some(0),
+            bus: Some(BusArgument::Local(0)),
+            kind: ConstraintKind::Reciprocal,
+        })]
+    );
+}
+
+#[test]
+#[cfg(feature = "parallel")]
+fn parallel_permutation_trace_matches_serial() {
+    // Several chunks of the parallel running sum, see `prefix_sums`.
+    let height = 1 << 13;
+    let machine = MockMachine::default();
+    let chip = SendReceiveChip {
+        rows: (0..height).map(|a| [a, 3 * a % height]).collect(),
+    };
+    let main = Chip::<_, MyConfig>::generate_trace(&chip, &machine);
+    let parallel =
+        generate_permutation_trace::<_, MyConfig>(&machine, &chip, &main, random_elements());
+
+    // `PermutationTraceBuilder` builds the same trace row by row on this thread.
+    let mut builder = PermutationTraceBuilder::<MyConfig>::new(&machine, &chip, &random_elements());
+    for n in 0..main.height() {
+        builder.push_row(main.row_slice(n));
+    }
+    let serial = builder.finish();
+    assert_eq!(parallel.values, serial.values);
+    assert_eq!(cumulative_sum_from_perm(&parallel), Challenge::zero());
+}
+
+#[test]
+fn dependent_chip_cancels_the_upstream_cumulative_sum() {
+    let machine = MockMachine::default();
+    let chips = [
+        UpstreamSumChip {
+            values: vec![1, 2, 3, 4],
+            upstream: None,
+        },
+        UpstreamSumChip {
+            values: vec![4, 3, 2, 1],
+            upstream: Some(0),
+        },
+    ];
+    let config = config(1);
+    let proof = prove_chips(&config, &machine, &chips);
+    assert!(verify_chips(&config, &machine, &chips, &proof).is_ok());
+
+    let sums = proof
+        .chip_proofs
+        .iter()
+        .map(|chip_proof| chip_proof.cumulative_sum)
+        .collect::<Vec<_>>();
+    assert!(!sums[0].is_zero());
+    assert_eq!(verify_aggregate_cumulative_sum(&machine, &sums), Ok(()));
+}
+
+#[test]
+#[should_panic(expected = "the cumulative sum does not cancel the upstream cumulative sum")]
+fn dependent_chip_reads_the_upstream_cumulative_sum() {
+    let machine = MockMachine::default();
+    let chips = [
+        UpstreamSumChip {
+            values: vec![1, 2, 3, 4],
+            upstream: None,
+        },
+        UpstreamSumChip {
+            values: vec![4, 3, 2, 2],
+            upstream: Some(0),
+        },
+    ];
+    prove_chips(&config(1), &machine, &chips);
+}
+
+#[test]
+fn cumulative_sum_of_unbalanced_bus() {
+    let machine = MockMachine::default();
+    // Columns `a, b` are sent and `c, d` received, so the bus does not balance.
+    let rows = vec![[1, 2, 3, 4], [5, 6, 7, 8]];
+    let [alpha, _, _] = random_elements().try_into().unwrap();
+    let expected = rows
+        .iter()
+        .map(|row| {
+            let [a, b, c, d] = row.map(|x| (alpha + Challenge::from_canonical_u32(x)).inverse());
+            a + b - c - d
+        })
+        .sum::<Challenge>();
+    for bundle_degree in [None, Some(3)] {
+        let chip = DoubleSendReceiveChip {
+            rows: rows.clone(),
+            bundle_degree,
+        };
+        let (perm, cumulative_sum) =
+            run_chip_permutation::<_, _, MyConfig>(&machine, &chip, &random_elements());
+        assert_eq!(cumulative_sum, expected);
+        // The value `when_last_row` binds to the claimed cumulative sum.
+        let phi_last = perm.row_slice(perm.height() - 1)[perm.width() - 1];
+        assert_eq!(cumulative_sum, phi_last);
+    }
+}
+
+#[test]
+#[should_panic(expected = "interaction 0 on Global(0) has count 3 on row 2")]
+fn count_above_declared_bound() {
+    let machine = MockMachine::default();
+    let chip = BoundedCountChip {
+        rows: vec![[5, 2], [6, 0], [7, 3], [8, 1]],
+        bound: CountBound::AtMost(2),
+    };
+    let main = Chip::<_, MyConfig>::generate_trace(&chip, &machine);
+    generate_permutation_trace::<_, MyConfig>(&machine, &chip, &main, random_elements());
+}
+
+fn assert_builder_matches(machine: &MockMachine<Val>, chip: &dyn Chip<MockMachine<Val>, MyConfig>) {
+    let main = chip.generate_trace(machine);
+    let mut builder = PermutationTraceBuilder::new(machine, chip, &random_elements());
+    for n in 0..main.height() {
+        builder.push_row(main.row_slice(n));
+    }
+    let streamed = builder.finish();
+    let perm = generate_permutation_trace(machine, chip, &main, random_elements());
+    assert_eq!(streamed.width(), perm.width());
+    assert_eq!(streamed.values, perm.values);
+}
+
+#[test]
+fn streamed_rows_match_permutation_trace() {
+    let machine = MockMachine::default();
+    assert_builder_matches(
+        &machine,
+        &SendReceiveChip {
+            rows: vec![[1, 3], [2, 1], [3, 4], [4, 4]],
+        },
+    );
+    assert_builder_matches(
+        &machine,
+        &FixedTableChip {
+            rows: vec![
+                [3, 2],
+                [1, 1],
+                [3, 1],
+                [7, 2],
+                [0, 0],
+                [0, 1],
+                [5, 0],
+                [2, 1],
+            ],
+        },
+    );
+}
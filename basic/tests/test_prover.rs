@@ -9,8 +9,8 @@ use valida_cpu::{
     MachineWithCpuChip, StopInstruction,
 };
 use valida_machine::{
-    FixedAdviceProvider, Instruction, InstructionWord, Machine, MachineProof, Operands, ProgramROM,
-    Word,
+    FixedAdviceProvider, Instruction, InstructionWord, Machine, MachineProof, MockPcs, Operands,
+    ProgramROM, Word,
 };
 
 use valida_memory::MachineWithMemoryChip;
@@ -31,8 +31,9 @@ use rand::thread_rng;
 use valida_machine::StarkConfigImpl;
 use valida_machine::__internal::p3_commit::ExtensionMmcs;
 
-#[test]
-fn prove_fibonacci() {
+type Val = BabyBear;
+
+fn fibonacci_program() -> Vec<InstructionWord<i32>> {
     let mut program = vec![];
 
     // Label locations
@@ -185,15 +186,36 @@ fn prove_fibonacci() {
         },
     ]);
 
+    program
+}
+
+fn run_fibonacci() -> BasicMachine<Val> {
     let mut machine = BasicMachine::<Val>::default();
-    let rom = ProgramROM::new(program);
+    let rom = ProgramROM::new(fibonacci_program());
     machine.program_mut().set_program_rom(&rom);
     machine.cpu_mut().fp = 0x1000;
     machine.cpu_mut().save_register_state(); // TODO: Initial register state should be saved
                                              // automatically by the machine, not manually here
     machine.run(&rom, &mut FixedAdviceProvider::empty());
+    machine
+}
+
+fn assert_fibonacci_execution(machine: &BasicMachine<Val>) {
+    assert_eq!(machine.cpu().clock, 192);
+    assert_eq!(machine.cpu().operations.len(), 192);
+    assert_eq!(machine.mem().operations.values().flatten().count(), 401);
+    assert_eq!(machine.add_u32().operations.len(), 105);
+
+    assert_eq!(
+        *machine.mem().cells.get(&(0x1000 + 4)).unwrap(), // Return value
+        Word([0, 1, 37, 17,])                             // 25th fibonacci number (75025)
+    );
+}
+
+#[test]
+fn prove_fibonacci() {
+    let machine = run_fibonacci();
 
-    type Val = BabyBear;
     type Challenge = BinomialExtensionField<Val, 5>;
     type PackedChallenge = BinomialExtensionField<<Val as Field>::Packing, 5>;
 
@@ -250,13 +272,33 @@ fn prove_fibonacci() {
         .verify(&config, &deserialized_proof)
         .expect("verification failed");
 
-    assert_eq!(machine.cpu().clock, 192);
-    assert_eq!(machine.cpu().operations.len(), 192);
-    assert_eq!(machine.mem().operations.values().flatten().count(), 401);
-    assert_eq!(machine.add_u32().operations.len(), 105);
+    assert_fibonacci_execution(&machine);
+}
 
-    assert_eq!(
-        *machine.mem().cells.get(&(0x1000 + 4)).unwrap(), // Return value
-        Word([0, 1, 37, 17,])                             // 25th fibonacci number (75025)
-    );
+#[test]
+fn prove_fibonacci_mock_pcs() {
+    let machine = run_fibonacci();
+
+    type Challenge = BinomialExtensionField<Val, 5>;
+    type PackedChallenge = BinomialExtensionField<<Val as Field>::Packing, 5>;
+
+    type Mds16 = CosetMds<Val, 16>;
+    let mds16 = Mds16::default();
+
+    type Perm16 = Poseidon<Val, Mds16, 16, 5>;
+    let perm16 = Perm16::new_from_rng(4, 22, mds16, &mut thread_rng());
+
+    type Challenger = DuplexChallenger<Val, Perm16, 16>;
+    type Pcs = MockPcs<Val, Radix2Bowers>;
+    type MyConfig = StarkConfigImpl<Val, Challenge, PackedChallenge, Pcs, Challenger>;
+
+    let pcs = Pcs::new(Radix2Bowers::default(), 1);
+    let config = MyConfig::new(pcs, Challenger::new(perm16));
+    let proof = machine.prove(&config);
+
+    machine
+        .verify(&config, &proof)
+        .expect("verification failed");
+
+    assert_fibonacci_execution(&machine);
 }
@@ -11,8 +11,8 @@ use core::marker::Sync;
 use core::mem::transmute;
 use valida_bus::{MachineWithGeneralBus, MachineWithMemBus, MachineWithProgramBus};
 use valida_machine::{
-    addr_of_word, index_of_byte, instructions, AdviceProvider, Chip, Instruction, InstructionWord,
-    Interaction, Operands, Word,
+    addr_of_word, index_of_byte, instructions, AdviceProvider, Chip, CountBound, Instruction,
+    InstructionWord, Interaction, InteractionAir, Operands, Word,
 };
 use valida_memory::{MachineWithMemoryChip, Operation as MemoryOperation};
 use valida_opcodes::{
@@ -65,7 +65,7 @@ pub struct Registers {
     fp: u32,
 }
 
-impl<M, SC> Chip<M, SC> for CpuChip
+impl<M, SC> InteractionAir<M, SC> for CpuChip
 where
     M: MachineWithProgramBus<SC::Val>
         + MachineWithMemoryChip<SC::Val>
@@ -74,26 +74,6 @@ where
         + Sync,
     SC: StarkConfig,
 {
-    fn generate_trace(&self, machine: &M) -> RowMajorMatrix<SC::Val> {
-        let mut rows = self
-            .operations
-            .as_slice()
-            .into_par_iter()
-            .enumerate()
-            .map(|(n, op)| self.op_to_row::<M, SC>(n, op, machine))
-            .collect::<Vec<_>>();
-
-        // Set diff, diff_inv, and not_equal
-        Self::compute_word_diffs(&mut rows);
-
-        let mut trace =
-            RowMajorMatrix::new(rows.into_iter().flatten().collect::<Vec<_>>(), NUM_CPU_COLS);
-
-        Self::pad_to_power_of_two(&mut trace.values);
-
-        trace
-    }
-
     fn global_sends(&self, machine: &M) -> Vec<Interaction<SC::Val>> {
         // Memory bus channels
         let mem_sends = (0..3).map(|i| {
@@ -111,6 +91,10 @@ where
                 fields,
                 count: VirtualPairCol::single_main(channel.used),
                 argument_index: machine.mem_bus(),
+                rotations: vec![],
+                count_bound: CountBound::Unbounded,
+                is_real: None,
+                limb_order: None,
             }
         });
 
@@ -131,6 +115,10 @@ where
             fields,
             count: VirtualPairCol::single_main(CPU_COL_MAP.opcode_flags.is_bus_op),
             argument_index: machine.general_bus(),
+            rotations: vec![],
+            count_bound: CountBound::Unbounded,
+            is_real: None,
+            limb_order: None,
         };
 
         // // Program ROM bus channel
@@ -157,6 +145,36 @@ where
     }
 }
 
+impl<M, SC> Chip<M, SC> for CpuChip
+where
+    M: MachineWithProgramBus<SC::Val>
+        + MachineWithMemoryChip<SC::Val>
+        + MachineWithGeneralBus<SC::Val>
+        + MachineWithMemBus<SC::Val>
+        + Sync,
+    SC: StarkConfig,
+{
+    fn generate_trace(&self, machine: &M) -> RowMajorMatrix<SC::Val> {
+        let mut rows = self
+            .operations
+            .as_slice()
+            .into_par_iter()
+            .enumerate()
+            .map(|(n, op)| self.op_to_row::<M, SC>(n, op, machine))
+            .collect::<Vec<_>>();
+
+        // Set diff, diff_inv, and not_equal
+        Self::compute_word_diffs(&mut rows);
+
+        let mut trace =
+            RowMajorMatrix::new(rows.into_iter().flatten().collect::<Vec<_>>(), NUM_CPU_COLS);
+
+        Self::pad_to_power_of_two(&mut trace.values);
+
+        trace
+    }
+}
+
 impl CpuChip {
     fn op_to_row<M, SC>(&self, clk: usize, op: &Operation, machine: &M) -> [SC::Val; NUM_CPU_COLS]
     where
@@ -232,7 +232,7 @@ fn prove_method(chips: &[&Field]) -> TokenStream2 {
         .map(|chip| {
             let chip_name = chip.ident.as_ref().unwrap();
             quote! {
-                get_log_quotient_degree::<Self, SC, _>(self, self.#chip_name()),
+                get_chip_log_quotient_degree::<Self, SC, _>(self, self.#chip_name()),
             }
         })
         .collect::<TokenStream2>();
@@ -283,7 +283,7 @@ fn prove_method(chips: &[&Field]) -> TokenStream2 {
             use ::valida_machine::__internal::p3_commit::{Pcs, UnivariatePcs, UnivariatePcsWithLde};
             use ::valida_machine::__internal::p3_matrix::{Matrix, MatrixRowSlices, dense::RowMajorMatrix};
             use ::valida_machine::__internal::p3_util::log2_strict_usize;
-            use ::valida_machine::{generate_permutation_trace, MachineProof, ChipProof, Commitments};
+            use ::valida_machine::{cumulative_sum_from_perm, num_permutation_challenges, permutation_challenge_field, MachineProof, ChipProof, Commitments};
             use ::valida_machine::OpenedValues;
             use alloc::vec;
             use alloc::vec::Vec;
@@ -314,7 +314,11 @@ fn prove_method(chips: &[&Field]) -> TokenStream2 {
                 tracing::info_span!("generate main traces")
                     .in_scope(||
                         chips.par_iter()
-                            .map(|chip| chip.generate_trace(self))
+                            .map(|chip| {
+                                let mut trace = chip.generate_main_trace(self);
+                                chip.pad_trace(self, &mut trace);
+                                trace
+                            })
                             .collect::<Vec<_>>()
                             .try_into().unwrap()
                     );
@@ -328,24 +332,23 @@ fn prove_method(chips: &[&Field]) -> TokenStream2 {
 
             let (main_commit, main_data) = tracing::info_span!("commit to main traces")
                 .in_scope(|| pcs.commit_batches(main_traces.to_vec()));
-            challenger.observe(main_commit.clone());
             let mut main_trace_ldes = pcs.get_ldes(&main_data);
 
-            let mut perm_challenges = Vec::new();
-            for _ in 0..3 {
-                perm_challenges.push(challenger.sample_ext_element());
-            }
-
-            let perm_traces = tracing::info_span!("generate permutation traces")
-                .in_scope(||
-                    chips.into_par_iter().enumerate().map(|(i, chip)| {
-                        generate_permutation_trace(self, *chip, &main_traces[i], perm_challenges.clone())
-                    }).collect::<Vec<_>>()
-                );
-
-            let cumulative_sums = perm_traces.iter()
-                .map(|trace| trace.row_slice(trace.height() - 1).last().unwrap().clone())
-                .collect::<Vec<_>>();
+            let perm_challenges: Vec<SC::Challenge> = self.derive_permutation_challenges(
+                &mut challenger,
+                &[main_commit.clone()],
+                num_permutation_challenges(self, chips.iter().map(|chip| **chip)),
+                permutation_challenge_field(chips.iter().map(|chip| **chip)),
+            );
+
+            let (perm_traces, cumulative_sums): (Vec<_>, Vec<_>) =
+                tracing::info_span!("generate permutation traces")
+                    .in_scope(|| {
+                        let chips = chips.iter().map(|chip| **chip).collect::<Vec<_>>();
+                        self.generate_all_permutation_traces(&chips, &main_traces, &perm_challenges)
+                            .into_iter()
+                            .unzip()
+                    });
 
             let (perm_commit, perm_data) = tracing::info_span!("commit to permutation traces")
                 .in_scope(|| {
@@ -373,9 +376,6 @@ fn prove_method(chips: &[&Field]) -> TokenStream2 {
 
             challenger.observe(quotient_commit.clone());
 
-            #[cfg(debug_assertions)]
-            check_cumulative_sums(&perm_traces[..]);
-
             let zeta: SC::Challenge = challenger.sample_ext_element();
             let zeta_and_next: [Vec<SC::Challenge>; #num_chips] =
                 g_subgroups.map(|g| vec![zeta, zeta * g]);
@@ -428,7 +428,7 @@ fn prove_method(chips: &[&Field]) -> TokenStream2 {
                         quotient_chunks,
                     };
 
-                    let cumulative_sum = perm_trace.row_slice(perm_trace.height() - 1).last().unwrap().clone();
+                    let cumulative_sum = cumulative_sum_from_perm(&perm_trace);
                     ChipProof {
                         log_degree: *log_degree,
                         opened_values,
@@ -463,7 +463,7 @@ fn verify_method(chips: &[&Field]) -> TokenStream2 {
         .map(|chip| {
             let chip_name = chip.ident.as_ref().unwrap();
             quote! {
-                get_log_quotient_degree::<Self, SC, _>(self, self.#chip_name()),
+                get_chip_log_quotient_degree::<Self, SC, _>(self, self.#chip_name()),
             }
         })
         .collect::<TokenStream2>();
@@ -503,7 +503,7 @@ fn verify_method(chips: &[&Field]) -> TokenStream2 {
             use ::valida_machine::__internal::p3_commit::{Pcs, UnivariatePcs, UnivariatePcsWithLde};
             use ::valida_machine::__internal::p3_matrix::Dimensions;
             use ::valida_machine::__internal::p3_util::log2_strict_usize;
-            use ::valida_machine::{verify_constraints, MachineProof, ChipProof, Commitments};
+            use ::valida_machine::{num_permutation_challenges, permutation_challenge_field, permutation_trace_width, sign_conventions, verify_constraints, MachineProof, ChipProof, Commitments};
             use ::valida_machine::OpenedValues;
             use ::valida_machine::{VerificationError, ProofShapeError, OodEvaluationMismatch};
             use alloc::vec;
@@ -517,11 +517,6 @@ fn verify_method(chips: &[&Field]) -> TokenStream2 {
             // TODO: Seed challenger with digest of all constraints & trace lengths.
             let pcs = config.pcs();
 
-            let chips_interactions = chips
-            .iter()
-            .map(|chip| chip.all_interactions(self))
-            .collect::<Vec<_>>();
-
             let dims = &[
                 chips
                     .iter()
@@ -531,17 +526,18 @@ fn verify_method(chips: &[&Field]) -> TokenStream2 {
                         height: 1 << chip_proof.log_degree,
                     })
                     .collect::<Vec<_>>(),
-                chips_interactions.iter()
-                  .zip(proof.chip_proofs.iter())
-                    .map(|(interactions, chip_proof)| Dimensions {
-                        width: (interactions.len() + 1) * SC::Challenge::D,
+                chips
+                    .iter()
+                    .zip(proof.chip_proofs.iter())
+                    .map(|(chip, chip_proof)| Dimensions {
+                        width: permutation_trace_width::<Self, SC, _>(self, **chip) * SC::Challenge::D,
                         height: 1 << chip_proof.log_degree,
                     })
                     .collect::<Vec<_>>(),
                 proof.chip_proofs.iter()
                     .zip(log_quotient_degrees)
                     .map(|(chip_proof, log_quotient_deg)| Dimensions {
-                        width: log_quotient_deg << SC::Challenge::D,
+                        width: (1 << log_quotient_deg) * SC::Challenge::D,
                         height: 1 << chip_proof.log_degree,
                     })
                     .collect::<Vec<_>>(),
@@ -598,12 +594,12 @@ fn verify_method(chips: &[&Field]) -> TokenStream2 {
 
             challenger.observe(preprocessed_commit.clone());
 
-            challenger.observe(main_trace.clone());
-
-            let mut perm_challenges = Vec::new();
-            for _ in 0..3 {
-                perm_challenges.push(challenger.sample_ext_element::<SC::Challenge>());
-            }
+            let perm_challenges: Vec<SC::Challenge> = self.derive_permutation_challenges(
+                &mut challenger,
+                &[main_trace.clone()],
+                num_permutation_challenges(self, chips.iter().map(|chip| **chip)),
+                permutation_challenge_field(chips.iter().map(|chip| **chip)),
+            );
 
             challenger.observe(perm_trace.clone());
 
@@ -635,15 +631,14 @@ fn verify_method(chips: &[&Field]) -> TokenStream2 {
             // Verify the constraints.
             #verify_constraints
             // Verify that the cumulative_sum sums add up to zero.
-            let sum: SC::Challenge = proof
+            let cumulative_sums = proof
                 .chip_proofs
                 .iter()
-                .map(|chip_proof| chip_proof.cumulative_sum)
-                .sum();
-
-            if sum != SC::Challenge::zero() {
-                return Err(());
-            }
+                .zip(sign_conventions(chips.iter().map(|chip| **chip)))
+                .map(|(chip_proof, sign_convention)| sign_convention.orient(chip_proof.cumulative_sum))
+                .collect::<Vec<_>>();
+            ::valida_machine::verify_aggregate_cumulative_sum(self, &cumulative_sums)
+                .map_err(|_| ())?;
 
             Ok(())
         }
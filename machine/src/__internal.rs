@@ -5,6 +5,8 @@ pub use crate::debug_builder::*;
 pub use crate::folding_builder::*;
 pub use crate::quotient::*;
 pub use crate::symbolic::symbolic_builder::*;
+pub use crate::symbolic::symbolic_expression::SymbolicExpression;
+pub use crate::symbolic::symbolic_variable::{SymbolicVariable, Trace};
 
 // Re-export some Plonky3 crates so that derives can use them.
 pub use p3_air;
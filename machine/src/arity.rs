@@ -0,0 +1,332 @@
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::{Display, Formatter};
+
+use crate::config::StarkConfig;
+use crate::{
+    BusArgument, BusName, BusParticipant, Chip, Interaction, InteractionType, LimbOrder, Machine,
+};
+use p3_air::VirtualPairCol;
+use p3_field::{AbstractField, Field};
+
+/// A bus whose participants disagree on the number of payload fields.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ArityMismatch {
+    pub bus: BusArgument,
+    /// The chip a local bus is internal to, or `None` for a global bus.
+    pub owner: Option<usize>,
+    /// The label of the bus, see `Machine::bus_label`.
+    pub label: Option<String>,
+    pub participants: Vec<BusParticipant>,
+}
+
+impl Display for ArityMismatch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        let name = BusName {
+            bus: self.bus,
+            label: self.label.as_deref(),
+        };
+        write!(f, "{} has participants of different arities:", name)?;
+        for participant in &self.participants {
+            write!(f, " chip {} arity {}", participant.chip, participant.arity)?;
+        }
+        Ok(())
+    }
+}
+
+/// A bus whose participants declare different limb orders for their fields.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LimbOrderMismatch {
+    pub bus: BusArgument,
+    /// The chip a local bus is internal to, or `None` for a global bus.
+    pub owner: Option<usize>,
+    /// The label of the bus, see `Machine::bus_label`.
+    pub label: Option<String>,
+    /// The chip and declared order of each interaction on the bus with an
+    /// `Interaction::limb_order`.
+    pub orders: Vec<(usize, LimbOrder)>,
+}
+
+impl Display for LimbOrderMismatch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        let name = BusName {
+            bus: self.bus,
+            label: self.label.as_deref(),
+        };
+        write!(f, "{} has participants of different limb orders:", name)?;
+        for (chip, order) in &self.orders {
+            write!(f, " chip {} {:?}", chip, order)?;
+        }
+        Ok(())
+    }
+}
+
+/// Check that every interaction on a bus carries the same number of fields, across all chips.
+///
+/// Each side of a bus reduces its tuples independently, so a send of `(a, b)` matched against a
+/// receive of `(a)` still produces well formed traces: the missing field simply weighs in as zero.
+/// A mismatch is almost always a bug, so it is reported rather than silently accepted; chips that
+/// mean the zero extension can use `zero_padded_interactions`.
+pub fn check_bus_arities<M, SC>(
+    machine: &M,
+    chips: &[&dyn Chip<M, SC>],
+) -> Result<(), Vec<ArityMismatch>>
+where
+    M: Machine<SC::Val>,
+    SC: StarkConfig,
+{
+    let mut mismatches = arity_mismatches(&chip_interactions(machine, chips));
+    for mismatch in &mut mismatches {
+        mismatch.label = machine.bus_label(mismatch.bus).map(ToString::to_string);
+    }
+    if mismatches.is_empty() {
+        Ok(())
+    } else {
+        Err(mismatches)
+    }
+}
+
+/// The interactions of every chip, with the fields of each interaction zero extended to the
+/// largest arity on its bus.
+///
+/// The appended fields are the highest powers of `\beta` and are zero, so padding changes no
+/// reciprocal: both sides of a bus weigh field `j` by `\beta^j` as before. It only makes the zero
+/// extension explicit, e.g. for comparisons of field tuples as in `check_global_balance`.
+pub fn zero_padded_interactions<M, SC>(
+    machine: &M,
+    chips: &[&dyn Chip<M, SC>],
+) -> Vec<Vec<(Interaction<SC::Val>, InteractionType)>>
+where
+    M: Machine<SC::Val>,
+    SC: StarkConfig,
+{
+    let mut interactions = chip_interactions(machine, chips);
+    pad_to_bus_arity(&mut interactions);
+    interactions
+}
+
+/// Check that the interactions on a bus which declare an `Interaction::limb_order` all declare the
+/// same one, across all chips.
+///
+/// Like an arity mismatch, a limb order mismatch still produces well formed traces, and the bus
+/// may even balance, but the values it matches are not the ones either side meant.
+pub fn check_limb_orders<M, SC>(
+    machine: &M,
+    chips: &[&dyn Chip<M, SC>],
+) -> Result<(), Vec<LimbOrderMismatch>>
+where
+    M: Machine<SC::Val>,
+    SC: StarkConfig,
+{
+    let mut mismatches = limb_order_mismatches(&chip_interactions(machine, chips));
+    for mismatch in &mut mismatches {
+        mismatch.label = machine.bus_label(mismatch.bus).map(ToString::to_string);
+    }
+    if mismatches.is_empty() {
+        Ok(())
+    } else {
+        Err(mismatches)
+    }
+}
+
+pub(crate) fn chip_interactions<M, SC>(
+    machine: &M,
+    chips: &[&dyn Chip<M, SC>],
+) -> Vec<Vec<(Interaction<SC::Val>, InteractionType)>>
+where
+    M: Machine<SC::Val>,
+    SC: StarkConfig,
+{
+    chips
+        .iter()
+        .map(|chip| chip.all_interactions(machine))
+        .collect()
+}
+
+/// The participants of every bus, keyed by `(owner, bus)` as in `lookup_graph_report`.
+pub(crate) fn bus_participants<F: Field>(
+    interactions: &[Vec<(Interaction<F>, InteractionType)>],
+) -> BTreeMap<(Option<usize>, BusArgument), Vec<BusParticipant>> {
+    let mut participants = BTreeMap::<_, Vec<_>>::new();
+    for (i, chip_interactions) in interactions.iter().enumerate() {
+        for (interaction, interaction_type) in chip_interactions {
+            let owner = interaction.is_local().then_some(i);
+            participants
+                .entry((owner, interaction.argument_index))
+                .or_default()
+                .push(BusParticipant {
+                    chip: i,
+                    interaction_type: interaction_type.clone(),
+                    arity: interaction.fields.len(),
+                });
+        }
+    }
+    participants
+}
+
+fn arity_mismatches<F: Field>(
+    interactions: &[Vec<(Interaction<F>, InteractionType)>],
+) -> Vec<ArityMismatch> {
+    bus_participants(interactions)
+        .into_iter()
+        .filter(|(_, participants)| {
+            participants
+                .windows(2)
+                .any(|pair| pair[0].arity != pair[1].arity)
+        })
+        .map(|((owner, bus), participants)| ArityMismatch {
+            bus,
+            owner,
+            label: None,
+            participants,
+        })
+        .collect()
+}
+
+fn limb_order_mismatches<F: Field>(
+    interactions: &[Vec<(Interaction<F>, InteractionType)>],
+) -> Vec<LimbOrderMismatch> {
+    let mut orders = BTreeMap::<_, Vec<_>>::new();
+    for (i, chip_interactions) in interactions.iter().enumerate() {
+        for (interaction, _) in chip_interactions {
+            if let Some(order) = interaction.limb_order {
+                let owner = interaction.is_local().then_some(i);
+                orders
+                    .entry((owner, interaction.argument_index))
+                    .or_default()
+                    .push((i, order));
+            }
+        }
+    }
+    orders
+        .into_iter()
+        .filter(|(_, orders)| orders.windows(2).any(|pair| pair[0].1 != pair[1].1))
+        .map(|((owner, bus), orders)| LimbOrderMismatch {
+            bus,
+            owner,
+            label: None,
+            orders,
+        })
+        .collect()
+}
+
+fn pad_to_bus_arity<F: Field>(interactions: &mut [Vec<(Interaction<F>, InteractionType)>]) {
+    let arities = bus_participants(interactions)
+        .into_iter()
+        .map(|(bus, participants)| {
+            let arity = participants.iter().map(|p| p.arity).max().unwrap_or(0);
+            (bus, arity)
+        })
+        .collect::<BTreeMap<_, _>>();
+    for (i, chip_interactions) in interactions.iter_mut().enumerate() {
+        for (interaction, _) in chip_interactions {
+            let owner = interaction.is_local().then_some(i);
+            let arity = arities[&(owner, interaction.argument_index)];
+            interaction
+                .fields
+                .resize(arity, VirtualPairCol::constant(F::zero()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chip::reduce_row;
+    use alloc::vec;
+
+    type F = p3_baby_bear::BabyBear;
+    type EF = p3_field::extension::BinomialExtensionField<F, 4>;
+
+    fn interaction(cols: &[usize]) -> Interaction<F> {
+        let fields = cols
+            .iter()
+            .map(|&col| VirtualPairCol::single_main(col))
+            .collect();
+        Interaction::with_multiplicity_col(fields, 0, BusArgument::Global(1))
+    }
+
+    #[test]
+    fn mismatched_arities() {
+        let interactions = vec![
+            vec![(interaction(&[1, 2]), InteractionType::GlobalSend)],
+            vec![(interaction(&[1]), InteractionType::GlobalReceive)],
+        ];
+        let mismatches = arity_mismatches(&interactions);
+        assert_eq!(mismatches.len(), 1);
+        assert_eq!(mismatches[0].bus, BusArgument::Global(1));
+        assert_eq!(mismatches[0].owner, None);
+        let arities = mismatches[0]
+            .participants
+            .iter()
+            .map(|p| (p.chip, p.arity))
+            .collect::<Vec<_>>();
+        assert_eq!(arities, vec![(0, 2), (1, 1)]);
+    }
+
+    #[test]
+    fn mismatched_limb_orders() {
+        let limbs = |order| {
+            Interaction::<F>::send_limbs(
+                BusArgument::Global(1),
+                &[0, 1, 2, 3],
+                VirtualPairCol::one(),
+                order,
+            )
+        };
+        let interactions = vec![
+            vec![(limbs(LimbOrder::LittleEndian), InteractionType::GlobalSend)],
+            vec![(limbs(LimbOrder::BigEndian), InteractionType::GlobalReceive)],
+            vec![(interaction(&[3, 2, 1, 0]), InteractionType::GlobalReceive)],
+        ];
+        // The arities agree, so only the declared limb orders tell the two sides apart.
+        assert!(arity_mismatches(&interactions).is_empty());
+        let mismatches = limb_order_mismatches(&interactions);
+        assert_eq!(mismatches.len(), 1);
+        assert_eq!(mismatches[0].bus, BusArgument::Global(1));
+        assert_eq!(
+            mismatches[0].orders,
+            vec![(0, LimbOrder::LittleEndian), (1, LimbOrder::BigEndian)]
+        );
+
+        let interactions = vec![
+            vec![(limbs(LimbOrder::LittleEndian), InteractionType::GlobalSend)],
+            vec![(
+                limbs(LimbOrder::LittleEndian),
+                InteractionType::GlobalReceive,
+            )],
+        ];
+        assert!(limb_order_mismatches(&interactions).is_empty());
+    }
+
+    #[test]
+    fn zero_padding() {
+        let mut interactions = vec![
+            vec![(interaction(&[1, 2, 3]), InteractionType::GlobalSend)],
+            vec![(interaction(&[1]), InteractionType::GlobalReceive)],
+        ];
+        pad_to_bus_arity(&mut interactions);
+        assert!(arity_mismatches(&interactions).is_empty());
+
+        // The padded fields are zero, so the receive reduces exactly as before.
+        let padded = &interactions[1][0].0;
+        assert_eq!(padded.fields.len(), 3);
+        let row = [1, 5, 6, 7].map(F::from_canonical_u32);
+        let alpha = EF::from_canonical_u32(11);
+        let betas = EF::from_canonical_u32(13)
+            .powers()
+            .take(3)
+            .collect::<Vec<_>>();
+        let reduce = |interaction: &Interaction<F>| {
+            reduce_row(
+                &[&row[..], &row[..]],
+                &[&[], &[]],
+                interaction,
+                alpha,
+                &betas,
+            )
+        };
+        assert_eq!(reduce(padded), reduce(&interaction(&[1])));
+    }
+}
@@ -0,0 +1,425 @@
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::{Display, Formatter};
+
+use crate::chip::{beta_powers, gated_count, generate_rlc_elements, reduce_row};
+use crate::config::StarkConfig;
+use crate::{BusArgument, Chip, Interaction, InteractionType, Machine};
+use p3_field::{AbstractField, Field, PrimeField32};
+use p3_matrix::dense::RowMajorMatrix;
+use p3_matrix::{Matrix, MatrixRowSlices};
+
+/// Check that the chips of a machine collectively balance every global bus.
+///
+/// This computes, from each chip's main trace, the signed contributions
+/// `count / (\alpha + \sum_j \beta^j f_j)` of every interaction on every row and sums them per
+/// global bus across all chips. Returns the buses whose total is nonzero. This is the machine-level analog
+/// of the per-chip running sum boundary constraint.
+pub fn assert_machine_balanced<M, SC>(
+    machine: &M,
+    chips: &[&dyn Chip<M, SC>],
+    mains: &[RowMajorMatrix<SC::Val>],
+    random_elements: &[SC::Challenge],
+) -> Result<(), Vec<BusArgument>>
+where
+    M: Machine<SC::Val>,
+    SC: StarkConfig,
+{
+    let unbalanced = bus_sums(machine, chips, mains, random_elements)
+        .into_iter()
+        .filter(|((owner, _), sum)| owner.is_none() && !sum.is_zero())
+        .map(|((_, bus), _)| bus)
+        .collect::<Vec<_>>();
+    if unbalanced.is_empty() {
+        Ok(())
+    } else {
+        Err(unbalanced)
+    }
+}
+
+/// A global bus whose sends and receives of some field tuple do not cancel out.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BusImbalance {
+    /// The index of the global bus.
+    pub bus: usize,
+    /// The label of the bus, see `Machine::bus_label`.
+    pub label: Option<String>,
+    /// The canonical values of an unbalanced field tuple.
+    pub fields: Vec<u32>,
+    /// The canonical value of the signed multiplicity of the tuple, sends minus receives.
+    pub multiplicity: u32,
+}
+
+/// Check that the chips of a machine balance every global bus, tuple by tuple.
+///
+/// Unlike `assert_machine_balanced`, this uses no challenges: each chip's main trace is generated
+/// and the counts of its global interactions are summed per `(bus, field tuple)`, sends added and
+/// receives subtracted. Returns the first tuple, in bus and tuple order, whose total is nonzero.
+pub fn check_global_balance<M, SC>(
+    machine: &M,
+    chips: &[&dyn Chip<M, SC>],
+) -> Result<(), BusImbalance>
+where
+    M: Machine<SC::Val>,
+    SC: StarkConfig,
+{
+    let mut multiplicities = BTreeMap::new();
+    for chip in chips {
+        let main = chip.generate_main_trace(machine);
+        add_global_multiplicities(
+            &mut multiplicities,
+            &chip.all_interactions(machine),
+            chip.preprocessed_trace().as_ref(),
+            &main,
+            chip.active_selector(),
+        );
+    }
+    first_imbalance(multiplicities).map_err(|mut imbalance| {
+        imbalance.label = machine
+            .bus_label(BusArgument::Global(imbalance.bus))
+            .map(ToString::to_string);
+        imbalance
+    })
+}
+
+impl Display for BusImbalance {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        let name = BusName {
+            bus: BusArgument::Global(self.bus),
+            label: self.label.as_deref(),
+        };
+        write!(
+            f,
+            "{} does not balance: {:?} has multiplicity {}",
+            name, self.fields, self.multiplicity
+        )
+    }
+}
+
+/// A local bus of a chip whose sends and receives of some field tuple do not cancel out.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LocalImbalance {
+    /// The index of the local bus.
+    pub bus: usize,
+    /// The canonical values of an unbalanced field tuple.
+    pub fields: Vec<u32>,
+    /// The canonical value of the signed multiplicity of the tuple, sends minus receives.
+    pub multiplicity: u32,
+}
+
+impl Display for LocalImbalance {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "{:?} does not balance: {:?} has multiplicity {}",
+            BusArgument::Local(self.bus),
+            self.fields,
+            self.multiplicity
+        )
+    }
+}
+
+/// See `Chip::check_local_balance`.
+pub(crate) fn check_local_balance<M, SC, C>(machine: &M, chip: &C) -> Result<(), LocalImbalance>
+where
+    M: Machine<SC::Val>,
+    SC: StarkConfig,
+    C: Chip<M, SC> + ?Sized,
+{
+    let main = chip.generate_main_trace(machine);
+    let mut multiplicities = BTreeMap::new();
+    add_multiplicities(
+        &mut multiplicities,
+        &chip.all_interactions(machine),
+        chip.preprocessed_trace().as_ref(),
+        &main,
+        chip.active_selector(),
+        false,
+    );
+    first_imbalance(multiplicities).map_err(|imbalance| LocalImbalance {
+        bus: imbalance.bus,
+        fields: imbalance.fields,
+        multiplicity: imbalance.multiplicity,
+    })
+}
+
+/// The total count of every field tuple the given chips send on a global bus, e.g. to fill in the
+/// multiplicity column of a table chip receiving those sends. Main traces are generated as in
+/// `check_global_balance`.
+pub fn global_send_multiplicities<M, SC>(
+    machine: &M,
+    chips: &[&dyn Chip<M, SC>],
+    bus: usize,
+) -> BTreeMap<Vec<u32>, SC::Val>
+where
+    M: Machine<SC::Val>,
+    SC: StarkConfig,
+{
+    let mut multiplicities = BTreeMap::new();
+    for chip in chips {
+        let sends = chip
+            .all_interactions(machine)
+            .into_iter()
+            .filter(|(interaction, interaction_type)| {
+                *interaction_type == InteractionType::GlobalSend
+                    && interaction.argument_index == BusArgument::Global(bus)
+            })
+            .collect::<Vec<_>>();
+        if sends.is_empty() {
+            continue;
+        }
+        let main = chip.generate_main_trace(machine);
+        add_global_multiplicities(
+            &mut multiplicities,
+            &sends,
+            chip.preprocessed_trace().as_ref(),
+            &main,
+            chip.active_selector(),
+        );
+    }
+    multiplicities
+        .into_iter()
+        .map(|((_, fields), count)| (fields, count))
+        .collect()
+}
+
+/// Add the signed count of every global interaction on every row to its `(bus, field tuple)`.
+fn add_global_multiplicities<F: PrimeField32>(
+    multiplicities: &mut BTreeMap<(usize, Vec<u32>), F>,
+    interactions: &[(Interaction<F>, InteractionType)],
+    preprocessed: Option<&RowMajorMatrix<F>>,
+    main: &RowMajorMatrix<F>,
+    active_selector: Option<usize>,
+) {
+    add_multiplicities(
+        multiplicities,
+        interactions,
+        preprocessed,
+        main,
+        active_selector,
+        true,
+    );
+}
+
+/// Add the signed count of every interaction of the given scope, global or local, on every row to
+/// its `(bus, field tuple)`.
+fn add_multiplicities<F: PrimeField32>(
+    multiplicities: &mut BTreeMap<(usize, Vec<u32>), F>,
+    interactions: &[(Interaction<F>, InteractionType)],
+    preprocessed: Option<&RowMajorMatrix<F>>,
+    main: &RowMajorMatrix<F>,
+    active_selector: Option<usize>,
+    global: bool,
+) {
+    let height = main.height();
+    for n in 0..height {
+        let n_next = (n + 1) % height;
+        let main_rows = [main.row_slice(n), main.row_slice(n_next)];
+        let preprocessed_rows = match preprocessed {
+            Some(preprocessed) => [preprocessed.row_slice(n), preprocessed.row_slice(n_next)],
+            None => [&[][..], &[][..]],
+        };
+        for (interaction, interaction_type) in interactions {
+            if interaction.is_global() != global {
+                continue;
+            }
+            let mult = interaction.count_at::<F, F>(preprocessed_rows[0], main_rows[0]);
+            let mult = gated_count(active_selector, mult, main_rows[0]);
+            if mult.is_zero() {
+                continue;
+            }
+            let fields = interaction
+                .fields
+                .iter()
+                .enumerate()
+                .map(|(i, field)| {
+                    let rotation = interaction.field_rotation(i);
+                    field
+                        .apply::<F, F>(preprocessed_rows[rotation], main_rows[rotation])
+                        .as_canonical_u32()
+                })
+                .collect();
+            let sum = multiplicities
+                .entry((interaction.argument_index(), fields))
+                .or_insert_with(F::zero);
+            match interaction_type {
+                InteractionType::LocalSend | InteractionType::GlobalSend => *sum += mult,
+                InteractionType::LocalReceive | InteractionType::GlobalReceive => *sum -= mult,
+            }
+        }
+    }
+}
+
+fn first_imbalance<F: PrimeField32>(
+    multiplicities: BTreeMap<(usize, Vec<u32>), F>,
+) -> Result<(), BusImbalance> {
+    match multiplicities.into_iter().find(|(_, sum)| !sum.is_zero()) {
+        Some(((bus, fields), sum)) => Err(BusImbalance {
+            bus,
+            label: None,
+            fields,
+            multiplicity: sum.as_canonical_u32(),
+        }),
+        None => Ok(()),
+    }
+}
+
+/// The signed sum of the contributions of every bus, keyed by `(owner, bus)`. Global buses have no
+/// owner, and local buses are owned by the chip, by index in `chips`, they are internal to.
+///
+/// Each reciprocal is computed from the main trace rather than read from a permutation trace, so
+/// the sums are the same whatever the chip's permutation trace looks like: bundled, under
+/// `LookupBackend::LogUp`, from a custom `Chip::generate_permutation_trace`, or with interactions
+/// whose simplified form owns no column. Zero denominators contribute zero, as in
+/// `generate_permutation_trace`.
+pub(crate) fn bus_sums<M, SC>(
+    machine: &M,
+    chips: &[&dyn Chip<M, SC>],
+    mains: &[RowMajorMatrix<SC::Val>],
+    random_elements: &[SC::Challenge],
+) -> BTreeMap<(Option<usize>, BusArgument), SC::Challenge>
+where
+    M: Machine<SC::Val>,
+    SC: StarkConfig,
+{
+    assert_eq!(chips.len(), mains.len());
+
+    let mut sums = BTreeMap::new();
+    for (i, (chip, main)) in chips.iter().zip(mains).enumerate() {
+        let preprocessed = chip.preprocessed_trace();
+        let interactions = chip.interaction_layout(machine).interactions;
+        let (alphas_local, alphas_global) =
+            generate_rlc_elements::<M, SC, _>(machine, *chip, random_elements);
+        let betas = beta_powers(random_elements[2], &interactions);
+
+        let height = main.height();
+        for n in 0..height {
+            let n_next = (n + 1) % height;
+            let main_rows = [main.row_slice(n), main.row_slice(n_next)];
+            let preprocessed_rows = match &preprocessed {
+                Some(preprocessed) => [preprocessed.row_slice(n), preprocessed.row_slice(n_next)],
+                None => [&[][..], &[][..]],
+            };
+            for (interaction, interaction_type) in interactions.iter() {
+                let mult =
+                    interaction.count_at::<SC::Val, SC::Val>(preprocessed_rows[0], main_rows[0]);
+                let mult = gated_count(chip.active_selector(), mult, main_rows[0]);
+                let alpha = if interaction.is_local() {
+                    alphas_local[interaction.argument_index()]
+                } else {
+                    alphas_global[interaction.argument_index()]
+                };
+                let q = reduce_row(&main_rows, &preprocessed_rows, interaction, alpha, &betas)
+                    .try_inverse()
+                    .unwrap_or(SC::Challenge::zero());
+                let owner = interaction.is_local().then_some(i);
+                let sum = sums
+                    .entry((owner, interaction.argument_index))
+                    .or_insert_with(SC::Challenge::zero);
+                match interaction_type {
+                    InteractionType::LocalSend | InteractionType::GlobalSend => *sum += q * mult,
+                    InteractionType::LocalReceive | InteractionType::GlobalReceive => {
+                        *sum -= q * mult
+                    }
+                }
+            }
+        }
+    }
+
+    sums
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+    use p3_air::VirtualPairCol;
+
+    type F = p3_baby_bear::BabyBear;
+
+    /// A memory bus carrying `(address, value)` with the count in column 2.
+    fn memory(interaction_type: InteractionType) -> (Interaction<F>, InteractionType) {
+        let fields = vec![
+            VirtualPairCol::single_main(0),
+            VirtualPairCol::single_main(1),
+        ];
+        (
+            Interaction::with_multiplicity_col(fields, 2, BusArgument::Global(0)),
+            interaction_type,
+        )
+    }
+
+    fn main(rows: &[[u32; 3]]) -> RowMajorMatrix<F> {
+        RowMajorMatrix::new(
+            rows.iter()
+                .flatten()
+                .map(|&x| F::from_canonical_u32(x))
+                .collect(),
+            3,
+        )
+    }
+
+    #[test]
+    fn memory_bus_balance() {
+        let writes = main(&[[10, 1, 1], [11, 2, 2], [12, 3, 0]]);
+        let reads = main(&[[11, 2, 1], [10, 1, 1], [11, 2, 1]]);
+
+        let mut multiplicities = BTreeMap::new();
+        for (rows, interaction_type) in [
+            (&writes, InteractionType::GlobalSend),
+            (&reads, InteractionType::GlobalReceive),
+        ] {
+            add_global_multiplicities(
+                &mut multiplicities,
+                &[memory(interaction_type)],
+                None,
+                rows,
+                None,
+            );
+        }
+        assert_eq!(first_imbalance(multiplicities), Ok(()));
+
+        // A read of a value which was never written.
+        let reads = main(&[[11, 2, 1], [10, 1, 1], [11, 5, 1]]);
+        let mut multiplicities = BTreeMap::new();
+        for (rows, interaction_type) in [
+            (&writes, InteractionType::GlobalSend),
+            (&reads, InteractionType::GlobalReceive),
+        ] {
+            add_global_multiplicities(
+                &mut multiplicities,
+                &[memory(interaction_type)],
+                None,
+                rows,
+                None,
+            );
+        }
+        let expected = BusImbalance {
+            bus: 0,
+            label: None,
+            fields: vec![11, 2],
+            multiplicity: 1,
+        };
+        assert_eq!(first_imbalance(multiplicities), Err(expected));
+    }
+
+    #[test]
+    fn labelled_imbalance() {
+        let imbalance = BusImbalance {
+            bus: 2,
+            label: Some("memory".to_string()),
+            fields: vec![11, 5],
+            multiplicity: 1,
+        };
+        assert_eq!(
+            imbalance.to_string(),
+            "memory bus #2 does not balance: [11, 5] has multiplicity 1"
+        );
+        let unlabelled = BusImbalance {
+            label: None,
+            ..imbalance
+        };
+        assert!(unlabelled.to_string().starts_with("Global(2) does not"));
+    }
+}
@@ -0,0 +1,40 @@
+use alloc::vec::Vec;
+
+use p3_field::PrimeField32;
+use p3_matrix::dense::RowMajorMatrix;
+
+/// Generate `num_rows` rows of `width` pseudorandom field elements for zero-knowledge blinding.
+///
+/// Rows are drawn from a SplitMix64 stream keyed by `seed`, so a chip's blinding is reproducible
+/// for testing while chips with distinct seeds (see `Chip::blinding_seed`) draw independent
+/// streams. This is not a cryptographic RNG; production provers should seed it from a secure
+/// source.
+pub fn generate_blinding_rows<F: PrimeField32>(
+    seed: u64,
+    num_rows: usize,
+    width: usize,
+) -> RowMajorMatrix<F> {
+    let mut rng = SplitMix64(seed);
+    let values = (0..num_rows * width)
+        .map(|_| F::from_wrapped_u32(rng.next_u32()))
+        .collect::<Vec<_>>();
+    RowMajorMatrix::new(values, width)
+}
+
+/// The SplitMix64 generator, also behind `SeededChallenges`.
+pub(crate) struct SplitMix64(pub(crate) u64);
+
+impl SplitMix64 {
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// The high bits of the next output.
+    pub(crate) fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+}
@@ -0,0 +1,360 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use core::any::type_name;
+
+use crate::chip::{beta_powers, gated_count, generate_rlc_elements, reduce_row, reduce_row_expr};
+use crate::config::StarkConfig;
+use crate::{
+    bundle_interactions, interaction_degrees, Chip, ConstraintKind, ConstraintProvenance,
+    Interaction, InteractionAir, InteractionType, Machine, SignConvention, ValidaAirBuilder,
+};
+use p3_air::{Air, ExtensionBuilder};
+use p3_field::{AbstractField, ExtensionField, Field};
+use p3_matrix::dense::RowMajorMatrix;
+use p3_matrix::{Matrix, MatrixRowSlices};
+use valida_util::batch_multiplicative_inverse_allowing_zero;
+
+/// The permutation argument of a machine's chips, see `Machine::lookup_backend`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum LookupBackend {
+    /// One reciprocal column per interaction whose payload depends on the row.
+    #[default]
+    Reciprocals,
+    /// The logarithmic derivative (LogUp) form, in which each permutation column holds the sum
+    /// `\sum_i \pm c_i / d_i` over a bundle of interactions, bundled under a constraint degree
+    /// budget of `max_degree` as for `InteractionAir::reciprocal_bundle_degree`.
+    LogUp { max_degree: usize },
+}
+
+/// The bundle degree budget of a chip's permutation argument: its own
+/// `InteractionAir::reciprocal_bundle_degree`, or else that of a `LookupBackend::LogUp` machine,
+/// or `None` for one reciprocal column per interaction.
+pub fn bundle_degree_budget<M, SC, C>(machine: &M, chip: &C) -> Option<usize>
+where
+    M: Machine<SC::Val>,
+    SC: StarkConfig,
+    C: InteractionAir<M, SC> + ?Sized,
+{
+    chip.reciprocal_bundle_degree()
+        .or(match machine.lookup_backend() {
+            LookupBackend::Reciprocals => None,
+            LookupBackend::LogUp { max_degree } => Some(max_degree),
+        })
+}
+
+/// The bundles of a chip declaring `InteractionAir::reciprocal_bundle_degree`: groups of
+/// consecutive interactions, by index in `InteractionAir::all_interactions`, sharing one
+/// permutation column.
+pub fn reciprocal_bundles<M, SC, C>(machine: &M, chip: &C, max_degree: usize) -> Vec<Vec<usize>>
+where
+    M: Machine<SC::Val>,
+    SC: StarkConfig,
+    C: InteractionAir<M, SC> + ?Sized,
+{
+    bundle_interactions(&interaction_degrees::<M, SC, C>(machine, chip), max_degree)
+}
+
+/// Generate the permutation trace of a chip with bundled reciprocals, see
+/// `InteractionAir::reciprocal_bundle_degree`.
+///
+/// Column `b` holds `\sum_{i \in b} \pm c_i / d_i` over the interactions of bundle `b`, with sends
+/// added and receives subtracted, so the running sum advances by the sum of the bundle columns.
+pub fn generate_bundled_permutation_trace<M, SC>(
+    machine: &M,
+    chip: &dyn Chip<M, SC>,
+    main: &RowMajorMatrix<SC::Val>,
+    random_elements: &[SC::Challenge],
+    max_degree: usize,
+) -> RowMajorMatrix<SC::Challenge>
+where
+    M: Machine<SC::Val>,
+    SC: StarkConfig,
+{
+    let interactions = chip.all_interactions(machine);
+    let (alphas_local, alphas_global) =
+        generate_rlc_elements::<M, SC, _>(machine, chip, random_elements);
+    bundled_permutation_trace(
+        &interactions,
+        &reciprocal_bundles::<M, SC, _>(machine, chip, max_degree),
+        chip.preprocessed_trace().as_ref(),
+        main,
+        (&alphas_local, &alphas_global),
+        &beta_powers(random_elements[2], &interactions),
+        chip.active_selector(),
+        chip.sign_convention(),
+    )
+}
+
+fn bundled_permutation_trace<F, EF>(
+    interactions: &[(Interaction<F>, InteractionType)],
+    bundles: &[Vec<usize>],
+    preprocessed: Option<&RowMajorMatrix<F>>,
+    main: &RowMajorMatrix<F>,
+    (alphas_local, alphas_global): (&[EF], &[EF]),
+    betas: &[EF],
+    active_selector: Option<usize>,
+    sign_convention: SignConvention,
+) -> RowMajorMatrix<EF>
+where
+    F: Field,
+    EF: ExtensionField<F>,
+{
+    let height = main.height();
+    let num_interactions = interactions.len();
+
+    // The denominator of every interaction on every row, inverted in one batch.
+    let mut denominators = Vec::with_capacity(height * num_interactions);
+    for n in 0..height {
+        let n_next = (n + 1) % height;
+        let main_rows = [main.row_slice(n), main.row_slice(n_next)];
+        let preprocessed_rows = match preprocessed {
+            Some(preprocessed) => [preprocessed.row_slice(n), preprocessed.row_slice(n_next)],
+            None => [&[][..], &[][..]],
+        };
+        for (interaction, _) in interactions {
+            let alpha = if interaction.is_local() {
+                alphas_local[interaction.argument_index()]
+            } else {
+                alphas_global[interaction.argument_index()]
+            };
+            denominators.push(reduce_row(
+                &main_rows,
+                &preprocessed_rows,
+                interaction,
+                alpha,
+                betas,
+            ));
+        }
+    }
+    #[cfg(feature = "cost-model")]
+    crate::cost::record_batch_inverse(&denominators);
+    let reciprocals = batch_multiplicative_inverse_allowing_zero(denominators);
+
+    let perm_width = bundles.len() + 1;
+    let mut perm_values = vec![EF::zero(); height * perm_width];
+    let mut phi = EF::zero();
+    for n in 0..height {
+        let main_row = main.row_slice(n);
+        let preprocessed_row = match preprocessed {
+            Some(preprocessed) => preprocessed.row_slice(n),
+            None => &[],
+        };
+        let perm_row = &mut perm_values[n * perm_width..(n + 1) * perm_width];
+        for (b, bundle) in bundles.iter().enumerate() {
+            let mut sum = EF::zero();
+            for &m in bundle {
+                let (interaction, interaction_type) = &interactions[m];
+                let mult = interaction.count_at::<F, F>(preprocessed_row, main_row);
+                let mult = gated_count(active_selector, mult, main_row);
+                let q = reciprocals[n * num_interactions + m];
+                if sign_convention.adds(interaction_type) {
+                    sum += q * mult;
+                } else {
+                    sum -= q * mult;
+                }
+            }
+            perm_row[b] = sum;
+            phi += sum;
+        }
+        perm_row[bundles.len()] = phi;
+    }
+    RowMajorMatrix::new(perm_values, perm_width)
+}
+
+/// Evaluate the permutation constraints of a chip with bundled reciprocals, for the trace of
+/// `generate_bundled_permutation_trace`.
+///
+/// Bundle column `q_b` is bound by `q_b \prod_i d_i = \sum_i \pm c_i \prod_{j \neq i} d_j`, whose
+/// degree is `bundle_degree`, and the running sum constraints are those of
+/// `eval_permutation_constraints` with the bundle columns in place of the weighted reciprocals.
+pub fn eval_bundled_permutation_constraints<M, C, SC, AB>(
+    chip: &C,
+    builder: &mut AB,
+    max_degree: usize,
+    cumulative_sum: AB::EF,
+) where
+    M: Machine<SC::Val>,
+    C: InteractionAir<M, SC> + Air<AB>,
+    SC: StarkConfig,
+    AB: ValidaAirBuilder<Machine = M, F = SC::Val, EF = SC::Challenge>,
+{
+    let rand_elems = builder.permutation_randomness().to_vec();
+
+    let main = builder.main();
+    let main_local: &[AB::Var] = main.row_slice(0);
+    let main_next: &[AB::Var] = main.row_slice(1);
+
+    let preprocessed = builder.preprocessed();
+    let preprocessed_local = preprocessed.row_slice(0);
+    let preprocessed_next = preprocessed.row_slice(1);
+
+    let perm = builder.permutation();
+    let perm_width = perm.width();
+    let perm_local: &[AB::VarEF] = perm.row_slice(0);
+    let perm_next: &[AB::VarEF] = perm.row_slice(1);
+
+    let phi_local: AB::ExprEF = perm_local[perm_width - 1].clone().into();
+    let phi_next: AB::ExprEF = perm_next[perm_width - 1].clone().into();
+
+    let machine = builder.machine();
+    let all_interactions = chip.all_interactions(machine);
+    let bundles = reciprocal_bundles::<M, SC, C>(machine, chip, max_degree);
+    let (alphas_local, alphas_global) =
+        generate_rlc_elements::<M, SC, _>(machine, chip, &rand_elems);
+    let betas = beta_powers(rand_elems[2], &all_interactions);
+
+    let active_selector = chip.active_selector();
+    if let Some(col) = active_selector {
+        builder.assert_bool(main_local[col]);
+    }
+    let sign_convention = chip.sign_convention();
+
+    let provenance = |kind, interaction_index| ConstraintProvenance {
+        chip: type_name::<C>(),
+        interaction_index,
+        bus: None,
+        kind,
+    };
+    let mut phi_0 = AB::ExprEF::zero();
+    let mut rhs = AB::ExprEF::zero();
+    for (b, bundle) in bundles.iter().enumerate() {
+        // Accumulate the bundle as a single fraction `numerator / denominator`.
+        let mut numerator = AB::ExprEF::zero();
+        let mut denominator = AB::ExprEF::one();
+        for &m in bundle {
+            let (interaction, interaction_type) = &all_interactions[m];
+            let alpha = if interaction.is_local() {
+                alphas_local[interaction.argument_index()]
+            } else {
+                alphas_global[interaction.argument_index()]
+            };
+            let rlc: AB::ExprEF = reduce_row_expr(
+                &[main_local, main_next],
+                &[preprocessed_local, preprocessed_next],
+                interaction,
+                alpha,
+                &betas,
+            );
+
+            let mut mult =
+                interaction.count_at::<AB::Expr, AB::Var>(preprocessed_local, main_local);
+            if let Some(col) = active_selector {
+                mult *= AB::Expr::from(main_local[col]);
+            }
+
+            let weighted = denominator.clone() * mult;
+            numerator = if sign_convention.adds(interaction_type) {
+                numerator * rlc.clone() + weighted
+            } else {
+                numerator * rlc.clone() - weighted
+            };
+            denominator *= rlc;
+        }
+
+        builder.set_provenance(provenance(ConstraintKind::Bundle, bundle.first().copied()));
+        let q_local: AB::ExprEF = perm_local[b].clone().into();
+        let q_next: AB::ExprEF = perm_next[b].clone().into();
+        builder.assert_eq_ext(q_local.clone() * denominator, numerator);
+
+        phi_0 += q_local;
+        rhs += q_next;
+    }
+
+    builder.set_provenance(provenance(ConstraintKind::RunningSumTransition, None));
+    builder
+        .when_transition()
+        .assert_eq_ext(phi_next - phi_local.clone(), rhs);
+    builder.set_provenance(provenance(ConstraintKind::RunningSumFirstRow, None));
+    builder
+        .when_first_row()
+        .assert_eq_ext(phi_local.clone(), phi_0);
+    builder.set_provenance(provenance(ConstraintKind::RunningSumLastRow, None));
+    builder
+        .when_last_row()
+        .assert_eq_ext(phi_local, AB::ExprEF::from_f(cumulative_sum));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BusArgument, CountBound, InteractionDegree};
+    use p3_air::VirtualPairCol;
+
+    type F = p3_baby_bear::BabyBear;
+    type EF = p3_field::extension::BinomialExtensionField<F, 4>;
+
+    #[test]
+    fn bundles_share_columns() {
+        // Three values sent and received on local bus 0, six interactions of degree one.
+        let interaction = |col| Interaction::<F> {
+            fields: vec![VirtualPairCol::single_main(col)],
+            count: VirtualPairCol::one(),
+            argument_index: BusArgument::Local(0),
+            rotations: vec![],
+            count_bound: CountBound::Unbounded,
+            is_real: None,
+            limb_order: None,
+        };
+        let interactions = (0..3)
+            .flat_map(|col| {
+                [
+                    (interaction(col), InteractionType::LocalSend),
+                    (interaction(2 - col), InteractionType::LocalReceive),
+                ]
+            })
+            .collect::<Vec<_>>();
+        let degree = InteractionDegree {
+            denominator: 1,
+            count: 0,
+        };
+        // Two degree one denominators and a reciprocal column reach degree three.
+        let bundles = bundle_interactions(&[degree; 6], 3);
+        assert_eq!(bundles, vec![vec![0, 1], vec![2, 3], vec![4, 5]]);
+
+        let main = RowMajorMatrix::new((1..=12).map(F::from_canonical_u32).collect(), 3);
+        let alpha = EF::from_canonical_u32(7);
+        let betas = [EF::one()];
+        let perm = bundled_permutation_trace(
+            &interactions,
+            &bundles,
+            None,
+            &main,
+            (&[alpha], &[]),
+            &betas,
+            None,
+            SignConvention::SendsPositive,
+        );
+        assert!(perm.width() < 7);
+
+        for n in 0..main.height() {
+            let main_row = main.row_slice(n);
+            let perm_row = perm.row_slice(n);
+            for (b, bundle) in bundles.iter().enumerate() {
+                let expected = bundle
+                    .iter()
+                    .map(|&m| {
+                        let (interaction, interaction_type) = &interactions[m];
+                        let q = reduce_row(
+                            &[main_row, main_row],
+                            &[&[], &[]],
+                            interaction,
+                            alpha,
+                            &betas,
+                        )
+                        .inverse();
+                        match interaction_type {
+                            InteractionType::LocalSend => q,
+                            _ => -q,
+                        }
+                    })
+                    .sum::<EF>();
+                assert_eq!(perm_row[b], expected);
+            }
+        }
+        // Every row sends and receives the same values, so the bus balances.
+        assert_eq!(
+            *perm.row_slice(main.height() - 1).last().unwrap(),
+            EF::zero()
+        );
+    }
+}
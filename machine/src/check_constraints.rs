@@ -1,16 +1,19 @@
 use crate::__internal::DebugConstraintBuilder;
-use crate::chip::eval_permutation_constraints;
+use crate::chip::{
+    cumulative_sum_from_perm, eval_permutation_constraints, try_eval_permutation_constraints,
+};
 use valida_machine::StarkConfig;
 
-use crate::{Chip, Machine};
-use p3_air::TwoRowMatrixView;
+use crate::{ConstraintFailure, InteractionAir, Machine, MissingCumulativeSum, SignConvention};
+use p3_air::{Air, TwoRowMatrixView};
 use p3_field::{AbstractField, Field};
 use p3_matrix::dense::RowMajorMatrix;
 use p3_matrix::Matrix;
 use p3_matrix::MatrixRowSlices;
 use p3_maybe_rayon::prelude::*;
 
-/// Check that all constraints vanish on the subgroup.
+/// Check that all constraints vanish on the subgroup. The AIR need not be a `Chip`: its
+/// `InteractionAir` is enough for the permutation constraints.
 pub fn check_constraints<M, A, SC>(
     machine: &M,
     air: &A,
@@ -19,75 +22,175 @@ pub fn check_constraints<M, A, SC>(
     perm_challenges: &[SC::Challenge],
 ) where
     M: Machine<SC::Val>,
-    A: Chip<M, SC>,
+    A: InteractionAir<M, SC> + for<'a> Air<DebugConstraintBuilder<'a, M, SC>>,
+    SC: StarkConfig,
+{
+    let cumulative_sum = cumulative_sum_from_perm(perm);
+    try_check_constraints::<M, A, SC>(
+        machine,
+        air,
+        main,
+        perm,
+        perm_challenges,
+        Some(cumulative_sum),
+    )
+    .expect("the cumulative sum is given");
+}
+
+/// Like `check_constraints`, checking the running sum against `cumulative_sum` rather than the
+/// last row of `perm`. Without a cumulative sum, only a chip with no interactions can be checked,
+/// see `try_eval_permutation_constraints`.
+pub fn try_check_constraints<M, A, SC>(
+    machine: &M,
+    air: &A,
+    main: &RowMajorMatrix<SC::Val>,
+    perm: &RowMajorMatrix<SC::Challenge>,
+    perm_challenges: &[SC::Challenge],
+    cumulative_sum: Option<SC::Challenge>,
+) -> Result<(), MissingCumulativeSum>
+where
+    M: Machine<SC::Val>,
+    A: InteractionAir<M, SC> + for<'a> Air<DebugConstraintBuilder<'a, M, SC>>,
     SC: StarkConfig,
 {
     assert_eq!(main.height(), perm.height());
     let height = main.height();
     if height == 0 {
-        return;
+        return Ok(());
     }
 
     let preprocessed = air.preprocessed_trace();
 
-    let cumulative_sum = *perm.row_slice(perm.height() - 1).last().unwrap();
-
     // Check that constraints are satisfied.
-    (0..height).into_par_iter().for_each(|i| {
-        let i_next = (i + 1) % height;
-
-        let main_local = main.row_slice(i);
-        let main_next = main.row_slice(i_next);
-        let preprocessed_local = if preprocessed.is_some() {
-            preprocessed.as_ref().unwrap().row_slice(i)
-        } else {
-            &[]
-        };
-        let preprocessed_next = if preprocessed.is_some() {
-            preprocessed.as_ref().unwrap().row_slice(i_next)
-        } else {
-            &[]
-        };
-        let perm_local = perm.row_slice(i);
-        let perm_next = perm.row_slice(i_next);
-
-        let mut builder = DebugConstraintBuilder {
+    (0..height).into_par_iter().try_for_each(|i| {
+        let mut builder = row_builder(
             machine,
-            main: TwoRowMatrixView {
-                local: &main_local,
-                next: &main_next,
-            },
-            preprocessed: TwoRowMatrixView {
-                local: &preprocessed_local,
-                next: &preprocessed_next,
-            },
-            perm: TwoRowMatrixView {
-                local: &perm_local,
-                next: &perm_next,
-            },
+            main,
+            preprocessed.as_ref(),
+            perm,
             perm_challenges,
-            is_first_row: SC::Val::zero(),
-            is_last_row: SC::Val::zero(),
-            is_transition: SC::Val::one(),
-        };
-        if i == 0 {
-            builder.is_first_row = SC::Val::one();
-        }
-        if i == height - 1 {
-            builder.is_last_row = SC::Val::one();
-            builder.is_transition = SC::Val::zero();
-        }
+            i,
+        );
+        air.eval(&mut builder);
+        try_eval_permutation_constraints(air, &mut builder, cumulative_sum)
+    })
+}
 
+/// Like `check_constraints`, but returns the first constraint, in row order, which does not hold
+/// rather than panicking.
+pub fn find_failing_constraint<M, A, SC>(
+    machine: &M,
+    air: &A,
+    main: &RowMajorMatrix<SC::Val>,
+    perm: &RowMajorMatrix<SC::Challenge>,
+    perm_challenges: &[SC::Challenge],
+) -> Result<(), ConstraintFailure>
+where
+    M: Machine<SC::Val>,
+    A: InteractionAir<M, SC> + for<'a> Air<DebugConstraintBuilder<'a, M, SC>>,
+    SC: StarkConfig,
+{
+    assert_eq!(main.height(), perm.height());
+    let preprocessed = air.preprocessed_trace();
+    let cumulative_sum = cumulative_sum_from_perm(perm);
+    for i in 0..main.height() {
+        let mut builder = row_builder(
+            machine,
+            main,
+            preprocessed.as_ref(),
+            perm,
+            perm_challenges,
+            i,
+        );
+        builder.record_failures = true;
         air.eval(&mut builder);
         eval_permutation_constraints(air, &mut builder, cumulative_sum);
-    });
+        if let Some(failure) = builder.failure {
+            return Err(failure);
+        }
+    }
+    Ok(())
+}
+
+/// The builder evaluating the constraints on row `i` of the traces, whose next row wraps around
+/// from the last row to the first.
+fn row_builder<'a, M, SC>(
+    machine: &'a M,
+    main: &'a RowMajorMatrix<SC::Val>,
+    preprocessed: Option<&'a RowMajorMatrix<SC::Val>>,
+    perm: &'a RowMajorMatrix<SC::Challenge>,
+    perm_challenges: &'a [SC::Challenge],
+    i: usize,
+) -> DebugConstraintBuilder<'a, M, SC>
+where
+    M: Machine<SC::Val>,
+    SC: StarkConfig,
+{
+    let height = main.height();
+    let i_next = (i + 1) % height;
+
+    let main_local = main.row_slice(i);
+    let main_next = main.row_slice(i_next);
+    let preprocessed_local = match preprocessed {
+        Some(preprocessed) => preprocessed.row_slice(i),
+        None => &[],
+    };
+    let preprocessed_next = match preprocessed {
+        Some(preprocessed) => preprocessed.row_slice(i_next),
+        None => &[],
+    };
+    let perm_local = perm.row_slice(i);
+    let perm_next = perm.row_slice(i_next);
+
+    let mut builder = DebugConstraintBuilder {
+        machine,
+        main: TwoRowMatrixView {
+            local: main_local,
+            next: main_next,
+        },
+        preprocessed: TwoRowMatrixView {
+            local: preprocessed_local,
+            next: preprocessed_next,
+        },
+        perm: TwoRowMatrixView {
+            local: perm_local,
+            next: perm_next,
+        },
+        perm_challenges,
+        is_first_row: SC::Val::zero(),
+        is_last_row: SC::Val::zero(),
+        is_transition: SC::Val::one(),
+        row: i,
+        record_failures: false,
+        failure: None,
+        constraints: 0,
+        provenance: None,
+    };
+    if i == 0 {
+        builder.is_first_row = SC::Val::one();
+    }
+    if i == height - 1 {
+        builder.is_last_row = SC::Val::one();
+        builder.is_transition = SC::Val::zero();
+    }
+    // The running sum recurrence would otherwise relate the last row to the first.
+    assert!(
+        builder.is_last_row.is_zero() || builder.is_transition.is_zero(),
+        "transition constraints must not wrap around from the last row to the first"
+    );
+    builder
 }
 
-/// Check that the combined cumulative sum across all lookup tables is zero.
-pub fn check_cumulative_sums<Challenge: Field>(perms: &[RowMajorMatrix<Challenge>]) {
+/// Check that the combined cumulative sum across all lookup tables is zero, once each is oriented
+/// by the sign convention of its chip, see `SignConvention::orient`.
+pub fn check_cumulative_sums<Challenge: Field>(
+    perms: &[RowMajorMatrix<Challenge>],
+    sign_conventions: &[SignConvention],
+) {
     let sum: Challenge = perms
         .iter()
-        .map(|perm| *perm.row_slice(perm.height() - 1).last().unwrap())
+        .zip(sign_conventions)
+        .map(|(perm, sign_convention)| sign_convention.orient(cumulative_sum_from_perm(perm)))
         .sum();
     assert_eq!(sum, Challenge::zero());
 }
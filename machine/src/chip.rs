@@ -1,26 +1,43 @@
 use crate::folding_builder::VerifierConstraintFolder;
+use crate::DomainId;
+use crate::LayoutMismatch;
+use crate::LocalImbalance;
 use crate::Machine;
+use crate::MissingCumulativeSum;
+use crate::PermutationChallengeError;
+use crate::LOOKUP_DEGREE_BOUND;
 use crate::__internal::{DebugConstraintBuilder, ProverConstraintFolder};
 use alloc::vec;
 use alloc::vec::Vec;
+use core::any::type_name;
+use core::ops::{Mul, Range};
 
+use crate::balance::check_local_balance;
+use crate::blinding::generate_blinding_rows;
+use crate::bundle::{
+    bundle_degree_budget, eval_bundled_permutation_constraints, generate_bundled_permutation_trace,
+    reciprocal_bundles,
+};
 use crate::config::StarkConfig;
-use crate::symbolic::symbolic_builder::SymbolicAirBuilder;
+use crate::degree::permutation_constraint_degree;
+use crate::dependency::compute_cumulative_sums;
+use crate::fingerprint::interaction_fingerprint;
+use crate::lookup_field::{generate_base_permutation_trace, LookupField};
+use crate::packed::reduce_rows_packed;
+use crate::symbolic::symbolic_builder::{get_max_constraint_degree, SymbolicAirBuilder};
+use crate::wide_trace::column_terms;
 use p3_air::ExtensionBuilder;
-use p3_air::{Air, PairBuilder, PermutationAirBuilder, VirtualPairCol};
-use p3_field::{AbstractField, ExtensionField, Field, Powers};
+use p3_air::{Air, BaseAir, PairBuilder, PairCol, PermutationAirBuilder, VirtualPairCol};
+use p3_field::{AbstractExtensionField, AbstractField, ExtensionField, Field, PrimeField32};
 use p3_matrix::{dense::RowMajorMatrix, Matrix, MatrixRowSlices};
+use p3_maybe_rayon::prelude::*;
+use serde::{Deserialize, Serialize};
 use valida_util::batch_multiplicative_inverse_allowing_zero;
 
-pub trait Chip<M: Machine<SC::Val>, SC: StarkConfig>:
-    for<'a> Air<ProverConstraintFolder<'a, M, SC>>
-    + for<'a> Air<VerifierConstraintFolder<'a, M, SC>>
-    + for<'a> Air<SymbolicAirBuilder<'a, M, SC>>
-    + for<'a> Air<DebugConstraintBuilder<'a, M, SC>>
-{
-    /// Generate the main trace for the chip given the provided machine.
-    fn generate_trace(&self, machine: &M) -> RowMajorMatrix<SC::Val>;
-
+/// The bus interface of a chip: its interactions and how its permutation trace lays them out,
+/// without the trace generation of `Chip`. This is all `eval_permutation_constraints` reads, so a
+/// verifier can evaluate the permutation constraints of a chip knowing only its layout.
+pub trait InteractionAir<M: Machine<SC::Val>, SC: StarkConfig>: BaseAir<SC::Val> {
     fn local_sends(&self) -> Vec<Interaction<SC::Val>> {
         vec![]
     }
@@ -65,21 +82,410 @@ pub trait Chip<M: Machine<SC::Val>, SC: StarkConfig>:
     fn trace_width(&self) -> usize {
         self.width()
     }
+
+    /// A main trace column gating every interaction of this chip: on rows where it is zero, the
+    /// chip sends and receives nothing, and elsewhere it interacts as declared. Each count is
+    /// multiplied by the selector, which `eval_permutation_constraints` constrains to be boolean.
+    fn active_selector(&self) -> Option<usize> {
+        None
+    }
+
+    /// The order of the reciprocal columns in this chip's permutation trace.
+    fn permutation_layout(&self) -> PermutationLayout {
+        PermutationLayout::Blocked
+    }
+
+    /// A constraint degree budget under which consecutive interactions share a permutation
+    /// column, see `reciprocal_bundles`, or `None` for the machine's `Machine::lookup_backend`.
+    ///
+    /// Bundling is honoured by `generate_permutation_trace`, `eval_permutation_constraints` and
+    /// `permutation_trace_width`. The other trace generators and the per-column tooling, such as
+    /// `PermutationLayout` and `interaction_log_of`, assume one reciprocal per column.
+    ///
+    /// A budget above 3 raises the chip's quotient degree with its bundle constraints, see
+    /// `get_chip_log_quotient_degree`, so the PCS blowup must be large enough for it.
+    fn reciprocal_bundle_degree(&self) -> Option<usize> {
+        None
+    }
+
+    /// The number of permutation challenges this chip reads, at least
+    /// `reserved_permutation_challenges`. The first three are the local alpha, the global alpha
+    /// and beta, followed by the further bus challenge roots of `Machine::BUS_CHALLENGE_ROOTS`;
+    /// the ones after these reserved slots are for the chip's own use, e.g. in a custom
+    /// `generate_permutation_trace`. Machines sample enough challenges for every chip, see
+    /// `num_permutation_challenges`, and every chip reads the same slots.
+    fn num_permutation_challenges(&self, _machine: &M) -> usize {
+        reserved_permutation_challenges(M::BUS_CHALLENGE_ROOTS)
+    }
+
+    /// Which of this chip's interactions add to its running sum. The last entry of the running sum
+    /// is the chip's cumulative sum in this convention; `SignConvention::orient` brings it to the
+    /// sends positive convention in which a machine's cumulative sums cancel out.
+    fn sign_convention(&self) -> SignConvention {
+        SignConvention::SendsPositive
+    }
+}
+
+pub trait Chip<M: Machine<SC::Val>, SC: StarkConfig>:
+    InteractionAir<M, SC>
+    + for<'a> Air<ProverConstraintFolder<'a, M, SC>>
+    + for<'a> Air<VerifierConstraintFolder<'a, M, SC>>
+    + for<'a> Air<SymbolicAirBuilder<'a, M, SC>>
+    + for<'a> Air<DebugConstraintBuilder<'a, M, SC>>
+{
+    /// Generate the main trace for the chip given the provided machine.
+    fn generate_trace(&self, machine: &M) -> RowMajorMatrix<SC::Val>;
+
+    /// Externally computed counts for this chip's interactions, e.g. from a separate pass over the
+    /// machine's execution, or `None` if `generate_trace` writes every count itself. Otherwise
+    /// there is one entry per interaction of `all_interactions`: `None` to keep the count of
+    /// `generate_trace`, or a count for every row of the unpadded trace.
+    ///
+    /// `generate_main_trace` writes these counts into the main trace before it is committed, so
+    /// the permutation constraints read them like any other count, and a wrong one unbalances its
+    /// bus. An interaction taking external counts must count a single main column, as
+    /// `Interaction::with_multiplicity_col` does.
+    fn external_multiplicities(&self, _machine: &M) -> Option<Vec<Option<Vec<SC::Val>>>> {
+        None
+    }
+
+    /// The main trace the prover pads and commits: that of `generate_trace`, with the counts of
+    /// `external_multiplicities` written into it by `write_external_multiplicities`.
+    fn generate_main_trace(&self, machine: &M) -> RowMajorMatrix<SC::Val> {
+        let mut trace = self.generate_trace(machine);
+        if let Some(multiplicities) = self.external_multiplicities(machine) {
+            write_external_multiplicities(
+                &self.all_interactions(machine),
+                self.preprocessed_trace().map_or(0, |trace| trace.width()),
+                &mut trace,
+                &multiplicities,
+            );
+        }
+        trace
+    }
+
+    /// Generate the main traces of a chip whose columns are split across domains of different
+    /// heights. A chip overriding this should return the aligned trace of `align_domain_traces`
+    /// from `generate_trace`, which is what the prover commits.
+    fn generate_traces(&self, machine: &M) -> Vec<(DomainId, RowMajorMatrix<SC::Val>)> {
+        vec![(DomainId(0), self.generate_main_trace(machine))]
+    }
+
+    /// The interactions of one of the traces of `generate_traces`, with columns local to that
+    /// trace, for `generate_segment_permutation_traces`. By default `DomainId(0)` has all the
+    /// chip's interactions and other domains have none, which suits a chip with a single trace.
+    fn segment_interactions(
+        &self,
+        machine: &M,
+        domain: DomainId,
+    ) -> Vec<(Interaction<SC::Val>, InteractionType)> {
+        if domain == DomainId(0) {
+            self.all_interactions(machine)
+        } else {
+            vec![]
+        }
+    }
+
+    /// The fewest rows this chip's trace may have, e.g. `2` for a chip whose boundary constraints
+    /// need a padding row after its data. Chips should pad their traces with `pad_to_min_height`.
+    fn min_height(&self) -> usize {
+        1
+    }
+
+    /// The row `pad_trace` appends to this chip's main trace. Every interaction must have a zero
+    /// count on it, so that the running sum stays flat across the padding and the cumulative sum
+    /// is that of the unpadded trace. All zeros by default, which suits a chip whose counts are
+    /// columns or gated by an `active_selector`.
+    fn padding_row(&self) -> Vec<SC::Val> {
+        vec![SC::Val::zero(); self.trace_width()]
+    }
+
+    /// Extend a main trace with `padding_row` to a power of two height of at least `min_height`,
+    /// as the PCS requires. Panics if an interaction has a nonzero count on a padding row. A trace
+    /// which is already padded is left as it is.
+    fn pad_trace(&self, machine: &M, trace: &mut RowMajorMatrix<SC::Val>) {
+        let height = trace.height();
+        let padded_height = height.max(self.min_height()).next_power_of_two();
+        if padded_height == height {
+            return;
+        }
+        let padding_row = self.padding_row();
+        assert_zero_padding_counts(
+            &self.all_interactions(machine),
+            self.preprocessed_trace().as_ref(),
+            &padding_row,
+            height..padded_height,
+            self.active_selector(),
+        );
+        for _ in height..padded_height {
+            trace.values.extend_from_slice(&padding_row);
+        }
+    }
+
+    /// The seed for this chip's zero-knowledge blinding rows, or `None` if the chip is not
+    /// blinded. Chips sharing a seed would draw correlated blinding, so each blinded chip should
+    /// return a distinct seed.
+    fn blinding_seed(&self) -> Option<u64> {
+        None
+    }
+
+    /// `num_rows` zero-knowledge blinding rows as wide as this chip's main trace, drawn from its
+    /// `blinding_seed` by `generate_blinding_rows`, or `None` if the chip is not blinded.
+    fn blinding_rows(&self, num_rows: usize) -> Option<RowMajorMatrix<SC::Val>> {
+        self.blinding_seed()
+            .map(|seed| generate_blinding_rows(seed, num_rows, self.trace_width()))
+    }
+
+    /// Generate a custom permutation trace for this chip, or `None` to use the default reciprocal
+    /// construction of `generate_permutation_trace`.
+    ///
+    /// The prover and verifier still apply `eval_permutation_constraints` to the returned trace, so
+    /// it must satisfy those constraints, with the width given by `permutation_trace_width` and the
+    /// cumulative sum in its last entry. A different argument would also need its own constraint
+    /// evaluation in place of `eval_permutation_constraints`.
+    fn generate_permutation_trace(
+        &self,
+        _machine: &M,
+        _main: &RowMajorMatrix<SC::Val>,
+        _random_elements: &[SC::Challenge],
+    ) -> Option<RowMajorMatrix<SC::Challenge>> {
+        None
+    }
+
+    /// The chips, by index in the machine's chip list, whose cumulative sums this chip reads.
+    /// `generate_all_permutation_traces` generates their permutation traces first and passes
+    /// their sums to `Chip::generate_dependent_permutation_trace`, see `compute_cumulative_sums`.
+    fn cumulative_sum_dependencies(&self) -> Vec<usize> {
+        vec![]
+    }
+
+    /// Generate this chip's permutation trace from the cumulative sums of its
+    /// `cumulative_sum_dependencies`, in the order they are declared, or `None` to use
+    /// `generate_permutation_trace`. Like `Chip::generate_permutation_trace`, the returned trace
+    /// must satisfy `eval_permutation_constraints`.
+    fn generate_dependent_permutation_trace(
+        &self,
+        _machine: &M,
+        _main: &RowMajorMatrix<SC::Val>,
+        _random_elements: &[SC::Challenge],
+        _upstream_sums: &[SC::Challenge],
+    ) -> Option<RowMajorMatrix<SC::Challenge>> {
+        None
+    }
+
+    /// Whether this chip is a table providing each entry once, so that its receives never repeat
+    /// a payload. See `check_provided_once`.
+    fn provides_once(&self) -> bool {
+        false
+    }
+
+    /// A stable fingerprint of this chip's interactions, which changes whenever its bus interface
+    /// does. See `interaction_fingerprint`.
+    fn interaction_fingerprint(&self, machine: &M) -> [u8; 32] {
+        interaction_fingerprint(
+            &self.all_interactions(machine),
+            self.preprocessed_trace().map_or(0, |trace| trace.width()),
+            self.trace_width(),
+        )
+    }
+
+    /// The largest degree the permutation constraints of this chip should reach, see
+    /// `check_running_sum_degree`.
+    fn max_permutation_degree(&self) -> usize {
+        LOOKUP_DEGREE_BOUND
+    }
+
+    /// The largest degree of any constraint this chip imposes, over both its AIR and its
+    /// permutation constraints, see `permutation_constraint_degree`. The quotient polynomial of
+    /// the chip has degree about this minus one times the trace height.
+    ///
+    /// With `reciprocal_bundle_degree`, the bundle constraints take the place of the reciprocal
+    /// constraints, so the result is usually the bundle degree budget rather than the count
+    /// degree plus two.
+    fn max_constraint_degree(&self, machine: &M) -> usize {
+        get_max_constraint_degree::<M, SC, Self>(machine, self)
+            .max(permutation_constraint_degree::<M, SC, Self>(machine, self))
+    }
+
+    /// The field `generate_permutation_trace` computes this chip's reciprocals over. See
+    /// `LookupField` for when `LookupField::Base` is sound.
+    fn lookup_field(&self) -> LookupField {
+        LookupField::Extension
+    }
+
+    /// This chip's interactions and their permutation trace columns, computed once so that
+    /// repeated trace generation and constraint evaluation can share them, see
+    /// `generate_permutation_trace_with_layout`.
+    fn interaction_layout(&self, machine: &M) -> InteractionLayout<SC::Val> {
+        InteractionLayout::new(
+            self.all_interactions(machine),
+            self.preprocessed_trace().map_or(0, |trace| trace.width()),
+            self.trace_width(),
+            &self.permutation_layout(),
+        )
+    }
+
+    /// Whether no interaction of this chip may repeat a field. Checked in debug builds during
+    /// permutation trace generation, see `Interaction::duplicate_fields`.
+    fn require_distinct_fields(&self) -> bool {
+        false
+    }
+
+    /// Check that every local bus of this chip balances on its main trace, tuple by tuple.
+    ///
+    /// Local buses never leave the chip, so its local sends and receives of each field tuple must
+    /// cancel out. Like `check_global_balance`, this uses no challenges, and points at the first
+    /// unbalanced tuple, in bus and tuple order, where a failing running sum boundary constraint
+    /// would not.
+    fn check_local_balance(&self, machine: &M) -> Result<(), LocalImbalance> {
+        check_local_balance::<M, SC, Self>(machine, self)
+    }
 }
 
 pub trait ValidaAirBuilder: PairBuilder + PermutationAirBuilder {
     type Machine;
 
     fn machine(&self) -> &Self::Machine;
+
+    /// The `i`th of the machine's `Machine::public_values`.
+    fn public_input(&self, i: usize) -> Self::F
+    where
+        Self::Machine: Machine<Self::F>,
+    {
+        self.machine().public_values()[i]
+    }
+
+    /// Note the origin of the constraints asserted next. Builders which export constraints attach
+    /// it to them; others ignore it.
+    fn set_provenance(&mut self, _provenance: ConstraintProvenance) {}
+}
+
+/// The origin of a permutation argument constraint.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ConstraintProvenance {
+    /// The type name of the chip.
+    pub chip: &'static str,
+    /// The index of the interaction among those being evaluated, for reciprocal constraints.
+    pub interaction_index: Option<usize>,
+    /// The bus of the interaction, for reciprocal constraints.
+    pub bus: Option<BusArgument>,
+    pub kind: ConstraintKind,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ConstraintKind {
+    /// A reciprocal column times its denominator is one.
+    Reciprocal,
+    /// The running sum advances by the contributions of the next row.
+    RunningSumTransition,
+    /// The running sum starts at the contributions of the first row.
+    RunningSumFirstRow,
+    /// The running sum ends at the cumulative sum.
+    RunningSumLastRow,
+    /// A bundle column times the product of its denominators is the signed sum of each count
+    /// times the other denominators, see `eval_bundled_permutation_constraints`.
+    Bundle,
 }
 
 pub struct Interaction<F: Field> {
+    /// The payload. Fields are combined as field elements, which are always reduced, so two
+    /// encodings of the same value (e.g. `x` and `x + p` in `from_wrapped_u64`) give the same
+    /// payload and need no canonicalization step. Payloads which encode a value across several
+    /// limbs are not: the limbs of `x` and of `x + p` differ, so chips sending limbs must
+    /// range-check them into the canonical range for both sides of a bus to agree.
     pub fields: Vec<VirtualPairCol<F>>,
     pub count: VirtualPairCol<F>,
     pub argument_index: BusArgument,
+    /// The row rotation of each field, where `0` is the local row and `1` the next row. Missing
+    /// entries default to `0`, so an empty vector reads every field from the local row.
+    ///
+    /// Rotations wrap around: on the last row, a field with rotation `1` reads the first row. This
+    /// matches the constraint window, whose next row on the last row is the first row, so the
+    /// reciprocal constraint holds on every row including the last.
+    pub rotations: Vec<usize>,
+    /// A declared bound on the values `count` takes, checked during trace generation.
+    pub count_bound: CountBound,
+    /// A selector marking the rows the interaction takes part in, which multiplies into its count
+    /// wherever the count is used, see `count_at`. `None` means every row takes part.
+    pub is_real: Option<VirtualPairCol<F>>,
+    /// The order of `fields`, when they hold the limbs of one value, see `send_limbs`. `None`
+    /// declares no order, and is never reported by `check_limb_orders`.
+    pub limb_order: Option<LimbOrder>,
+}
+
+/// The order in which the limbs of a multi-limb value, such as the bytes of a `Word`, appear among
+/// the fields of an interaction. Fields are weighed by position, so a little-endian send of a
+/// value matches a big-endian receive of its reversed limbs: both sides of a bus must agree.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum LimbOrder {
+    /// Most significant limb first, as a `Word` stores its bytes.
+    #[default]
+    BigEndian,
+    /// Least significant limb first.
+    LittleEndian,
+}
+
+/// A bound on the multiplicity of an interaction.
+///
+/// Bounds are not enforced by the constraints; they let trace generation catch a mistyped count
+/// (e.g. `VirtualPairCol::constant(F::two())` in place of `F::one()`) before it silently skews a
+/// bus.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum CountBound {
+    #[default]
+    Unbounded,
+    /// The count is exactly one on every row.
+    Unit,
+    /// The count is an integer between zero and the given bound on every row.
+    AtMost(u32),
+}
+
+/// The largest multiplicity a table-side interaction is expected to carry. A lookup cannot be
+/// made more often than there are rows, and trace heights stay below `2^27`, the two-adicity of
+/// BabyBear.
+pub const MAX_MULTIPLICITY: u32 = 1 << 27;
+
+/// The number of permutation challenges read by the default permutation argument: the local
+/// alpha, the global alpha and beta.
+pub const NUM_PERMUTATION_CHALLENGES: usize = 3;
+
+/// The number of permutation challenges every chip reads in the same way: the
+/// `NUM_PERMUTATION_CHALLENGES` of the default argument and the further bus challenge roots of
+/// each scope. A chip's own challenges follow them, see
+/// `InteractionAir::num_permutation_challenges`.
+pub fn reserved_permutation_challenges(roots: usize) -> usize {
+    assert!(roots >= 1, "BUS_CHALLENGE_ROOTS must be at least 1");
+    NUM_PERMUTATION_CHALLENGES + 2 * (roots - 1)
+}
+
+/// The challenge slots of the roots of one scope, local or global, see `bus_challenge`: its
+/// alpha, then its further roots.
+pub(crate) fn bus_challenge_root_slots(local: bool, roots: usize) -> impl Iterator<Item = usize> {
+    assert!(roots >= 1, "BUS_CHALLENGE_ROOTS must be at least 1");
+    let scope = if local { 0 } else { 1 };
+    core::iter::once(scope)
+        .chain((1..roots).map(move |j| NUM_PERMUTATION_CHALLENGES + scope * (roots - 1) + j - 1))
+}
+
+/// The challenge of a bus, given the number of challenge roots of each scope, see
+/// `Machine::BUS_CHALLENGE_ROOTS`. Bus `i` of a scope takes `r^{i / roots + 1}`, where `r` is the
+/// `i % roots`th root of its scope. The first root of a scope is its alpha; the remaining roots
+/// follow the first `NUM_PERMUTATION_CHALLENGES` challenges, those of local buses first.
+pub fn bus_challenge<EF: Field>(random_elements: &[EF], bus: BusArgument, roots: usize) -> EF {
+    let (local, i) = match bus {
+        BusArgument::Local(i) => (true, i),
+        BusArgument::Global(i) => (false, i),
+    };
+    let slot = bus_challenge_root_slots(local, roots)
+        .nth(i % roots)
+        .unwrap();
+    random_elements[slot].exp_u64((i / roots) as u64 + 1)
 }
 
-#[derive(Clone, Debug)]
+/// The largest supported field rotation. Constraints are evaluated over a window of two rows.
+pub const MAX_ROTATION: usize = 1;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum InteractionType {
     LocalSend,
     LocalReceive,
@@ -87,13 +493,182 @@ pub enum InteractionType {
     GlobalReceive,
 }
 
-#[derive(Clone, Copy, Ord, PartialOrd, Eq, PartialEq)]
+/// The sign of each interaction's term in a chip's running sum, see
+/// `InteractionAir::sign_convention`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SignConvention {
+    /// Sends add `count / d` to the running sum and receives subtract it.
+    #[default]
+    SendsPositive,
+    /// Receives add to the running sum and sends subtract from it, e.g. for a table chip which
+    /// is the source of the entries it receives.
+    ReceivesPositive,
+}
+
+impl SignConvention {
+    /// Whether an interaction of the given type adds to the running sum.
+    pub fn adds(self, interaction_type: &InteractionType) -> bool {
+        let is_send = matches!(
+            interaction_type,
+            InteractionType::LocalSend | InteractionType::GlobalSend
+        );
+        is_send == (self == SignConvention::SendsPositive)
+    }
+
+    /// Convert a cumulative sum in this convention to the sends positive convention, or back.
+    pub fn orient<F: AbstractField>(self, cumulative_sum: F) -> F {
+        match self {
+            SignConvention::SendsPositive => cumulative_sum,
+            SignConvention::ReceivesPositive => -cumulative_sum,
+        }
+    }
+}
+
+/// Whether an interaction is on a local or a global bus.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InteractionScope {
+    Local,
+    Global,
+}
+
+impl InteractionScope {
+    /// Keep the interactions in this scope.
+    pub fn filter<F: Field>(
+        self,
+        interactions: Vec<(Interaction<F>, InteractionType)>,
+    ) -> Vec<(Interaction<F>, InteractionType)> {
+        interactions
+            .into_iter()
+            .filter(|(interaction, _)| match self {
+                InteractionScope::Local => interaction.is_local(),
+                InteractionScope::Global => interaction.is_global(),
+            })
+            .collect()
+    }
+}
+
+#[derive(Clone, Copy, Debug, Ord, PartialOrd, Eq, PartialEq, Serialize, Deserialize)]
 pub enum BusArgument {
     Local(usize),
     Global(usize),
 }
 
 impl<F: Field> Interaction<F> {
+    /// An interaction whose multiplicity is read from main trace column `mult_col`, as used by
+    /// table chips which receive each entry as many times as it is looked up.
+    pub fn with_multiplicity_col(
+        fields: Vec<VirtualPairCol<F>>,
+        mult_col: usize,
+        argument_index: BusArgument,
+    ) -> Self {
+        Self {
+            fields,
+            count: VirtualPairCol::single_main(mult_col),
+            argument_index,
+            rotations: vec![],
+            count_bound: CountBound::AtMost(MAX_MULTIPLICITY),
+            is_real: None,
+            limb_order: None,
+        }
+    }
+
+    /// An interaction whose net multiplicity is `provided - consumed`, read from two main trace
+    /// columns, as used by LogUp-style tables. The net is bounded by `MAX_MULTIPLICITY`, so a row
+    /// consuming more than it provides wraps to a huge count and is reported by
+    /// `check_count_bounds` and the debug checks of `generate_permutation_trace`.
+    pub fn with_count_difference(
+        fields: Vec<VirtualPairCol<F>>,
+        provided_col: usize,
+        consumed_col: usize,
+        argument_index: BusArgument,
+    ) -> Self {
+        Self {
+            fields,
+            count: count_difference(provided_col, consumed_col),
+            argument_index,
+            rotations: vec![],
+            count_bound: CountBound::AtMost(MAX_MULTIPLICITY),
+            is_real: None,
+            limb_order: None,
+        }
+    }
+
+    /// An interaction on `bus` whose fields are the main trace columns `cols`, in order, with the
+    /// given count.
+    pub fn send_columns(bus: BusArgument, cols: &[usize], count: VirtualPairCol<F>) -> Self {
+        Self {
+            fields: cols
+                .iter()
+                .map(|&col| VirtualPairCol::single_main(col))
+                .collect(),
+            count,
+            argument_index: bus,
+            rotations: vec![],
+            count_bound: CountBound::Unbounded,
+            is_real: None,
+            limb_order: None,
+        }
+    }
+
+    /// An interaction on `bus` whose fields are the limbs of one value, held in main trace columns
+    /// `cols` from most to least significant as in a `Word`, laid out in `order`, with the given
+    /// count. Passing `Machine::limb_order` keeps a chip and the table it looks up into in step.
+    pub fn send_limbs(
+        bus: BusArgument,
+        cols: &[usize],
+        count: VirtualPairCol<F>,
+        order: LimbOrder,
+    ) -> Self {
+        let mut interaction = Self::send_columns(bus, cols, count);
+        if order == LimbOrder::LittleEndian {
+            interaction.fields.reverse();
+        }
+        interaction.limb_order = Some(order);
+        interaction
+    }
+
+    /// An interaction on `bus` whose fields are the main trace columns `cols`, in order, with
+    /// count one on every row.
+    pub fn receive_always(bus: BusArgument, cols: &[usize]) -> Self {
+        Self::send_columns(bus, cols, VirtualPairCol::one()).assert_unit_count()
+    }
+
+    /// A lookup of main trace column `col` into the range table receiving on `bus`, with the
+    /// given count, typically the chip's `is_real` column. The range itself is fixed by the table,
+    /// e.g. `RangeCheckerChip<MAX>`, so it is not part of the interaction.
+    pub fn range_check(bus: BusArgument, col: usize, count: VirtualPairCol<F>) -> Self {
+        Self::send_columns(bus, &[col], count)
+    }
+
+    /// A receive on `in_bus` and a send on `out_bus` of the same payload, for a chip relaying
+    /// values from one bus to another. Returns `(receive, send)`, both with count one. The fields
+    /// are shared, so the two payloads cannot drift apart.
+    pub fn pass_through(
+        in_bus: BusArgument,
+        out_bus: BusArgument,
+        fields: Vec<VirtualPairCol<F>>,
+    ) -> (Self, Self) {
+        let receive = Self {
+            fields: fields.clone(),
+            count: VirtualPairCol::one(),
+            argument_index: in_bus,
+            rotations: vec![],
+            count_bound: CountBound::Unit,
+            is_real: None,
+            limb_order: None,
+        };
+        let send = Self {
+            fields,
+            count: VirtualPairCol::one(),
+            argument_index: out_bus,
+            rotations: vec![],
+            count_bound: CountBound::Unit,
+            is_real: None,
+            limb_order: None,
+        };
+        (receive, send)
+    }
+
     pub fn is_local(&self) -> bool {
         match self.argument_index {
             BusArgument::Local(_) => true,
@@ -114,249 +689,2001 @@ impl<F: Field> Interaction<F> {
             BusArgument::Global(i) => i,
         }
     }
-}
 
-/// Generate the permutation trace for a chip with the provided machine.
-/// This is called only after `generate_trace` has been called on all chips.
-pub fn generate_permutation_trace<M, SC>(
-    machine: &M,
-    chip: &dyn Chip<M, SC>,
-    main: &RowMajorMatrix<SC::Val>,
-    random_elements: Vec<SC::Challenge>,
-) -> RowMajorMatrix<SC::Challenge>
-where
-    M: Machine<SC::Val>,
-    SC: StarkConfig,
-{
-    let all_interactions = chip.all_interactions(machine);
-    let (alphas_local, alphas_global) = generate_rlc_elements(machine, chip, &random_elements);
-    let betas = random_elements[2].powers();
+    /// The row rotation of the `i`th field.
+    pub fn field_rotation(&self, i: usize) -> usize {
+        self.rotations.get(i).copied().unwrap_or(0)
+    }
 
-    let preprocessed = chip.preprocessed_trace();
+    /// Append a field reading main trace column `col_index` at the given row rotation.
+    pub fn field_rotated(mut self, col_index: usize, rotation: usize) -> Self {
+        assert!(
+            rotation <= MAX_ROTATION,
+            "field rotation {} exceeds the constraint window",
+            rotation
+        );
+        self.rotations.resize(self.fields.len(), 0);
+        self.fields.push(VirtualPairCol::single_main(col_index));
+        self.rotations.push(rotation);
+        self
+    }
 
-    // Compute the reciprocal columns
-    //
-    // Row: | q_1 | q_2 | q_3 | ... | q_n | \phi |
-    // * q_i = \frac{1}{\alpha^i + \sum_j \beta^j * f_{i,j}}
-    // * f_{i,j} is the jth main trace column for the ith interaction
-    // * \phi is the running sum
-    //
-    // Note: We can optimize this by combining several reciprocal columns into one (the
-    // number is subject to a target constraint degree).
-    let perm_width = all_interactions.len() + 1;
-    let mut perm_values = Vec::with_capacity(main.height() * perm_width);
+    /// Find two field positions `(i, j)`, `i < j`, holding the same combination of columns.
+    ///
+    /// A repeated field is still well defined: field `i` is weighted by `\beta^i` and field `j` by
+    /// `\beta^j`, so the pair contributes `(\beta^i + \beta^j) f` to the reciprocal's denominator.
+    /// It is nonetheless usually a mistake, since the two positions carry no independent data.
+    ///
+    /// Fields are compared by evaluating them on a fixed row of distinct generic values, so two
+    /// different combinations are only reported in the unlikely case they agree on that row.
+    pub fn duplicate_fields(
+        &self,
+        preprocessed_width: usize,
+        main_width: usize,
+    ) -> Option<(usize, usize)> {
+        let (preprocessed_row, main_row) = generic_rows(preprocessed_width, main_width);
+        let values = self
+            .fields
+            .iter()
+            .map(|field| field.apply::<F, F>(&preprocessed_row, &main_row))
+            .collect::<Vec<_>>();
+        (0..values.len())
+            .flat_map(|i| (i + 1..values.len()).map(move |j| (i, j)))
+            .find(|&(i, j)| {
+                values[i] == values[j] && self.field_rotation(i) == self.field_rotation(j)
+            })
+    }
 
-    for (n, main_row) in main.rows().enumerate() {
-        let mut row = vec![SC::Challenge::zero(); perm_width];
-        for (m, (interaction, _)) in all_interactions.iter().enumerate() {
-            let alpha_m = if interaction.is_local() {
-                alphas_local[interaction.argument_index()]
-            } else {
-                alphas_global[interaction.argument_index()]
-            };
-            let preprocessed_row = if preprocessed.is_some() {
-                preprocessed.as_ref().unwrap().row_slice(n)
-            } else {
-                &[]
-            };
-            row[m] = reduce_row(
-                main_row,
-                preprocessed_row,
-                &interaction.fields,
-                alpha_m,
-                betas.clone(),
-            );
-        }
-        perm_values.extend(row);
+    /// Whether every field of this interaction is independent of the row, in which case its
+    /// reciprocal is the same on every row and needs no column in the permutation trace.
+    ///
+    /// A field is read by its `column_terms`, so weights which cancel out do not count, e.g.
+    /// `Main(3) - Main(3) + 7` is constant. An interaction and its `simplified` form therefore
+    /// always agree, and so do the layouts built from either.
+    pub fn has_constant_payload(&self, preprocessed_width: usize, main_width: usize) -> bool {
+        self.fields.iter().all(|field| {
+            column_terms(field, preprocessed_width, main_width)
+                .0
+                .is_empty()
+        })
     }
-    // TODO: Switch to batch_multiplicative_inverse (not allowing zero)?
-    // Zero should be vanishingly unlikely if properly randomized?
-    let perm_values = batch_multiplicative_inverse_allowing_zero(perm_values);
-    let mut perm = RowMajorMatrix::new(perm_values, perm_width);
 
-    // Compute the running sum column
-    let mut phi = vec![SC::Challenge::zero(); perm.height()];
-    for (n, (main_row, perm_row)) in main.rows().zip(perm.rows()).enumerate() {
-        if n > 0 {
-            phi[n] = phi[n - 1];
-        }
-        let preprocessed_row = if preprocessed.is_some() {
-            preprocessed.as_ref().unwrap().row_slice(n)
-        } else {
-            &[]
-        };
-        for (m, (interaction, interaction_type)) in all_interactions.iter().enumerate() {
-            let mult = interaction
-                .count
-                .apply::<SC::Val, SC::Val>(preprocessed_row, main_row);
-            match interaction_type {
-                InteractionType::LocalSend | InteractionType::GlobalSend => {
-                    phi[n] += perm_row[m] * mult;
-                }
-                InteractionType::LocalReceive | InteractionType::GlobalReceive => {
-                    phi[n] -= perm_row[m] * mult;
-                }
-            }
-        }
+    /// Declare that the count of this interaction is one on every row.
+    pub fn assert_unit_count(mut self) -> Self {
+        self.count_bound = CountBound::Unit;
+        self
     }
 
-    for (n, row) in perm.as_view_mut().rows_mut().enumerate() {
-        *row.last_mut().unwrap() = phi[n];
+    /// Take part only in the rows where `is_real` is one, e.g. to leave out padding rows without
+    /// folding a selector into `count`.
+    pub fn with_is_real(mut self, is_real: VirtualPairCol<F>) -> Self {
+        self.is_real = Some(is_real);
+        self
     }
 
-    perm
+    /// The value of the `is_real` selector on a row, or one if there is none.
+    pub fn is_real_at<Expr, Var>(&self, preprocessed_row: &[Var], main_row: &[Var]) -> Expr
+    where
+        F: Into<Expr>,
+        Expr: AbstractField + Mul<F, Output = Expr>,
+        Var: Into<Expr> + Copy,
+    {
+        match &self.is_real {
+            Some(is_real) => is_real.apply::<Expr, Var>(preprocessed_row, main_row),
+            None => Expr::one(),
+        }
+    }
+
+    /// The count of this interaction on a row, masked by its `is_real` selector. This is the
+    /// multiplicity of the interaction in the running sum.
+    pub fn count_at<Expr, Var>(&self, preprocessed_row: &[Var], main_row: &[Var]) -> Expr
+    where
+        F: Into<Expr>,
+        Expr: AbstractField + Mul<F, Output = Expr>,
+        Var: Into<Expr> + Copy,
+    {
+        let count = self.count.apply::<Expr, Var>(preprocessed_row, main_row);
+        match &self.is_real {
+            Some(_) => count * self.is_real_at::<Expr, Var>(preprocessed_row, main_row),
+            None => count,
+        }
+    }
 }
 
-pub fn eval_permutation_constraints<M, C, SC, AB>(
-    chip: &C,
-    builder: &mut AB,
-    cumulative_sum: AB::EF,
-) where
-    M: Machine<SC::Val>,
-    C: Chip<M, SC> + Air<AB>,
-    SC: StarkConfig,
-    AB: ValidaAirBuilder<Machine = M, F = SC::Val, EF = SC::Challenge>,
-{
-    let rand_elems = builder.permutation_randomness().to_vec();
+impl<F: PrimeField32> Interaction<F> {
+    /// Whether `count` satisfies the declared bound of this interaction.
+    pub fn count_within_bound(&self, count: F) -> bool {
+        match self.count_bound {
+            CountBound::Unbounded => true,
+            CountBound::Unit => count == F::one(),
+            CountBound::AtMost(bound) => count.as_canonical_u32() <= bound,
+        }
+    }
+}
 
-    let main = builder.main();
-    let main_local: &[AB::Var] = main.row_slice(0);
-    let main_next: &[AB::Var] = main.row_slice(1);
+/// The count `provided - consumed` of two main trace columns.
+pub fn count_difference<F: Field>(provided_col: usize, consumed_col: usize) -> VirtualPairCol<F> {
+    VirtualPairCol::new_main(
+        vec![(provided_col, F::one()), (consumed_col, F::neg_one())],
+        F::zero(),
+    )
+}
 
-    let preprocessed = builder.preprocessed();
-    let preprocessed_local = preprocessed.row_slice(0);
-    let preprocessed_next = preprocessed.row_slice(1);
+/// A preprocessed and a main row of distinct generic values, on which distinct combinations of
+/// columns evaluate to distinct values except by unlikely coincidence.
+pub(crate) fn generic_rows<F: Field>(
+    preprocessed_width: usize,
+    main_width: usize,
+) -> (Vec<F>, Vec<F>) {
+    let mut generic = F::generator().powers().skip(1);
+    let preprocessed_row = generic
+        .by_ref()
+        .take(preprocessed_width)
+        .collect::<Vec<_>>();
+    let main_row = generic.take(main_width).collect::<Vec<_>>();
+    (preprocessed_row, main_row)
+}
 
-    let perm = builder.permutation();
-    let perm_width = perm.width();
-    let perm_local: &[AB::VarEF] = perm.row_slice(0);
-    let perm_next: &[AB::VarEF] = perm.row_slice(1);
+/// Check that every interaction has a zero count on `padding_row` at each of `rows`, whose
+/// preprocessed rows may differ, see `Chip::pad_trace`.
+fn assert_zero_padding_counts<F: Field>(
+    interactions: &[(Interaction<F>, InteractionType)],
+    preprocessed: Option<&RowMajorMatrix<F>>,
+    padding_row: &[F],
+    rows: Range<usize>,
+    active_selector: Option<usize>,
+) {
+    for n in rows {
+        let preprocessed_row = match preprocessed {
+            Some(preprocessed) => preprocessed.row_slice(n),
+            None => &[],
+        };
+        for (m, (interaction, _)) in interactions.iter().enumerate() {
+            let count = interaction.count_at::<F, F>(preprocessed_row, padding_row);
+            let count = gated_count(active_selector, count, padding_row);
+            assert!(
+                count.is_zero(),
+                "interaction {} on {:?} has a nonzero count on padding row {}",
+                m,
+                interaction.argument_index,
+                n
+            );
+        }
+    }
+}
 
-    let phi_local = perm_local[perm_width - 1].clone();
-    let phi_next = perm_next[perm_width - 1].clone();
+/// Apply a chip's `InteractionAir::active_selector` to the count of an interaction on a row.
+pub(crate) fn gated_count<F: Field>(active_selector: Option<usize>, count: F, main_row: &[F]) -> F {
+    match active_selector {
+        Some(col) => count * main_row[col],
+        None => count,
+    }
+}
 
-    let all_interactions = chip.all_interactions(builder.machine());
+/// Replace each value by the sum of the values up to and including it. The sums are taken over
+/// chunks in parallel, then offset by the totals of the preceding chunks, which gives the same
+/// result as a serial scan since field addition is exact.
+fn prefix_sums<F: Field>(values: &mut [F]) {
+    const CHUNK_SIZE: usize = 1 << 12;
+    let totals = values
+        .par_chunks_mut(CHUNK_SIZE)
+        .map(|chunk| {
+            let mut sum = F::zero();
+            for value in chunk.iter_mut() {
+                sum += *value;
+                *value = sum;
+            }
+            sum
+        })
+        .collect::<Vec<_>>();
+    let mut offset = F::zero();
+    let offsets = totals
+        .into_iter()
+        .map(|total| {
+            let chunk_offset = offset;
+            offset += total;
+            chunk_offset
+        })
+        .collect::<Vec<_>>();
+    values
+        .par_chunks_mut(CHUNK_SIZE)
+        .zip(offsets)
+        .for_each(|(chunk, offset)| {
+            for value in chunk.iter_mut() {
+                *value += offset;
+            }
+        });
+}
 
-    let (alphas_local, alphas_global) = generate_rlc_elements(builder.machine(), chip, &rand_elems);
-    let betas = rand_elems[2].powers();
+/// Where the reciprocal of an interaction is found.
+#[derive(Copy, Clone, Debug)]
+pub enum Reciprocal<EF> {
+    /// A column of the permutation trace.
+    Column(usize),
+    /// A value shared by every row, for an interaction with a constant payload. The verifier
+    /// computes it from the challenges, so it is not committed.
+    Constant(EF),
+}
 
-    let lhs = phi_next.into() - phi_local.into();
-    let mut rhs = AB::ExprEF::zero();
-    let mut phi_0 = AB::ExprEF::zero();
-    for (m, (interaction, interaction_type)) in all_interactions.iter().enumerate() {
-        // Reciprocal constraints
-        let mut rlc = AB::ExprEF::zero();
-        for (field, beta) in interaction.fields.iter().zip(betas.clone()) {
-            let elem = field.apply::<AB::Expr, AB::Var>(preprocessed_local, main_local);
-            rlc += AB::ExprEF::from_f(beta) * elem;
-        }
-        if interaction.is_local() {
-            rlc = rlc + AB::ExprEF::from_f(alphas_local[interaction.argument_index()]);
-        } else {
-            rlc = rlc + AB::ExprEF::from_f(alphas_global[interaction.argument_index()]);
+impl<EF: Copy> Reciprocal<EF> {
+    /// The reciprocal on a row of the permutation trace.
+    pub fn value(&self, perm_row: &[EF]) -> EF {
+        match *self {
+            Reciprocal::Column(c) => perm_row[c],
+            Reciprocal::Constant(q) => q,
         }
-        builder.assert_one_ext(rlc * perm_local[m].into());
+    }
+}
 
-        let mult_local = interaction
-            .count
-            .apply::<AB::Expr, AB::Var>(preprocessed_local, main_local);
-        let mult_next = interaction
-            .count
-            .apply::<AB::Expr, AB::Var>(preprocessed_next, main_next);
+/// The order of the reciprocal columns in a permutation trace. The running sum column is always
+/// last.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum PermutationLayout {
+    /// Reciprocal columns in the order of `InteractionAir::all_interactions`.
+    #[default]
+    Blocked,
+    /// The reciprocal column of the `i`th interaction with a column, counted in the order of
+    /// `InteractionAir::all_interactions`, is placed at position `order[i]`. This lets a chip
+    /// interleave reciprocals to follow the layout of the main columns they read.
+    Interleaved(Vec<usize>),
+}
 
-        // Build the RHS of the permutation constraint
-        match interaction_type {
-            InteractionType::LocalSend | InteractionType::GlobalSend => {
-                phi_0 += perm_local[m].into() * mult_local;
-                rhs += perm_next[m].into() * mult_next;
-            }
-            InteractionType::LocalReceive | InteractionType::GlobalReceive => {
-                phi_0 -= perm_local[m].into() * mult_local;
-                rhs -= perm_next[m].into() * mult_next;
+/// The permutation trace column of each interaction, or `None` for an interaction with a
+/// constant payload, see `Reciprocal::Constant`.
+pub fn reciprocal_columns<F: Field>(
+    interactions: &[(Interaction<F>, InteractionType)],
+    preprocessed_width: usize,
+    main_width: usize,
+    layout: &PermutationLayout,
+) -> Vec<Option<usize>> {
+    let mut num_columns = 0;
+    let columns = interactions
+        .iter()
+        .map(|(interaction, _)| {
+            if interaction.has_constant_payload(preprocessed_width, main_width) {
+                None
+            } else {
+                num_columns += 1;
+                Some(num_columns - 1)
             }
+        })
+        .collect::<Vec<_>>();
+
+    match layout {
+        PermutationLayout::Blocked => columns,
+        PermutationLayout::Interleaved(order) => {
+            let mut sorted = order.clone();
+            sorted.sort_unstable();
+            assert!(
+                sorted.into_iter().eq(0..num_columns),
+                "interleaved layout must order each of the {} reciprocal columns once",
+                num_columns
+            );
+            columns.into_iter().map(|c| c.map(|c| order[c])).collect()
         }
     }
-
-    // Running sum constraints
-    builder.when_transition().assert_eq_ext(lhs, rhs);
-    builder
-        .when_first_row()
-        .assert_eq_ext(perm_local.last().unwrap().clone(), phi_0);
-    builder.when_last_row().assert_eq_ext(
-        perm_local.last().unwrap().clone(),
-        AB::ExprEF::from_f(cumulative_sum),
-    );
 }
 
-fn generate_rlc_elements<M, SC>(
-    machine: &M,
-    chip: &dyn Chip<M, SC>,
-    random_elements: &[SC::Challenge],
-) -> (Vec<SC::Challenge>, Vec<SC::Challenge>)
+/// The permutation trace column of each interaction of a chip, under its
+/// `InteractionAir::permutation_layout`.
+pub fn permutation_columns<M, SC, C>(machine: &M, chip: &C) -> Vec<Option<usize>>
 where
     M: Machine<SC::Val>,
     SC: StarkConfig,
+    C: InteractionAir<M, SC> + ?Sized,
 {
-    let alphas_local = random_elements[0]
-        .powers()
-        .skip(1)
-        .take(
-            chip.local_sends()
-                .into_iter()
-                .chain(chip.local_receives())
-                .into_iter()
-                .map(|interaction| interaction.argument_index())
-                .max()
-                .unwrap_or(0)
-                + 1,
-        )
-        .collect::<Vec<_>>();
+    InteractionLayout::new(
+        chip.all_interactions(machine),
+        chip.preprocessed_trace().map_or(0, |trace| trace.width()),
+        chip.trace_width(),
+        &chip.permutation_layout(),
+    )
+    .columns
+}
 
-    let alphas_global = random_elements[1]
-        .powers()
-        .skip(1)
-        .take(
-            chip.global_sends(machine)
-                .into_iter()
-                .chain(chip.global_receives(machine))
-                .into_iter()
-                .map(|interaction| interaction.argument_index())
-                .max()
-                .unwrap_or(0)
-                + 1,
-        )
-        .collect::<Vec<_>>();
+/// The interactions of a chip together with their permutation trace columns, see
+/// `Chip::interaction_layout`. Each interaction is simplified once, on construction, by
+/// `Interaction::simplified`, so that the constraints fold no redundant terms on any row.
+///
+/// Position `m` holds the `m`-th interaction of `InteractionAir::all_interactions` and the column
+/// it owns, `columns[m]`. Trace generation and `eval_permutation_constraints` each build their
+/// own layout, and read the reciprocal of interaction `m` from whatever column they find there,
+/// so both must list the interactions in the same order; `check_matches` compares two layouts.
+pub struct InteractionLayout<F: Field> {
+    pub interactions: Vec<(Interaction<F>, InteractionType)>,
+    /// The permutation trace column of each interaction, as given by `reciprocal_columns`.
+    pub columns: Vec<Option<usize>>,
+}
+
+impl<F: Field> InteractionLayout<F> {
+    pub fn new(
+        interactions: Vec<(Interaction<F>, InteractionType)>,
+        preprocessed_width: usize,
+        main_width: usize,
+        layout: &PermutationLayout,
+    ) -> Self {
+        let interactions = interactions
+            .into_iter()
+            .map(|(interaction, interaction_type)| {
+                (
+                    interaction.simplified(preprocessed_width, main_width),
+                    interaction_type,
+                )
+            })
+            .collect::<Vec<_>>();
+        let columns = reciprocal_columns(&interactions, preprocessed_width, main_width, layout);
+        Self {
+            interactions,
+            columns,
+        }
+    }
+}
+
+impl<F: PrimeField32> InteractionLayout<F> {
+    /// Check that `verifier` lays out the same interactions, at the same positions and in the
+    /// same columns, as this layout, for traces of the given widths.
+    pub fn check_matches(
+        &self,
+        verifier: &Self,
+        preprocessed_width: usize,
+        main_width: usize,
+    ) -> Result<(), LayoutMismatch> {
+        if self.interactions.len() != verifier.interactions.len() {
+            return Err(LayoutMismatch::InteractionCount {
+                prover: self.interactions.len(),
+                verifier: verifier.interactions.len(),
+            });
+        }
+        let fingerprint = |interaction: &(Interaction<F>, InteractionType)| {
+            interaction_fingerprint(
+                core::slice::from_ref(interaction),
+                preprocessed_width,
+                main_width,
+            )
+        };
+        for (m, (prover, verifier)) in self
+            .interactions
+            .iter()
+            .zip(&verifier.interactions)
+            .enumerate()
+        {
+            if fingerprint(prover) != fingerprint(verifier) {
+                return Err(LayoutMismatch::Interaction(m));
+            }
+        }
+        for (m, (&prover, &verifier)) in self.columns.iter().zip(&verifier.columns).enumerate() {
+            if prover != verifier {
+                return Err(LayoutMismatch::Column {
+                    interaction: m,
+                    prover,
+                    verifier,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Assign each interaction its reciprocal: a permutation trace column under `layout` for
+/// interactions whose payload depends on the row, and a constant otherwise.
+pub(crate) fn reciprocal_layout<F, EF>(
+    interactions: &[(Interaction<F>, InteractionType)],
+    preprocessed_width: usize,
+    main_width: usize,
+    layout: &PermutationLayout,
+    alphas_local: &[EF],
+    alphas_global: &[EF],
+    betas: &[EF],
+) -> Vec<Reciprocal<EF>>
+where
+    F: Field,
+    EF: ExtensionField<F>,
+{
+    let columns = reciprocal_columns(interactions, preprocessed_width, main_width, layout);
+    reciprocals_of_columns(interactions, &columns, alphas_local, alphas_global, betas)
+}
+
+/// Assign each interaction its reciprocal given its permutation trace column, computing constant
+/// reciprocals for interactions without one.
+pub(crate) fn reciprocals_of_columns<F, EF>(
+    interactions: &[(Interaction<F>, InteractionType)],
+    columns: &[Option<usize>],
+    alphas_local: &[EF],
+    alphas_global: &[EF],
+    betas: &[EF],
+) -> Vec<Reciprocal<EF>>
+where
+    F: Field,
+    EF: ExtensionField<F>,
+{
+    interactions
+        .iter()
+        .zip(columns)
+        .map(|((interaction, _), &column)| match column {
+            Some(c) => Reciprocal::Column(c),
+            None => {
+                let alpha = if interaction.is_local() {
+                    alphas_local[interaction.argument_index()]
+                } else {
+                    alphas_global[interaction.argument_index()]
+                };
+                let rlc = reduce_row(&[&[], &[]], &[&[], &[]], interaction, alpha, betas);
+                #[cfg(feature = "cost-model")]
+                crate::cost::record(0, 1);
+                Reciprocal::Constant(rlc.try_inverse().unwrap_or(EF::zero()))
+            }
+        })
+        .collect()
+}
+
+/// The width of a chip's permutation trace: one reciprocal column per interaction whose payload
+/// depends on the row, or one column per bundle for a chip with a `bundle_degree_budget`, plus
+/// the running sum column.
+pub fn permutation_trace_width<M, SC, C>(machine: &M, chip: &C) -> usize
+where
+    M: Machine<SC::Val>,
+    SC: StarkConfig,
+    C: InteractionAir<M, SC> + ?Sized,
+{
+    if let Some(max_degree) = bundle_degree_budget::<M, SC, C>(machine, chip) {
+        return reciprocal_bundles::<M, SC, C>(machine, chip, max_degree).len() + 1;
+    }
+    let num_columns = permutation_columns::<M, SC, C>(machine, chip)
+        .into_iter()
+        .flatten()
+        .count();
+    num_columns + 1
+}
+
+/// A permutation trace column evaluated at a row rotation, where `0` is the local row and `1` the
+/// next row.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct OpeningPoint {
+    pub column: usize,
+    pub rotation: usize,
+}
+
+/// The evaluations of a chip's permutation trace read by `eval_permutation_constraints`: every
+/// column at both rotations. The running sum transition `\phi' - \phi = \sum_i \pm c_i' q_i'`
+/// reads the running sum on both rows and each reciprocal on the next row, and the reciprocal
+/// constraints read each reciprocal on the local row.
+pub fn permutation_opening_points<M, SC, C>(chip: &C, machine: &M) -> Vec<OpeningPoint>
+where
+    M: Machine<SC::Val>,
+    SC: StarkConfig,
+    C: InteractionAir<M, SC> + ?Sized,
+{
+    opening_points(permutation_trace_width::<M, SC, C>(machine, chip))
+}
+
+fn opening_points(permutation_width: usize) -> Vec<OpeningPoint> {
+    (0..permutation_width)
+        .flat_map(|column| (0..=1).map(move |rotation| OpeningPoint { column, rotation }))
+        .collect()
+}
+
+/// Write `multiplicities[i]`, when given, into the count column of the `i`th interaction on every
+/// row of `main`, see `Chip::external_multiplicities`. Panics if such an interaction does not count
+/// a single main column, since its counts could then not be written into the trace.
+pub fn write_external_multiplicities<F: Field>(
+    interactions: &[(Interaction<F>, InteractionType)],
+    preprocessed_width: usize,
+    main: &mut RowMajorMatrix<F>,
+    multiplicities: &[Option<Vec<F>>],
+) {
+    assert_eq!(
+        multiplicities.len(),
+        interactions.len(),
+        "expected one external multiplicity entry per interaction"
+    );
+    let width = main.width();
+    let height = main.height();
+    for (m, ((interaction, _), counts)) in interactions.iter().zip(multiplicities).enumerate() {
+        let Some(counts) = counts else {
+            continue;
+        };
+        let (terms, constant) = column_terms(&interaction.count, preprocessed_width, width);
+        let col = match terms.as_slice() {
+            [(PairCol::Main(col), weight)] if *weight == F::one() && constant.is_zero() => *col,
+            _ => panic!(
+                "interaction {} must count a single main column to take external multiplicities",
+                m
+            ),
+        };
+        assert_eq!(
+            counts.len(),
+            height,
+            "interaction {} needs an external count for every row",
+            m
+        );
+        for (row, &count) in main.values.chunks_mut(width).zip(counts) {
+            row[col] = count;
+        }
+    }
+}
+
+/// Generate the permutation trace for a chip with the provided machine.
+/// This is called only after `generate_trace` has been called on all chips.
+///
+/// If the chip provides its own trace via `Chip::generate_permutation_trace`, that is used instead.
+/// `random_elements` must be derived with `Machine::derive_permutation_challenges` after every main
+/// trace is committed. With the `packed` feature, the denominators are computed as in
+/// `generate_permutation_trace_packed`.
+pub fn generate_permutation_trace<M, SC>(
+    machine: &M,
+    chip: &dyn Chip<M, SC>,
+    main: &RowMajorMatrix<SC::Val>,
+    random_elements: Vec<SC::Challenge>,
+) -> RowMajorMatrix<SC::Challenge>
+where
+    M: Machine<SC::Val>,
+    SC: StarkConfig,
+{
+    let layout = chip.interaction_layout(machine);
+    generate_permutation_trace_with_layout(machine, chip, main, random_elements, &layout)
+}
+
+/// Like `generate_permutation_trace`, reusing the chip's `Chip::interaction_layout`.
+pub fn generate_permutation_trace_with_layout<M, SC>(
+    machine: &M,
+    chip: &dyn Chip<M, SC>,
+    main: &RowMajorMatrix<SC::Val>,
+    random_elements: Vec<SC::Challenge>,
+    layout: &InteractionLayout<SC::Val>,
+) -> RowMajorMatrix<SC::Challenge>
+where
+    M: Machine<SC::Val>,
+    SC: StarkConfig,
+{
+    expect_independent_challenges(&random_elements);
+    debug_assert_enough_challenges(machine, chip, &random_elements);
+    if let Some(perm) = chip.generate_permutation_trace(machine, main, &random_elements) {
+        return perm;
+    }
+    let bundle_degree = bundle_degree_budget::<M, SC, _>(machine, chip);
+    if chip.lookup_field() == LookupField::Base {
+        // `eval_permutation_constraints` bundles by the budget alone, so the unbundled base field
+        // trace would not satisfy its constraints.
+        assert!(
+            bundle_degree.is_none(),
+            "a base field lookup cannot bundle its reciprocals"
+        );
+        return generate_base_permutation_trace(machine, chip, main, &random_elements);
+    }
+    if let Some(max_degree) = bundle_degree {
+        return generate_bundled_permutation_trace(
+            machine,
+            chip,
+            main,
+            &random_elements,
+            max_degree,
+        );
+    }
+
+    generate_permutation_trace_of(
+        machine,
+        chip,
+        main,
+        random_elements,
+        layout,
+        cfg!(feature = "packed"),
+        false,
+    )
+}
+
+/// The cumulative sum of a permutation trace: the running sum `phi` in the last column of the last
+/// row, which `eval_permutation_constraints` binds to the claimed cumulative sum. An empty trace
+/// contributes nothing.
+pub fn cumulative_sum_from_perm<EF: Field>(perm: &RowMajorMatrix<EF>) -> EF {
+    match perm.height() {
+        0 => EF::zero(),
+        height => *perm.row_slice(height - 1).last().unwrap(),
+    }
+}
+
+/// Generate the permutation trace of a chip, see `generate_permutation_trace`, and return its
+/// cumulative sum, the value a prover must claim for the chip.
+pub fn generate_cumulative_sum<M, SC>(
+    machine: &M,
+    chip: &dyn Chip<M, SC>,
+    main: &RowMajorMatrix<SC::Val>,
+    random_elements: Vec<SC::Challenge>,
+) -> SC::Challenge
+where
+    M: Machine<SC::Val>,
+    SC: StarkConfig,
+{
+    cumulative_sum_from_perm(&generate_permutation_trace(
+        machine,
+        chip,
+        main,
+        random_elements,
+    ))
+}
+
+/// Generate a permutation trace covering only the interactions of a chip in the given scope, with
+/// its own running sum. The local and global traces of a chip together carry the same reciprocals
+/// as its combined trace, and their cumulative sums add up to its cumulative sum. Scoped traces
+/// always use `PermutationLayout::Blocked`.
+pub fn generate_scoped_permutation_trace<M, SC>(
+    machine: &M,
+    chip: &dyn Chip<M, SC>,
+    main: &RowMajorMatrix<SC::Val>,
+    random_elements: Vec<SC::Challenge>,
+    scope: InteractionScope,
+) -> RowMajorMatrix<SC::Challenge>
+where
+    M: Machine<SC::Val>,
+    SC: StarkConfig,
+{
+    expect_independent_challenges(&random_elements);
+    let layout = InteractionLayout::new(
+        scope.filter(chip.all_interactions(machine)),
+        chip.preprocessed_trace().map_or(0, |trace| trace.width()),
+        main.width(),
+        &PermutationLayout::Blocked,
+    );
+    generate_permutation_trace_of(machine, chip, main, random_elements, &layout, false, false)
+}
+
+/// Generate the permutation trace of the given interactions of a chip. With `packed`, the
+/// reciprocal denominators are computed by `reduce_rows_packed`. With `allow_zero`, the debug
+/// reciprocal check is skipped, so that a zero denominator leaves its reciprocal zero, see
+/// `ZeroPolicy::SkipAsZero`.
+pub(crate) fn generate_permutation_trace_of<M, SC>(
+    machine: &M,
+    chip: &dyn Chip<M, SC>,
+    main: &RowMajorMatrix<SC::Val>,
+    random_elements: Vec<SC::Challenge>,
+    layout: &InteractionLayout<SC::Val>,
+    packed: bool,
+    allow_zero: bool,
+) -> RowMajorMatrix<SC::Challenge>
+where
+    M: Machine<SC::Val>,
+    SC: StarkConfig,
+{
+    let all_interactions = &layout.interactions;
+    // A chip without interactions only has the running sum column, which stays zero.
+    if all_interactions.is_empty() {
+        return RowMajorMatrix::new(vec![SC::Challenge::zero(); main.height()], 1);
+    }
+
+    let (alphas_local, alphas_global) =
+        generate_rlc_elements::<M, SC, _>(machine, chip, &random_elements);
+    let betas = beta_powers(random_elements[2], all_interactions);
+
+    let preprocessed = chip.preprocessed_trace();
+    let preprocessed_width = preprocessed.as_ref().map_or(0, |p| p.width());
+    let active_selector = chip.active_selector();
+    let sign_convention = chip.sign_convention();
+    let reciprocals = reciprocals_of_columns(
+        all_interactions,
+        &layout.columns,
+        &alphas_local,
+        &alphas_global,
+        &betas,
+    );
+
+    #[cfg(debug_assertions)]
+    if chip.require_distinct_fields() {
+        for (m, (interaction, _)) in all_interactions.iter().enumerate() {
+            if let Some((i, j)) = interaction.duplicate_fields(preprocessed_width, main.width()) {
+                panic!("interaction {} repeats field {} at position {}", m, i, j);
+            }
+        }
+    }
+
+    // Compute the reciprocal columns
+    //
+    // Row: | q_1 | q_2 | q_3 | ... | q_n | \phi |
+    // * q_i = \frac{1}{\alpha^i + \sum_j \beta^j * f_{i,j}}
+    // * f_{i,j} is the jth main trace column for the ith interaction
+    // * \phi is the running sum
+    //
+    // Interactions with a constant payload have no column, see `Reciprocal::Constant`.
+    //
+    // Chips may combine several reciprocal columns into one under a target constraint degree,
+    // see `InteractionAir::reciprocal_bundle_degree`.
+    let perm_width = reciprocals
+        .iter()
+        .filter(|r| matches!(r, Reciprocal::Column(_)))
+        .count()
+        + 1;
+    let height = main.height();
+    let perm_values = in_debug_span!("reduce rows", || {
+        if packed {
+            let alphas = all_interactions
+                .iter()
+                .map(|(interaction, _)| {
+                    if interaction.is_local() {
+                        alphas_local[interaction.argument_index()]
+                    } else {
+                        alphas_global[interaction.argument_index()]
+                    }
+                })
+                .collect::<Vec<_>>();
+            return reduce_rows_packed(
+                all_interactions,
+                &reciprocals,
+                &alphas,
+                preprocessed.as_ref(),
+                main,
+                &betas,
+                perm_width,
+            );
+        }
+        let mut perm_values = vec![SC::Challenge::zero(); height * perm_width];
+        perm_values
+            .par_chunks_mut(perm_width)
+            .enumerate()
+            .for_each(|(n, row)| {
+                // Rotated fields wrap around to the first row, see `Interaction::rotations`.
+                let n_next = (n + 1) % height;
+                let main_rows = [main.row_slice(n), main.row_slice(n_next)];
+                let preprocessed_rows = match &preprocessed {
+                    Some(preprocessed) => {
+                        [preprocessed.row_slice(n), preprocessed.row_slice(n_next)]
+                    }
+                    None => [&[][..], &[][..]],
+                };
+                for ((interaction, _), reciprocal) in all_interactions.iter().zip(&reciprocals) {
+                    let Reciprocal::Column(c) = *reciprocal else {
+                        continue;
+                    };
+                    let alpha_m = if interaction.is_local() {
+                        alphas_local[interaction.argument_index()]
+                    } else {
+                        alphas_global[interaction.argument_index()]
+                    };
+                    row[c] =
+                        reduce_row(&main_rows, &preprocessed_rows, interaction, alpha_m, &betas);
+                }
+            });
+        perm_values
+    });
+    // TODO: Switch to batch_multiplicative_inverse (not allowing zero)?
+    // Zero should be vanishingly unlikely if properly randomized?
+    #[cfg(feature = "cost-model")]
+    crate::cost::record_batch_inverse(&perm_values);
+    let perm_values = in_debug_span!("batch inverse", || {
+        batch_multiplicative_inverse_allowing_zero(perm_values)
+    });
+    let mut perm = RowMajorMatrix::new(perm_values, perm_width);
+    #[cfg(debug_assertions)]
+    if !allow_zero {
+        assert_reciprocals(
+            all_interactions,
+            &reciprocals,
+            preprocessed.as_ref(),
+            main,
+            &perm,
+            (&alphas_local, &alphas_global),
+            &betas,
+        );
+    }
+    #[cfg(not(debug_assertions))]
+    let _ = allow_zero;
+
+    // Compute the running sum column, as the prefix sums of the contributions of each row.
+    let phi = in_debug_span!("running sum", || {
+        let mut phi = (0..height)
+            .into_par_iter()
+            .map(|n| {
+                let main_row = main.row_slice(n);
+                let perm_row = perm.row_slice(n);
+                let preprocessed_row = if preprocessed.is_some() {
+                    preprocessed.as_ref().unwrap().row_slice(n)
+                } else {
+                    &[]
+                };
+                let mut contribution = SC::Challenge::zero();
+                for (m, (interaction, interaction_type)) in all_interactions.iter().enumerate() {
+                    let count = interaction
+                        .count
+                        .apply::<SC::Val, SC::Val>(preprocessed_row, main_row);
+                    debug_assert!(
+                        interaction.count_within_bound(count),
+                        "interaction {} on {:?} has count {} on row {}, violating its {:?} count \
+                         bound",
+                        m,
+                        interaction.argument_index,
+                        count.as_canonical_u32(),
+                        n,
+                        interaction.count_bound
+                    );
+                    let mult = count
+                        * interaction.is_real_at::<SC::Val, SC::Val>(preprocessed_row, main_row);
+                    let mult = gated_count(active_selector, mult, main_row);
+                    let q = reciprocals[m].value(perm_row);
+                    #[cfg(feature = "cost-model")]
+                    crate::cost::record(1, 0);
+                    if sign_convention.adds(interaction_type) {
+                        contribution += q * mult;
+                    } else {
+                        contribution -= q * mult;
+                    }
+                }
+                contribution
+            })
+            .collect::<Vec<_>>();
+        prefix_sums(&mut phi);
+        phi
+    });
+
+    for (n, row) in perm.as_view_mut().rows_mut().enumerate() {
+        *row.last_mut().unwrap() = phi[n];
+    }
+
+    perm
+}
+
+/// Check that every reciprocal column of a permutation trace inverts the reduced row of its
+/// interaction, as the reciprocal constraint requires. Panics with the row, interaction and bus of
+/// the first mismatch, which points at a mis-wired field or challenge at trace generation rather
+/// than as a failed proof. A zero reduced row has no reciprocal, so no trace satisfies the
+/// constraint there; this panics on it too, see `ZeroPolicy`.
+#[cfg(debug_assertions)]
+fn assert_reciprocals<F, EF>(
+    interactions: &[(Interaction<F>, InteractionType)],
+    reciprocals: &[Reciprocal<EF>],
+    preprocessed: Option<&RowMajorMatrix<F>>,
+    main: &RowMajorMatrix<F>,
+    perm: &RowMajorMatrix<EF>,
+    (alphas_local, alphas_global): (&[EF], &[EF]),
+    betas: &[EF],
+) where
+    F: Field,
+    EF: ExtensionField<F>,
+{
+    let height = main.height();
+    for n in 0..height {
+        let n_next = (n + 1) % height;
+        let main_rows = [main.row_slice(n), main.row_slice(n_next)];
+        let preprocessed_rows = match preprocessed {
+            Some(preprocessed) => [preprocessed.row_slice(n), preprocessed.row_slice(n_next)],
+            None => [&[][..], &[][..]],
+        };
+        let perm_row = perm.row_slice(n);
+        for (m, ((interaction, _), reciprocal)) in interactions.iter().zip(reciprocals).enumerate()
+        {
+            let Reciprocal::Column(c) = *reciprocal else {
+                continue;
+            };
+            let alpha = if interaction.is_local() {
+                alphas_local[interaction.argument_index()]
+            } else {
+                alphas_global[interaction.argument_index()]
+            };
+            // Not `reduce_row`, so that the check is not counted by the cost model.
+            let rlc = reduce_row_expr::<F, F, F, EF, EF>(
+                &main_rows,
+                &preprocessed_rows,
+                interaction,
+                alpha,
+                betas,
+            );
+            assert!(
+                !rlc.is_zero(),
+                "interaction {} on {:?} has a zero denominator on row {}",
+                m,
+                interaction.argument_index,
+                n
+            );
+            assert!(
+                perm_row[c] * rlc == EF::one(),
+                "reciprocal of interaction {} on {:?} is wrong on row {}",
+                m,
+                interaction.argument_index,
+                n
+            );
+        }
+    }
+}
+
+/// Evaluate the constraints of the permutation argument of a chip.
+///
+/// The running sum `\phi` is constrained by the recurrence `\phi' - \phi = \sum_i \pm c_i' q_i'`
+/// on transition rows only. Across the wraparound from the last row to the first, the recurrence
+/// would force the entire sum to vanish per chip, so instead the first row is pinned to its own
+/// contribution and the last row to the claimed `cumulative_sum`. Builders must therefore report
+/// a zero transition selector on the last row.
+pub fn eval_permutation_constraints<M, C, SC, AB>(
+    chip: &C,
+    builder: &mut AB,
+    cumulative_sum: AB::EF,
+) where
+    M: Machine<SC::Val>,
+    C: InteractionAir<M, SC> + Air<AB>,
+    SC: StarkConfig,
+    AB: ValidaAirBuilder<Machine = M, F = SC::Val, EF = SC::Challenge>,
+{
+    let layout = InteractionLayout::new(
+        chip.all_interactions(builder.machine()),
+        builder.preprocessed().width(),
+        builder.main().width(),
+        &chip.permutation_layout(),
+    );
+    eval_permutation_constraints_with_layout(chip, builder, &layout, cumulative_sum);
+}
+
+/// Like `eval_permutation_constraints`, for a caller which may hold no cumulative sum for the
+/// chip. A chip without interactions needs none: its running sum constraints pin its single
+/// permutation column to zero, and only the last row boundary, which reads the cumulative sum, is
+/// skipped. A chip with interactions cannot be checked without one, and nothing is evaluated.
+pub fn try_eval_permutation_constraints<M, C, SC, AB>(
+    chip: &C,
+    builder: &mut AB,
+    cumulative_sum: Option<AB::EF>,
+) -> Result<(), MissingCumulativeSum>
+where
+    M: Machine<SC::Val>,
+    C: InteractionAir<M, SC> + Air<AB>,
+    SC: StarkConfig,
+    AB: ValidaAirBuilder<Machine = M, F = SC::Val, EF = SC::Challenge>,
+{
+    if let Some(cumulative_sum) = cumulative_sum {
+        eval_permutation_constraints(chip, builder, cumulative_sum);
+        return Ok(());
+    }
+    let layout = InteractionLayout::new(
+        chip.all_interactions(builder.machine()),
+        builder.preprocessed().width(),
+        builder.main().width(),
+        &chip.permutation_layout(),
+    );
+    if !layout.interactions.is_empty() {
+        return Err(MissingCumulativeSum {
+            chip: type_name::<C>(),
+        });
+    }
+    eval_permutation_constraints_of(chip, builder, &layout, None);
+    Ok(())
+}
+
+/// Like `eval_permutation_constraints`, reusing the chip's `Chip::interaction_layout`, e.g. across
+/// the rows of a quotient computation.
+pub fn eval_permutation_constraints_with_layout<M, C, SC, AB>(
+    chip: &C,
+    builder: &mut AB,
+    layout: &InteractionLayout<SC::Val>,
+    cumulative_sum: AB::EF,
+) where
+    M: Machine<SC::Val>,
+    C: InteractionAir<M, SC> + Air<AB>,
+    SC: StarkConfig,
+    AB: ValidaAirBuilder<Machine = M, F = SC::Val, EF = SC::Challenge>,
+{
+    debug_assert_enough_challenges(builder.machine(), chip, builder.permutation_randomness());
+    if let Some(max_degree) = bundle_degree_budget::<M, SC, C>(builder.machine(), chip) {
+        return eval_bundled_permutation_constraints(chip, builder, max_degree, cumulative_sum);
+    }
+    eval_permutation_constraints_of(chip, builder, layout, Some(cumulative_sum));
+}
+
+/// Evaluate the constraints of a permutation trace generated by
+/// `generate_scoped_permutation_trace` for the same scope.
+pub fn eval_scoped_permutation_constraints<M, C, SC, AB>(
+    chip: &C,
+    builder: &mut AB,
+    scope: InteractionScope,
+    cumulative_sum: AB::EF,
+) where
+    M: Machine<SC::Val>,
+    C: InteractionAir<M, SC> + Air<AB>,
+    SC: StarkConfig,
+    AB: ValidaAirBuilder<Machine = M, F = SC::Val, EF = SC::Challenge>,
+{
+    let layout = InteractionLayout::new(
+        scope.filter(chip.all_interactions(builder.machine())),
+        builder.preprocessed().width(),
+        builder.main().width(),
+        &PermutationLayout::Blocked,
+    );
+    eval_permutation_constraints_of(chip, builder, &layout, Some(cumulative_sum));
+}
+
+/// Evaluate the permutation constraints of `layout`, leaving out the last row boundary when there
+/// is no `cumulative_sum`, see `try_eval_permutation_constraints`.
+fn eval_permutation_constraints_of<M, C, SC, AB>(
+    chip: &C,
+    builder: &mut AB,
+    layout: &InteractionLayout<SC::Val>,
+    cumulative_sum: Option<AB::EF>,
+) where
+    M: Machine<SC::Val>,
+    C: InteractionAir<M, SC> + Air<AB>,
+    SC: StarkConfig,
+    AB: ValidaAirBuilder<Machine = M, F = SC::Val, EF = SC::Challenge>,
+{
+    let rand_elems = builder.permutation_randomness().to_vec();
+
+    let main = builder.main();
+    let main_local: &[AB::Var] = main.row_slice(0);
+    let main_next: &[AB::Var] = main.row_slice(1);
+
+    let preprocessed = builder.preprocessed();
+    let preprocessed_local = preprocessed.row_slice(0);
+    let preprocessed_next = preprocessed.row_slice(1);
+
+    let perm = builder.permutation();
+    let perm_width = perm.width();
+    let perm_local: &[AB::VarEF] = perm.row_slice(0);
+    let perm_next: &[AB::VarEF] = perm.row_slice(1);
+
+    let phi_local = perm_local[perm_width - 1].clone();
+    let phi_next = perm_next[perm_width - 1].clone();
+
+    let all_interactions = &layout.interactions;
+    let (alphas_local, alphas_global) =
+        generate_rlc_elements::<M, SC, _>(builder.machine(), chip, &rand_elems);
+    let betas = beta_powers(rand_elems[2], all_interactions);
+    let reciprocals = reciprocals_of_columns(
+        all_interactions,
+        &layout.columns,
+        &alphas_local,
+        &alphas_global,
+        &betas,
+    );
+
+    let active_selector = chip.active_selector();
+    if let Some(col) = active_selector {
+        builder.assert_bool(main_local[col]);
+    }
+    let sign_convention = chip.sign_convention();
+
+    let lhs = phi_next.into() - phi_local.clone().into();
+    let mut rhs = AB::ExprEF::zero();
+    let mut phi_0 = AB::ExprEF::zero();
+    let running_sum_provenance = |kind| ConstraintProvenance {
+        chip: type_name::<C>(),
+        interaction_index: None,
+        bus: None,
+        kind,
+    };
+    for (m, ((interaction, interaction_type), reciprocal)) in
+        all_interactions.iter().zip(reciprocals).enumerate()
+    {
+        let (q_local, q_next): (AB::ExprEF, AB::ExprEF) = match reciprocal {
+            Reciprocal::Column(c) => (perm_local[c].clone().into(), perm_next[c].clone().into()),
+            Reciprocal::Constant(q) => (AB::ExprEF::from_f(q), AB::ExprEF::from_f(q)),
+        };
+
+        // Reciprocal constraints. The verifier computes constant reciprocals itself.
+        if let Reciprocal::Column(_) = reciprocal {
+            let alpha = if interaction.is_local() {
+                alphas_local[interaction.argument_index()]
+            } else {
+                alphas_global[interaction.argument_index()]
+            };
+            let rlc: AB::ExprEF = reduce_row_expr(
+                &[main_local, main_next],
+                &[preprocessed_local, preprocessed_next],
+                interaction,
+                alpha,
+                &betas,
+            );
+            builder.set_provenance(ConstraintProvenance {
+                chip: type_name::<C>(),
+                interaction_index: Some(m),
+                bus: Some(interaction.argument_index),
+                kind: ConstraintKind::Reciprocal,
+            });
+            builder.assert_one_ext(rlc * q_local.clone());
+        }
+
+        let mut mult_local =
+            interaction.count_at::<AB::Expr, AB::Var>(preprocessed_local, main_local);
+        let mut mult_next = interaction.count_at::<AB::Expr, AB::Var>(preprocessed_next, main_next);
+        if let Some(col) = active_selector {
+            mult_local *= AB::Expr::from(main_local[col]);
+            mult_next *= AB::Expr::from(main_next[col]);
+        }
+
+        // Build the RHS of the permutation constraint
+        if sign_convention.adds(interaction_type) {
+            phi_0 += q_local * mult_local;
+            rhs += q_next * mult_next;
+        } else {
+            phi_0 -= q_local * mult_local;
+            rhs -= q_next * mult_next;
+        }
+    }
+
+    // Running sum constraints. Without interactions these pin the single column to zero, matching
+    // the trace from `generate_permutation_trace`, and a given cumulative sum must be zero.
+    builder.set_provenance(running_sum_provenance(ConstraintKind::RunningSumTransition));
+    builder.when_transition().assert_eq_ext(lhs, rhs);
+    builder.set_provenance(running_sum_provenance(ConstraintKind::RunningSumFirstRow));
+    builder
+        .when_first_row()
+        .assert_eq_ext(phi_local.clone(), phi_0);
+    // The running sum is the last permutation column, whatever the reciprocal layout.
+    if let Some(cumulative_sum) = cumulative_sum {
+        builder.set_provenance(running_sum_provenance(ConstraintKind::RunningSumLastRow));
+        builder
+            .when_last_row()
+            .assert_eq_ext(phi_local, AB::ExprEF::from_f(cumulative_sum));
+    }
+}
+
+/// Check that the local alpha, global alpha and beta challenges are pairwise distinct. If local
+/// and global alphas coincide, local and global buses share challenges.
+pub fn check_permutation_challenges<EF: Field>(
+    random_elements: &[EF],
+) -> Result<(), PermutationChallengeError> {
+    let [alpha_local, alpha_global, beta] = match random_elements {
+        [a, b, c, ..] => [*a, *b, *c],
+        _ => {
+            return Err(PermutationChallengeError::MissingChallenges(
+                random_elements.len(),
+            ))
+        }
+    };
+    if alpha_local == alpha_global {
+        Err(PermutationChallengeError::SharedAlpha)
+    } else if alpha_local == beta || alpha_global == beta {
+        Err(PermutationChallengeError::AlphaEqualsBeta)
+    } else {
+        Ok(())
+    }
+}
+
+pub(crate) fn expect_independent_challenges<EF: Field>(random_elements: &[EF]) {
+    if let Err(err) = check_permutation_challenges(random_elements) {
+        panic!("{}", err);
+    }
+}
+
+/// The number of permutation challenges a machine samples: enough for every chip, see
+/// `InteractionAir::num_permutation_challenges`.
+pub fn num_permutation_challenges<'a, M, SC>(
+    machine: &M,
+    chips: impl IntoIterator<Item = &'a dyn Chip<M, SC>>,
+) -> usize
+where
+    M: Machine<SC::Val> + 'a,
+    SC: StarkConfig + 'a,
+{
+    chips
+        .into_iter()
+        .map(|chip| chip.num_permutation_challenges(machine))
+        .fold(
+            reserved_permutation_challenges(M::BUS_CHALLENGE_ROOTS),
+            usize::max,
+        )
+}
+
+/// The running sum direction of each chip, see `InteractionAir::sign_convention`.
+pub fn sign_conventions<'a, M, SC>(
+    chips: impl IntoIterator<Item = &'a dyn Chip<M, SC>>,
+) -> Vec<SignConvention>
+where
+    M: Machine<SC::Val> + 'a,
+    SC: StarkConfig + 'a,
+{
+    chips
+        .into_iter()
+        .map(|chip| chip.sign_convention())
+        .collect()
+}
+
+pub(crate) fn generate_all_permutation_traces<M, SC>(
+    machine: &M,
+    chips: &[&dyn Chip<M, SC>],
+    mains: &[RowMajorMatrix<SC::Val>],
+    random_elements: &[SC::Challenge],
+) -> Vec<(RowMajorMatrix<SC::Challenge>, SC::Challenge)>
+where
+    M: Machine<SC::Val>,
+    SC: StarkConfig,
+{
+    assert_eq!(chips.len(), mains.len(), "expected one main trace per chip");
+    let dependencies = chips
+        .iter()
+        .map(|chip| chip.cumulative_sum_dependencies())
+        .collect::<Vec<_>>();
+    let perms = if dependencies.iter().all(Vec::is_empty) {
+        (0..chips.len())
+            .into_par_iter()
+            .map(|i| {
+                let perm = generate_permutation_trace(
+                    machine,
+                    chips[i],
+                    &mains[i],
+                    random_elements.to_vec(),
+                );
+                let cumulative_sum = cumulative_sum_from_perm(&perm);
+                (perm, cumulative_sum)
+            })
+            .collect::<Vec<_>>()
+    } else {
+        generate_dependent_permutation_traces(machine, chips, mains, random_elements, &dependencies)
+    };
+
+    #[cfg(debug_assertions)]
+    {
+        let oriented = sign_conventions(chips.iter().copied())
+            .into_iter()
+            .zip(&perms)
+            .map(|(sign_convention, (_, sum))| sign_convention.orient(*sum))
+            .collect::<Vec<_>>();
+        assert!(
+            machine.aggregate_cumulative_sum(&oriented).is_zero(),
+            "the cumulative sums of the chips do not cancel out"
+        );
+    }
+    perms
+}
+
+/// Generate the permutation traces of chips with `Chip::cumulative_sum_dependencies` one at a
+/// time, in the order of `compute_cumulative_sums`.
+pub(crate) fn generate_dependent_permutation_traces<M, SC>(
+    machine: &M,
+    chips: &[&dyn Chip<M, SC>],
+    mains: &[RowMajorMatrix<SC::Val>],
+    random_elements: &[SC::Challenge],
+    dependencies: &[Vec<usize>],
+) -> Vec<(RowMajorMatrix<SC::Challenge>, SC::Challenge)>
+where
+    M: Machine<SC::Val>,
+    SC: StarkConfig,
+{
+    let mut perms = (0..chips.len()).map(|_| None).collect::<Vec<_>>();
+    let cumulative_sums = compute_cumulative_sums(dependencies, |i, upstream_sums| {
+        let perm = chips[i]
+            .generate_dependent_permutation_trace(
+                machine,
+                &mains[i],
+                random_elements,
+                upstream_sums,
+            )
+            .unwrap_or_else(|| {
+                generate_permutation_trace(machine, chips[i], &mains[i], random_elements.to_vec())
+            });
+        let cumulative_sum = cumulative_sum_from_perm(&perm);
+        perms[i] = Some(perm);
+        cumulative_sum
+    })
+    .unwrap_or_else(|cycle| {
+        panic!(
+            "the cumulative sums of chips {:?} depend on each other",
+            cycle.chips
+        )
+    });
+    perms
+        .into_iter()
+        .zip(cumulative_sums)
+        .map(|(perm, cumulative_sum)| (perm.unwrap(), cumulative_sum))
+        .collect()
+}
+
+pub(crate) fn debug_assert_enough_challenges<M, SC, C>(
+    machine: &M,
+    chip: &C,
+    random_elements: &[SC::Challenge],
+) where
+    M: Machine<SC::Val>,
+    SC: StarkConfig,
+    C: InteractionAir<M, SC> + ?Sized,
+{
+    let reserved = reserved_permutation_challenges(M::BUS_CHALLENGE_ROOTS);
+    debug_assert!(
+        chip.num_permutation_challenges(machine) >= reserved,
+        "{} reads {} permutation challenges, fewer than the {} reserved for every chip, so its \
+         own challenges would share slots with the bus challenge roots",
+        type_name::<C>(),
+        chip.num_permutation_challenges(machine),
+        reserved
+    );
+    debug_assert!(
+        random_elements.len() >= chip.num_permutation_challenges(machine),
+        "{} reads {} permutation challenges, but only {} were sampled",
+        type_name::<C>(),
+        chip.num_permutation_challenges(machine),
+        random_elements.len()
+    );
+}
+
+pub(crate) fn generate_rlc_elements<M, SC, C>(
+    machine: &M,
+    chip: &C,
+    random_elements: &[SC::Challenge],
+) -> (Vec<SC::Challenge>, Vec<SC::Challenge>)
+where
+    M: Machine<SC::Val>,
+    SC: StarkConfig,
+    C: InteractionAir<M, SC> + ?Sized,
+{
+    let roots = M::BUS_CHALLENGE_ROOTS;
+    let alphas_local = (0..rlc_element_count(&chip.local_sends(), &chip.local_receives()))
+        .map(|i| bus_challenge(random_elements, BusArgument::Local(i), roots))
+        .collect::<Vec<_>>();
+
+    let alphas_global =
+        (0..rlc_element_count(&chip.global_sends(machine), &chip.global_receives(machine)))
+            .map(|i| bus_challenge(random_elements, BusArgument::Global(i), roots))
+            .collect::<Vec<_>>();
 
     (alphas_local, alphas_global)
 }
 
-// TODO: Use Var and Expr type bounds in place of concrete fields so that
-// this function can be used in `eval_permutation_constraints`.
-fn reduce_row<F, EF>(
-    main_row: &[F],
-    preprocessed_row: &[F],
-    fields: &[VirtualPairCol<F>],
+/// The number of bus challenges needed by the sends and receives of one scope: one past the
+/// largest bus index over both, since a receive-only bus may exceed every send, or none if the
+/// scope has no interactions.
+fn rlc_element_count<F: Field>(sends: &[Interaction<F>], receives: &[Interaction<F>]) -> usize {
+    sends
+        .iter()
+        .chain(receives)
+        .map(|interaction| interaction.argument_index() + 1)
+        .max()
+        .unwrap_or(0)
+}
+
+/// The powers `\beta^0, ..., \beta^{k - 1}` for the widest of `interactions`, with `k` fields,
+/// computed once so that reducing each row does not rederive them, see the `beta powers` benchmark
+/// of `valida-basic`.
+///
+/// The reductions zip the fields of an interaction with these powers, so they must stand for an
+/// unbounded power sequence over every interaction they are used with: a shorter vector would
+/// leave fields unweighted and make distinct payloads collide. `reduce_row_expr` checks this in
+/// debug builds.
+pub(crate) fn beta_powers<F: Field, EF: Field>(
+    beta: EF,
+    interactions: &[(Interaction<F>, InteractionType)],
+) -> Vec<EF> {
+    let max_fields = interactions
+        .iter()
+        .map(|(interaction, _)| interaction.fields.len())
+        .max()
+        .unwrap_or(0);
+    beta.powers().take(max_fields).collect()
+}
+
+/// Compute the reduced row `\alpha + \sum_i \beta^i f_i` of an interaction during trace
+/// generation, see `reduce_row_expr`.
+pub(crate) fn reduce_row<F, EF>(
+    main_rows: &[&[F]; 2],
+    preprocessed_rows: &[&[F]; 2],
+    interaction: &Interaction<F>,
     alpha: EF,
-    betas: Powers<EF>,
+    betas: &[EF],
 ) -> EF
 where
     F: Field,
     EF: ExtensionField<F>,
 {
-    let mut rlc = EF::zero();
-    for (columns, beta) in fields.iter().zip(betas) {
-        rlc += beta * columns.apply::<F, F>(preprocessed_row, main_row)
+    // Only extension field operations are counted, see `measure_cost`.
+    #[cfg(feature = "cost-model")]
+    if EF::D > 1 {
+        crate::cost::record(interaction.fields.len(), 0);
     }
-    rlc += alpha;
+    reduce_row_expr::<F, F, F, EF, EF>(main_rows, preprocessed_rows, interaction, alpha, betas)
+}
+
+/// Compute the reduced row `\alpha + \sum_i \beta^i f_i` of an interaction over any expression
+/// type, so that trace generation and `eval_permutation_constraints` share one definition of the
+/// payload combination.
+///
+/// `main_rows` and `preprocessed_rows` hold the local and next rows, indexed by field rotation,
+/// and `betas` holds at least one power of `\beta` per field, see `beta_powers`.
+pub(crate) fn reduce_row_expr<F, Expr, Var, EF, ExprEF>(
+    main_rows: &[&[Var]; 2],
+    preprocessed_rows: &[&[Var]; 2],
+    interaction: &Interaction<F>,
+    alpha: EF,
+    betas: &[EF],
+) -> ExprEF
+where
+    F: Field + Into<Expr>,
+    Expr: AbstractField + Mul<F, Output = Expr>,
+    Var: Into<Expr> + Copy,
+    EF: ExtensionField<F>,
+    ExprEF: AbstractExtensionField<Expr, F = EF>,
+{
+    // Field `i` is weighted by `\beta^i`, so distinct positions always get distinct powers.
+    //
+    // Each field is evaluated over `Expr` and promoted only through `ExprEF: Mul<Expr>` from
+    // `AbstractExtensionField<Expr>`, which scales every coordinate of `\beta^i` by it.
+    let mut rlc = ExprEF::zero();
+    let mut weighted = 0;
+    for (i, (columns, &beta)) in interaction.fields.iter().zip(betas).enumerate() {
+        let rotation = interaction.field_rotation(i);
+        rlc += ExprEF::from_f(beta)
+            * columns.apply::<Expr, Var>(preprocessed_rows[rotation], main_rows[rotation]);
+        weighted += 1;
+    }
+    debug_assert_eq!(
+        weighted,
+        interaction.fields.len(),
+        "only {} of {} interaction fields have a power of beta, see `beta_powers`",
+        weighted,
+        interaction.fields.len()
+    );
+    rlc += ExprEF::from_f(alpha);
     rlc
 }
 
+/// Declare instruction types.
+///
+/// The bare form `instructions!(Add32Instruction, Sub32Instruction)` declares empty structs. The
+/// form `instructions!(Add32Instruction(a, b, c) = ADD32)` also gives each struct an inherent
+/// `OPCODE`, a `usize` constant per operand holding its position, `NUM_OPERANDS`, and an
+/// `operands` accessor reading the operands, in order, from a row of operand values.
+///
+/// ```
+/// valida_machine::instructions!(Add32Instruction(a, b, c) = 8);
+///
+/// let row = [4u32, 5, 6, 7];
+/// assert_eq!(Add32Instruction::OPCODE, 8);
+/// assert_eq!(Add32Instruction::c, 2);
+/// assert_eq!(Add32Instruction::default().operands(&row), [4, 5, 6]);
+/// ```
+///
+/// The operands are an array with one entry per declared operand, so reading the wrong number of
+/// them does not compile:
+///
+/// ```compile_fail
+/// valida_machine::instructions!(Add32Instruction(a, b, c) = 8);
+///
+/// let [a, b]: [u32; 2] = Add32Instruction::default().operands(&[4, 5, 6]);
+/// ```
+///
+/// Nor does reading the opcode of an instruction declared in the bare form:
+///
+/// ```compile_fail
+/// valida_machine::instructions!(Add32Instruction);
+///
+/// let _ = Add32Instruction::OPCODE;
+/// ```
 #[macro_export]
 macro_rules! instructions {
+    (@operands $index:expr;) => {};
+    (@operands $index:expr; $op:ident $(, $rest:ident)*) => {
+        #[allow(non_upper_case_globals)]
+        pub const $op: usize = $index;
+        $crate::instructions!(@operands $index + 1; $($rest),*);
+    };
+    (@count $($op:ident)*) => {
+        0 $(+ { let _ = stringify!($op); 1 })*
+    };
     ($($t:ident),*) => {
         $(
             #[derive(Default)]
             pub struct $t {}
         )*
+    };
+    ($($t:ident $(($($op:ident),* $(,)?))? = $opcode:expr),+ $(,)?) => {
+        $(
+            #[derive(Default)]
+            pub struct $t {}
+
+            impl $t {
+                pub const OPCODE: u32 = $opcode;
+                pub const NUM_OPERANDS: usize = $crate::instructions!(@count $($($op)*)?);
+                $($crate::instructions!(@operands 0; $($op),*);)?
+
+                pub fn operands<F: Copy>(
+                    &self,
+                    row: &[F],
+                ) -> [F; $crate::instructions!(@count $($($op)*)?)] {
+                    let _ = row;
+                    [$($(row[Self::$op]),*)?]
+                }
+            }
+        )+
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    type F = p3_baby_bear::BabyBear;
+    type EF = p3_field::extension::BinomialExtensionField<F, 4>;
+
+    #[test]
+    fn rlc_elements_cover_receive_only_buses() {
+        use super::*;
+
+        let interaction = |bus| Interaction::<F> {
+            fields: vec![VirtualPairCol::single_main(0)],
+            count: VirtualPairCol::one(),
+            argument_index: bus,
+            rotations: vec![],
+            count_bound: CountBound::Unbounded,
+            is_real: None,
+            limb_order: None,
+        };
+        // Local bus 3 is only received on, global bus 1 is only sent on.
+        let local_sends = [interaction(BusArgument::Local(0))];
+        let local_receives = [interaction(BusArgument::Local(3))];
+        let global_sends = [interaction(BusArgument::Global(1))];
+
+        assert_eq!(rlc_element_count(&local_sends, &local_receives), 4);
+        assert_eq!(rlc_element_count(&[], &local_receives), 4);
+        assert_eq!(rlc_element_count(&global_sends, &[]), 2);
+        assert_eq!(rlc_element_count::<F>(&[], &[]), 0);
+    }
+
+    #[test]
+    fn bus_challenges_from_several_roots() {
+        use super::*;
+        use p3_field::AbstractExtensionField;
+
+        let random_elements = [2, 3, 5, 7, 11, 13, 17]
+            .map(|x| EF::from_base_fn(|i| F::from_canonical_u32(x + i as u32)))
+            .to_vec();
+        let (alpha_local, alpha_global) = (random_elements[0], random_elements[1]);
+        let (r_local_1, r_local_2) = (random_elements[3], random_elements[4]);
+        let (r_global_1, r_global_2) = (random_elements[5], random_elements[6]);
+
+        // A single root is the power chain `alpha^{i + 1}`.
+        for (i, power) in alpha_local.powers().skip(1).take(5).enumerate() {
+            assert_eq!(
+                bus_challenge(&random_elements, BusArgument::Local(i), 1),
+                power
+            );
+        }
+
+        let local = (0..6)
+            .map(|i| bus_challenge(&random_elements, BusArgument::Local(i), 3))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            local,
+            vec![
+                alpha_local,
+                r_local_1,
+                r_local_2,
+                alpha_local.square(),
+                r_local_1.square(),
+                r_local_2.square(),
+            ]
+        );
+        let global = (0..3)
+            .map(|i| bus_challenge(&random_elements, BusArgument::Global(i), 3))
+            .collect::<Vec<_>>();
+        assert_eq!(global, vec![alpha_global, r_global_1, r_global_2]);
+
+        // The three default challenges and two more roots for each scope.
+        assert_eq!(reserved_permutation_challenges(3), 7);
+    }
+
+    #[test]
+    #[should_panic(expected = "BUS_CHALLENGE_ROOTS must be at least 1")]
+    fn zero_bus_challenge_roots() {
+        use super::*;
+
+        bus_challenge(&[EF::one(); 3], BusArgument::Local(0), 0);
+    }
+
+    #[test]
+    fn reduce_row_over_extension() {
+        use super::*;
+        use p3_field::AbstractExtensionField;
+
+        let interaction = Interaction::<F> {
+            fields: (0..3).map(VirtualPairCol::single_main).collect(),
+            count: VirtualPairCol::one(),
+            argument_index: BusArgument::Local(0),
+            rotations: vec![],
+            count_bound: CountBound::Unbounded,
+            is_real: None,
+            limb_order: None,
+        };
+        let main_row = [3, 5, 2].map(F::from_canonical_u32);
+        let alpha = EF::from_base_slice(&[1, 2, 0, 0].map(F::from_canonical_u32));
+        let beta = EF::from_base_slice(&[0, 1, 0, 0].map(F::from_canonical_u32));
+
+        // 3 + 5 X + 2 X^2 + (1 + 2 X)
+        let rlc = reduce_row(
+            &[&main_row, &main_row],
+            &[&[], &[]],
+            &interaction,
+            alpha,
+            &beta.powers().take(3).collect::<Vec<_>>(),
+        );
+        let expected = EF::from_base_slice(&[4, 7, 2, 0].map(F::from_canonical_u32));
+        assert_eq!(rlc, expected);
+    }
+
+    #[test]
+    fn every_field_is_weighted() {
+        use super::*;
+
+        let width = 32;
+        let interaction = Interaction::<F>::send_columns(
+            BusArgument::Global(0),
+            &(0..width).collect::<Vec<_>>(),
+            VirtualPairCol::one(),
+        );
+        let interactions = [(interaction.clone(), InteractionType::GlobalSend)];
+        let alpha = EF::from_canonical_u32(7);
+        let beta = EF::from_canonical_u32(13);
+        let betas = beta_powers(beta, &interactions);
+        assert_eq!(betas.len(), width);
+
+        let main_row = (0..width)
+            .map(|x| F::from_canonical_usize(x * x + 1))
+            .collect::<Vec<_>>();
+        let rlc = |row: &[F]| reduce_row(&[row, row], &[&[], &[]], &interaction, alpha, &betas);
+        let expected = main_row
+            .iter()
+            .zip(beta.powers())
+            .map(|(&x, power)| power * x)
+            .sum::<EF>()
+            + alpha;
+        assert_eq!(rlc(&main_row), expected);
+
+        // Changing any one field, the last included, changes the reduction.
+        for i in 0..width {
+            let mut changed = main_row.clone();
+            changed[i] += F::one();
+            assert_ne!(rlc(&changed), expected);
+        }
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "only 31 of 32 interaction fields have a power of beta")]
+    fn missing_beta_power() {
+        use super::*;
+
+        let interaction = Interaction::<F>::send_columns(
+            BusArgument::Global(0),
+            &(0..32).collect::<Vec<_>>(),
+            VirtualPairCol::one(),
+        );
+        let main_row = [F::one(); 32];
+        let betas = EF::two().powers().take(31).collect::<Vec<_>>();
+        reduce_row(
+            &[&main_row, &main_row],
+            &[&[], &[]],
+            &interaction,
+            EF::zero(),
+            &betas,
+        );
+    }
+
+    #[test]
+    fn prefix_sums_across_chunks() {
+        use super::*;
+
+        let values = (0..10_000)
+            .map(|x| F::from_canonical_u32(x * x + 1))
+            .collect::<Vec<_>>();
+        let mut sums = values.clone();
+        prefix_sums(&mut sums);
+
+        let mut sum = F::zero();
+        for (value, prefix_sum) in values.into_iter().zip(sums) {
+            sum += value;
+            assert_eq!(prefix_sum, sum);
+        }
+    }
+
+    #[test]
+    fn constraint_reduction_matches_trace_generation() {
+        use super::*;
+
+        let interaction = Interaction::<F> {
+            fields: vec![
+                VirtualPairCol::single_main(0),
+                VirtualPairCol::new_main(vec![(1, F::two())], F::one()),
+                VirtualPairCol::single_preprocessed(0),
+            ],
+            count: VirtualPairCol::one(),
+            argument_index: BusArgument::Local(0),
+            rotations: vec![0, 1],
+            count_bound: CountBound::Unbounded,
+            is_real: None,
+            limb_order: None,
+        };
+        let main_rows = [[3, 5], [8, 13]].map(|row| row.map(F::from_canonical_u32));
+        let preprocessed_rows = [[21], [34]].map(|row| row.map(F::from_canonical_u32));
+        let alpha = EF::from_canonical_u32(7);
+        let beta = EF::from_canonical_u32(11);
+
+        let generated: EF = reduce_row(
+            &[&main_rows[0], &main_rows[1]],
+            &[&preprocessed_rows[0], &preprocessed_rows[1]],
+            &interaction,
+            alpha,
+            &beta.powers().take(3).collect::<Vec<_>>(),
+        );
+
+        // The verifier folds constraints over extension field values.
+        let main_rows = main_rows.map(|row| row.map(EF::from_base));
+        let preprocessed_rows = preprocessed_rows.map(|row| row.map(EF::from_base));
+        let evaluated: EF = reduce_row_expr::<F, EF, EF, EF, EF>(
+            &[&main_rows[0], &main_rows[1]],
+            &[&preprocessed_rows[0], &preprocessed_rows[1]],
+            &interaction,
+            alpha,
+            &beta.powers().take(3).collect::<Vec<_>>(),
+        );
+        assert_eq!(generated, evaluated);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "reciprocal of interaction 1 on Global(0) is wrong on row 2")]
+    fn corrupted_reciprocal() {
+        use super::*;
+
+        let interaction = |col, bus| Interaction::<F> {
+            fields: vec![VirtualPairCol::single_main(col)],
+            count: VirtualPairCol::one(),
+            argument_index: bus,
+            rotations: vec![],
+            count_bound: CountBound::Unbounded,
+            is_real: None,
+            limb_order: None,
+        };
+        let interactions = vec![
+            (
+                interaction(0, BusArgument::Local(0)),
+                InteractionType::LocalSend,
+            ),
+            (
+                interaction(1, BusArgument::Global(0)),
+                InteractionType::GlobalSend,
+            ),
+        ];
+        let reciprocals = [Reciprocal::Column(0), Reciprocal::Column(1)];
+        let main = RowMajorMatrix::new((1..=8).map(F::from_canonical_u32).collect(), 2);
+        let alphas = ([EF::from_canonical_u32(7)], [EF::from_canonical_u32(11)]);
+        let betas = [EF::one()];
+
+        let mut values = Vec::new();
+        for n in 0..main.height() {
+            let row = main.row_slice(n);
+            values.push((alphas.0[0] + row[0]).inverse());
+            values.push((alphas.1[0] + row[1]).inverse());
+            values.push(EF::zero());
+        }
+        let mut perm = RowMajorMatrix::new(values, 3);
+        assert_reciprocals(
+            &interactions,
+            &reciprocals,
+            None,
+            &main,
+            &perm,
+            (&alphas.0, &alphas.1),
+            &betas,
+        );
+
+        // Corrupt the reciprocal of the global send on row 2.
+        perm.values[2 * 3 + 1] += EF::one();
+        assert_reciprocals(
+            &interactions,
+            &reciprocals,
+            None,
+            &main,
+            &perm,
+            (&alphas.0, &alphas.1),
+            &betas,
+        );
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "interaction 0 on Local(0) has a zero denominator on row 1")]
+    fn zero_denominator_reciprocal() {
+        use super::*;
+
+        let interactions = vec![(
+            Interaction::<F> {
+                fields: vec![VirtualPairCol::single_main(0)],
+                count: VirtualPairCol::one(),
+                argument_index: BusArgument::Local(0),
+                rotations: vec![],
+                count_bound: CountBound::Unbounded,
+                is_real: None,
+                limb_order: None,
+            },
+            InteractionType::LocalSend,
+        )];
+        // The denominator `alpha + x` vanishes on row 1, where the reciprocal is left zero.
+        let alpha = -EF::from_canonical_u32(2);
+        let main = RowMajorMatrix::new_col([1, 2, 3, 4].map(F::from_canonical_u32).to_vec());
+        let values = (0..main.height())
+            .flat_map(|n| {
+                let rlc = alpha + main.row_slice(n)[0];
+                [rlc.try_inverse().unwrap_or(EF::zero()), EF::zero()]
+            })
+            .collect();
+        assert_reciprocals(
+            &interactions,
+            &[Reciprocal::Column(0)],
+            None,
+            &main,
+            &RowMajorMatrix::new(values, 2),
+            (&[alpha], &[]),
+            &[EF::one()],
+        );
+    }
+
+    #[test]
+    fn column_constructors() {
+        use super::*;
+
+        let main_row = [3, 5, 7, 1].map(F::from_canonical_u32);
+        let alpha = EF::from_canonical_u32(11);
+        let betas = EF::from_canonical_u32(13)
+            .powers()
+            .take(2)
+            .collect::<Vec<_>>();
+        let reduce = |interaction: &Interaction<F>| {
+            let mult = interaction.count.apply::<F, F>(&[], &main_row);
+            let rlc = reduce_row(
+                &[&main_row, &main_row],
+                &[&[], &[]],
+                interaction,
+                alpha,
+                &betas,
+            );
+            (interaction.argument_index, mult, rlc)
+        };
+        let by_hand = |cols: &[usize], count, bus| Interaction::<F> {
+            fields: cols
+                .iter()
+                .map(|&c| VirtualPairCol::single_main(c))
+                .collect(),
+            count,
+            argument_index: bus,
+            rotations: vec![],
+            count_bound: CountBound::Unbounded,
+            is_real: None,
+            limb_order: None,
+        };
+
+        let send = Interaction::send_columns(
+            BusArgument::Global(1),
+            &[2, 0],
+            VirtualPairCol::single_main(3),
+        );
+        let expected = by_hand(
+            &[2, 0],
+            VirtualPairCol::single_main(3),
+            BusArgument::Global(1),
+        );
+        assert_eq!(reduce(&send), reduce(&expected));
+
+        let receive = Interaction::receive_always(BusArgument::Local(0), &[1, 2]);
+        let expected = by_hand(&[1, 2], VirtualPairCol::one(), BusArgument::Local(0));
+        assert_eq!(reduce(&receive), reduce(&expected));
+        assert_eq!(receive.count_bound, CountBound::Unit);
+
+        let range =
+            Interaction::range_check(BusArgument::Global(3), 1, VirtualPairCol::single_main(3));
+        let expected = by_hand(&[1], VirtualPairCol::single_main(3), BusArgument::Global(3));
+        assert_eq!(reduce(&range), reduce(&expected));
+    }
+
+    #[test]
+    fn instruction_declarations() {
+        crate::instructions!(BareInstruction);
+        crate::instructions!(
+            StoreInstruction(a, b) = 6,
+            LoadInstruction(dest, addr, offset) = 7,
+            StopInstruction = 8,
+        );
+        let _ = BareInstruction::default();
+
+        assert_eq!(StoreInstruction::OPCODE, 6);
+        assert_eq!(LoadInstruction::OPCODE, 7);
+        assert_eq!(StopInstruction::OPCODE, 8);
+
+        assert_eq!(LoadInstruction::NUM_OPERANDS, 3);
+        assert_eq!(
+            (
+                LoadInstruction::dest,
+                LoadInstruction::addr,
+                LoadInstruction::offset
+            ),
+            (0, 1, 2)
+        );
+        assert_eq!(StoreInstruction::NUM_OPERANDS, 2);
+        assert_eq!(StoreInstruction::default().operands(&[4, 5, 6]), [4, 5]);
+        assert_eq!(LoadInstruction::default().operands(&[4, 5, 6]), [4, 5, 6]);
+        assert_eq!(StopInstruction::NUM_OPERANDS, 0);
+        assert_eq!(StopInstruction::default().operands(&[4]), [0u32; 0]);
+    }
+
+    #[test]
+    fn pass_through_payloads() {
+        use super::*;
+
+        let fields = vec![
+            VirtualPairCol::single_main(1),
+            VirtualPairCol::new_main(vec![(0, F::two())], F::one()),
+        ];
+        let (receive, send) =
+            Interaction::pass_through(BusArgument::Local(0), BusArgument::Global(1), fields);
+        assert_eq!(receive.fields.len(), send.fields.len());
+        assert_eq!(receive.argument_index, BusArgument::Local(0));
+        assert_eq!(send.argument_index, BusArgument::Global(1));
+
+        let main_row = [3, 5].map(F::from_canonical_u32);
+        let payload = |interaction: &Interaction<F>| {
+            interaction
+                .fields
+                .iter()
+                .map(|field| field.apply::<F, F>(&[], &main_row))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(payload(&receive), payload(&send));
+        assert_eq!(payload(&send), [5, 7].map(F::from_canonical_u32));
+    }
+
+    #[test]
+    fn reduced_payloads_agree() {
+        use super::*;
+        use p3_field::PrimeField64;
+
+        let interaction = Interaction::<F> {
+            fields: vec![VirtualPairCol::single_main(0)],
+            count: VirtualPairCol::one(),
+            argument_index: BusArgument::Global(0),
+            rotations: vec![],
+            count_bound: CountBound::Unbounded,
+            is_real: None,
+            limb_order: None,
+        };
+        let sender = [F::from_wrapped_u64(5)];
+        let receiver = [F::from_wrapped_u64(5 + F::ORDER_U64)];
+        let rlc = |row: &[F]| {
+            reduce_row::<F, EF>(
+                &[row, row],
+                &[&[], &[]],
+                &interaction,
+                EF::two(),
+                &[EF::one()],
+            )
+        };
+        assert_eq!(rlc(&sender), rlc(&receiver));
+    }
+
+    #[test]
+    fn single_interaction_opening_points() {
+        use super::*;
+
+        // One reciprocal column and the running sum.
+        let points = opening_points(2);
+        let running_sum = [0, 1].map(|rotation| OpeningPoint {
+            column: 1,
+            rotation,
+        });
+        assert!(running_sum.iter().all(|point| points.contains(point)));
+        assert_eq!(points.len(), 4);
+    }
+
+    #[test]
+    fn net_count_difference() {
+        use super::*;
+
+        let interaction = Interaction::<F>::with_count_difference(
+            vec![VirtualPairCol::single_main(0)],
+            1,
+            2,
+            BusArgument::Local(0),
+        );
+        let count = |provided: u32, consumed: u32| {
+            let main_row = [7, provided, consumed].map(F::from_canonical_u32);
+            interaction.count.apply::<F, F>(&[], &main_row)
+        };
+        assert_eq!(count(3, 1), F::two());
+        assert!(interaction.count_within_bound(count(3, 1)));
+        assert!(!interaction.count_within_bound(count(1, 3)));
+    }
+
+    #[test]
+    fn equal_alpha_challenges() {
+        use super::*;
+
+        let challenges = [5, 5, 7].map(EF::from_canonical_u32);
+        assert_eq!(
+            check_permutation_challenges(&challenges),
+            Err(PermutationChallengeError::SharedAlpha)
+        );
     }
 }
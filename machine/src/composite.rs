@@ -0,0 +1,180 @@
+//! Composing two chips into one, see `Composite`.
+
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use crate::config::StarkConfig;
+use crate::{
+    concat_main_traces, BusArgument, Chip, Interaction, InteractionAir, Machine, SignConvention,
+};
+use p3_air::{Air, AirBuilder, BaseAir};
+use p3_field::Field;
+use p3_matrix::dense::RowMajorMatrix;
+
+/// A chip made of two sub-chips side by side. The main trace holds the columns of `left` followed
+/// by those of `right`, and the composite has the interactions of both, with the columns of
+/// `right` shifted past those of `left`. The permutation argument is computed once, over the
+/// composite, and its cumulative sum is that of the two sub-chips together.
+///
+/// The local buses of `right` are renumbered after those of `left`, so each sub-chip's local buses
+/// stay internal to it; global buses are unchanged. Both sub-chips must generate traces of the
+/// same height, and neither may have a preprocessed trace or an `active_selector`. Both must also
+/// share a `sign_convention`, which becomes that of the composite.
+pub struct Composite<A, B> {
+    pub left: A,
+    pub right: B,
+}
+
+impl<A, B> Composite<A, B> {
+    pub fn new(left: A, right: B) -> Self {
+        Self { left, right }
+    }
+}
+
+/// A builder whose main trace can be narrowed to a range of columns, so that each sub-chip of a
+/// `Composite` evaluates its constraints on its own columns.
+pub trait MainWindowBuilder: AirBuilder {
+    /// Run `f` with `main` showing only the given columns.
+    fn with_main_window(&mut self, columns: Range<usize>, f: impl FnOnce(&mut Self));
+}
+
+impl<F, A: BaseAir<F>, B: BaseAir<F>> BaseAir<F> for Composite<A, B> {
+    fn width(&self) -> usize {
+        self.left.width() + self.right.width()
+    }
+}
+
+impl<AB, A, B> Air<AB> for Composite<A, B>
+where
+    AB: MainWindowBuilder,
+    A: Air<AB>,
+    B: Air<AB>,
+{
+    fn eval(&self, builder: &mut AB) {
+        let left_width = self.left.width();
+        let width = left_width + self.right.width();
+        builder.with_main_window(0..left_width, |builder| self.left.eval(builder));
+        builder.with_main_window(left_width..width, |builder| self.right.eval(builder));
+    }
+}
+
+impl<A, B> Composite<A, B> {
+    /// Move interactions of `right` onto the composite, whose columns and local buses follow
+    /// those of `left`.
+    fn shift_right<M, SC>(
+        &self,
+        interactions: Vec<Interaction<SC::Val>>,
+    ) -> Vec<Interaction<SC::Val>>
+    where
+        M: Machine<SC::Val>,
+        SC: StarkConfig,
+        A: InteractionAir<M, SC>,
+        B: InteractionAir<M, SC>,
+    {
+        let left_width = BaseAir::<SC::Val>::width(&self.left);
+        let right_width = BaseAir::<SC::Val>::width(&self.right);
+        let local_bus_offset = num_local_buses(
+            InteractionAir::<M, SC>::local_sends(&self.left)
+                .iter()
+                .chain(&InteractionAir::<M, SC>::local_receives(&self.left)),
+        );
+        interactions
+            .into_iter()
+            .map(|interaction| {
+                let mut shifted = interaction.rebased(0, right_width, left_width);
+                if let BusArgument::Local(i) = shifted.argument_index {
+                    shifted.argument_index = BusArgument::Local(i + local_bus_offset);
+                }
+                shifted
+            })
+            .collect()
+    }
+}
+
+/// One more than the largest local bus index among the interactions, or zero if none is local.
+fn num_local_buses<'a, F: Field + 'a>(
+    interactions: impl Iterator<Item = &'a Interaction<F>>,
+) -> usize {
+    interactions
+        .filter(|interaction| interaction.is_local())
+        .map(|interaction| interaction.argument_index() + 1)
+        .max()
+        .unwrap_or(0)
+}
+
+impl<M, SC, A, B> InteractionAir<M, SC> for Composite<A, B>
+where
+    M: Machine<SC::Val>,
+    SC: StarkConfig,
+    A: InteractionAir<M, SC>,
+    B: InteractionAir<M, SC>,
+{
+    fn local_sends(&self) -> Vec<Interaction<SC::Val>> {
+        let mut interactions = InteractionAir::<M, SC>::local_sends(&self.left);
+        interactions
+            .extend(self.shift_right::<M, SC>(InteractionAir::<M, SC>::local_sends(&self.right)));
+        interactions
+    }
+
+    fn local_receives(&self) -> Vec<Interaction<SC::Val>> {
+        let mut interactions = InteractionAir::<M, SC>::local_receives(&self.left);
+        interactions.extend(
+            self.shift_right::<M, SC>(InteractionAir::<M, SC>::local_receives(&self.right)),
+        );
+        interactions
+    }
+
+    fn global_sends(&self, machine: &M) -> Vec<Interaction<SC::Val>> {
+        let mut interactions = InteractionAir::<M, SC>::global_sends(&self.left, machine);
+        interactions.extend(
+            self.shift_right::<M, SC>(InteractionAir::<M, SC>::global_sends(&self.right, machine)),
+        );
+        interactions
+    }
+
+    fn global_receives(&self, machine: &M) -> Vec<Interaction<SC::Val>> {
+        let mut interactions = InteractionAir::<M, SC>::global_receives(&self.left, machine);
+        interactions.extend(
+            self.shift_right::<M, SC>(InteractionAir::<M, SC>::global_receives(
+                &self.right,
+                machine,
+            )),
+        );
+        interactions
+    }
+
+    fn sign_convention(&self) -> SignConvention {
+        InteractionAir::<M, SC>::sign_convention(&self.left)
+    }
+}
+
+impl<M, SC, A, B> Chip<M, SC> for Composite<A, B>
+where
+    M: Machine<SC::Val>,
+    SC: StarkConfig,
+    A: Chip<M, SC>,
+    B: Chip<M, SC>,
+{
+    fn generate_trace(&self, machine: &M) -> RowMajorMatrix<SC::Val> {
+        assert!(
+            BaseAir::<SC::Val>::preprocessed_trace(&self.left).is_none()
+                && BaseAir::<SC::Val>::preprocessed_trace(&self.right).is_none(),
+            "the sub-chips of a composite cannot have preprocessed traces"
+        );
+        assert!(
+            InteractionAir::<M, SC>::active_selector(&self.left).is_none()
+                && InteractionAir::<M, SC>::active_selector(&self.right).is_none(),
+            "the sub-chips of a composite cannot have active selectors"
+        );
+        assert_eq!(
+            InteractionAir::<M, SC>::sign_convention(&self.left),
+            InteractionAir::<M, SC>::sign_convention(&self.right),
+            "the sub-chips of a composite must share a sign convention"
+        );
+        let (main, _) = concat_main_traces(&[
+            Chip::<M, SC>::generate_main_trace(&self.left, machine),
+            Chip::<M, SC>::generate_main_trace(&self.right, machine),
+        ]);
+        main
+    }
+}
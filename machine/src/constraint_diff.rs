@@ -0,0 +1,110 @@
+use alloc::vec::Vec;
+
+use crate::symbolic::symbolic_builder::PermutationConstraint;
+use crate::ConstraintProvenance;
+use p3_field::Field;
+
+/// The permutation constraints of a chip, as exported by `get_symbolic_permutation_constraints`.
+pub type ConstraintSystem<EF> = Vec<PermutationConstraint<EF>>;
+
+/// A difference between two constraint systems, identified by provenance.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ConstraintDiff {
+    /// A constraint only in the second system.
+    Added(ConstraintProvenance),
+    /// A constraint only in the first system.
+    Removed(ConstraintProvenance),
+    /// A constraint in both systems whose expression changed structurally.
+    Modified(ConstraintProvenance),
+}
+
+/// Compare two exported constraint systems, e.g. a chip's current constraints against a golden
+/// snapshot. Constraints are matched by provenance, in order when several share one. Matched
+/// constraints are compared with `SymbolicExpression::structurally_eq`, so the challenges and
+/// cumulative sum the systems were exported with must agree.
+pub fn diff_constraint_systems<EF: Field>(
+    a: &[PermutationConstraint<EF>],
+    b: &[PermutationConstraint<EF>],
+) -> Vec<ConstraintDiff> {
+    let mut matched = Vec::with_capacity(b.len());
+    matched.resize(b.len(), false);
+
+    let mut diffs = Vec::new();
+    for constraint in a {
+        let other = b
+            .iter()
+            .enumerate()
+            .find(|(j, other)| !matched[*j] && other.provenance == constraint.provenance);
+        match other {
+            Some((j, other)) => {
+                matched[j] = true;
+                if !constraint.expr.structurally_eq(&other.expr) {
+                    diffs.push(ConstraintDiff::Modified(constraint.provenance));
+                }
+            }
+            None => diffs.push(ConstraintDiff::Removed(constraint.provenance)),
+        }
+    }
+    diffs.extend(
+        b.iter()
+            .zip(matched)
+            .filter(|(_, matched)| !matched)
+            .map(|(constraint, _)| ConstraintDiff::Added(constraint.provenance)),
+    );
+    diffs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::symbolic::symbolic_expression::SymbolicExpression;
+    use crate::{BusArgument, ConstraintKind};
+    use alloc::vec;
+    use p3_field::AbstractField;
+
+    type EF = p3_field::extension::BinomialExtensionField<p3_baby_bear::BabyBear, 4>;
+
+    fn provenance(kind: ConstraintKind, interaction_index: Option<usize>) -> ConstraintProvenance {
+        ConstraintProvenance {
+            chip: "Chip",
+            interaction_index,
+            bus: interaction_index.map(|_| BusArgument::Local(0)),
+            kind,
+        }
+    }
+
+    fn constraint(provenance: ConstraintProvenance, value: u32) -> PermutationConstraint<EF> {
+        let expr = SymbolicExpression::IsFirstRow
+            * SymbolicExpression::from(EF::from_canonical_u32(value));
+        PermutationConstraint { provenance, expr }
+    }
+
+    #[test]
+    fn diff_against_snapshot() {
+        let reciprocal = provenance(ConstraintKind::Reciprocal, Some(0));
+        let transition = provenance(ConstraintKind::RunningSumTransition, None);
+        let first_row = provenance(ConstraintKind::RunningSumFirstRow, None);
+        let last_row = provenance(ConstraintKind::RunningSumLastRow, None);
+
+        let golden = vec![
+            constraint(reciprocal, 1),
+            constraint(transition, 2),
+            constraint(last_row, 3),
+        ];
+        assert_eq!(diff_constraint_systems(&golden, &golden), vec![]);
+
+        let current = vec![
+            constraint(reciprocal, 5),
+            constraint(first_row, 2),
+            constraint(last_row, 3),
+        ];
+        assert_eq!(
+            diff_constraint_systems(&golden, &current),
+            vec![
+                ConstraintDiff::Modified(reciprocal),
+                ConstraintDiff::Removed(transition),
+                ConstraintDiff::Added(first_row),
+            ]
+        );
+    }
+}
@@ -0,0 +1,136 @@
+//! Counting of extension field operations in permutation trace generation, for comparing the cost
+//! of lookup argument variants.
+//!
+//! With the `cost-model` feature enabled, `generate_permutation_trace` and its streaming variant
+//! tally the extension field multiplications and inversions they perform in `reduce_row`, the
+//! batch inversion and the running sum. `measure_cost` returns the tally of a closure. Base field
+//! arithmetic, such as evaluating `VirtualPairCol`s, is not counted.
+//!
+//! The counters are per thread, and trace generation runs on the calling thread, so concurrent
+//! measurements on other threads do not interfere.
+
+use core::cell::Cell;
+
+use p3_field::Field;
+
+std::thread_local! {
+    static MULTIPLICATIONS: Cell<usize> = const { Cell::new(0) };
+    static INVERSIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+/// The extension field operations performed while generating permutation traces.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct CostReport {
+    pub multiplications: usize,
+    pub inversions: usize,
+}
+
+/// Run `f`, returning its result and the operations it performed. Counters are per thread, so
+/// operations run on worker threads under the `parallel` feature are not counted.
+pub fn measure_cost<R>(f: impl FnOnce() -> R) -> (R, CostReport) {
+    let before = current();
+    let result = f();
+    let after = current();
+    let report = CostReport {
+        multiplications: after.multiplications - before.multiplications,
+        inversions: after.inversions - before.inversions,
+    };
+    (result, report)
+}
+
+fn current() -> CostReport {
+    CostReport {
+        multiplications: MULTIPLICATIONS.with(Cell::get),
+        inversions: INVERSIONS.with(Cell::get),
+    }
+}
+
+pub(crate) fn record(multiplications: usize, inversions: usize) {
+    MULTIPLICATIONS.with(|count| count.set(count.get() + multiplications));
+    INVERSIONS.with(|count| count.set(count.get() + inversions));
+}
+
+/// Record the inversion of `values` by `batch_multiplicative_inverse_allowing_zero`, which skips
+/// zeros and inverts the remaining `n` values with Montgomery's trick: `3 (n - 1)` multiplications
+/// and one inversion.
+pub(crate) fn record_batch_inverse<F: Field>(values: &[F]) {
+    let n = values.iter().filter(|value| !value.is_zero()).count();
+    if n > 0 {
+        record(3 * (n - 1), 1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chip::reduce_row;
+    use crate::{BusArgument, CountBound, Interaction};
+    use alloc::vec;
+    use p3_air::VirtualPairCol;
+    use p3_field::AbstractField;
+
+    type F = p3_baby_bear::BabyBear;
+    type EF = p3_field::extension::BinomialExtensionField<F, 4>;
+
+    #[test]
+    fn reduce_row_cost() {
+        let interaction = Interaction::<F> {
+            fields: (0..3).map(VirtualPairCol::single_main).collect(),
+            count: VirtualPairCol::one(),
+            argument_index: BusArgument::Local(0),
+            rotations: vec![],
+            count_bound: CountBound::Unbounded,
+            is_real: None,
+            limb_order: None,
+        };
+        let main_row = [3, 5, 2].map(F::from_canonical_u32);
+        let values = [1, 0, 4, 9].map(EF::from_canonical_u32);
+
+        // One scaling per field, then 3 * (3 - 1) for the three nonzero values.
+        let (_, report) = measure_cost(|| {
+            reduce_row(
+                &[&main_row, &main_row],
+                &[&[], &[]],
+                &interaction,
+                EF::one(),
+                &[EF::one(), EF::two(), EF::from_canonical_u32(4)],
+            );
+            record_batch_inverse(&values);
+        });
+        let expected = CostReport {
+            multiplications: 3 + 6,
+            inversions: 1,
+        };
+        assert_eq!(report, expected);
+    }
+
+    #[test]
+    fn wide_interaction_cost() {
+        let interaction = Interaction::<F> {
+            fields: (0..16).map(VirtualPairCol::single_main).collect(),
+            count: VirtualPairCol::one(),
+            argument_index: BusArgument::Local(0),
+            rotations: vec![],
+            count_bound: CountBound::Unbounded,
+            is_real: None,
+            limb_order: None,
+        };
+        let main_row = [F::one(); 16];
+        let betas = EF::two().powers().take(16).collect::<Vec<_>>();
+
+        // With the powers of beta precomputed, each of the 64 rows costs 16 scalings rather than
+        // 16 scalings and 16 powers.
+        let (_, report) = measure_cost(|| {
+            for _ in 0..64 {
+                reduce_row(
+                    &[&main_row, &main_row],
+                    &[&[], &[]],
+                    &interaction,
+                    EF::one(),
+                    &betas,
+                );
+            }
+        });
+        assert_eq!(report.multiplications, 64 * 16);
+    }
+}
@@ -0,0 +1,159 @@
+use alloc::vec::Vec;
+
+use crate::config::StarkConfig;
+use crate::{BusArgument, Chip, CountBound, Interaction, InteractionType, Machine};
+use p3_field::PrimeField32;
+use p3_matrix::dense::RowMajorMatrix;
+use p3_matrix::{Matrix, MatrixRowSlices};
+
+/// An interaction whose count violates its declared `CountBound` on some row.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CountBoundViolation {
+    /// Index of the interaction in `InteractionAir::all_interactions`.
+    pub interaction: usize,
+    pub bus: BusArgument,
+    pub bound: CountBound,
+    pub row: usize,
+}
+
+/// Check every interaction of a chip against its declared count bound on every row of the main
+/// trace, returning the violations in row order.
+pub fn check_count_bounds<M, SC>(
+    machine: &M,
+    chip: &dyn Chip<M, SC>,
+    main: &RowMajorMatrix<SC::Val>,
+) -> Result<(), Vec<CountBoundViolation>>
+where
+    M: Machine<SC::Val>,
+    SC: StarkConfig,
+{
+    let interactions = chip.all_interactions(machine);
+    let preprocessed = chip.preprocessed_trace();
+
+    let mut violations = Vec::new();
+    for n in 0..main.height() {
+        let main_row = main.row_slice(n);
+        let preprocessed_row = match &preprocessed {
+            Some(preprocessed) => preprocessed.row_slice(n),
+            None => &[],
+        };
+        for (m, (interaction, _)) in interactions.iter().enumerate() {
+            let count = interaction
+                .count
+                .apply::<SC::Val, SC::Val>(preprocessed_row, main_row);
+            if !interaction.count_within_bound(count) {
+                violations.push(CountBoundViolation {
+                    interaction: m,
+                    bus: interaction.argument_index,
+                    bound: interaction.count_bound,
+                    row: n,
+                });
+            }
+        }
+    }
+
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(violations)
+    }
+}
+
+/// An interaction whose count exceeds the configured maximum on some row.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CountOverflow {
+    /// Index of the interaction in `InteractionAir::all_interactions`.
+    pub interaction: usize,
+    pub bus: BusArgument,
+    /// The canonical value of the count.
+    pub count: u32,
+    pub row: usize,
+}
+
+/// Check that every count of a chip lies in `0..=max_count` on every row, returning the overflows
+/// in row order.
+///
+/// Counts are field elements, so a count that grows past the field order wraps around to a small
+/// value, and a negative count appears as a value close to the field order. Either silently
+/// corrupts the bus rather than failing. The valid multiplicities are `0..=max_count` for any
+/// `max_count` such that the total count on a bus, at most `max_count` times the number of rows,
+/// stays below the field order. `MAX_MULTIPLICITY` only bounds a single row, so chips with tall
+/// traces should pass a smaller maximum.
+pub fn check_count_overflow<M, SC>(
+    machine: &M,
+    chip: &dyn Chip<M, SC>,
+    main: &RowMajorMatrix<SC::Val>,
+    max_count: u32,
+) -> Result<(), Vec<CountOverflow>>
+where
+    M: Machine<SC::Val>,
+    SC: StarkConfig,
+{
+    let overflows = count_overflows(
+        &chip.all_interactions(machine),
+        chip.preprocessed_trace().as_ref(),
+        main,
+        max_count,
+    );
+    if overflows.is_empty() {
+        Ok(())
+    } else {
+        Err(overflows)
+    }
+}
+
+fn count_overflows<F: PrimeField32>(
+    interactions: &[(Interaction<F>, InteractionType)],
+    preprocessed: Option<&RowMajorMatrix<F>>,
+    main: &RowMajorMatrix<F>,
+    max_count: u32,
+) -> Vec<CountOverflow> {
+    let mut overflows = Vec::new();
+    for n in 0..main.height() {
+        let main_row = main.row_slice(n);
+        let preprocessed_row = match preprocessed {
+            Some(preprocessed) => preprocessed.row_slice(n),
+            None => &[],
+        };
+        for (m, (interaction, _)) in interactions.iter().enumerate() {
+            let count = interaction
+                .count_at::<F, F>(preprocessed_row, main_row)
+                .as_canonical_u32();
+            if count > max_count {
+                overflows.push(CountOverflow {
+                    interaction: m,
+                    bus: interaction.argument_index,
+                    count,
+                    row: n,
+                });
+            }
+        }
+    }
+    overflows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+    use p3_field::AbstractField;
+
+    type F = p3_baby_bear::BabyBear;
+
+    #[test]
+    fn count_above_maximum() {
+        let interactions = vec![(
+            Interaction::<F>::with_multiplicity_col(vec![], 0, BusArgument::Global(2)),
+            InteractionType::GlobalReceive,
+        )];
+        let main = RowMajorMatrix::new([3, 8, 5, 9].map(F::from_canonical_u32).to_vec(), 1);
+        let overflows = count_overflows(&interactions, None, &main, 8);
+        let expected = CountOverflow {
+            interaction: 0,
+            bus: BusArgument::Global(2),
+            count: 9,
+            row: 3,
+        };
+        assert_eq!(overflows, vec![expected]);
+    }
+}
@@ -1,6 +1,9 @@
-use crate::{Machine, ValidaAirBuilder};
+use crate::{
+    ConstraintFailure, ConstraintProvenance, Machine, MainWindowBuilder, ValidaAirBuilder,
+};
+use core::ops::Range;
 use p3_air::{AirBuilder, ExtensionBuilder, PairBuilder, PermutationAirBuilder, TwoRowMatrixView};
-use p3_field::AbstractField;
+use p3_field::{AbstractField, Field};
 use valida_machine::StarkConfig;
 /// An `AirBuilder` which asserts that each constraint is zero, allowing any failed constraints to
 /// be detected early.
@@ -13,6 +16,34 @@ pub struct DebugConstraintBuilder<'a, M: Machine<SC::Val>, SC: StarkConfig> {
     pub(crate) is_first_row: SC::Val,
     pub(crate) is_last_row: SC::Val,
     pub(crate) is_transition: SC::Val,
+    /// The row being evaluated.
+    pub(crate) row: usize,
+    /// Whether a failing constraint is kept in `failure` rather than panicking.
+    pub(crate) record_failures: bool,
+    /// The first constraint on the row which does not hold.
+    pub(crate) failure: Option<ConstraintFailure>,
+    /// The number of constraints asserted so far.
+    pub(crate) constraints: usize,
+    /// The provenance of the constraints asserted next, see `ValidaAirBuilder::set_provenance`.
+    pub(crate) provenance: Option<ConstraintProvenance>,
+}
+
+impl<M, SC> DebugConstraintBuilder<'_, M, SC>
+where
+    M: Machine<SC::Val>,
+    SC: StarkConfig,
+{
+    /// Count a constraint, keeping it as the `failure` of the row if it is the first not to hold.
+    fn record(&mut self, holds: bool) {
+        if !holds && self.failure.is_none() {
+            self.failure = Some(ConstraintFailure {
+                row: self.row,
+                constraint: self.constraints,
+                provenance: self.provenance,
+            });
+        }
+        self.constraints += 1;
+    }
 }
 
 impl<'a, M, SC> AirBuilder for DebugConstraintBuilder<'a, M, SC>
@@ -46,11 +77,11 @@ where
     }
 
     fn assert_zero<I: Into<Self::Expr>>(&mut self, x: I) {
-        assert_eq!(
-            x.into(),
-            SC::Val::zero(),
-            "constraints must evaluate to zero"
-        );
+        let x = x.into();
+        if !self.record_failures {
+            assert_eq!(x, SC::Val::zero(), "constraints must evaluate to zero");
+        }
+        self.record(x.is_zero());
     }
 }
 
@@ -77,11 +108,15 @@ where
     where
         I: Into<Self::ExprEF>,
     {
-        assert_eq!(
-            x.into(),
-            SC::Challenge::zero(),
-            "constraints must evaluate to zero"
-        );
+        let x = x.into();
+        if !self.record_failures {
+            assert_eq!(
+                x,
+                SC::Challenge::zero(),
+                "constraints must evaluate to zero"
+            );
+        }
+        self.record(x.is_zero());
     }
 }
 
@@ -111,4 +146,24 @@ where
     fn machine(&self) -> &Self::Machine {
         self.machine
     }
+
+    fn set_provenance(&mut self, provenance: ConstraintProvenance) {
+        self.provenance = Some(provenance);
+    }
+}
+
+impl<'a, M, SC> MainWindowBuilder for DebugConstraintBuilder<'a, M, SC>
+where
+    M: Machine<SC::Val>,
+    SC: StarkConfig,
+{
+    fn with_main_window(&mut self, columns: Range<usize>, f: impl FnOnce(&mut Self)) {
+        let main = self.main;
+        self.main = TwoRowMatrixView {
+            local: &main.local[columns.clone()],
+            next: &main.next[columns],
+        };
+        f(self);
+        self.main = main;
+    }
 }
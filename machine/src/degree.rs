@@ -0,0 +1,203 @@
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::bundle::{bundle_degree_budget, reciprocal_bundles};
+use crate::config::StarkConfig;
+use crate::symbolic::symbolic_expression::SymbolicExpression;
+use crate::symbolic::symbolic_variable::{SymbolicVariable, Trace};
+use crate::{permutation_columns, Chip, Interaction, InteractionAir, Machine};
+use p3_field::Field;
+use p3_matrix::{Matrix, MatrixRowSlices};
+#[cfg(feature = "tracing")]
+use tracing::warn;
+
+/// The degrees of the two polynomials an interaction contributes to the permutation argument.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct InteractionDegree {
+    /// Degree of the reciprocal denominator `\alpha + \sum_j \beta^j f_j`.
+    pub denominator: usize,
+    /// Degree of the multiplicity `count`.
+    pub count: usize,
+}
+
+/// Compute the degree of each interaction of a chip by evaluating its fields and count over
+/// symbolic variables. The count includes the chip's active selector, if any.
+pub fn interaction_degrees<M, SC, C>(machine: &M, chip: &C) -> Vec<InteractionDegree>
+where
+    M: Machine<SC::Val>,
+    SC: StarkConfig,
+    C: InteractionAir<M, SC> + ?Sized,
+{
+    let main = SymbolicVariable::<SC::Val>::window(Trace::Main, chip.trace_width());
+    let preprocessed = chip
+        .preprocessed_trace()
+        .map(|trace| SymbolicVariable::<SC::Val>::window(Trace::Preprocessed, trace.width()));
+    let preprocessed_row = match &preprocessed {
+        Some(preprocessed) => preprocessed.row_slice(0),
+        None => &[],
+    };
+
+    let selector_degree = chip.active_selector().map_or(0, |_| 1);
+    chip.all_interactions(machine)
+        .iter()
+        .map(|(interaction, _)| {
+            let mut degree = interaction_degree(interaction, preprocessed_row, main.row_slice(0));
+            degree.count += selector_degree;
+            degree
+        })
+        .collect()
+}
+
+fn interaction_degree<F: Field>(
+    interaction: &Interaction<F>,
+    preprocessed_row: &[SymbolicVariable<F>],
+    main_row: &[SymbolicVariable<F>],
+) -> InteractionDegree {
+    let denominator = interaction
+        .fields
+        .iter()
+        .map(|field| {
+            field
+                .apply::<SymbolicExpression<F>, SymbolicVariable<F>>(preprocessed_row, main_row)
+                .degree_multiple()
+        })
+        .max()
+        .unwrap_or(0);
+    let count = interaction
+        .count_at::<SymbolicExpression<F>, SymbolicVariable<F>>(preprocessed_row, main_row)
+        .degree_multiple();
+    InteractionDegree { denominator, count }
+}
+
+/// The degree of the constraint binding one permutation column to a bundle of interactions,
+///
+/// `q \prod_i d_i = \sum_i c_i \prod_{j \neq i} d_j`,
+///
+/// where `d_i` are the denominators and `c_i` the counts of the bundled interactions.
+pub fn bundle_degree(bundle: &[InteractionDegree]) -> usize {
+    let denominators: usize = bundle.iter().map(|d| d.denominator).sum();
+    let numerator = bundle
+        .iter()
+        .map(|d| d.count + denominators - d.denominator)
+        .max()
+        .unwrap_or(0);
+    (denominators + 1).max(numerator)
+}
+
+/// Greedily group consecutive interactions into bundles whose constraint degree stays within
+/// `max_degree`. Each returned bundle holds interaction indices. An interaction which exceeds the
+/// budget on its own is placed in a bundle by itself.
+pub fn bundle_interactions(degrees: &[InteractionDegree], max_degree: usize) -> Vec<Vec<usize>> {
+    let mut bundles: Vec<Vec<usize>> = vec![];
+    let mut current: Vec<InteractionDegree> = vec![];
+    for (i, degree) in degrees.iter().enumerate() {
+        current.push(*degree);
+        if bundles.is_empty() || bundle_degree(&current) > max_degree {
+            bundles.push(vec![i]);
+            current = vec![*degree];
+        } else {
+            bundles.last_mut().unwrap().push(i);
+        }
+    }
+    bundles
+}
+
+/// Returns `(unbundled_width, bundled_width)`: the width of the chip's permutation trace with one
+/// reciprocal column per interaction, and with interactions bundled under a constraint degree
+/// budget of `max_degree`. Both widths include the running sum column.
+pub fn bundling_savings<M, SC, C>(machine: &M, chip: &C, max_degree: usize) -> (usize, usize)
+where
+    M: Machine<SC::Val>,
+    SC: StarkConfig,
+    C: Chip<M, SC> + ?Sized,
+{
+    let degrees = interaction_degrees::<M, SC, C>(machine, chip);
+    let bundles = bundle_interactions(&degrees, max_degree);
+    (degrees.len() + 1, bundles.len() + 1)
+}
+
+/// The degree of the running sum transition constraint of a chip, whose right hand side sums
+/// `count' q'` over the interactions: the largest count degree, plus one for the reciprocal.
+pub fn running_sum_degree<M, SC, C>(machine: &M, chip: &C) -> usize
+where
+    M: Machine<SC::Val>,
+    SC: StarkConfig,
+    C: Chip<M, SC> + ?Sized,
+{
+    interaction_degrees::<M, SC, C>(machine, chip)
+        .iter()
+        .map(|d| d.count)
+        .max()
+        .unwrap_or(0)
+        + 1
+}
+
+/// Check the running sum degree of a chip against `Chip::max_permutation_degree`, logging a
+/// warning and returning the degree if it is exceeded.
+pub fn check_running_sum_degree<M, SC, C>(machine: &M, chip: &C) -> Result<(), usize>
+where
+    M: Machine<SC::Val>,
+    SC: StarkConfig,
+    C: Chip<M, SC> + ?Sized,
+{
+    let degree = running_sum_degree::<M, SC, C>(machine, chip);
+    let max_degree = chip.max_permutation_degree();
+    if degree > max_degree {
+        #[cfg(feature = "tracing")]
+        warn!(
+            "running sum constraint of degree {} exceeds the permutation degree budget of {}",
+            degree, max_degree
+        );
+        Err(degree)
+    } else {
+        Ok(())
+    }
+}
+
+/// The largest degree of the permutation constraints `eval_permutation_constraints` imposes on a
+/// chip:
+///
+/// - `rlc \cdot q = 1` for each reciprocal column, of degree one more than the denominator,
+/// - the running sum transition `\phi' - \phi = \sum_i \pm c_i' q_i'`, of the largest count
+///   degree plus one for a reciprocal column,
+/// - the first row constraint, the same sum gated by the first row selector, so one higher,
+/// - the last row constraint and the booleanity of the active selector, both of degree 2.
+///
+/// With a `bundle_degree_budget`, each bundle is bound by a constraint of `bundle_degree`
+/// instead, and the running sum adds the bundle columns alone, so high degree counts raise the
+/// bundle constraints rather than the running sum.
+pub fn permutation_constraint_degree<M, SC, C>(machine: &M, chip: &C) -> usize
+where
+    M: Machine<SC::Val>,
+    SC: StarkConfig,
+    C: Chip<M, SC> + ?Sized,
+{
+    let degrees = interaction_degrees::<M, SC, C>(machine, chip);
+    // The last row constraint `\phi = s` and the selector booleanity.
+    let mut degree = 2;
+
+    let running_sum = match bundle_degree_budget::<M, SC, C>(machine, chip) {
+        Some(max_degree) => {
+            for bundle in reciprocal_bundles::<M, SC, C>(machine, chip, max_degree) {
+                let bundle = bundle.iter().map(|&m| degrees[m]).collect::<Vec<_>>();
+                degree = degree.max(bundle_degree(&bundle));
+            }
+            1
+        }
+        None => {
+            let columns = permutation_columns::<M, SC, C>(machine, chip);
+            let mut running_sum = 1;
+            for (d, column) in degrees.iter().zip(columns) {
+                if column.is_some() {
+                    degree = degree.max(d.denominator + 1);
+                    running_sum = running_sum.max(d.count + 1);
+                } else {
+                    running_sum = running_sum.max(d.count);
+                }
+            }
+            running_sum
+        }
+    };
+    // The first row constraint gates the running sum by the first row selector.
+    degree.max(running_sum + 1)
+}
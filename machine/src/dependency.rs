@@ -0,0 +1,139 @@
+//! Ordering of chips whose permutation traces depend on the cumulative sums of other chips.
+//!
+//! A chip declares the chips it reads cumulative sums from with
+//! `Chip::cumulative_sum_dependencies`, naming them by their index in the machine's chip list.
+//! `compute_cumulative_sums` then visits the chips so that every chip comes after the chips it
+//! depends on, which is how `Machine::generate_all_permutation_traces` orders the permutation
+//! traces of the provers. The dependencies must be acyclic: if chip A waits on chip B and B waits
+//! on A, neither sum can be computed first, and the chips on the cycle are reported as a
+//! `DependencyCycle`.
+
+use alloc::collections::VecDeque;
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// The chips of a strongly connected component of the dependencies, in index order: each of them
+/// depends, directly or not, on all the others. Chips which are only downstream of the cycle could
+/// not be ordered either, but are not reported.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DependencyCycle {
+    pub chips: Vec<usize>,
+}
+
+/// Order the chips so that each comes after all chips it depends on, where `dependencies[i]`
+/// lists the chips chip `i` depends on.
+pub fn cumulative_sum_order(dependencies: &[Vec<usize>]) -> Result<Vec<usize>, DependencyCycle> {
+    let num_chips = dependencies.len();
+    let mut num_pending = vec![0; num_chips];
+    let mut dependents = vec![vec![]; num_chips];
+    for (chip, deps) in dependencies.iter().enumerate() {
+        for &dep in deps {
+            assert!(
+                dep < num_chips,
+                "chip {} depends on unknown chip {}",
+                chip,
+                dep
+            );
+            num_pending[chip] += 1;
+            dependents[dep].push(chip);
+        }
+    }
+
+    let mut ready = (0..num_chips)
+        .filter(|&chip| num_pending[chip] == 0)
+        .collect::<VecDeque<_>>();
+    let mut order = Vec::with_capacity(num_chips);
+    while let Some(chip) = ready.pop_front() {
+        order.push(chip);
+        for &dependent in &dependents[chip] {
+            num_pending[dependent] -= 1;
+            if num_pending[dependent] == 0 {
+                ready.push_back(dependent);
+            }
+        }
+    }
+
+    if order.len() == num_chips {
+        Ok(order)
+    } else {
+        let unordered = num_pending.iter().map(|&n| n > 0).collect::<Vec<_>>();
+        Err(DependencyCycle {
+            chips: first_cycle(dependencies, &unordered),
+        })
+    }
+}
+
+/// The strongly connected component of the lowest `unordered` chip lying on a cycle. Every
+/// `unordered` chip waits on another `unordered` chip, so following dependencies from any of
+/// them must eventually revisit a chip.
+fn first_cycle(dependencies: &[Vec<usize>], unordered: &[bool]) -> Vec<usize> {
+    let reachable = |from: usize| {
+        let mut seen = vec![false; dependencies.len()];
+        let mut stack = dependencies[from].clone();
+        while let Some(chip) = stack.pop() {
+            if unordered[chip] && !seen[chip] {
+                seen[chip] = true;
+                stack.extend_from_slice(&dependencies[chip]);
+            }
+        }
+        seen
+    };
+    let root = (0..dependencies.len())
+        .find(|&chip| unordered[chip] && reachable(chip)[chip])
+        .expect("unordered chips must contain a cycle");
+    let from_root = reachable(root);
+    (0..dependencies.len())
+        .filter(|&chip| from_root[chip] && reachable(chip)[root])
+        .collect()
+}
+
+/// Compute the cumulative sum of every chip in dependency order. `compute` is called once per
+/// chip with the chip's index and the sums of its dependencies, in the order they are declared.
+pub fn compute_cumulative_sums<EF, F>(
+    dependencies: &[Vec<usize>],
+    mut compute: F,
+) -> Result<Vec<EF>, DependencyCycle>
+where
+    EF: Copy,
+    F: FnMut(usize, &[EF]) -> EF,
+{
+    let order = cumulative_sum_order(dependencies)?;
+    let mut sums: Vec<Option<EF>> = vec![None; dependencies.len()];
+    for chip in order {
+        let upstream = dependencies[chip]
+            .iter()
+            .map(|&dep| sums[dep].unwrap())
+            .collect::<Vec<_>>();
+        sums[chip] = Some(compute(chip, &upstream));
+    }
+    Ok(sums.into_iter().map(Option::unwrap).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cycle_excludes_downstream_chips() {
+        // Chips 1 and 2 wait on each other, chip 3 waits on chip 2 and chip 0 is independent.
+        let dependencies = vec![vec![], vec![2], vec![1], vec![2]];
+        assert_eq!(
+            cumulative_sum_order(&dependencies),
+            Err(DependencyCycle { chips: vec![1, 2] })
+        );
+    }
+
+    #[test]
+    fn sums_follow_dependencies() {
+        let dependencies = vec![vec![2], vec![], vec![1], vec![0, 1]];
+        assert_eq!(cumulative_sum_order(&dependencies), Ok(vec![1, 2, 0, 3]));
+
+        let mut visited = vec![];
+        let sums = compute_cumulative_sums(&dependencies, |chip, upstream: &[u64]| {
+            visited.push(chip);
+            10 * chip as u64 + upstream.iter().sum::<u64>()
+        });
+        assert_eq!(visited, vec![1, 2, 0, 3]);
+        assert_eq!(sums, Ok(vec![30, 10, 30, 70]));
+    }
+}
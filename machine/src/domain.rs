@@ -0,0 +1,122 @@
+//! Chips whose main columns are split across traces of different heights.
+//!
+//! A chip may return several main traces from `Chip::generate_traces`, each tagged with a
+//! `DomainId`. Interactions refer to their columns through `DomainLayout::column`, which maps a
+//! column of a domain to a column of the aligned trace produced by `align_domain_traces`.
+//!
+//! Alignment rule: every height must be a power of two, and the aligned trace has the largest
+//! height `H`. A trace of height `h` is repeated cyclically, so row `n` of the aligned trace reads
+//! row `n mod h` of it. The permutation argument then runs over the aligned trace unchanged.
+
+use alloc::vec::Vec;
+
+use p3_air::VirtualPairCol;
+use p3_field::Field;
+use p3_matrix::dense::RowMajorMatrix;
+use p3_matrix::{Matrix, MatrixRowSlices};
+
+/// Identifies one of the main traces of a chip.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DomainId(pub usize);
+
+/// The position of each domain's columns in the aligned trace.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DomainLayout {
+    /// `(domain, first column, width)` in the aligned trace, in the order the traces were given.
+    pub domains: Vec<(DomainId, usize, usize)>,
+}
+
+impl DomainLayout {
+    /// The aligned trace column of column `col` of `domain`.
+    pub fn column(&self, domain: DomainId, col: usize) -> usize {
+        let &(_, offset, width) = self
+            .domains
+            .iter()
+            .find(|(id, _, _)| *id == domain)
+            .expect("unknown domain");
+        assert!(col < width, "column {} out of range for {:?}", col, domain);
+        offset + col
+    }
+
+    /// A `VirtualPairCol` reading column `col` of `domain`.
+    pub fn single_main<F: Field>(&self, domain: DomainId, col: usize) -> VirtualPairCol<F> {
+        VirtualPairCol::single_main(self.column(domain, col))
+    }
+}
+
+/// Combine the traces of several domains into one trace of the largest height, following the
+/// alignment rule in the module documentation.
+pub fn align_domain_traces<F: Field>(
+    traces: &[(DomainId, RowMajorMatrix<F>)],
+) -> (RowMajorMatrix<F>, DomainLayout) {
+    let height = traces.iter().map(|(_, t)| t.height()).max().unwrap_or(0);
+    let mut domains = Vec::with_capacity(traces.len());
+    let mut width = 0;
+    for (domain, trace) in traces {
+        assert!(
+            trace.height().is_power_of_two(),
+            "trace of {:?} has height {}, which is not a power of two",
+            domain,
+            trace.height()
+        );
+        assert!(
+            domains.iter().all(|(id, _, _)| id != domain),
+            "{:?} appears twice",
+            domain
+        );
+        domains.push((*domain, width, trace.width()));
+        width += trace.width();
+    }
+
+    let mut values = Vec::with_capacity(height * width);
+    for n in 0..height {
+        for (_, trace) in traces {
+            values.extend_from_slice(trace.row_slice(n % trace.height()));
+        }
+    }
+    (RowMajorMatrix::new(values, width), DomainLayout { domains })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chip::reduce_row;
+    use crate::{BusArgument, CountBound, Interaction};
+    use alloc::vec;
+    use p3_field::AbstractField;
+
+    type F = p3_baby_bear::BabyBear;
+    type EF = p3_field::extension::BinomialExtensionField<F, 4>;
+
+    #[test]
+    fn interaction_across_domains() {
+        let sparse = RowMajorMatrix::new((0..4).map(F::from_canonical_u32).collect(), 1);
+        let dense = RowMajorMatrix::new((10..18).map(F::from_canonical_u32).collect(), 1);
+        let (aligned, layout) = align_domain_traces(&[(DomainId(0), sparse), (DomainId(1), dense)]);
+        assert_eq!(aligned.height(), 8);
+
+        let interaction = Interaction::<F> {
+            fields: vec![
+                layout.single_main(DomainId(0), 0),
+                layout.single_main(DomainId(1), 0),
+            ],
+            count: VirtualPairCol::one(),
+            argument_index: BusArgument::Local(0),
+            rotations: vec![],
+            count_bound: CountBound::Unbounded,
+            is_real: None,
+            limb_order: None,
+        };
+
+        // Row 5 reads row 1 of the sparse trace and row 5 of the dense one.
+        let row = aligned.row_slice(5);
+        let rlc = reduce_row(
+            &[row, row],
+            &[&[], &[]],
+            &interaction,
+            EF::zero(),
+            &[EF::one(), EF::two()],
+        );
+        assert_eq!(rlc, EF::from_canonical_u32(1 + 2 * 15));
+    }
+}
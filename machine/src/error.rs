@@ -1,5 +1,7 @@
 use core::fmt::{Display, Formatter};
 
+use crate::ConstraintProvenance;
+
 #[derive(Debug)]
 pub enum VerificationError {
     /// The shape of opennings does not match the chip shapes.
@@ -15,6 +17,53 @@ pub enum VerificationError {
 #[derive(Debug)]
 pub struct OodEvaluationMismatch;
 
+/// The permutation constraints of a chip with interactions were evaluated without a cumulative
+/// sum, see `try_eval_permutation_constraints`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct MissingCumulativeSum {
+    /// The type name of the chip.
+    pub chip: &'static str,
+}
+
+/// The challenges `[\alpha_{local}, \alpha_{global}, \beta]` passed to permutation trace
+/// generation are not independent.
+#[derive(Debug, PartialEq, Eq)]
+pub enum PermutationChallengeError {
+    /// Fewer than three challenges were passed.
+    MissingChallenges(usize),
+    /// The local and global alphas are equal, so local and global buses share challenges.
+    SharedAlpha,
+    /// An alpha is equal to beta.
+    AlphaEqualsBeta,
+}
+
+/// The prover and verifier lay out the permutation trace of a chip differently, see
+/// `InteractionLayout::check_matches`.
+#[derive(Debug, PartialEq, Eq)]
+pub enum LayoutMismatch {
+    /// The layouts have different numbers of interactions.
+    InteractionCount { prover: usize, verifier: usize },
+    /// The layouts have different interactions at this position.
+    Interaction(usize),
+    /// The interaction at this position has different permutation trace columns.
+    Column {
+        interaction: usize,
+        prover: Option<usize>,
+        verifier: Option<usize>,
+    },
+}
+
+/// The first constraint of a chip found not to hold, see `find_failing_constraint`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ConstraintFailure {
+    pub row: usize,
+    /// The index of the constraint among those evaluated on the row: the chip's AIR constraints,
+    /// followed by its permutation constraints.
+    pub constraint: usize,
+    /// The origin of a permutation constraint, or `None` for a constraint of the chip's AIR.
+    pub provenance: Option<ConstraintProvenance>,
+}
+
 #[derive(Debug)]
 pub enum ProofShapeError {
     Preprocessed,
@@ -58,6 +107,81 @@ impl Display for ProofShapeError {
     }
 }
 
+impl Display for PermutationChallengeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            PermutationChallengeError::MissingChallenges(n) => {
+                write!(f, "Expected 3 permutation challenges, got {}", n)
+            }
+            PermutationChallengeError::SharedAlpha => {
+                write!(f, "Local and global alpha challenges are equal")
+            }
+            PermutationChallengeError::AlphaEqualsBeta => {
+                write!(f, "An alpha challenge is equal to the beta challenge")
+            }
+        }
+    }
+}
+
+impl Display for LayoutMismatch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            LayoutMismatch::InteractionCount { prover, verifier } => {
+                write!(
+                    f,
+                    "Prover has {} interactions, verifier has {}",
+                    prover, verifier
+                )
+            }
+            LayoutMismatch::Interaction(m) => {
+                write!(f, "Prover and verifier disagree on interaction {}", m)
+            }
+            LayoutMismatch::Column {
+                interaction,
+                prover,
+                verifier,
+            } => {
+                write!(
+                    f,
+                    "Interaction {} has column {:?} for the prover and {:?} for the verifier",
+                    interaction, prover, verifier
+                )
+            }
+        }
+    }
+}
+
+impl Display for MissingCumulativeSum {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{} has interactions but no cumulative sum", self.chip)
+    }
+}
+
+impl Display for ConstraintFailure {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match &self.provenance {
+            None => write!(
+                f,
+                "AIR constraint {} fails on row {}",
+                self.constraint, self.row
+            ),
+            Some(provenance) => {
+                write!(
+                    f,
+                    "{:?} constraint {} of {} fails on row {}",
+                    provenance.kind, self.constraint, provenance.chip, self.row
+                )?;
+                if let (Some(interaction), Some(bus)) =
+                    (provenance.interaction_index, provenance.bus)
+                {
+                    write!(f, ", for interaction {} on {:?}", interaction, bus)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
 impl From<ProofShapeError> for VerificationError {
     fn from(err: ProofShapeError) -> Self {
         VerificationError::InvalidProofShape(err)
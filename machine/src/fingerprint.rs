@@ -0,0 +1,108 @@
+use crate::chip::generic_rows;
+use crate::{BusArgument, CountBound, Interaction, InteractionType};
+use p3_field::PrimeField32;
+
+/// Fingerprint a list of interactions, covering in order each interaction's type, bus, fields,
+/// rotations, count and count bound.
+///
+/// Fields and counts are described by their values on a fixed generic row, so two declarations
+/// get the same fingerprint exactly when they combine the same columns with the same weights. The
+/// hash is a fixed, non-cryptographic FNV-1a over four lanes: it detects accidental changes, and
+/// is stable across versions, but offers no collision resistance against deliberate tampering.
+pub fn interaction_fingerprint<F: PrimeField32>(
+    interactions: &[(Interaction<F>, InteractionType)],
+    preprocessed_width: usize,
+    main_width: usize,
+) -> [u8; 32] {
+    let (preprocessed_row, main_row) = generic_rows::<F>(preprocessed_width, main_width);
+    let mut hasher = Fingerprinter::new();
+
+    hasher.write(interactions.len() as u32);
+    for (interaction, interaction_type) in interactions {
+        hasher.write(match interaction_type {
+            InteractionType::LocalSend => 0,
+            InteractionType::LocalReceive => 1,
+            InteractionType::GlobalSend => 2,
+            InteractionType::GlobalReceive => 3,
+        });
+        match interaction.argument_index {
+            BusArgument::Local(i) => hasher.write_pair(0, i as u32),
+            BusArgument::Global(i) => hasher.write_pair(1, i as u32),
+        }
+        hasher.write(interaction.fields.len() as u32);
+        for (i, field) in interaction.fields.iter().enumerate() {
+            hasher.write(interaction.field_rotation(i) as u32);
+            hasher.write(
+                field
+                    .apply::<F, F>(&preprocessed_row, &main_row)
+                    .as_canonical_u32(),
+            );
+        }
+        hasher.write(
+            interaction
+                .count_at::<F, F>(&preprocessed_row, &main_row)
+                .as_canonical_u32(),
+        );
+        match interaction.count_bound {
+            CountBound::Unbounded => hasher.write(0),
+            CountBound::Unit => hasher.write(1),
+            CountBound::AtMost(bound) => hasher.write_pair(2, bound),
+        }
+    }
+
+    hasher.finish()
+}
+
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+
+pub(crate) struct Fingerprinter {
+    lanes: [u64; 4],
+}
+
+impl Fingerprinter {
+    /// Four FNV-1a states, seeded differently so that together they give 256 bits.
+    pub(crate) fn new() -> Self {
+        let mut lanes = [FNV_OFFSET; 4];
+        for (i, lane) in lanes.iter_mut().enumerate() {
+            *lane ^= i as u64;
+            *lane = lane.wrapping_mul(FNV_PRIME);
+        }
+        Self { lanes }
+    }
+
+    pub(crate) fn write(&mut self, value: u32) {
+        for byte in value.to_le_bytes() {
+            self.write_byte(byte);
+        }
+    }
+
+    fn write_byte(&mut self, byte: u8) {
+        for lane in self.lanes.iter_mut() {
+            *lane ^= byte as u64;
+            *lane = lane.wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    fn write_pair(&mut self, tag: u32, value: u32) {
+        self.write(tag);
+        self.write(value);
+    }
+
+    /// Write a length-prefixed byte string.
+    #[cfg(feature = "trace-cache")]
+    pub(crate) fn write_bytes(&mut self, bytes: &[u8]) {
+        self.write(bytes.len() as u32);
+        for &byte in bytes {
+            self.write_byte(byte);
+        }
+    }
+
+    pub(crate) fn finish(self) -> [u8; 32] {
+        let mut out = [0; 32];
+        for (chunk, lane) in out.chunks_exact_mut(8).zip(self.lanes) {
+            chunk.copy_from_slice(&lane.to_le_bytes());
+        }
+        out
+    }
+}
@@ -1,4 +1,5 @@
-use crate::{Machine, ValidaAirBuilder};
+use crate::{Machine, MainWindowBuilder, ValidaAirBuilder};
+use core::ops::Range;
 use p3_air::{AirBuilder, ExtensionBuilder, PairBuilder, PermutationAirBuilder, TwoRowMatrixView};
 use p3_field::AbstractField;
 use valida_machine::StarkConfig;
@@ -215,3 +216,35 @@ where
         self.machine
     }
 }
+
+impl<'a, M, SC> MainWindowBuilder for ProverConstraintFolder<'a, M, SC>
+where
+    M: Machine<SC::Val>,
+    SC: StarkConfig,
+{
+    fn with_main_window(&mut self, columns: Range<usize>, f: impl FnOnce(&mut Self)) {
+        let main = self.main;
+        self.main = TwoRowMatrixView {
+            local: &main.local[columns.clone()],
+            next: &main.next[columns],
+        };
+        f(self);
+        self.main = main;
+    }
+}
+
+impl<'a, M, SC> MainWindowBuilder for VerifierConstraintFolder<'a, M, SC>
+where
+    M: Machine<SC::Val>,
+    SC: StarkConfig,
+{
+    fn with_main_window(&mut self, columns: Range<usize>, f: impl FnOnce(&mut Self)) {
+        let main = self.main;
+        self.main = TwoRowMatrixView {
+            local: &main.local[columns.clone()],
+            next: &main.next[columns],
+        };
+        f(self);
+        self.main = main;
+    }
+}
@@ -0,0 +1,152 @@
+//! Recording and replaying the interactions behind a permutation trace.
+//!
+//! An `InteractionLog` holds every payload and count a chip's interactions produced on every row,
+//! independent of the challenges. It can be serialized next to a failing trace and replayed with
+//! `replay_permutation_trace` to regenerate the permutation trace without the original machine.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::chip::gated_count;
+use crate::config::StarkConfig;
+use crate::{
+    bus_challenge, permutation_columns, BusArgument, Chip, InteractionType, Machine, SignConvention,
+};
+use p3_field::{AbstractField, ExtensionField, Field};
+use p3_matrix::dense::RowMajorMatrix;
+use p3_matrix::{Matrix, MatrixRowSlices};
+use serde::{Deserialize, Serialize};
+use valida_util::batch_multiplicative_inverse_allowing_zero;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InteractionLogEntry<F> {
+    pub row: usize,
+    /// Index of the interaction in `InteractionAir::all_interactions`.
+    pub interaction: usize,
+    pub bus: BusArgument,
+    pub interaction_type: InteractionType,
+    /// The payload, with rotated fields already read from their rows.
+    pub payload: Vec<F>,
+    /// The count, including the chip's active selector.
+    pub count: F,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InteractionLog<F> {
+    pub height: usize,
+    /// The permutation trace column of each interaction, or `None` if its payload is constant.
+    pub columns: Vec<Option<usize>>,
+    /// One entry per interaction per row, in row order.
+    pub entries: Vec<InteractionLogEntry<F>>,
+    /// The chip's `InteractionAir::sign_convention`.
+    pub sign_convention: SignConvention,
+    /// The machine's `Machine::BUS_CHALLENGE_ROOTS`, which the bus challenges are drawn with.
+    pub bus_challenge_roots: usize,
+}
+
+/// Record the interactions of a chip over its main trace.
+pub fn record_interaction_log<M, SC>(
+    machine: &M,
+    chip: &dyn Chip<M, SC>,
+    main: &RowMajorMatrix<SC::Val>,
+) -> InteractionLog<SC::Val>
+where
+    M: Machine<SC::Val>,
+    SC: StarkConfig,
+{
+    let interactions = chip.all_interactions(machine);
+    let preprocessed = chip.preprocessed_trace();
+    let height = main.height();
+
+    let columns = permutation_columns::<M, SC, _>(machine, chip);
+
+    let mut entries = Vec::with_capacity(height * interactions.len());
+    for n in 0..height {
+        let n_next = (n + 1) % height;
+        let main_rows = [main.row_slice(n), main.row_slice(n_next)];
+        let preprocessed_rows = match &preprocessed {
+            Some(preprocessed) => [preprocessed.row_slice(n), preprocessed.row_slice(n_next)],
+            None => [&[][..], &[][..]],
+        };
+        for (m, (interaction, interaction_type)) in interactions.iter().enumerate() {
+            let payload = interaction
+                .fields
+                .iter()
+                .enumerate()
+                .map(|(i, field)| {
+                    let rotation = interaction.field_rotation(i);
+                    field
+                        .apply::<SC::Val, SC::Val>(preprocessed_rows[rotation], main_rows[rotation])
+                })
+                .collect();
+            let count =
+                interaction.count_at::<SC::Val, SC::Val>(preprocessed_rows[0], main_rows[0]);
+            entries.push(InteractionLogEntry {
+                row: n,
+                interaction: m,
+                bus: interaction.argument_index,
+                interaction_type: interaction_type.clone(),
+                payload,
+                count: gated_count(chip.active_selector(), count, main_rows[0]),
+            });
+        }
+    }
+
+    InteractionLog {
+        height,
+        columns,
+        entries,
+        sign_convention: chip.sign_convention(),
+        bus_challenge_roots: M::BUS_CHALLENGE_ROOTS,
+    }
+}
+
+/// Regenerate the permutation trace of a recorded chip for the given permutation challenges.
+pub fn replay_permutation_trace<F, EF>(
+    log: &InteractionLog<F>,
+    random_elements: &[EF],
+) -> RowMajorMatrix<EF>
+where
+    F: Field,
+    EF: ExtensionField<F>,
+{
+    let perm_width = log.columns.iter().flatten().count() + 1;
+    let denominator = |entry: &InteractionLogEntry<F>| {
+        let mut rlc = bus_challenge(random_elements, entry.bus, log.bus_challenge_roots);
+        for (&f, beta) in entry.payload.iter().zip(random_elements[2].powers()) {
+            rlc += beta * f;
+        }
+        rlc
+    };
+
+    let mut perm_values = vec![EF::zero(); log.height * perm_width];
+    for entry in &log.entries {
+        if let Some(c) = log.columns[entry.interaction] {
+            perm_values[entry.row * perm_width + c] = denominator(entry);
+        }
+    }
+    let mut perm_values = batch_multiplicative_inverse_allowing_zero(perm_values);
+
+    let mut phi = EF::zero();
+    let mut row = 0;
+    for entry in &log.entries {
+        if entry.row != row {
+            perm_values[row * perm_width + perm_width - 1] = phi;
+            row = entry.row;
+        }
+        let q = match log.columns[entry.interaction] {
+            Some(c) => perm_values[entry.row * perm_width + c],
+            None => denominator(entry).try_inverse().unwrap_or(EF::zero()),
+        };
+        if log.sign_convention.adds(&entry.interaction_type) {
+            phi += q * entry.count;
+        } else {
+            phi -= q * entry.count;
+        }
+    }
+    if log.height > 0 {
+        perm_values[row * perm_width + perm_width - 1] = phi;
+    }
+
+    RowMajorMatrix::new(perm_values, perm_width)
+}
@@ -3,31 +3,115 @@
 extern crate alloc;
 extern crate self as valida_machine;
 
+/// Run the closure `$f` in a debug span named `$name` with the `tracing` feature, or just run it
+/// without.
+macro_rules! in_debug_span {
+    ($name:literal, $f:expr) => {{
+        #[cfg(feature = "tracing")]
+        let result = tracing::debug_span!($name).in_scope($f);
+        #[cfg(not(feature = "tracing"))]
+        let result = ($f)();
+        result
+    }};
+}
+
 pub mod __internal;
 mod advice;
+mod arity;
+mod balance;
+mod blinding;
+mod bundle;
 mod check_constraints;
 mod chip;
+mod composite;
 mod config;
+mod constraint_diff;
 mod core;
+#[cfg(feature = "cost-model")]
+mod cost;
+mod count;
 mod debug_builder;
+mod degree;
+mod dependency;
+mod domain;
 mod error;
+mod fingerprint;
 mod folding_builder;
+mod interaction_log;
+mod lookup_field;
+mod lookup_graph;
 mod machine;
+#[cfg(feature = "mock-pcs")]
+mod mock_pcs;
+mod packed;
+mod payload_hash;
+mod permutation_cost;
+mod permutation_air;
+mod power_table;
 mod program;
 mod proof;
 mod quotient;
+#[cfg(feature = "row-contributions")]
+mod row_contribution;
+mod running_sum;
+mod segment;
+mod soundness;
+mod streaming;
 mod symbolic;
+mod table;
+#[cfg(feature = "test-util")]
+pub mod test_util;
+#[cfg(feature = "trace-cache")]
+mod trace_cache;
+mod transcript;
 mod verify;
+mod wide_trace;
+mod zero_policy;
 
 pub use advice::*;
+pub use arity::*;
+pub use balance::*;
+pub use blinding::*;
+pub use bundle::*;
 pub use chip::*;
+pub use composite::*;
 pub use config::*;
+pub use constraint_diff::*;
 pub use core::*;
+#[cfg(feature = "cost-model")]
+pub use cost::*;
+pub use count::*;
+pub use degree::*;
+pub use dependency::*;
+pub use domain::*;
 pub use error::*;
+pub use fingerprint::*;
+pub use interaction_log::*;
+pub use lookup_field::*;
+pub use lookup_graph::*;
 pub use machine::*;
+#[cfg(feature = "mock-pcs")]
+pub use mock_pcs::*;
+pub use packed::*;
+pub use payload_hash::*;
+pub use permutation_cost::*;
+pub use permutation_air::*;
+pub use power_table::*;
 pub use program::*;
 pub use proof::*;
+#[cfg(feature = "row-contributions")]
+pub use row_contribution::*;
+pub use running_sum::*;
+pub use segment::*;
+pub use soundness::*;
+pub use streaming::*;
+pub use table::*;
+#[cfg(feature = "trace-cache")]
+pub use trace_cache::*;
+pub use transcript::*;
 pub use verify::*;
+pub use wide_trace::*;
+pub use zero_policy::*;
 
 pub const OPERAND_ELEMENTS: usize = 5;
 pub const INSTRUCTION_ELEMENTS: usize = OPERAND_ELEMENTS + 1;
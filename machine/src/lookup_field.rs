@@ -0,0 +1,225 @@
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::chip::{
+    beta_powers, gated_count, generate_rlc_elements, reciprocal_layout, reduce_row, Reciprocal,
+};
+use crate::config::StarkConfig;
+use crate::{Chip, Interaction, InteractionType, Machine, PermutationLayout, SignConvention};
+use p3_field::{AbstractExtensionField, AbstractField, Field};
+use p3_matrix::dense::RowMajorMatrix;
+use p3_matrix::{Matrix, MatrixRowSlices};
+use valida_util::batch_multiplicative_inverse_allowing_zero;
+
+/// The field a chip's permutation argument is computed over, see `Chip::lookup_field`.
+///
+/// Over `Base`, the challenges must lie in the base field, and a cheating prover balances a bus
+/// with probability on the order of `N d / |F|` rather than `N d / |EF|`, see `soundness_error`.
+/// For BabyBear this is far too weak for buses a prover controls, so `Base` is only suitable for
+/// chips whose interactions it cannot influence, such as fixed preprocessed tables, and only if
+/// every chip on their buses agrees with the base field challenges. A `Base` chip cannot bundle
+/// its reciprocals, so it must not declare an `InteractionAir::reciprocal_bundle_degree` nor be
+/// part of a machine with a `LookupBackend::LogUp` backend.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum LookupField {
+    Base,
+    #[default]
+    Extension,
+}
+
+/// The field a machine samples its permutation challenges from, see
+/// `Machine::derive_permutation_challenges`: `LookupField::Base` if any of its chips computes its
+/// permutation trace over the base field, since every chip shares the challenges.
+pub fn permutation_challenge_field<'a, M, SC>(
+    chips: impl IntoIterator<Item = &'a dyn Chip<M, SC>>,
+) -> LookupField
+where
+    M: Machine<SC::Val> + 'a,
+    SC: StarkConfig + 'a,
+{
+    if chips
+        .into_iter()
+        .any(|chip| chip.lookup_field() == LookupField::Base)
+    {
+        LookupField::Base
+    } else {
+        LookupField::Extension
+    }
+}
+
+/// Generate the permutation trace of a chip over the base field, for `LookupField::Base`. The
+/// challenges must be embedded base field elements. Every reciprocal and the running sum are
+/// computed in the base field and embedded into the extension field, so the trace satisfies the
+/// usual `eval_permutation_constraints`.
+pub fn generate_base_permutation_trace<M, SC>(
+    machine: &M,
+    chip: &dyn Chip<M, SC>,
+    main: &RowMajorMatrix<SC::Val>,
+    random_elements: &[SC::Challenge],
+) -> RowMajorMatrix<SC::Challenge>
+where
+    M: Machine<SC::Val>,
+    SC: StarkConfig,
+{
+    let (alphas_local, alphas_global) =
+        generate_rlc_elements::<M, SC, _>(machine, chip, random_elements);
+    let alphas_local = alphas_local
+        .iter()
+        .map(to_base::<SC::Val, _>)
+        .collect::<Vec<_>>();
+    let alphas_global = alphas_global
+        .iter()
+        .map(to_base::<SC::Val, _>)
+        .collect::<Vec<_>>();
+    let beta = to_base::<SC::Val, _>(&random_elements[2]);
+
+    let perm = base_permutation_trace(
+        &chip.all_interactions(machine),
+        chip.preprocessed_trace().as_ref(),
+        main,
+        &chip.permutation_layout(),
+        (&alphas_local, &alphas_global),
+        beta,
+        chip.active_selector(),
+        chip.sign_convention(),
+    );
+    let width = perm.width();
+    RowMajorMatrix::new(
+        perm.values
+            .into_iter()
+            .map(SC::Challenge::from_base)
+            .collect(),
+        width,
+    )
+}
+
+/// The base field element a challenge embeds. Panics if it is not in the base field.
+pub(crate) fn to_base<F: Field, EF: AbstractExtensionField<F>>(x: &EF) -> F {
+    let coordinates = x.as_base_slice();
+    assert!(
+        coordinates[1..].iter().all(|c| c.is_zero()),
+        "a base field lookup needs base field challenges"
+    );
+    coordinates[0]
+}
+
+fn base_permutation_trace<F: Field>(
+    interactions: &[(Interaction<F>, InteractionType)],
+    preprocessed: Option<&RowMajorMatrix<F>>,
+    main: &RowMajorMatrix<F>,
+    layout: &PermutationLayout,
+    (alphas_local, alphas_global): (&[F], &[F]),
+    beta: F,
+    active_selector: Option<usize>,
+    sign_convention: SignConvention,
+) -> RowMajorMatrix<F> {
+    let preprocessed_width = preprocessed.map_or(0, |p| p.width());
+    let betas = beta_powers(beta, interactions);
+    let reciprocals = reciprocal_layout(
+        interactions,
+        preprocessed_width,
+        main.width(),
+        layout,
+        alphas_local,
+        alphas_global,
+        &betas,
+    );
+    let perm_width = reciprocals
+        .iter()
+        .filter(|r| matches!(r, Reciprocal::Column(_)))
+        .count()
+        + 1;
+
+    let height = main.height();
+    let mut perm_values = vec![F::zero(); height * perm_width];
+    for n in 0..height {
+        let n_next = (n + 1) % height;
+        let main_rows = [main.row_slice(n), main.row_slice(n_next)];
+        let preprocessed_rows = match preprocessed {
+            Some(preprocessed) => [preprocessed.row_slice(n), preprocessed.row_slice(n_next)],
+            None => [&[][..], &[][..]],
+        };
+        for ((interaction, _), reciprocal) in interactions.iter().zip(&reciprocals) {
+            let Reciprocal::Column(c) = *reciprocal else {
+                continue;
+            };
+            let alpha = if interaction.is_local() {
+                alphas_local[interaction.argument_index()]
+            } else {
+                alphas_global[interaction.argument_index()]
+            };
+            perm_values[n * perm_width + c] =
+                reduce_row(&main_rows, &preprocessed_rows, interaction, alpha, &betas);
+        }
+    }
+    let mut perm_values = batch_multiplicative_inverse_allowing_zero(perm_values);
+
+    let mut phi = F::zero();
+    for n in 0..height {
+        let main_row = main.row_slice(n);
+        let preprocessed_row = match preprocessed {
+            Some(preprocessed) => preprocessed.row_slice(n),
+            None => &[],
+        };
+        let perm_row = &mut perm_values[n * perm_width..(n + 1) * perm_width];
+        for ((interaction, interaction_type), reciprocal) in interactions.iter().zip(&reciprocals) {
+            let mult = interaction.count_at::<F, F>(preprocessed_row, main_row);
+            let mult = gated_count(active_selector, mult, main_row);
+            let q = reciprocal.value(perm_row);
+            if sign_convention.adds(interaction_type) {
+                phi += q * mult;
+            } else {
+                phi -= q * mult;
+            }
+        }
+        *perm_row.last_mut().unwrap() = phi;
+    }
+    RowMajorMatrix::new(perm_values, perm_width)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BusArgument, CountBound};
+    use p3_air::VirtualPairCol;
+
+    type F = p3_baby_bear::BabyBear;
+
+    #[test]
+    fn base_field_lookup_balances() {
+        let interaction = || Interaction::<F> {
+            fields: vec![VirtualPairCol::single_main(0)],
+            count: VirtualPairCol::one(),
+            argument_index: BusArgument::Local(0),
+            rotations: vec![],
+            count_bound: CountBound::Unbounded,
+            is_real: None,
+            limb_order: None,
+        };
+        let interactions = vec![
+            (interaction(), InteractionType::LocalSend),
+            (interaction(), InteractionType::LocalReceive),
+        ];
+        let main = RowMajorMatrix::new([1, 2, 3, 4].map(F::from_canonical_u32).to_vec(), 1);
+        let perm = base_permutation_trace(
+            &interactions,
+            None,
+            &main,
+            &PermutationLayout::Blocked,
+            (&[F::from_canonical_u32(7)], &[]),
+            F::from_canonical_u32(3),
+            None,
+            SignConvention::SendsPositive,
+        );
+
+        assert_eq!(perm.width(), 3);
+        for n in 0..4 {
+            let row = perm.row_slice(n);
+            assert_eq!(
+                row[0] * (F::from_canonical_u32(7) + main.row_slice(n)[0]),
+                F::one()
+            );
+            assert_eq!(row[2], F::zero());
+        }
+    }
+}
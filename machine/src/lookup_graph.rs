@@ -0,0 +1,343 @@
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::{Display, Formatter, Write};
+
+use crate::arity::{bus_participants, chip_interactions};
+use crate::balance::bus_sums;
+use crate::config::StarkConfig;
+use crate::{BusArgument, BusName, Chip, InteractionType, Machine};
+use p3_field::Field;
+use p3_matrix::dense::RowMajorMatrix;
+
+/// One interaction of a chip on a bus.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BusParticipant {
+    /// Index of the chip in the chip list.
+    pub chip: usize,
+    pub interaction_type: InteractionType,
+    /// The number of payload fields.
+    pub arity: usize,
+}
+
+/// Every interaction on one bus, and whether the bus balances.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BusReport {
+    pub bus: BusArgument,
+    /// The chip a local bus is internal to, or `None` for a global bus.
+    pub owner: Option<usize>,
+    pub participants: Vec<BusParticipant>,
+    /// Whether the participants disagree on the payload arity.
+    pub arity_mismatch: bool,
+    pub balanced: bool,
+}
+
+/// The lookup graph of a machine for one execution, see `lookup_graph_report`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LookupGraphReport {
+    /// Global buses first, then the local buses of each chip, in bus order.
+    pub buses: Vec<BusReport>,
+}
+
+impl LookupGraphReport {
+    pub fn is_balanced(&self) -> bool {
+        self.buses.iter().all(|bus| bus.balanced)
+    }
+
+    /// The buses which do not balance.
+    pub fn unbalanced(&self) -> impl Iterator<Item = &BusReport> {
+        self.buses.iter().filter(|bus| !bus.balanced)
+    }
+}
+
+/// List every bus of a machine with its participating chips, their roles and payload arities,
+/// and whether it balances on the given main traces. Balance is computed as in
+/// `assert_machine_balanced`, per bus.
+pub fn lookup_graph_report<M, SC>(
+    machine: &M,
+    chips: &[&dyn Chip<M, SC>],
+    mains: &[RowMajorMatrix<SC::Val>],
+    random_elements: &[SC::Challenge],
+) -> LookupGraphReport
+where
+    M: Machine<SC::Val>,
+    SC: StarkConfig,
+{
+    let participants = bus_participants(&chip_interactions(machine, chips));
+
+    let sums = bus_sums(machine, chips, mains, random_elements);
+    let buses = participants
+        .into_iter()
+        .map(|((owner, bus), participants)| {
+            let arity_mismatch = participants
+                .windows(2)
+                .any(|pair| pair[0].arity != pair[1].arity);
+            let balanced = sums.get(&(owner, bus)).is_none_or(|sum| sum.is_zero());
+            BusReport {
+                bus,
+                owner,
+                participants,
+                arity_mismatch,
+                balanced,
+            }
+        })
+        .collect();
+    LookupGraphReport { buses }
+}
+
+/// A bus that cannot balance because it has only one side.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BusIssue {
+    /// Interactions send on the bus but none receive.
+    SendOnly {
+        bus: BusArgument,
+        /// The chip a local bus is internal to, or `None` for a global bus.
+        owner: Option<usize>,
+    },
+    /// Interactions receive on the bus but none send.
+    ReceiveOnly {
+        bus: BusArgument,
+        owner: Option<usize>,
+    },
+}
+
+/// Check that every bus of a machine has both senders and receivers. Global buses are checked
+/// across all chips, and local buses separately within each chip, since a local send of one chip
+/// cannot be received by another. A one-sided bus guarantees a nonzero cumulative sum whenever it
+/// carries any nonzero count.
+pub fn validate_bus_connectivity<M, SC>(
+    machine: &M,
+    chips: &[&dyn Chip<M, SC>],
+) -> Result<(), Vec<BusIssue>>
+where
+    M: Machine<SC::Val>,
+    SC: StarkConfig,
+{
+    let issues = connectivity_issues(&bus_participants(&chip_interactions(machine, chips)));
+    if issues.is_empty() {
+        Ok(())
+    } else {
+        Err(issues)
+    }
+}
+
+fn connectivity_issues(
+    participants: &BTreeMap<(Option<usize>, BusArgument), Vec<BusParticipant>>,
+) -> Vec<BusIssue> {
+    participants
+        .iter()
+        .filter_map(|(&(owner, bus), participants)| {
+            let sends = participants.iter().any(|p| {
+                matches!(
+                    p.interaction_type,
+                    InteractionType::LocalSend | InteractionType::GlobalSend
+                )
+            });
+            let receives = participants.iter().any(|p| {
+                matches!(
+                    p.interaction_type,
+                    InteractionType::LocalReceive | InteractionType::GlobalReceive
+                )
+            });
+            match (sends, receives) {
+                (true, false) => Some(BusIssue::SendOnly { bus, owner }),
+                (false, true) => Some(BusIssue::ReceiveOnly { bus, owner }),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+impl Display for LookupGraphReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        for bus in &self.buses {
+            match bus.owner {
+                Some(chip) => write!(f, "{:?} of chip {}", bus.bus, chip)?,
+                None => write!(f, "{:?}", bus.bus)?,
+            }
+            let status = if bus.balanced {
+                "balanced"
+            } else {
+                "UNBALANCED"
+            };
+            write!(f, ": {}", status)?;
+            if bus.arity_mismatch {
+                write!(f, ", arity mismatch")?;
+            }
+            writeln!(f)?;
+            for participant in &bus.participants {
+                writeln!(
+                    f,
+                    "  chip {} {:?} arity {}",
+                    participant.chip, participant.interaction_type, participant.arity
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Render the buses of a machine as a Graphviz digraph, e.g. for `dot -Tpng`. Chips and buses are
+/// nodes; a send is an edge from the chip to the bus and a receive an edge from the bus to the
+/// chip, labelled with its scope and payload arity. A bus with edges in only one direction has a
+/// sender but no receiver or vice versa.
+pub fn interaction_graph_dot<M, SC>(machine: &M, chips: &[&dyn Chip<M, SC>]) -> String
+where
+    M: Machine<SC::Val>,
+    SC: StarkConfig,
+{
+    let participants = bus_participants(&chip_interactions(machine, chips));
+    dot_graph(chips.len(), &participants, |bus| machine.bus_label(bus))
+}
+
+fn dot_graph<'a>(
+    num_chips: usize,
+    participants: &BTreeMap<(Option<usize>, BusArgument), Vec<BusParticipant>>,
+    bus_label: impl Fn(BusArgument) -> Option<&'a str>,
+) -> String {
+    let mut dot = String::from("digraph interactions {\n");
+    for chip in 0..num_chips {
+        writeln!(dot, "  chip{} [shape=box, label=\"chip {}\"];", chip, chip).unwrap();
+    }
+    for (&(owner, bus), participants) in participants {
+        let node = match bus {
+            BusArgument::Local(i) => format!("chip{}_local{}", owner.unwrap(), i),
+            BusArgument::Global(i) => format!("global{}", i),
+        };
+        let name = BusName {
+            bus,
+            label: bus_label(bus),
+        };
+        let name = format!("{}", name).replace('"', "\\\"");
+        writeln!(dot, "  {} [shape=ellipse, label=\"{}\"];", node, name).unwrap();
+        for participant in participants {
+            let (scope, direction) = match participant.interaction_type {
+                InteractionType::LocalSend => ("local", "send"),
+                InteractionType::LocalReceive => ("local", "receive"),
+                InteractionType::GlobalSend => ("global", "send"),
+                InteractionType::GlobalReceive => ("global", "receive"),
+            };
+            let chip = format!("chip{}", participant.chip);
+            let (from, to) = if direction == "send" {
+                (&chip, &node)
+            } else {
+                (&node, &chip)
+            };
+            writeln!(
+                dot,
+                "  {} -> {} [label=\"{} {}, {} fields\"];",
+                from, to, scope, direction, participant.arity
+            )
+            .unwrap();
+        }
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Interaction;
+    use alloc::vec;
+    use p3_air::VirtualPairCol;
+
+    type F = p3_baby_bear::BabyBear;
+
+    #[test]
+    fn two_chip_graph() {
+        let interaction = |arity, bus| {
+            let fields = (0..arity).map(VirtualPairCol::single_main).collect();
+            Interaction::<F>::with_multiplicity_col(fields, 0, bus)
+        };
+        // Chip 0 sends on the memory bus and has an internal local bus; chip 1 receives.
+        let interactions = vec![
+            vec![
+                (
+                    interaction(2, BusArgument::Global(2)),
+                    InteractionType::GlobalSend,
+                ),
+                (
+                    interaction(1, BusArgument::Local(0)),
+                    InteractionType::LocalSend,
+                ),
+                (
+                    interaction(1, BusArgument::Local(0)),
+                    InteractionType::LocalReceive,
+                ),
+            ],
+            vec![(
+                interaction(2, BusArgument::Global(2)),
+                InteractionType::GlobalReceive,
+            )],
+        ];
+        let participants = bus_participants(&interactions);
+        let dot = dot_graph(2, &participants, |bus| {
+            (bus == BusArgument::Global(2)).then_some("memory")
+        });
+
+        assert!(dot.starts_with("digraph interactions {\n"));
+        assert!(dot.ends_with("}\n"));
+        let lines = dot.lines().map(str::trim).collect::<Vec<_>>();
+        for expected in [
+            "global2 [shape=ellipse, label=\"memory bus #2\"];",
+            "chip0 -> global2 [label=\"global send, 2 fields\"];",
+            "global2 -> chip1 [label=\"global receive, 2 fields\"];",
+            "chip0 -> chip0_local0 [label=\"local send, 1 fields\"];",
+            "chip0_local0 -> chip0 [label=\"local receive, 1 fields\"];",
+        ] {
+            assert!(lines.contains(&expected), "missing {}", expected);
+        }
+    }
+
+    #[test]
+    fn one_sided_buses() {
+        let interaction = |bus| Interaction::<F>::with_multiplicity_col(vec![], 0, bus);
+        let interactions = vec![
+            vec![
+                // A global send nobody receives.
+                (
+                    interaction(BusArgument::Global(0)),
+                    InteractionType::GlobalSend,
+                ),
+                // A local bus balanced within the chip.
+                (
+                    interaction(BusArgument::Local(0)),
+                    InteractionType::LocalSend,
+                ),
+                (
+                    interaction(BusArgument::Local(0)),
+                    InteractionType::LocalReceive,
+                ),
+                // Chip 1 has its own local bus 1, so neither side is matched.
+                (
+                    interaction(BusArgument::Local(1)),
+                    InteractionType::LocalSend,
+                ),
+            ],
+            vec![(
+                interaction(BusArgument::Local(1)),
+                InteractionType::LocalReceive,
+            )],
+        ];
+        let issues = connectivity_issues(&bus_participants(&interactions));
+        assert_eq!(
+            issues,
+            vec![
+                BusIssue::SendOnly {
+                    bus: BusArgument::Global(0),
+                    owner: None,
+                },
+                BusIssue::SendOnly {
+                    bus: BusArgument::Local(1),
+                    owner: Some(0),
+                },
+                BusIssue::ReceiveOnly {
+                    bus: BusArgument::Local(1),
+                    owner: Some(1),
+                },
+            ]
+        );
+    }
+}
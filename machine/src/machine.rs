@@ -1,8 +1,20 @@
+use crate::chip::generate_all_permutation_traces;
 use crate::config::StarkConfig;
+use crate::permutation_cost::permutation_cost_report;
 use crate::program::ProgramROM;
 use crate::proof::MachineProof;
-use crate::AdviceProvider;
-use p3_field::Field;
+use crate::{
+    AdviceProvider, BusArgument, Chip, LimbOrder, LookupBackend, LookupField,
+    PermutationCostReport, Transcript,
+};
+use alloc::vec::Vec;
+use core::fmt::{Display, Formatter};
+#[cfg(feature = "trace-cache")]
+use p3_field::PrimeField32;
+use p3_field::{AbstractExtensionField, Field};
+use p3_matrix::dense::RowMajorMatrix;
+#[cfg(feature = "trace-cache")]
+use std::path::Path;
 
 #[derive(PartialEq, Eq)]
 pub enum StoppingFlag {
@@ -11,6 +23,13 @@ pub enum StoppingFlag {
 }
 
 pub trait Machine<F: Field>: Sync {
+    /// The number of independent roots the bus challenges of each scope are powers of, see
+    /// `bus_challenge`. With one, bus `i` takes `alpha^{i + 1}` from a single power chain. More
+    /// roots spread the buses of a scope over several chains, e.g. for a larger security margin,
+    /// at the cost of sampling `BUS_CHALLENGE_ROOTS - 1` more permutation challenges per scope.
+    /// Must be at least one; the extra roots take the slots of `reserved_permutation_challenges`.
+    const BUS_CHALLENGE_ROOTS: usize = 1;
+
     fn run<Adv>(&mut self, program: &ProgramROM<i32>, advice: &mut Adv)
     where
         Adv: AdviceProvider;
@@ -26,4 +45,149 @@ pub trait Machine<F: Field>: Sync {
     fn verify<SC>(&self, config: &SC, proof: &MachineProof<SC>) -> Result<(), ()>
     where
         SC: StarkConfig<Val = F>;
+
+    /// A human readable name for a bus, such as `"memory"`, used only in diagnostics. The bus
+    /// index remains the sole identifier of a bus everywhere else.
+    fn bus_label(&self, _bus: BusArgument) -> Option<&str> {
+        None
+    }
+
+    /// The limb order in which chips of this machine lay out multi-limb values among the fields
+    /// of their interactions, see `Interaction::send_limbs`.
+    fn limb_order(&self) -> LimbOrder {
+        LimbOrder::BigEndian
+    }
+
+    /// The public values of the current execution, such as the initial program counter or a
+    /// claimed output, which the prover and verifier agree on. Chips read them through the machine
+    /// when building their interactions, e.g. as `VirtualPairCol::constant`, so that fields and
+    /// counts may depend on public data in both the permutation trace and its constraints.
+    fn public_values(&self) -> &[F] {
+        &[]
+    }
+
+    /// The state this machine's chip traces are generated from, such as the executed program and
+    /// its inputs, as bytes keying cached traces, see `generate_or_load_trace`. Machines returning
+    /// `None` never use the cache.
+    #[cfg(feature = "trace-cache")]
+    fn trace_cache_state(&self) -> Option<Vec<u8>> {
+        None
+    }
+
+    /// Load the main trace of `chip` from the cache at `path`, or generate and cache it, see
+    /// `generate_or_load_trace`.
+    #[cfg(feature = "trace-cache")]
+    fn generate_or_load_trace<SC, C>(&self, chip: &C, path: &Path) -> RowMajorMatrix<F>
+    where
+        Self: Sized,
+        SC: StarkConfig<Val = F>,
+        C: Chip<Self, SC> + ?Sized,
+        F: PrimeField32,
+    {
+        crate::trace_cache::generate_or_load_trace::<Self, SC, C>(self, chip, path)
+    }
+
+    /// Observe the `public_values` and the main trace commitments of all chips in `transcript`,
+    /// then sample `num` permutation challenges from it. The challenges of
+    /// `generate_permutation_trace` must come from here, after the main traces are committed, so
+    /// that a prover cannot choose its main traces knowing them; the verifier derives them the same
+    /// way from the proof's commitments. The public values are observed since interactions may
+    /// read them, and a prover must not be able to choose them knowing the challenges either.
+    ///
+    /// Over `LookupField::Base`, the field of machines with a chip of that `Chip::lookup_field`,
+    /// see `permutation_challenge_field`, the challenges are base field elements embedded in `EF`.
+    fn derive_permutation_challenges<EF, Commitment, T>(
+        &self,
+        transcript: &mut T,
+        main_commitments: &[Commitment],
+        num: usize,
+        field: LookupField,
+    ) -> Vec<EF>
+    where
+        EF: AbstractExtensionField<F>,
+        Commitment: Clone,
+        T: Transcript<F, Commitment>,
+    {
+        transcript.observe_public_values(self.public_values());
+        for commitment in main_commitments {
+            transcript.observe_commitment(commitment.clone());
+        }
+        match field {
+            LookupField::Base => (0..num)
+                .map(|_| EF::from_base(transcript.sample_base_element()))
+                .collect(),
+            LookupField::Extension => (0..num).map(|_| transcript.sample_ext_element()).collect(),
+        }
+    }
+
+    /// The permutation argument of chips not declaring an
+    /// `InteractionAir::reciprocal_bundle_degree`. The prover and verifier must agree on it, and
+    /// the cumulative sums are the same under either.
+    fn lookup_backend(&self) -> LookupBackend {
+        LookupBackend::Reciprocals
+    }
+
+    /// Combine the cumulative sums of this machine's chips, in chip order, into the total the
+    /// verifier requires to be zero, see `verify_aggregate_cumulative_sum`.
+    fn aggregate_cumulative_sum<EF: Field>(&self, per_chip: &[EF]) -> EF {
+        per_chip.iter().copied().sum()
+    }
+
+    /// Generate the permutation traces of this machine's chips from their main traces, both in chip
+    /// order, each with its cumulative sum, see `generate_permutation_trace`. All chips share the
+    /// `random_elements`, and with the `parallel` feature they are processed in parallel. In
+    /// debug builds, the cumulative sums must cancel out under `aggregate_cumulative_sum`.
+    fn generate_all_permutation_traces<SC>(
+        &self,
+        chips: &[&dyn Chip<Self, SC>],
+        mains: &[RowMajorMatrix<F>],
+        random_elements: &[SC::Challenge],
+    ) -> Vec<(RowMajorMatrix<SC::Challenge>, SC::Challenge)>
+    where
+        Self: Sized,
+        SC: StarkConfig<Val = F>,
+    {
+        generate_all_permutation_traces(self, chips, mains, random_elements)
+    }
+
+    /// The largest `Chip::max_constraint_degree` over the chips of this machine, which bounds the
+    /// quotient degree of every chip.
+    fn max_constraint_degree<SC>(&self, chips: &[&dyn Chip<Self, SC>]) -> usize
+    where
+        Self: Sized,
+        SC: StarkConfig<Val = F>,
+    {
+        chips
+            .iter()
+            .map(|chip| chip.max_constraint_degree(self))
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// The main and permutation trace widths of each of this machine's chips and the number of
+    /// interactions they have on each bus, e.g. to find the chip dominating the proof size before
+    /// generating any trace.
+    fn permutation_cost_report<SC>(&self, chips: &[&dyn Chip<Self, SC>]) -> PermutationCostReport
+    where
+        Self: Sized,
+        SC: StarkConfig<Val = F>,
+    {
+        permutation_cost_report(self, chips)
+    }
+}
+
+/// Displays a bus by its label, e.g. `memory bus #2`, or as `Global(2)` if it has none.
+pub struct BusName<'a> {
+    pub bus: BusArgument,
+    pub label: Option<&'a str>,
+}
+
+impl Display for BusName<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match (self.label, self.bus) {
+            (Some(label), BusArgument::Global(i)) => write!(f, "{} bus #{}", label, i),
+            (Some(label), BusArgument::Local(i)) => write!(f, "{} local bus #{}", label, i),
+            (None, bus) => write!(f, "{:?}", bus),
+        }
+    }
 }
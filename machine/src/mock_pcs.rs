@@ -0,0 +1,335 @@
+//! A mock polynomial commitment scheme for fast correctness testing.
+//!
+//! `MockPcs` "commits" to matrices by storing them, and "opens" them by evaluating the stored
+//! matrices at the requested points directly. A matrix committed with a coset shift `s` holds the
+//! evaluations of its columns over `s H`, as the quotient chunks do, and is opened accordingly.
+//! The opening proof carries the matrices themselves, so the verifier simply re-evaluates them. Nothing here is binding or hiding; it only exists so
+//! that `prove`/`verify` round-trips can exercise the trace, permutation and constraint logic
+//! without paying for FRI.
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+
+use p3_challenger::FieldChallenger;
+use p3_commit::{OpenedValues, Pcs, UnivariatePcs, UnivariatePcsWithLde};
+use p3_dft::TwoAdicSubgroupDft;
+use p3_field::{batch_multiplicative_inverse, AbstractField, ExtensionField, Field, TwoAdicField};
+use p3_matrix::dense::{RowMajorMatrix, RowMajorMatrixView};
+use p3_matrix::{Dimensions, Matrix, MatrixRowSlices};
+
+/// Number of field elements in a mock commitment.
+pub const MOCK_DIGEST_ELEMS: usize = 4;
+
+pub struct MockPcs<Val, Dft> {
+    dft: Dft,
+    log_blowup: usize,
+    _phantom: PhantomData<Val>,
+}
+
+/// The stored matrices of one commitment, together with their low-degree extensions.
+pub struct MockProverData<Val> {
+    matrices: Vec<RowMajorMatrix<Val>>,
+    /// The coset shift of each matrix, whose rows are evaluations over `shift H`.
+    shifts: Vec<Val>,
+    ldes: Vec<RowMajorMatrix<Val>>,
+}
+
+#[derive(Debug)]
+pub enum MockPcsError {
+    /// The matrices in the proof do not hash to the commitment.
+    CommitmentMismatch,
+    /// A claimed opening does not match the evaluation of the stored matrix.
+    OpeningMismatch,
+    /// The shape of the proof does not match the commitments.
+    InvalidShape,
+}
+
+impl<Val, Dft> MockPcs<Val, Dft> {
+    pub fn new(dft: Dft, log_blowup: usize) -> Self {
+        Self {
+            dft,
+            log_blowup,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<Val, Dft> MockPcs<Val, Dft>
+where
+    Val: TwoAdicField,
+    Dft: TwoAdicSubgroupDft<Val>,
+{
+    /// Store matrices of evaluations over the cosets `shifts[i] H`, and extend each to the
+    /// evaluations of the same polynomial over the LDE coset `g H'` of the generator `g`.
+    fn commit_on_cosets(
+        &self,
+        polynomials: Vec<RowMajorMatrix<Val>>,
+        shifts: &[Val],
+    ) -> ([Val; MOCK_DIGEST_ELEMS], MockProverData<Val>) {
+        assert_eq!(polynomials.len(), shifts.len());
+        let commitment = mock_digest(&polynomials, shifts);
+        // The LDE reads its input as evaluations over `H`, that is of `p(shift x)`, so it must
+        // shift by `g / shift` to evaluate `p` over `g H'`.
+        let ldes = polynomials
+            .iter()
+            .zip(shifts)
+            .map(|(mat, &shift)| {
+                self.dft.coset_lde_batch(
+                    mat.clone(),
+                    self.log_blowup,
+                    Val::generator() * shift.inverse(),
+                )
+            })
+            .collect();
+        let data = MockProverData {
+            matrices: polynomials,
+            shifts: shifts.to_vec(),
+            ldes,
+        };
+        (commitment, data)
+    }
+}
+
+impl<Val, Dft> Pcs<Val, RowMajorMatrix<Val>> for MockPcs<Val, Dft>
+where
+    Val: TwoAdicField,
+    Dft: TwoAdicSubgroupDft<Val>,
+{
+    type Commitment = [Val; MOCK_DIGEST_ELEMS];
+    type ProverData = MockProverData<Val>;
+    /// For each round, the `(width, shift, values)` of every committed matrix.
+    type Proof = Vec<Vec<(usize, Val, Vec<Val>)>>;
+    type Error = MockPcsError;
+
+    fn commit_batches(
+        &self,
+        polynomials: Vec<RowMajorMatrix<Val>>,
+    ) -> (Self::Commitment, Self::ProverData) {
+        let shifts = vec![Val::one(); polynomials.len()];
+        self.commit_on_cosets(polynomials, &shifts)
+    }
+}
+
+impl<Val, EF, Dft, Challenger> UnivariatePcs<Val, EF, RowMajorMatrix<Val>, Challenger>
+    for MockPcs<Val, Dft>
+where
+    Val: TwoAdicField,
+    EF: ExtensionField<Val> + TwoAdicField,
+    Dft: TwoAdicSubgroupDft<Val>,
+    Challenger: FieldChallenger<Val>,
+{
+    fn open_multi_batches(
+        &self,
+        prover_data_and_points: &[(&Self::ProverData, &[Vec<EF>])],
+        _challenger: &mut Challenger,
+    ) -> (OpenedValues<EF>, Self::Proof) {
+        let openings = prover_data_and_points
+            .iter()
+            .map(|(data, points)| open_matrices(&data.matrices, &data.shifts, points))
+            .collect();
+        let proof = prover_data_and_points
+            .iter()
+            .map(|(data, _)| {
+                data.matrices
+                    .iter()
+                    .zip(&data.shifts)
+                    .map(|(mat, &shift)| (mat.width(), shift, mat.values.clone()))
+                    .collect()
+            })
+            .collect();
+        (openings, proof)
+    }
+
+    fn verify_multi_batches(
+        &self,
+        commits_and_points: &[(Self::Commitment, &[Vec<EF>])],
+        _dims: &[Vec<Dimensions>],
+        values: OpenedValues<EF>,
+        proof: &Self::Proof,
+        _challenger: &mut Challenger,
+    ) -> Result<(), Self::Error> {
+        if commits_and_points.len() != proof.len() || values.len() != proof.len() {
+            return Err(MockPcsError::InvalidShape);
+        }
+        for (((commit, points), round), claimed) in commits_and_points.iter().zip(proof).zip(values)
+        {
+            let matrices = round
+                .iter()
+                .map(|(width, _, values)| RowMajorMatrix::new(values.clone(), *width))
+                .collect::<Vec<_>>();
+            let shifts = round.iter().map(|&(_, shift, _)| shift).collect::<Vec<_>>();
+            if mock_digest(&matrices, &shifts) != *commit {
+                return Err(MockPcsError::CommitmentMismatch);
+            }
+            if points.len() != matrices.len() {
+                return Err(MockPcsError::InvalidShape);
+            }
+            if open_matrices(&matrices, &shifts, points) != claimed {
+                return Err(MockPcsError::OpeningMismatch);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<Val, EF, Dft, Challenger> UnivariatePcsWithLde<Val, EF, RowMajorMatrix<Val>, Challenger>
+    for MockPcs<Val, Dft>
+where
+    Val: TwoAdicField,
+    EF: ExtensionField<Val> + TwoAdicField,
+    Dft: TwoAdicSubgroupDft<Val>,
+    Challenger: FieldChallenger<Val>,
+{
+    type Lde<'a>
+        = RowMajorMatrixView<'a, Val>
+    where
+        Self: 'a;
+
+    fn coset_shift(&self) -> Val {
+        Val::generator()
+    }
+
+    fn log_blowup(&self) -> usize {
+        self.log_blowup
+    }
+
+    fn get_ldes<'a, 'b>(&'a self, prover_data: &'b Self::ProverData) -> Vec<Self::Lde<'b>>
+    where
+        'a: 'b,
+    {
+        prover_data.ldes.iter().map(|lde| lde.as_view()).collect()
+    }
+
+    fn commit_shifted_batches(
+        &self,
+        polynomials: Vec<RowMajorMatrix<Val>>,
+        coset_shift: &[Val],
+    ) -> (Self::Commitment, Self::ProverData) {
+        self.commit_on_cosets(polynomials, coset_shift)
+    }
+}
+
+/// A cheap, non-cryptographic fingerprint of a batch of matrices and their coset shifts.
+fn mock_digest<Val: Field>(
+    matrices: &[RowMajorMatrix<Val>],
+    shifts: &[Val],
+) -> [Val; MOCK_DIGEST_ELEMS] {
+    let mut digest = [Val::zero(); MOCK_DIGEST_ELEMS];
+    let weight = Val::from_canonical_u32(7);
+    let mut n = 0;
+    for (mat, &shift) in matrices.iter().zip(shifts) {
+        let dims = [mat.width(), mat.height()];
+        for value in dims
+            .into_iter()
+            .map(Val::from_canonical_usize)
+            .chain([shift])
+            .chain(mat.values.iter().copied())
+        {
+            let i = n % MOCK_DIGEST_ELEMS;
+            digest[i] = digest[i] * weight + value;
+            n += 1;
+        }
+    }
+    digest
+}
+
+/// Evaluate each matrix, viewed as the evaluations of its columns over the coset `shift H` of the
+/// canonical subgroup of its height, at each of its points.
+fn open_matrices<Val, EF>(
+    matrices: &[RowMajorMatrix<Val>],
+    shifts: &[Val],
+    points: &[Vec<EF>],
+) -> Vec<Vec<Vec<EF>>>
+where
+    Val: TwoAdicField,
+    EF: ExtensionField<Val> + TwoAdicField,
+{
+    matrices
+        .iter()
+        .zip(shifts)
+        .zip(points)
+        .map(|((mat, &shift), points)| {
+            // `p(z) = q(z / shift)` for the polynomial `q(x) = p(shift x)` interpolating over `H`.
+            let shift_inverse = EF::from_base(shift.inverse());
+            points
+                .iter()
+                .map(|&point| evaluate_at_point(mat, point * shift_inverse))
+                .collect()
+        })
+        .collect()
+}
+
+/// Barycentric evaluation over the subgroup `H` of size `n`:
+/// `p(z) = (z^n - 1) / n * \sum_i g^i y_i / (z - g^i)`.
+fn evaluate_at_point<Val, EF>(mat: &RowMajorMatrix<Val>, point: EF) -> Vec<EF>
+where
+    Val: TwoAdicField,
+    EF: ExtensionField<Val> + TwoAdicField,
+{
+    let height = mat.height();
+    let log_height = p3_util::log2_strict_usize(height);
+    let g = Val::two_adic_generator(log_height);
+    let subgroup = g.powers().take(height).collect::<Vec<_>>();
+
+    // If the point lies in the subgroup, the value is the matching row.
+    if let Some(i) = subgroup.iter().position(|&x| EF::from_base(x) == point) {
+        return mat.row_slice(i).iter().map(|&v| EF::from_base(v)).collect();
+    }
+
+    let denominators = subgroup
+        .iter()
+        .map(|&x| point - EF::from_base(x))
+        .collect::<Vec<_>>();
+    let inv_denominators = batch_multiplicative_inverse(&denominators);
+    let scale = (point.exp_power_of_2(log_height) - EF::one())
+        * EF::from_base(Val::from_canonical_usize(height).inverse());
+
+    let mut result = vec![EF::zero(); mat.width()];
+    for (i, (&x, &inv_denominator)) in subgroup.iter().zip(inv_denominators.iter()).enumerate() {
+        let weight = inv_denominator * x;
+        for (acc, &v) in result.iter_mut().zip(mat.row_slice(i)) {
+            *acc += weight * v;
+        }
+    }
+    result.into_iter().map(|acc| acc * scale).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use p3_baby_bear::BabyBear;
+    use p3_dft::Radix2Bowers;
+    use p3_field::extension::BinomialExtensionField;
+
+    type F = BabyBear;
+    type EF = BinomialExtensionField<F, 4>;
+
+    /// `p(x) = 3 + 5x + 2x^2 + x^3`.
+    fn p<A: AbstractField>(x: A) -> A {
+        [1, 2, 5, 3].into_iter().fold(A::zero(), |acc, c| {
+            acc * x.clone() + A::from_canonical_u32(c)
+        })
+    }
+
+    #[test]
+    fn shifted_matrix_opens_to_its_polynomial() {
+        // A quotient chunk: the evaluations of `p` over `s H`, with `s` as in the quotient
+        // commitment of a chip of quotient degree 2.
+        let shift = F::generator().exp_power_of_2(1);
+        let h = F::two_adic_generator(2);
+        let values = h.powers().take(4).map(|x| p(shift * x)).collect();
+        let pcs = MockPcs::new(Radix2Bowers, 1);
+        let (_, data) = pcs.commit_on_cosets(vec![RowMajorMatrix::new_col(values)], &[shift]);
+
+        let zeta = EF::from_canonical_u32(1234567);
+        let opened = open_matrices(&data.matrices, &data.shifts, &[vec![zeta]]);
+        assert_eq!(opened, vec![vec![vec![p(zeta)]]]);
+
+        // The LDE evaluates `p` over `g H'`, like that of an unshifted matrix.
+        let h_extended = F::two_adic_generator(3);
+        for (i, x) in h_extended.powers().take(8).enumerate() {
+            assert_eq!(data.ldes[0].row_slice(i), &[p(F::generator() * x)]);
+        }
+    }
+}
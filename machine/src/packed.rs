@@ -0,0 +1,212 @@
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::chip::{
+    debug_assert_enough_challenges, expect_independent_challenges, generate_permutation_trace_of,
+    reduce_row, Reciprocal,
+};
+use crate::config::StarkConfig;
+use crate::{Chip, Interaction, InteractionType, Machine};
+use p3_field::{AbstractField, ExtensionField, Field, PackedField};
+use p3_matrix::dense::RowMajorMatrix;
+use p3_matrix::{Matrix, MatrixRowSlices};
+
+/// Like `generate_permutation_trace`, but evaluates interaction fields on `Val::Packing::WIDTH`
+/// rows at a time, see `reduce_rows_packed`. The output is identical.
+pub fn generate_permutation_trace_packed<M, SC>(
+    machine: &M,
+    chip: &dyn Chip<M, SC>,
+    main: &RowMajorMatrix<SC::Val>,
+    random_elements: Vec<SC::Challenge>,
+) -> RowMajorMatrix<SC::Challenge>
+where
+    M: Machine<SC::Val>,
+    SC: StarkConfig,
+{
+    expect_independent_challenges(&random_elements);
+    debug_assert_enough_challenges(machine, chip, &random_elements);
+    if let Some(perm) = chip.generate_permutation_trace(machine, main, &random_elements) {
+        return perm;
+    }
+
+    generate_permutation_trace_of(
+        machine,
+        chip,
+        main,
+        random_elements,
+        &chip.interaction_layout(machine),
+        true,
+        false,
+    )
+}
+
+/// Compute the reciprocal denominators `\alpha_m + \sum_j \beta^j f_{m,j}` of every row, laid out
+/// as the rows of a permutation trace of width `perm_width` with a zero running sum.
+///
+/// The fields of each interaction are evaluated on packed lanes, one row per lane, for the rows up
+/// to the last multiple of the lane count; the remaining rows fall back to `reduce_row`. The
+/// weighting by powers of `\beta` is done in the extension field, lane by lane.
+pub(crate) fn reduce_rows_packed<F, EF>(
+    interactions: &[(Interaction<F>, InteractionType)],
+    reciprocals: &[Reciprocal<EF>],
+    alphas: &[EF],
+    preprocessed: Option<&RowMajorMatrix<F>>,
+    main: &RowMajorMatrix<F>,
+    betas: &[EF],
+    perm_width: usize,
+) -> Vec<EF>
+where
+    F: Field,
+    EF: ExtensionField<F>,
+{
+    let lanes = F::Packing::WIDTH;
+    let height = main.height();
+    let packed_height = height - height % lanes;
+
+    let mut perm_values = vec![EF::zero(); height * perm_width];
+    for n in (0..packed_height).step_by(lanes) {
+        // Rotated fields wrap around to the first row, see `Interaction::rotations`.
+        let main_rows = [
+            pack_rows(main, |lane| n + lane),
+            pack_rows(main, |lane| (n + lane + 1) % height),
+        ];
+        let preprocessed_rows = match preprocessed {
+            Some(preprocessed) => [
+                pack_rows(preprocessed, |lane| n + lane),
+                pack_rows(preprocessed, |lane| (n + lane + 1) % height),
+            ],
+            None => [vec![], vec![]],
+        };
+        for (m, ((interaction, _), reciprocal)) in interactions.iter().zip(reciprocals).enumerate()
+        {
+            let Reciprocal::Column(c) = *reciprocal else {
+                continue;
+            };
+            for lane in 0..lanes {
+                perm_values[(n + lane) * perm_width + c] = alphas[m];
+            }
+            // As in `reduce_row_expr`, every field must get its own power of `\beta`.
+            debug_assert!(betas.len() >= interaction.fields.len());
+            for (i, (field, &beta)) in interaction.fields.iter().zip(betas).enumerate() {
+                let rotation = interaction.field_rotation(i);
+                let value = field.apply::<F::Packing, F::Packing>(
+                    &preprocessed_rows[rotation],
+                    &main_rows[rotation],
+                );
+                for (lane, &x) in value.as_slice().iter().enumerate() {
+                    perm_values[(n + lane) * perm_width + c] += beta * x;
+                }
+            }
+            #[cfg(feature = "cost-model")]
+            crate::cost::record(lanes * interaction.fields.len(), 0);
+        }
+    }
+
+    for n in packed_height..height {
+        let n_next = (n + 1) % height;
+        let main_rows = [main.row_slice(n), main.row_slice(n_next)];
+        let preprocessed_rows = match preprocessed {
+            Some(preprocessed) => [preprocessed.row_slice(n), preprocessed.row_slice(n_next)],
+            None => [&[][..], &[][..]],
+        };
+        for (m, ((interaction, _), reciprocal)) in interactions.iter().zip(reciprocals).enumerate()
+        {
+            let Reciprocal::Column(c) = *reciprocal else {
+                continue;
+            };
+            perm_values[n * perm_width + c] = reduce_row(
+                &main_rows,
+                &preprocessed_rows,
+                interaction,
+                alphas[m],
+                betas,
+            );
+        }
+    }
+    perm_values
+}
+
+/// Pack one row per lane of a matrix, reading row `row(lane)` into lane `lane` of each column.
+fn pack_rows<F: Field>(mat: &RowMajorMatrix<F>, row: impl Fn(usize) -> usize) -> Vec<F::Packing> {
+    let lanes = F::Packing::WIDTH;
+    let mut lane_values = vec![F::zero(); lanes];
+    (0..mat.width())
+        .map(|col| {
+            for (lane, value) in lane_values.iter_mut().enumerate() {
+                *value = mat.row_slice(row(lane))[col];
+            }
+            *F::Packing::from_slice(&lane_values)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BusArgument, CountBound};
+    use p3_air::VirtualPairCol;
+
+    type F = p3_baby_bear::BabyBear;
+    type EF = p3_field::extension::BinomialExtensionField<F, 4>;
+
+    #[test]
+    fn packed_matches_scalar() {
+        // A height which is not a multiple of any lane count, so the scalar remainder is covered.
+        let height = 67;
+        let main = RowMajorMatrix::new(
+            (0..height * 3)
+                .map(|x| F::from_canonical_usize(x * x + 1))
+                .collect(),
+            3,
+        );
+        let interactions = vec![
+            (
+                Interaction::<F> {
+                    fields: vec![
+                        VirtualPairCol::single_main(0),
+                        VirtualPairCol::new_main(vec![(1, F::two()), (2, F::one())], F::one()),
+                    ],
+                    count: VirtualPairCol::one(),
+                    argument_index: BusArgument::Local(0),
+                    rotations: vec![0, 1],
+                    count_bound: CountBound::Unbounded,
+                    is_real: None,
+                    limb_order: None,
+                },
+                InteractionType::LocalSend,
+            ),
+            (
+                Interaction::<F> {
+                    fields: vec![VirtualPairCol::single_main(2)],
+                    count: VirtualPairCol::one(),
+                    argument_index: BusArgument::Global(0),
+                    rotations: vec![],
+                    count_bound: CountBound::Unbounded,
+                    is_real: None,
+                    limb_order: None,
+                },
+                InteractionType::GlobalReceive,
+            ),
+        ];
+        let reciprocals = [Reciprocal::Column(1), Reciprocal::Column(0)];
+        let alphas = [EF::from_canonical_u32(7), EF::from_canonical_u32(11)];
+        let betas = EF::from_canonical_u32(13)
+            .powers()
+            .take(2)
+            .collect::<Vec<_>>();
+
+        let packed =
+            reduce_rows_packed(&interactions, &reciprocals, &alphas, None, &main, &betas, 3);
+        for n in 0..height {
+            let main_rows = [main.row_slice(n), main.row_slice((n + 1) % height)];
+            for (m, (interaction, _)) in interactions.iter().enumerate() {
+                let Reciprocal::Column(c) = reciprocals[m] else {
+                    unreachable!()
+                };
+                let scalar = reduce_row(&main_rows, &[&[], &[]], interaction, alphas[m], &betas);
+                assert_eq!(packed[n * 3 + c], scalar);
+            }
+            assert_eq!(packed[n * 3 + 2], EF::zero());
+        }
+    }
+}
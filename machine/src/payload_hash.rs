@@ -0,0 +1,98 @@
+//! Hashing of wide interaction payloads into a single field element.
+//!
+//! A wide payload (e.g. a 16-field memory record) costs one beta power per field and raises the
+//! degree of the reciprocal constraint if fields are not linear. Instead, a chip can store the
+//! hash of the payload in a main trace column, constrain that column with `eval_payload_hash`,
+//! and send or receive only the hash column via `Interaction::with_hashed_payload`. Both sides of
+//! a bus must use the same hash.
+//!
+//! Soundness requires the hash to be collision resistant over every payload the chips on the bus
+//! can produce: a collision lets a sender balance against a receiver with a different record.
+//! Algebraic hashes such as `RadixPayloadHash` are only injective when the payload is
+//! range-constrained so that it fits in the field.
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ops::Mul;
+
+use crate::Interaction;
+use p3_air::{AirBuilder, PairBuilder, VirtualPairCol};
+use p3_field::{AbstractField, Field};
+use p3_matrix::MatrixRowSlices;
+
+/// A hash compressing an interaction payload into one field element, evaluable both over field
+/// values during trace generation and over symbolic expressions during constraint evaluation.
+pub trait PayloadHash<F: Field> {
+    fn hash<Expr>(&self, payload: &[Expr]) -> Expr
+    where
+        Expr: AbstractField + Mul<F, Output = Expr>;
+}
+
+/// Hashes `(x_0, ..., x_{k-1})` to `\sum_j x_j r^{k-1-j}`, which is injective when each `x_j` is
+/// known to be less than `r` and `r^k` does not exceed the field order.
+pub struct RadixPayloadHash<F> {
+    pub radix: F,
+}
+
+impl<F: Field> PayloadHash<F> for RadixPayloadHash<F> {
+    fn hash<Expr>(&self, payload: &[Expr]) -> Expr
+    where
+        Expr: AbstractField + Mul<F, Output = Expr>,
+    {
+        payload
+            .iter()
+            .cloned()
+            .fold(Expr::zero(), |acc, x| acc * self.radix + x)
+    }
+}
+
+impl<F: Field> Interaction<F> {
+    /// Replace the payload of this interaction by the single main trace column `hash_col`, which
+    /// the chip must constrain to the hash of the original payload using `eval_payload_hash`.
+    pub fn with_hashed_payload(mut self, hash_col: usize) -> Self {
+        self.fields = vec![VirtualPairCol::single_main(hash_col)];
+        self.rotations = vec![];
+        self
+    }
+}
+
+/// Compute the hash of a payload on one row, for filling in the hash column of a main trace.
+pub fn generate_payload_hash<F, H>(
+    hasher: &H,
+    payload: &[VirtualPairCol<F>],
+    preprocessed_row: &[F],
+    main_row: &[F],
+) -> F
+where
+    F: Field,
+    H: PayloadHash<F>,
+{
+    let values = payload
+        .iter()
+        .map(|field| field.apply::<F, F>(preprocessed_row, main_row))
+        .collect::<Vec<_>>();
+    hasher.hash(&values)
+}
+
+/// Constrain main trace column `hash_col` to equal the hash of the payload on every row.
+pub fn eval_payload_hash<AB, H>(
+    builder: &mut AB,
+    hasher: &H,
+    payload: &[VirtualPairCol<AB::F>],
+    hash_col: usize,
+) where
+    AB: PairBuilder,
+    H: PayloadHash<AB::F>,
+{
+    let main = builder.main();
+    let main_local: &[AB::Var] = main.row_slice(0);
+    let preprocessed = builder.preprocessed();
+    let preprocessed_local = preprocessed.row_slice(0);
+
+    let values = payload
+        .iter()
+        .map(|field| field.apply::<AB::Expr, AB::Var>(preprocessed_local, main_local))
+        .collect::<Vec<_>>();
+    let hash = hasher.hash(&values);
+    builder.assert_eq(main_local[hash_col], hash);
+}
@@ -0,0 +1,56 @@
+use core::marker::PhantomData;
+
+use crate::config::StarkConfig;
+use crate::{eval_permutation_constraints, Chip, Machine, ValidaAirBuilder};
+use p3_air::{Air, BaseAir};
+use p3_matrix::dense::RowMajorMatrix;
+
+/// The permutation argument of a chip as a standalone `Air`, whose `eval` emits exactly the
+/// constraints of `eval_permutation_constraints` for the claimed `cumulative_sum`.
+///
+/// The builder must be a `ValidaAirBuilder` over the chip's machine, with `F = SC::Val` and
+/// `EF = SC::Challenge`, and must expose the chip's main and preprocessed traces and its
+/// permutation trace. The width of this `Air` is the chip's main trace width, and its
+/// preprocessed trace is the chip's.
+pub struct PermutationAir<'a, M, SC: StarkConfig, C> {
+    pub chip: &'a C,
+    pub cumulative_sum: SC::Challenge,
+    _phantom: PhantomData<fn() -> M>,
+}
+
+impl<'a, M, SC: StarkConfig, C> PermutationAir<'a, M, SC, C> {
+    pub fn new(chip: &'a C, cumulative_sum: SC::Challenge) -> Self {
+        Self {
+            chip,
+            cumulative_sum,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<'a, M, SC, C> BaseAir<SC::Val> for PermutationAir<'a, M, SC, C>
+where
+    M: Machine<SC::Val>,
+    SC: StarkConfig,
+    C: Chip<M, SC>,
+{
+    fn width(&self) -> usize {
+        self.chip.trace_width()
+    }
+
+    fn preprocessed_trace(&self) -> Option<RowMajorMatrix<SC::Val>> {
+        self.chip.preprocessed_trace()
+    }
+}
+
+impl<'a, M, SC, C, AB> Air<AB> for PermutationAir<'a, M, SC, C>
+where
+    M: Machine<SC::Val>,
+    SC: StarkConfig,
+    C: Chip<M, SC> + Air<AB>,
+    AB: ValidaAirBuilder<Machine = M, F = SC::Val, EF = SC::Challenge>,
+{
+    fn eval(&self, builder: &mut AB) {
+        eval_permutation_constraints::<M, C, SC, AB>(self.chip, builder, self.cumulative_sum);
+    }
+}
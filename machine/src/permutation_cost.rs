@@ -0,0 +1,82 @@
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::fmt::{Display, Formatter};
+
+use crate::arity::chip_interactions;
+use crate::config::StarkConfig;
+use crate::{permutation_trace_width, BusArgument, Chip, Machine};
+
+/// The trace widths of one chip, see `Machine::permutation_cost_report`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ChipPermutationCost {
+    pub main_width: usize,
+    /// The number of extension field columns of the permutation trace, including the running sum,
+    /// see `permutation_trace_width`.
+    pub permutation_width: usize,
+    /// The number of interactions of the chip on each bus.
+    pub interactions_per_bus: BTreeMap<BusArgument, usize>,
+}
+
+/// The committed widths of a machine's chips, in chip order, see
+/// `Machine::permutation_cost_report`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PermutationCostReport {
+    pub chips: Vec<ChipPermutationCost>,
+}
+
+impl PermutationCostReport {
+    /// The extension field columns committed across all permutation traces.
+    pub fn extension_columns(&self) -> usize {
+        self.chips.iter().map(|chip| chip.permutation_width).sum()
+    }
+
+    /// The index of the chip with the widest permutation trace.
+    pub fn widest_chip(&self) -> Option<usize> {
+        (0..self.chips.len()).max_by_key(|&i| self.chips[i].permutation_width)
+    }
+}
+
+pub(crate) fn permutation_cost_report<M, SC>(
+    machine: &M,
+    chips: &[&dyn Chip<M, SC>],
+) -> PermutationCostReport
+where
+    M: Machine<SC::Val>,
+    SC: StarkConfig,
+{
+    let chips = chips
+        .iter()
+        .zip(chip_interactions(machine, chips))
+        .map(|(&chip, interactions)| {
+            let mut interactions_per_bus = BTreeMap::new();
+            for (interaction, _) in &interactions {
+                *interactions_per_bus
+                    .entry(interaction.argument_index)
+                    .or_default() += 1;
+            }
+            ChipPermutationCost {
+                main_width: chip.trace_width(),
+                permutation_width: permutation_trace_width::<M, SC, _>(machine, chip),
+                interactions_per_bus,
+            }
+        })
+        .collect();
+    PermutationCostReport { chips }
+}
+
+impl Display for PermutationCostReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        for (i, chip) in self.chips.iter().enumerate() {
+            write!(
+                f,
+                "chip {}: main width {}, permutation width {}",
+                i, chip.main_width, chip.permutation_width
+            )?;
+            for (bus, count) in &chip.interactions_per_bus {
+                write!(f, ", {:?} x{}", bus, count)?;
+            }
+            writeln!(f)?;
+        }
+        writeln!(f, "extension field columns: {}", self.extension_columns())
+    }
+}
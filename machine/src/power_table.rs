@@ -0,0 +1,122 @@
+use alloc::vec::Vec;
+
+use p3_air::VirtualPairCol;
+use p3_field::Field;
+use p3_matrix::dense::RowMajorMatrix;
+use p3_matrix::{Matrix, MatrixRowSlices};
+
+/// Main trace columns holding successive powers of a base column, for interactions that send
+/// `value^k` patterns. Column `power_cols[i]` holds `base^(i + 2)` on every row.
+///
+/// The table itself adds no constraints: a chip must constrain each power column, e.g. with
+/// `power_cols[0] = base * base`. `check_powers` verifies the columns during trace generation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PowerTable {
+    pub base_col: usize,
+    pub power_cols: Vec<usize>,
+}
+
+/// A power column which does not hold its claimed power.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct PowerMismatch {
+    pub column: usize,
+    pub power: usize,
+    pub row: usize,
+}
+
+impl PowerTable {
+    /// The largest power held by the table.
+    pub fn max_power(&self) -> usize {
+        self.power_cols.len() + 1
+    }
+
+    /// A field referencing `base^k`, where `base_col` must be the base column of this table.
+    pub fn field_power<F: Field>(&self, base_col: usize, k: usize) -> VirtualPairCol<F> {
+        assert_eq!(
+            base_col, self.base_col,
+            "column is not the base of this power table"
+        );
+        match k {
+            0 => VirtualPairCol::one(),
+            1 => VirtualPairCol::single_main(self.base_col),
+            _ => {
+                assert!(
+                    k <= self.max_power(),
+                    "power {} exceeds the table maximum of {}",
+                    k,
+                    self.max_power()
+                );
+                VirtualPairCol::single_main(self.power_cols[k - 2])
+            }
+        }
+    }
+
+    /// Fill in the power columns of a main trace row from its base column.
+    pub fn generate_powers<F: Field>(&self, main_row: &mut [F]) {
+        let base = main_row[self.base_col];
+        let mut power = base;
+        for &col in &self.power_cols {
+            power *= base;
+            main_row[col] = power;
+        }
+    }
+
+    /// Check that every power column of a main trace holds its claimed power.
+    pub fn check_powers<F: Field>(&self, main: &RowMajorMatrix<F>) -> Result<(), PowerMismatch> {
+        for n in 0..main.height() {
+            let row = main.row_slice(n);
+            let base = row[self.base_col];
+            let mut power = base;
+            for (i, &col) in self.power_cols.iter().enumerate() {
+                power *= base;
+                if row[col] != power {
+                    return Err(PowerMismatch {
+                        column: col,
+                        power: i + 2,
+                        row: n,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+    use p3_field::AbstractField;
+
+    type F = p3_baby_bear::BabyBear;
+
+    #[test]
+    fn square_column() {
+        let table = PowerTable {
+            base_col: 0,
+            power_cols: vec![1],
+        };
+        let mut values = vec![F::zero(); 8];
+        for (n, row) in values.chunks_mut(2).enumerate() {
+            row[0] = F::from_canonical_usize(n + 3);
+            table.generate_powers(row);
+        }
+        let mut main = RowMajorMatrix::new(values, 2);
+
+        let square = table.field_power::<F>(0, 2);
+        for n in 0..main.height() {
+            let row = main.row_slice(n);
+            assert_eq!(square.apply::<F, F>(&[], row), row[0].square());
+        }
+        assert_eq!(table.check_powers(&main), Ok(()));
+
+        // Corrupt the square on row 2.
+        main.values[2 * 2 + 1] += F::one();
+        let mismatch = PowerMismatch {
+            column: 1,
+            power: 2,
+            row: 2,
+        };
+        assert_eq!(table.check_powers(&main), Err(mismatch));
+    }
+}
@@ -1,7 +1,7 @@
 use crate::__internal::ProverConstraintFolder;
 use crate::config::StarkConfig;
-use crate::symbolic::symbolic_builder::get_log_quotient_degree;
-use crate::{eval_permutation_constraints, Chip, Machine};
+use crate::symbolic::symbolic_builder::get_chip_log_quotient_degree;
+use crate::{eval_permutation_constraints_with_layout, Chip, InteractionLayout, Machine};
 use itertools::Itertools;
 use p3_air::TwoRowMatrixView;
 use p3_commit::UnivariatePcsWithLde;
@@ -13,6 +13,7 @@ use p3_matrix::dense::RowMajorMatrix;
 use p3_matrix::{MatrixGet, MatrixRows};
 use p3_maybe_rayon::prelude::*;
 use p3_uni_stark::{decompose_and_flatten, ZerofierOnCoset};
+#[cfg(feature = "tracing")]
 use tracing::instrument;
 
 pub fn quotient<M, A, SC, PreprocessedTraceLde, MainTraceLde, PermTraceLde>(
@@ -36,7 +37,14 @@ where
     PermTraceLde: MatrixRows<SC::Val> + MatrixGet<SC::Val> + Sync,
 {
     let pcs = config.pcs();
-    let log_quotient_degree = get_log_quotient_degree::<M, SC, A>(machine, air);
+    let log_quotient_degree = get_chip_log_quotient_degree::<M, SC, A>(machine, air);
+    assert!(
+        log_quotient_degree <= pcs.log_blowup(),
+        "{} has quotient degree {}, above the PCS blowup {}",
+        core::any::type_name::<A>(),
+        1 << log_quotient_degree,
+        1 << pcs.log_blowup()
+    );
 
     let log_stride_for_quotient = pcs.log_blowup() - log_quotient_degree;
     let preprocessed_trace_lde_for_quotient =
@@ -67,7 +75,10 @@ where
     )
 }
 
-#[instrument(name = "compute quotient polynomial", skip_all)]
+#[cfg_attr(
+    feature = "tracing",
+    instrument(name = "compute quotient polynomial", skip_all)
+)]
 fn quotient_values<M, SC, A, PreprocessedTraceLde, MainTraceLde, PermTraceLde>(
     machine: &M,
     config: &SC,
@@ -117,6 +128,14 @@ where
         lagrange_last_evals.push(SC::Val::default());
     }
 
+    // The interactions and their columns are the same on every row.
+    let layout = InteractionLayout::new(
+        air.all_interactions(machine),
+        preprocessed_trace_lde.as_ref().map_or(0, |lde| lde.width()),
+        main_trace_lde.width(),
+        &air.permutation_layout(),
+    );
+
     (0..quotient_size)
         .into_par_iter()
         .step_by(SC::PackedVal::WIDTH)
@@ -219,7 +238,7 @@ where
                 accumulator,
             };
             air.eval(&mut folder);
-            eval_permutation_constraints(air, &mut folder, cumulative_sum);
+            eval_permutation_constraints_with_layout(air, &mut folder, &layout, cumulative_sum);
 
             // quotient(x) = constraints(x) / Z_H(x)
             let zerofier_inv: SC::PackedVal = zerofier_on_coset.eval_inverse_packed(i_local_start);
@@ -0,0 +1,129 @@
+//! Per-row contributions to the running sum of a permutation trace, for tools inspecting why a
+//! proof fails.
+//!
+//! With the `row-contributions` feature, `generate_permutation_trace_with_trace` returns, next to
+//! the permutation trace of a chip, the reciprocal of every interaction on every row and the
+//! signed term it adds to `phi` there. Without the feature nothing is recorded, so proving pays
+//! nothing for it.
+
+use alloc::vec::Vec;
+
+use crate::chip::{
+    beta_powers, debug_assert_enough_challenges, expect_independent_challenges, gated_count,
+    generate_permutation_trace_of, generate_rlc_elements, reciprocals_of_columns,
+};
+use crate::config::StarkConfig;
+use crate::{bundle_degree_budget, Chip, InteractionAir, LookupField, Machine};
+use p3_air::BaseAir;
+use p3_field::Field;
+use p3_matrix::dense::RowMajorMatrix;
+use p3_matrix::{Matrix, MatrixRowSlices};
+
+/// The interactions of a chip on one row of its permutation trace, in the order of
+/// `Chip::interaction_layout`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RowContribution<EF> {
+    pub row: usize,
+    /// The reciprocal `1 / (\alpha + \sum_j \beta^j f_j)` of each interaction, whether it has a
+    /// permutation trace column or a constant payload.
+    pub reciprocals: Vec<EF>,
+    /// The signed term `\pm count \cdot q` each interaction adds to the running sum, under the
+    /// chip's `InteractionAir::sign_convention`.
+    pub deltas: Vec<EF>,
+}
+
+impl<EF: Field> RowContribution<EF> {
+    /// The change of the running sum on this row, so that `phi` on row `n` is the sum of the
+    /// deltas of rows `0..=n`.
+    pub fn delta(&self) -> EF {
+        self.deltas.iter().copied().sum()
+    }
+}
+
+/// Like `generate_permutation_trace`, but also returns the contribution of every row. Only the
+/// reciprocal layout is supported: the chip must not generate its own permutation trace, declare
+/// `InteractionAir::reciprocal_bundle_degree` or use `LookupField::Base`.
+pub fn generate_permutation_trace_with_trace<M, SC>(
+    machine: &M,
+    chip: &dyn Chip<M, SC>,
+    main: &RowMajorMatrix<SC::Val>,
+    random_elements: Vec<SC::Challenge>,
+) -> (
+    RowMajorMatrix<SC::Challenge>,
+    Vec<RowContribution<SC::Challenge>>,
+)
+where
+    M: Machine<SC::Val>,
+    SC: StarkConfig,
+{
+    expect_independent_challenges(&random_elements);
+    debug_assert_enough_challenges(machine, chip, &random_elements);
+    assert!(
+        chip.generate_permutation_trace(machine, main, &random_elements)
+            .is_none()
+            && chip.lookup_field() == LookupField::Extension
+            && bundle_degree_budget::<M, SC, _>(machine, chip).is_none(),
+        "row contributions are only recorded for reciprocal columns"
+    );
+
+    let layout = chip.interaction_layout(machine);
+    let perm = generate_permutation_trace_of(
+        machine,
+        chip,
+        main,
+        random_elements.clone(),
+        &layout,
+        false,
+        false,
+    );
+
+    let (alphas_local, alphas_global) =
+        generate_rlc_elements::<M, SC, _>(machine, chip, &random_elements);
+    let betas = beta_powers(random_elements[2], &layout.interactions);
+    let reciprocals = reciprocals_of_columns(
+        &layout.interactions,
+        &layout.columns,
+        &alphas_local,
+        &alphas_global,
+        &betas,
+    );
+    let preprocessed = chip.preprocessed_trace();
+    let active_selector = chip.active_selector();
+    let sign_convention = chip.sign_convention();
+
+    let contributions = (0..main.height())
+        .map(|n| {
+            let main_row = main.row_slice(n);
+            let perm_row = perm.row_slice(n);
+            let preprocessed_row = match &preprocessed {
+                Some(preprocessed) => preprocessed.row_slice(n),
+                None => &[],
+            };
+            let row_reciprocals = reciprocals
+                .iter()
+                .map(|reciprocal| reciprocal.value(perm_row))
+                .collect::<Vec<_>>();
+            let deltas = layout
+                .interactions
+                .iter()
+                .zip(&row_reciprocals)
+                .map(|((interaction, interaction_type), &q)| {
+                    let count =
+                        interaction.count_at::<SC::Val, SC::Val>(preprocessed_row, main_row);
+                    let term = q * gated_count(active_selector, count, main_row);
+                    if sign_convention.adds(interaction_type) {
+                        term
+                    } else {
+                        -term
+                    }
+                })
+                .collect();
+            RowContribution {
+                row: n,
+                reciprocals: row_reciprocals,
+                deltas,
+            }
+        })
+        .collect();
+    (perm, contributions)
+}
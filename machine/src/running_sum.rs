@@ -0,0 +1,132 @@
+//! The running sum of a chip as a rational function of the permutation challenges.
+//!
+//! For challenges `\alpha_L, \alpha_G, \beta`, the cumulative sum of a chip is
+//!
+//! `\sum_{rows} \sum_i \pm c_i / (\alpha_b^{k+1} + \sum_j \beta^j f_{i,j})`,
+//!
+//! where interaction `i` is on bus `b \in {L, G}` with index `k`, or with the `bus_challenge` of
+//! the bus in place of `\alpha_b^{k+1}` under several `Machine::BUS_CHALLENGE_ROOTS`.
+//! `symbolic_running_sum` collects this sum from the main trace without fixing the challenges,
+//! merging terms with the same bus and payload. The result can be inspected directly, e.g. to see
+//! that every payload's multiplicity cancels, or evaluated at concrete challenges.
+
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+
+use crate::chip::gated_count;
+use crate::config::StarkConfig;
+use crate::{bus_challenge, BusArgument, Chip, Machine};
+use p3_field::{AbstractField, ExtensionField, Field, PrimeField32};
+use p3_matrix::dense::RowMajorMatrix;
+use p3_matrix::{Matrix, MatrixRowSlices};
+
+/// One fraction `multiplicity / (\alpha_b^{k+1} + \sum_j \beta^j payload_j)` of a running sum.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RunningSumTerm<F> {
+    pub bus: BusArgument,
+    pub payload: Vec<F>,
+    /// The net multiplicity, with sends counted positively and receives negatively, or the other
+    /// way around under `SignConvention::ReceivesPositive`.
+    pub multiplicity: F,
+}
+
+/// A running sum as a sum of fractions in the challenges, see the module documentation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SymbolicRunningSum<F> {
+    /// The terms, sorted by bus and payload, with no two sharing both.
+    pub terms: Vec<RunningSumTerm<F>>,
+    /// The machine's `Machine::BUS_CHALLENGE_ROOTS`, which the bus challenges are drawn with.
+    pub bus_challenge_roots: usize,
+}
+
+impl<F> Default for SymbolicRunningSum<F> {
+    fn default() -> Self {
+        Self {
+            terms: Vec::new(),
+            bus_challenge_roots: 1,
+        }
+    }
+}
+
+impl<F: Field> SymbolicRunningSum<F> {
+    /// Evaluate the sum at the permutation challenges, as drawn for the permutation argument,
+    /// with the bus challenges of `bus_challenge`.
+    pub fn evaluate<EF: ExtensionField<F>>(&self, random_elements: &[EF]) -> EF {
+        self.terms
+            .iter()
+            .map(|term| {
+                let mut rlc = bus_challenge(random_elements, term.bus, self.bus_challenge_roots);
+                for (&f, beta) in term.payload.iter().zip(random_elements[2].powers()) {
+                    rlc += beta * f;
+                }
+                rlc.try_inverse().unwrap_or(EF::zero()) * term.multiplicity
+            })
+            .sum()
+    }
+}
+
+/// Collect the running sum of a chip over its main trace as a `SymbolicRunningSum`.
+pub fn symbolic_running_sum<M, SC>(
+    machine: &M,
+    chip: &dyn Chip<M, SC>,
+    main: &RowMajorMatrix<SC::Val>,
+) -> SymbolicRunningSum<SC::Val>
+where
+    M: Machine<SC::Val>,
+    SC: StarkConfig,
+{
+    let interactions = chip.all_interactions(machine);
+    let preprocessed = chip.preprocessed_trace();
+    let height = main.height();
+
+    let mut terms = BTreeMap::<(BusArgument, Vec<u32>), SC::Val>::new();
+    for n in 0..height {
+        let n_next = (n + 1) % height;
+        let main_rows = [main.row_slice(n), main.row_slice(n_next)];
+        let preprocessed_rows = match &preprocessed {
+            Some(preprocessed) => [preprocessed.row_slice(n), preprocessed.row_slice(n_next)],
+            None => [&[][..], &[][..]],
+        };
+        for (interaction, interaction_type) in interactions.iter() {
+            let count =
+                interaction.count_at::<SC::Val, SC::Val>(preprocessed_rows[0], main_rows[0]);
+            let count = gated_count(chip.active_selector(), count, main_rows[0]);
+            let payload = interaction
+                .fields
+                .iter()
+                .enumerate()
+                .map(|(i, field)| {
+                    let rotation = interaction.field_rotation(i);
+                    field
+                        .apply::<SC::Val, SC::Val>(preprocessed_rows[rotation], main_rows[rotation])
+                        .as_canonical_u32()
+                })
+                .collect::<Vec<_>>();
+            let multiplicity = terms
+                .entry((interaction.argument_index, payload))
+                .or_insert_with(SC::Val::zero);
+            if chip.sign_convention().adds(interaction_type) {
+                *multiplicity += count;
+            } else {
+                *multiplicity -= count;
+            }
+        }
+    }
+
+    let terms = terms
+        .into_iter()
+        .filter(|(_, multiplicity)| !multiplicity.is_zero())
+        .map(|((bus, payload), multiplicity)| RunningSumTerm {
+            bus,
+            payload: payload
+                .into_iter()
+                .map(SC::Val::from_canonical_u32)
+                .collect(),
+            multiplicity,
+        })
+        .collect();
+    SymbolicRunningSum {
+        terms,
+        bus_challenge_roots: M::BUS_CHALLENGE_ROOTS,
+    }
+}
@@ -0,0 +1,157 @@
+//! Permutation traces of chips with several independently sized main traces.
+//!
+//! `align_domain_traces` merges the traces of a chip into one trace of the largest height, over
+//! which the permutation argument runs once. A chip may instead give each trace, or segment, its
+//! own interactions with `Chip::segment_interactions`, whose columns are local to the segment.
+//! `generate_segment_permutation_traces` then produces one permutation trace per segment, of the
+//! segment's height, and the cumulative sum of the chip is the sum of those of its segments.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::config::StarkConfig;
+use crate::{
+    cumulative_sum_from_perm, generate_permutation_trace, Chip, DomainId, Interaction,
+    InteractionAir, InteractionType, Machine, SignConvention,
+};
+use p3_air::{Air, AirBuilder, BaseAir};
+use p3_field::Field;
+use p3_matrix::dense::RowMajorMatrix;
+use p3_matrix::Matrix;
+
+/// One segment of a chip, seen as a chip of its own: its main trace is the segment's trace and
+/// its interactions are `Chip::segment_interactions`. A segment has no AIR constraints, no
+/// preprocessed trace and no `active_selector`; rows which take no part should be masked with
+/// `Interaction::is_real`.
+pub struct ChipSegment<'a, M: Machine<SC::Val>, SC: StarkConfig> {
+    pub machine: &'a M,
+    pub chip: &'a dyn Chip<M, SC>,
+    pub domain: DomainId,
+    /// The width of the segment's main trace.
+    pub width: usize,
+}
+
+impl<M, SC> ChipSegment<'_, M, SC>
+where
+    M: Machine<SC::Val>,
+    SC: StarkConfig,
+{
+    fn interactions_of(&self, interaction_type: InteractionType) -> Vec<Interaction<SC::Val>> {
+        self.chip
+            .segment_interactions(self.machine, self.domain)
+            .into_iter()
+            .filter(|(_, t)| *t == interaction_type)
+            .map(|(interaction, _)| interaction)
+            .collect()
+    }
+}
+
+impl<F, M, SC> BaseAir<F> for ChipSegment<'_, M, SC>
+where
+    M: Machine<SC::Val>,
+    SC: StarkConfig,
+{
+    fn width(&self) -> usize {
+        self.width
+    }
+}
+
+impl<AB, M, SC> Air<AB> for ChipSegment<'_, M, SC>
+where
+    AB: AirBuilder,
+    M: Machine<SC::Val>,
+    SC: StarkConfig,
+{
+    fn eval(&self, _builder: &mut AB) {}
+}
+
+impl<M, SC> InteractionAir<M, SC> for ChipSegment<'_, M, SC>
+where
+    M: Machine<SC::Val>,
+    SC: StarkConfig,
+{
+    fn local_sends(&self) -> Vec<Interaction<SC::Val>> {
+        self.interactions_of(InteractionType::LocalSend)
+    }
+
+    fn local_receives(&self) -> Vec<Interaction<SC::Val>> {
+        self.interactions_of(InteractionType::LocalReceive)
+    }
+
+    fn global_sends(&self, _machine: &M) -> Vec<Interaction<SC::Val>> {
+        self.interactions_of(InteractionType::GlobalSend)
+    }
+
+    fn global_receives(&self, _machine: &M) -> Vec<Interaction<SC::Val>> {
+        self.interactions_of(InteractionType::GlobalReceive)
+    }
+
+    fn num_permutation_challenges(&self, machine: &M) -> usize {
+        self.chip.num_permutation_challenges(machine)
+    }
+
+    fn sign_convention(&self) -> SignConvention {
+        self.chip.sign_convention()
+    }
+}
+
+impl<M, SC> Chip<M, SC> for ChipSegment<'_, M, SC>
+where
+    M: Machine<SC::Val>,
+    SC: StarkConfig,
+{
+    fn generate_trace(&self, machine: &M) -> RowMajorMatrix<SC::Val> {
+        let (_, trace) = self
+            .chip
+            .generate_traces(machine)
+            .into_iter()
+            .find(|(domain, _)| *domain == self.domain)
+            .expect("unknown domain");
+        trace
+    }
+
+    fn require_distinct_fields(&self) -> bool {
+        self.chip.require_distinct_fields()
+    }
+}
+
+/// Generate one permutation trace per segment of a chip, in the order of `traces`, as returned
+/// by `Chip::generate_traces`. A chip with the single trace `DomainId(0)` gets its usual
+/// permutation trace from `generate_permutation_trace`.
+pub fn generate_segment_permutation_traces<M, SC>(
+    machine: &M,
+    chip: &dyn Chip<M, SC>,
+    traces: &[(DomainId, RowMajorMatrix<SC::Val>)],
+    random_elements: Vec<SC::Challenge>,
+) -> Vec<RowMajorMatrix<SC::Challenge>>
+where
+    M: Machine<SC::Val>,
+    SC: StarkConfig,
+{
+    if let [(DomainId(0), main)] = traces {
+        return vec![generate_permutation_trace(
+            machine,
+            chip,
+            main,
+            random_elements,
+        )];
+    }
+    traces
+        .iter()
+        .map(|(domain, main)| {
+            let segment = ChipSegment {
+                machine,
+                chip,
+                domain: *domain,
+                width: main.width(),
+            };
+            generate_permutation_trace(machine, &segment, main, random_elements.clone())
+        })
+        .collect()
+}
+
+/// The cumulative sum of a chip with segment permutation traces: the sum of the cumulative sums
+/// of its segments.
+pub fn segment_cumulative_sum<EF: Field>(perms: &[RowMajorMatrix<EF>]) -> EF {
+    perms.iter().map(cumulative_sum_from_perm).sum()
+}
@@ -0,0 +1,42 @@
+use crate::config::StarkConfig;
+use crate::{Chip, Machine};
+use p3_field::{AbstractExtensionField, PrimeField32};
+
+/// An upper bound on the probability that the bus arguments of a machine accept unbalanced
+/// buses, over the choice of permutation challenges.
+///
+/// The chips balance their buses when
+///
+/// `\sum_i \pm c_i / (\alpha^{k_i+1} + \sum_j \beta^j f_{i,j}) = 0`,
+///
+/// summed over the `N` interactions on all rows of all chips. If the multisets of payloads do not
+/// balance, clearing denominators gives a nonzero polynomial in `\alpha, \beta` of total degree at
+/// most `N d`, where `d = max(k_max + 1, L - 1)` bounds the degree of a denominator for bus indices
+/// up to `k_max` and at most `L` fields. By Schwartz-Zippel it vanishes with probability at most
+/// `N d / |EF|`. Each denominator is also zero with probability at most `d / |EF|`, so the bound
+/// returned is `2 N d / |EF|`.
+///
+/// `log_heights[i]` is the log of the trace height of `chips[i]`.
+pub fn soundness_error<M, SC>(machine: &M, chips: &[&dyn Chip<M, SC>], log_heights: &[usize]) -> f64
+where
+    M: Machine<SC::Val>,
+    SC: StarkConfig,
+{
+    assert_eq!(chips.len(), log_heights.len());
+
+    let mut num_fractions = 0f64;
+    let mut degree = 1;
+    for (chip, &log_height) in chips.iter().zip(log_heights) {
+        let interactions = chip.all_interactions(machine);
+        num_fractions += interactions.len() as f64 * (1u64 << log_height) as f64;
+        for (interaction, _) in interactions.iter() {
+            degree = degree
+                .max(interaction.argument_index() + 1)
+                .max(interaction.fields.len().saturating_sub(1));
+        }
+    }
+
+    let ext_degree = <SC::Challenge as AbstractExtensionField<SC::Val>>::D;
+    let field_size = (0..ext_degree).fold(1f64, |size, _| size * SC::Val::ORDER_U32 as f64);
+    2.0 * num_fractions * degree as f64 / field_size
+}
@@ -0,0 +1,349 @@
+//! Block-wise permutation trace generation for long executions.
+//!
+//! `generate_permutation_trace_streaming` consumes the main trace of a chip as a sequence of row
+//! blocks and hands back the matching blocks of the permutation trace, carrying the running sum
+//! from one block to the next. Only the current block and the one after it are held at a time,
+//! so peak memory is bounded by the block size rather than the trace height.
+//!
+//! Only the permutation trace generation is streamed: there is no streaming commitment or
+//! streaming prover. The committed traces still need to be whole, since `Pcs::commit_batches`
+//! takes complete matrices, so callers must concatenate the blocks before committing, and the
+//! proof is then that of the batch path. Committing block by block would need an incremental
+//! commitment scheme, which no `Pcs` here provides.
+//!
+//! Both entry points cover chips with one reciprocal column per interaction, over either lookup
+//! field: with base field challenges, the extension field arithmetic here gives the embedded base
+//! field trace of `generate_base_permutation_trace`. Bundled reciprocals are not supported.
+//!
+//! `PermutationTraceBuilder` instead takes main rows one at a time, for chips whose rows come from
+//! an execution stream, and produces the whole permutation trace at the end without the main trace
+//! ever being materialized.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::bundle::bundle_degree_budget;
+use crate::chip::{beta_powers, gated_count, generate_rlc_elements, reciprocal_layout, reduce_row};
+use crate::config::StarkConfig;
+use crate::lookup_field::{to_base, LookupField};
+use crate::{Chip, Interaction, InteractionType, Machine, Reciprocal, SignConvention};
+use p3_field::AbstractField;
+use p3_matrix::dense::RowMajorMatrix;
+use p3_matrix::{Matrix, MatrixRowSlices};
+use valida_util::batch_multiplicative_inverse_allowing_zero;
+
+/// Generate the permutation trace of a chip block by block, passing each block to `sink`, and
+/// return the cumulative sum.
+///
+/// The concatenation of the blocks equals the output of `generate_permutation_trace` on the
+/// concatenated main trace. Chips overriding `Chip::generate_permutation_trace` are not supported,
+/// and chips with bundled reciprocals, including every chip of a `LookupBackend::LogUp` machine,
+/// panic.
+pub fn generate_permutation_trace_streaming<M, SC, I, S>(
+    machine: &M,
+    chip: &dyn Chip<M, SC>,
+    main_blocks: I,
+    random_elements: Vec<SC::Challenge>,
+    mut sink: S,
+) -> SC::Challenge
+where
+    M: Machine<SC::Val>,
+    SC: StarkConfig,
+    I: IntoIterator<Item = RowMajorMatrix<SC::Val>>,
+    S: FnMut(RowMajorMatrix<SC::Challenge>),
+{
+    assert_one_column_per_interaction(machine, chip, &random_elements);
+    let all_interactions = chip.all_interactions(machine);
+    let (alphas_local, alphas_global) =
+        generate_rlc_elements::<M, SC, _>(machine, chip, &random_elements);
+    let betas = beta_powers(random_elements[2], &all_interactions);
+    let preprocessed = chip.preprocessed_trace();
+    let reciprocals = reciprocal_layout(
+        &all_interactions,
+        preprocessed.as_ref().map_or(0, |p| p.width()),
+        chip.trace_width(),
+        &chip.permutation_layout(),
+        &alphas_local,
+        &alphas_global,
+        &betas,
+    );
+    let perm_width = reciprocals
+        .iter()
+        .filter(|r| matches!(r, Reciprocal::Column(_)))
+        .count()
+        + 1;
+
+    let mut main_blocks = main_blocks.into_iter();
+    let mut phi = SC::Challenge::zero();
+    let Some(mut block) = main_blocks.next() else {
+        return phi;
+    };
+    // Rotated fields on the last row wrap around to the first row.
+    let first_row = block.row_slice(0).to_vec();
+    let mut offset = 0;
+
+    loop {
+        let next_block = main_blocks.next();
+        let height = block.height();
+
+        let mut perm_values = vec![SC::Challenge::zero(); height * perm_width];
+        for (n, row) in perm_values.chunks_exact_mut(perm_width).enumerate() {
+            let is_last_row = n + 1 == height && next_block.is_none();
+            let main_next = if n + 1 < height {
+                block.row_slice(n + 1)
+            } else {
+                match &next_block {
+                    Some(next_block) => next_block.row_slice(0),
+                    None => &first_row[..],
+                }
+            };
+            let main_rows = [block.row_slice(n), main_next];
+            let preprocessed_rows = match &preprocessed {
+                Some(preprocessed) => {
+                    let n_next = if is_last_row { 0 } else { offset + n + 1 };
+                    [
+                        preprocessed.row_slice(offset + n),
+                        preprocessed.row_slice(n_next),
+                    ]
+                }
+                None => [&[][..], &[][..]],
+            };
+            for ((interaction, _), reciprocal) in all_interactions.iter().zip(&reciprocals) {
+                let Reciprocal::Column(c) = *reciprocal else {
+                    continue;
+                };
+                let alpha_m = if interaction.is_local() {
+                    alphas_local[interaction.argument_index()]
+                } else {
+                    alphas_global[interaction.argument_index()]
+                };
+                row[c] = reduce_row(&main_rows, &preprocessed_rows, interaction, alpha_m, &betas);
+            }
+        }
+        #[cfg(feature = "cost-model")]
+        crate::cost::record_batch_inverse(&perm_values);
+        let perm_values = batch_multiplicative_inverse_allowing_zero(perm_values);
+        let mut perm = RowMajorMatrix::new(perm_values, perm_width);
+
+        for (n, perm_row) in perm.as_view_mut().rows_mut().enumerate() {
+            let main_row = block.row_slice(n);
+            let preprocessed_row = match &preprocessed {
+                Some(preprocessed) => preprocessed.row_slice(offset + n),
+                None => &[],
+            };
+            for (m, (interaction, interaction_type)) in all_interactions.iter().enumerate() {
+                let mult = interaction.count_at::<SC::Val, SC::Val>(preprocessed_row, main_row);
+                let mult = gated_count(chip.active_selector(), mult, main_row);
+                let q = reciprocals[m].value(perm_row);
+                #[cfg(feature = "cost-model")]
+                crate::cost::record(1, 0);
+                if chip.sign_convention().adds(interaction_type) {
+                    phi += q * mult;
+                } else {
+                    phi -= q * mult;
+                }
+            }
+            *perm_row.last_mut().unwrap() = phi;
+        }
+        sink(perm);
+
+        offset += height;
+        match next_block {
+            Some(next_block) => block = next_block,
+            None => return phi,
+        }
+    }
+}
+
+/// Panic unless a chip has one reciprocal column per interaction, the only permutation traces
+/// built here, and, for a base field lookup, the challenges are base field elements.
+fn assert_one_column_per_interaction<M, SC>(
+    machine: &M,
+    chip: &dyn Chip<M, SC>,
+    random_elements: &[SC::Challenge],
+) where
+    M: Machine<SC::Val>,
+    SC: StarkConfig,
+{
+    if chip.lookup_field() == LookupField::Base {
+        for challenge in random_elements {
+            to_base::<SC::Val, SC::Challenge>(challenge);
+        }
+    }
+    assert!(
+        bundle_degree_budget::<M, SC, _>(machine, chip).is_none(),
+        "streamed permutation traces do not support bundled reciprocals"
+    );
+}
+
+/// Builds the permutation trace of a chip from its main rows, pushed one at a time.
+///
+/// The reciprocal denominators of each row are computed as soon as the next row arrives, which
+/// rotated fields read, and only they and the gated counts are kept: `perm_width` extension
+/// elements and one base element per interaction for each row, instead of the main trace. `finish`
+/// inverts every denominator in a single batch and scans the running sum, giving the same trace as
+/// `generate_permutation_trace` on the pushed rows. As for `generate_permutation_trace_streaming`,
+/// chips overriding `Chip::generate_permutation_trace` are not supported, and chips with bundled
+/// reciprocals panic.
+pub struct PermutationTraceBuilder<SC: StarkConfig> {
+    interactions: Vec<(Interaction<SC::Val>, InteractionType)>,
+    reciprocals: Vec<Reciprocal<SC::Challenge>>,
+    /// The bus challenge of each interaction.
+    alphas: Vec<SC::Challenge>,
+    betas: Vec<SC::Challenge>,
+    preprocessed: Option<RowMajorMatrix<SC::Val>>,
+    active_selector: Option<usize>,
+    sign_convention: SignConvention,
+    perm_width: usize,
+    /// The first row, which the rotated fields of the last row read.
+    first_row: Option<Vec<SC::Val>>,
+    /// The last pushed row, whose denominators wait for the next row.
+    pending_row: Option<Vec<SC::Val>>,
+    height: usize,
+    denominators: Vec<SC::Challenge>,
+    counts: Vec<SC::Val>,
+}
+
+impl<SC: StarkConfig> PermutationTraceBuilder<SC> {
+    pub fn new<M>(machine: &M, chip: &dyn Chip<M, SC>, random_elements: &[SC::Challenge]) -> Self
+    where
+        M: Machine<SC::Val>,
+    {
+        assert_one_column_per_interaction(machine, chip, random_elements);
+        let interactions = chip.all_interactions(machine);
+        let (alphas_local, alphas_global) =
+            generate_rlc_elements::<M, SC, _>(machine, chip, random_elements);
+        let betas = beta_powers(random_elements[2], &interactions);
+        let preprocessed = chip.preprocessed_trace();
+        let reciprocals = reciprocal_layout(
+            &interactions,
+            preprocessed.as_ref().map_or(0, |p| p.width()),
+            chip.trace_width(),
+            &chip.permutation_layout(),
+            &alphas_local,
+            &alphas_global,
+            &betas,
+        );
+        let perm_width = reciprocals
+            .iter()
+            .filter(|r| matches!(r, Reciprocal::Column(_)))
+            .count()
+            + 1;
+        let alphas = interactions
+            .iter()
+            .map(|(interaction, _)| {
+                if interaction.is_local() {
+                    alphas_local[interaction.argument_index()]
+                } else {
+                    alphas_global[interaction.argument_index()]
+                }
+            })
+            .collect();
+        Self {
+            interactions,
+            reciprocals,
+            alphas,
+            betas,
+            preprocessed,
+            active_selector: chip.active_selector(),
+            sign_convention: chip.sign_convention(),
+            perm_width,
+            first_row: None,
+            pending_row: None,
+            height: 0,
+            denominators: vec![],
+            counts: vec![],
+        }
+    }
+
+    /// Append the next row of the main trace.
+    pub fn push_row(&mut self, row: &[SC::Val]) {
+        if self.first_row.is_none() {
+            self.first_row = Some(row.to_vec());
+        }
+        // The buffer of the pending row is reused for the next one.
+        let mut pending_row = match self.pending_row.take() {
+            Some(pending_row) => {
+                self.reduce(self.height - 1, &pending_row, self.height, row);
+                pending_row
+            }
+            None => Vec::with_capacity(row.len()),
+        };
+
+        let preprocessed_row = match &self.preprocessed {
+            Some(preprocessed) => preprocessed.row_slice(self.height),
+            None => &[],
+        };
+        for (interaction, _) in &self.interactions {
+            let count = interaction.count_at::<SC::Val, SC::Val>(preprocessed_row, row);
+            self.counts
+                .push(gated_count(self.active_selector, count, row));
+        }
+        pending_row.clear();
+        pending_row.extend_from_slice(row);
+        self.pending_row = Some(pending_row);
+        self.height += 1;
+    }
+
+    /// Compute the denominators of row `n` of the main trace, whose rotated fields read row
+    /// `n_next`.
+    fn reduce(&mut self, n: usize, row: &[SC::Val], n_next: usize, next_row: &[SC::Val]) {
+        let main_rows = [row, next_row];
+        let preprocessed_rows = match &self.preprocessed {
+            Some(preprocessed) => [preprocessed.row_slice(n), preprocessed.row_slice(n_next)],
+            None => [&[][..], &[][..]],
+        };
+        // Each row's denominators are written in place at the end of `denominators`.
+        let start = self.denominators.len();
+        self.denominators
+            .resize(start + self.perm_width, SC::Challenge::zero());
+        let perm_row = &mut self.denominators[start..];
+        for (m, ((interaction, _), reciprocal)) in
+            self.interactions.iter().zip(&self.reciprocals).enumerate()
+        {
+            let Reciprocal::Column(c) = *reciprocal else {
+                continue;
+            };
+            perm_row[c] = reduce_row(
+                &main_rows,
+                &preprocessed_rows,
+                interaction,
+                self.alphas[m],
+                &self.betas,
+            );
+        }
+    }
+
+    /// Invert the denominators of every row in one batch and compute the running sum, returning
+    /// the permutation trace.
+    pub fn finish(mut self) -> RowMajorMatrix<SC::Challenge> {
+        // Rotated fields on the last row wrap around to the first row.
+        if let Some(pending_row) = self.pending_row.take() {
+            let first_row = self.first_row.take().unwrap();
+            self.reduce(self.height - 1, &pending_row, 0, &first_row);
+        }
+
+        #[cfg(feature = "cost-model")]
+        crate::cost::record_batch_inverse(&self.denominators);
+        let mut perm_values = batch_multiplicative_inverse_allowing_zero(self.denominators);
+        let num_interactions = self.interactions.len();
+        let mut phi = SC::Challenge::zero();
+        for (n, perm_row) in perm_values.chunks_exact_mut(self.perm_width).enumerate() {
+            let counts = &self.counts[n * num_interactions..(n + 1) * num_interactions];
+            for (((_, interaction_type), reciprocal), &mult) in
+                self.interactions.iter().zip(&self.reciprocals).zip(counts)
+            {
+                let q = reciprocal.value(perm_row);
+                #[cfg(feature = "cost-model")]
+                crate::cost::record(1, 0);
+                if self.sign_convention.adds(interaction_type) {
+                    phi += q * mult;
+                } else {
+                    phi -= q * mult;
+                }
+            }
+            *perm_row.last_mut().unwrap() = phi;
+        }
+        RowMajorMatrix::new(perm_values, self.perm_width)
+    }
+}
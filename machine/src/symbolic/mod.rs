@@ -1,4 +1,4 @@
 pub(crate) mod symbolic_builder;
-mod symbolic_expression;
+pub(crate) mod symbolic_expression;
 mod symbolic_expression_ext;
-mod symbolic_variable;
+pub(crate) mod symbolic_variable;
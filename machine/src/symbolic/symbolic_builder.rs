@@ -1,12 +1,17 @@
 use alloc::vec;
 use alloc::vec::Vec;
+use core::ops::Range;
 
 use crate::config::StarkConfig;
-use crate::{Machine, ValidaAirBuilder};
+use crate::{
+    eval_permutation_constraints, permutation_trace_width, Chip, ConstraintProvenance, Machine,
+    MainWindowBuilder, ValidaAirBuilder,
+};
 use p3_air::ExtensionBuilder;
 use p3_air::{Air, AirBuilder, PairBuilder, PermutationAirBuilder};
-use p3_field::AbstractExtensionField;
+use p3_field::Field;
 use p3_matrix::dense::RowMajorMatrix;
+use p3_matrix::Matrix;
 use p3_util::log2_ceil_usize;
 use valida_machine::symbolic::symbolic_expression_ext::SymbolicExpressionExt;
 use valida_machine::symbolic::symbolic_variable::Trace;
@@ -29,11 +34,25 @@ where
     log2_ceil_usize(constraint_degree - 1)
 }
 
+/// The log quotient degree of a chip, sized by `Chip::max_constraint_degree` so that its
+/// permutation constraints fit as well as its AIR constraints. With a `bundle_degree_budget`,
+/// the bundle constraints can exceed degree 3, so sizing from `get_log_quotient_degree` would
+/// leave the quotient too small to prove them.
+pub fn get_chip_log_quotient_degree<M, SC, C>(machine: &M, chip: &C) -> usize
+where
+    M: Machine<SC::Val>,
+    SC: StarkConfig,
+    C: Chip<M, SC> + ?Sized,
+{
+    let constraint_degree = chip.max_constraint_degree(machine).max(3);
+    log2_ceil_usize(constraint_degree - 1)
+}
+
 pub fn get_max_constraint_degree<M, SC, A>(machine: &M, air: &A) -> usize
 where
     M: Machine<SC::Val>,
     SC: StarkConfig,
-    A: for<'a> Air<SymbolicAirBuilder<'a, M, SC>>,
+    A: for<'a> Air<SymbolicAirBuilder<'a, M, SC>> + ?Sized,
 {
     get_symbolic_constraints(machine, air)
         .iter()
@@ -46,20 +65,74 @@ pub fn get_symbolic_constraints<M, SC, A>(machine: &M, air: &A) -> Vec<SymbolicE
 where
     M: Machine<SC::Val>,
     SC: StarkConfig,
-    A: for<'a> Air<SymbolicAirBuilder<'a, M, SC>>,
+    A: for<'a> Air<SymbolicAirBuilder<'a, M, SC>> + ?Sized,
 {
     let mut builder = SymbolicAirBuilder::new(machine, air.width());
     air.eval(&mut builder);
     builder.constraints()
 }
 
+/// A permutation argument constraint, captured by `get_symbolic_permutation_constraints`.
+#[derive(Clone, Debug)]
+pub struct PermutationConstraint<EF: Field> {
+    pub provenance: ConstraintProvenance,
+    pub expr: SymbolicExpression<EF>,
+}
+
+/// Capture the permutation constraints of a chip as symbolic expression trees over the
+/// extension field, one per constraint, in the order `eval_permutation_constraints` emits them:
+/// one reciprocal constraint per interaction with a permutation column, then the transition,
+/// first row and last row constraints of the running sum.
+///
+/// The random elements and cumulative sum are concrete, so they appear as constants.
+pub fn get_symbolic_permutation_constraints<M, SC, C>(
+    machine: &M,
+    chip: &C,
+    random_elements: Vec<SC::Challenge>,
+    cumulative_sum: SC::Challenge,
+) -> Vec<PermutationConstraint<SC::Challenge>>
+where
+    M: Machine<SC::Val>,
+    SC: StarkConfig,
+    C: Chip<M, SC>,
+{
+    let preprocessed_width = chip.preprocessed_trace().map_or(0, |trace| trace.width());
+    let permutation_width = permutation_trace_width::<M, SC, C>(machine, chip);
+    let mut builder = SymbolicAirBuilder {
+        machine,
+        preprocessed: SymbolicVariable::window(Trace::Preprocessed, preprocessed_width),
+        main: SymbolicVariable::window(Trace::Main, chip.trace_width()),
+        permutation: SymbolicVariable::window(Trace::Permutation, permutation_width),
+        permutation_randomness: random_elements,
+        constraints: vec![],
+        ext_constraints: vec![],
+        provenance: None,
+    };
+    eval_permutation_constraints::<M, C, SC, _>(chip, &mut builder, cumulative_sum);
+    builder
+        .ext_constraints
+        .into_iter()
+        .map(|(provenance, expr)| PermutationConstraint {
+            provenance: provenance.expect("permutation constraints have a provenance"),
+            expr,
+        })
+        .collect()
+}
+
 /// An `AirBuilder` for evaluating constraints symbolically, and recording them for later use.
 pub struct SymbolicAirBuilder<'a, M: Machine<SC::Val>, SC: StarkConfig> {
     machine: &'a M,
     preprocessed: RowMajorMatrix<SymbolicVariable<SC::Val>>,
     main: RowMajorMatrix<SymbolicVariable<SC::Val>>,
     permutation: RowMajorMatrix<SymbolicVariable<SC::Challenge>>,
+    permutation_randomness: Vec<SC::Challenge>,
     constraints: Vec<SymbolicExpression<SC::Val>>,
+    /// Extension field constraints, kept whole rather than split into base field coordinates.
+    ext_constraints: Vec<(
+        Option<ConstraintProvenance>,
+        SymbolicExpression<SC::Challenge>,
+    )>,
+    provenance: Option<ConstraintProvenance>,
 }
 
 impl<'a, M: Machine<SC::Val>, SC: StarkConfig> SymbolicAirBuilder<'a, M, SC> {
@@ -70,7 +143,10 @@ impl<'a, M: Machine<SC::Val>, SC: StarkConfig> SymbolicAirBuilder<'a, M, SC> {
             preprocessed: SymbolicVariable::window(Trace::Preprocessed, width),
             main: SymbolicVariable::window(Trace::Main, width),
             permutation: SymbolicVariable::window(Trace::Permutation, width),
+            permutation_randomness: vec![],
             constraints: vec![],
+            ext_constraints: vec![],
+            provenance: None,
         }
     }
 
@@ -125,9 +201,7 @@ impl<'a, M: Machine<SC::Val>, SC: StarkConfig> ExtensionBuilder for SymbolicAirB
     where
         I: Into<Self::ExprEF>,
     {
-        for xb in x.into().as_base_slice().iter().cloned() {
-            self.assert_zero::<SymbolicExpression<SC::Val>>(xb);
-        }
+        self.ext_constraints.push((self.provenance, x.into().0));
     }
 }
 
@@ -141,7 +215,7 @@ impl<'a, M: Machine<SC::Val>, SC: StarkConfig> PermutationAirBuilder
     }
 
     fn permutation_randomness(&self) -> &[Self::EF] {
-        &[] // TODO
+        &self.permutation_randomness
     }
 }
 
@@ -151,4 +225,22 @@ impl<'a, M: Machine<SC::Val>, SC: StarkConfig> ValidaAirBuilder for SymbolicAirB
     fn machine(&self) -> &Self::Machine {
         self.machine
     }
+
+    fn set_provenance(&mut self, provenance: ConstraintProvenance) {
+        self.provenance = Some(provenance);
+    }
+}
+
+impl<'a, M: Machine<SC::Val>, SC: StarkConfig> MainWindowBuilder for SymbolicAirBuilder<'a, M, SC> {
+    fn with_main_window(&mut self, columns: Range<usize>, f: impl FnOnce(&mut Self)) {
+        let window = self
+            .main
+            .values
+            .chunks(self.main.width())
+            .flat_map(|row| row[columns.clone()].to_vec())
+            .collect();
+        let main = core::mem::replace(&mut self.main, RowMajorMatrix::new(window, columns.len()));
+        f(self);
+        self.main = main;
+    }
 }
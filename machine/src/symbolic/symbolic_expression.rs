@@ -61,6 +61,33 @@ impl<F: Field> SymbolicExpression<F> {
     }
 }
 
+impl<F: Field> SymbolicExpression<F> {
+    /// Whether two expressions are the same tree: the same operations on the same variables and
+    /// constants, in the same order. Equal polynomials written differently are not structurally
+    /// equal.
+    pub(crate) fn structurally_eq(&self, other: &Self) -> bool {
+        use SymbolicExpression::*;
+        match (self, other) {
+            (Variable(a), Variable(b)) => {
+                core::mem::discriminant(&a.trace) == core::mem::discriminant(&b.trace)
+                    && a.is_next == b.is_next
+                    && a.column == b.column
+            }
+            (IsFirstRow, IsFirstRow) | (IsLastRow, IsLastRow) | (IsTransition, IsTransition) => {
+                true
+            }
+            (Constant(a), Constant(b)) => a == b,
+            (Add { x: x1, y: y1, .. }, Add { x: x2, y: y2, .. })
+            | (Sub { x: x1, y: y1, .. }, Sub { x: x2, y: y2, .. })
+            | (Mul { x: x1, y: y1, .. }, Mul { x: x2, y: y2, .. }) => {
+                x1.structurally_eq(x2) && y1.structurally_eq(y2)
+            }
+            (Neg { x: x1, .. }, Neg { x: x2, .. }) => x1.structurally_eq(x2),
+            _ => false,
+        }
+    }
+}
+
 impl<F: Field> Default for SymbolicExpression<F> {
     fn default() -> Self {
         Self::Constant(F::zero())
@@ -0,0 +1,92 @@
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+
+use crate::chip::gated_count;
+use crate::config::StarkConfig;
+use crate::{Chip, InteractionType, Machine};
+use p3_field::{AbstractField, PrimeField32};
+use p3_matrix::dense::RowMajorMatrix;
+use p3_matrix::{Matrix, MatrixRowSlices};
+
+/// A payload received twice by a chip which provides each entry once.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DuplicateEntry {
+    /// Index of the receive in `InteractionAir::all_interactions`.
+    pub interaction: usize,
+    /// The row where the payload was first received.
+    pub first_row: usize,
+    /// The row receiving it again.
+    pub row: usize,
+}
+
+/// Check that a chip declaring `Chip::provides_once` receives each payload on at most one row of
+/// its main trace. Rows with a zero count, such as padding, are ignored. Rotated fields are read
+/// from the next row, wrapping around on the last, see `Interaction::rotations`.
+pub fn check_provided_once<M, SC>(
+    machine: &M,
+    chip: &dyn Chip<M, SC>,
+    main: &RowMajorMatrix<SC::Val>,
+) -> Result<(), Vec<DuplicateEntry>>
+where
+    M: Machine<SC::Val>,
+    SC: StarkConfig,
+{
+    if !chip.provides_once() {
+        return Ok(());
+    }
+
+    let interactions = chip.all_interactions(machine);
+    let preprocessed = chip.preprocessed_trace();
+
+    let mut duplicates = Vec::new();
+    for (m, (interaction, interaction_type)) in interactions.iter().enumerate() {
+        match interaction_type {
+            InteractionType::LocalReceive | InteractionType::GlobalReceive => {}
+            InteractionType::LocalSend | InteractionType::GlobalSend => continue,
+        }
+
+        let mut seen = BTreeMap::<Vec<u32>, usize>::new();
+        let height = main.height();
+        for n in 0..height {
+            let n_next = (n + 1) % height;
+            let main_rows = [main.row_slice(n), main.row_slice(n_next)];
+            let preprocessed_rows = match &preprocessed {
+                Some(preprocessed) => [preprocessed.row_slice(n), preprocessed.row_slice(n_next)],
+                None => [&[][..], &[][..]],
+            };
+            let count =
+                interaction.count_at::<SC::Val, SC::Val>(preprocessed_rows[0], main_rows[0]);
+            let count = gated_count(chip.active_selector(), count, main_rows[0]);
+            if count == SC::Val::zero() {
+                continue;
+            }
+            let payload = interaction
+                .fields
+                .iter()
+                .enumerate()
+                .map(|(i, field)| {
+                    let rotation = interaction.field_rotation(i);
+                    field
+                        .apply::<SC::Val, SC::Val>(preprocessed_rows[rotation], main_rows[rotation])
+                        .as_canonical_u32()
+                })
+                .collect::<Vec<_>>();
+            match seen.get(&payload) {
+                Some(&first_row) => duplicates.push(DuplicateEntry {
+                    interaction: m,
+                    first_row,
+                    row: n,
+                }),
+                None => {
+                    seen.insert(payload, n);
+                }
+            }
+        }
+    }
+
+    if duplicates.is_empty() {
+        Ok(())
+    } else {
+        Err(duplicates)
+    }
+}
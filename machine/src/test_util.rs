@@ -0,0 +1,706 @@
+//! Helpers for testing a single chip without a full machine.
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+
+use crate::__internal::{
+    check_constraints, find_failing_constraint, get_chip_log_quotient_degree, quotient,
+};
+use crate::blinding::SplitMix64;
+use crate::chip::generate_dependent_permutation_traces;
+use crate::config::StarkConfig;
+use crate::fingerprint::Fingerprinter;
+use crate::program::ProgramROM;
+use crate::proof::{ChipProof, Commitments, MachineProof, OpenedValues};
+use crate::{
+    cumulative_sum_from_perm, generate_permutation_trace, num_permutation_challenges,
+    permutation_challenge_field, permutation_trace_width, verify_constraints, AdviceProvider, Chip,
+    ConstraintFailure, InteractionAir, InteractionLayout, LayoutMismatch, LookupBackend, Machine,
+    ProofShapeError, StoppingFlag, Transcript, VerificationError,
+};
+use p3_air::BaseAir;
+use p3_challenger::{CanObserve, FieldChallenger};
+use p3_commit::{Pcs, UnivariatePcs, UnivariatePcsWithLde};
+use p3_field::{AbstractExtensionField, AbstractField, Field, PrimeField32, TwoAdicField};
+use p3_matrix::dense::RowMajorMatrix;
+use p3_matrix::{Dimensions, Matrix};
+use p3_util::log2_strict_usize;
+
+/// A machine with no chips or state, for testing chips whose traces depend only on themselves.
+/// It does not execute programs. Its own proofs cover no chips; prove and verify chips with it
+/// through `prove_chips` and `verify_chips` instead.
+pub struct MockMachine<F> {
+    lookup_backend: LookupBackend,
+    public_values: Vec<F>,
+    #[cfg(feature = "trace-cache")]
+    trace_cache_state: Vec<u8>,
+    _phantom: PhantomData<F>,
+}
+
+impl<F> MockMachine<F> {
+    /// A mock machine using `backend` as its `Machine::lookup_backend`.
+    pub fn with_lookup_backend(backend: LookupBackend) -> Self {
+        Self {
+            lookup_backend: backend,
+            ..Self::default()
+        }
+    }
+
+    /// A mock machine with the given `Machine::public_values`.
+    pub fn with_public_values(public_values: Vec<F>) -> Self {
+        Self {
+            public_values,
+            ..Self::default()
+        }
+    }
+
+    /// A mock machine reporting `state` as its `Machine::trace_cache_state`, standing in for the
+    /// execution state of a real machine.
+    #[cfg(feature = "trace-cache")]
+    pub fn with_trace_cache_state(state: Vec<u8>) -> Self {
+        Self {
+            trace_cache_state: state,
+            ..Self::default()
+        }
+    }
+}
+
+impl<F> Default for MockMachine<F> {
+    fn default() -> Self {
+        Self {
+            lookup_backend: LookupBackend::default(),
+            public_values: Vec::new(),
+            #[cfg(feature = "trace-cache")]
+            trace_cache_state: Vec::new(),
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<F: Field> Machine<F> for MockMachine<F> {
+    fn run<Adv>(&mut self, _program: &ProgramROM<i32>, _advice: &mut Adv)
+    where
+        Adv: AdviceProvider,
+    {
+    }
+
+    fn step<Adv>(&mut self, _advice: &mut Adv) -> StoppingFlag
+    where
+        Adv: AdviceProvider,
+    {
+        StoppingFlag::DidStop
+    }
+
+    /// A proof of no chips: empty commitments for each round, opened at no points.
+    fn prove<SC>(&self, config: &SC) -> MachineProof<SC>
+    where
+        SC: StarkConfig<Val = F>,
+    {
+        let pcs = config.pcs();
+        let mut challenger = config.challenger();
+        let (main_trace, _) = pcs.commit_batches(vec![]);
+        let (perm_trace, _) = pcs.commit_batches(vec![]);
+        let (quotient_chunks, _) = pcs.commit_batches(vec![]);
+        let (_, opening_proof) = pcs.open_multi_batches(&[], &mut challenger);
+        MachineProof {
+            commitments: Commitments {
+                main_trace,
+                perm_trace,
+                quotient_chunks,
+            },
+            opening_proof,
+            chip_proofs: vec![],
+        }
+    }
+
+    /// Always fails: with no chips, there is no statement for a proof to establish. Use
+    /// `verify_chip` to verify the proof of a chip.
+    fn verify<SC>(&self, _config: &SC, _proof: &MachineProof<SC>) -> Result<(), ()>
+    where
+        SC: StarkConfig<Val = F>,
+    {
+        Err(())
+    }
+
+    fn public_values(&self) -> &[F] {
+        &self.public_values
+    }
+
+    fn lookup_backend(&self) -> LookupBackend {
+        self.lookup_backend
+    }
+
+    #[cfg(feature = "trace-cache")]
+    fn trace_cache_state(&self) -> Option<Vec<u8>> {
+        Some(self.trace_cache_state.clone())
+    }
+}
+
+/// Generate the main and permutation traces of a chip, check that the chip's constraints and its
+/// permutation constraints, including every reciprocal, hold on every row, and return the
+/// permutation trace with its cumulative sum. Panics first if the chip's verifier-side layout
+/// differs from its prover-side one, see `check_layout_consistency`.
+pub fn run_chip_permutation<M, C, SC>(
+    machine: &M,
+    chip: &C,
+    random_elements: &[SC::Challenge],
+) -> (RowMajorMatrix<SC::Challenge>, SC::Challenge)
+where
+    M: Machine<SC::Val>,
+    C: Chip<M, SC>,
+    SC: StarkConfig,
+{
+    let main = chip.generate_main_trace(machine);
+    if let Err(mismatch) = check_layout_consistency::<M, C, C, SC>(machine, chip, chip, &main) {
+        panic!("{}", mismatch);
+    }
+    let perm = generate_permutation_trace(machine, chip, &main, random_elements.to_vec());
+    check_constraints::<M, C, SC>(machine, chip, &main, &perm, random_elements);
+    let cumulative_sum = cumulative_sum_from_perm(&perm);
+    (perm, cumulative_sum)
+}
+
+/// Check that `verifier`, the view of `chip` which `eval_permutation_constraints` is given, lays
+/// out the permutation trace for `main` as `Chip::interaction_layout` does during trace
+/// generation, see `InteractionLayout::check_matches`.
+pub fn check_layout_consistency<M, C, A, SC>(
+    machine: &M,
+    chip: &C,
+    verifier: &A,
+    main: &RowMajorMatrix<SC::Val>,
+) -> Result<(), LayoutMismatch>
+where
+    M: Machine<SC::Val>,
+    C: Chip<M, SC>,
+    A: InteractionAir<M, SC>,
+    SC: StarkConfig,
+{
+    let preprocessed_width = verifier
+        .preprocessed_trace()
+        .map_or(0, |trace| trace.width());
+    let verifier_layout = InteractionLayout::new(
+        verifier.all_interactions(machine),
+        preprocessed_width,
+        main.width(),
+        &verifier.permutation_layout(),
+    );
+    chip.interaction_layout(machine).check_matches(
+        &verifier_layout,
+        preprocessed_width,
+        main.width(),
+    )
+}
+
+/// Check a chip end to end, as a prover and verifier would: generate its main trace, derive its
+/// permutation challenges from a transcript of it with `Machine::derive_permutation_challenges`,
+/// generate its permutation trace and evaluate its AIR and permutation constraints on every row.
+/// Returns the first constraint which does not hold, see `find_failing_constraint`, where
+/// `run_chip_permutation` panics.
+pub fn prove_and_verify_chip<M, C, SC>(chip: &C, machine: &M) -> Result<(), ConstraintFailure>
+where
+    M: Machine<SC::Val>,
+    C: Chip<M, SC>,
+    SC: StarkConfig,
+{
+    let main = chip.generate_main_trace(machine);
+    let random_elements = machine.derive_permutation_challenges(
+        &mut FingerprintTranscript::default(),
+        &[trace_fingerprint(&main)],
+        chip.num_permutation_challenges(machine),
+        chip.lookup_field(),
+    );
+    let perm = generate_permutation_trace(machine, chip, &main, random_elements.clone());
+    find_failing_constraint::<M, C, SC>(machine, chip, &main, &perm, &random_elements)
+}
+
+/// Prove `chip` alone, as the `Machine::prove` of a machine with this one chip does, see
+/// `prove_chips`.
+pub fn prove_chip<M, C, SC>(config: &SC, machine: &M, chip: &C) -> MachineProof<SC>
+where
+    M: Machine<SC::Val>,
+    C: Chip<M, SC>,
+    SC: StarkConfig,
+{
+    prove_chips(config, machine, core::slice::from_ref(chip))
+}
+
+/// Prove `chips` together, as the `Machine::prove` of a machine with these chips does: commit to
+/// their main traces, derive their shared permutation challenges, then commit to their
+/// permutation traces, generated in the order of `Chip::cumulative_sum_dependencies`, and their
+/// quotients and open them all at the out-of-domain point. Preprocessed traces are committed and
+/// opened as well, so chips with one are covered.
+pub fn prove_chips<M, C, SC>(config: &SC, machine: &M, chips: &[C]) -> MachineProof<SC>
+where
+    M: Machine<SC::Val>,
+    C: Chip<M, SC>,
+    SC: StarkConfig,
+{
+    let pcs = config.pcs();
+    let mut challenger = config.challenger();
+    let dyn_chips = chips
+        .iter()
+        .map(|chip| chip as &dyn Chip<M, SC>)
+        .collect::<Vec<_>>();
+    let log_quotient_degrees = chips
+        .iter()
+        .map(|chip| get_chip_log_quotient_degree::<M, SC, C>(machine, chip))
+        .collect::<Vec<_>>();
+
+    let preprocessed = chips
+        .iter()
+        .map(|chip| chip.preprocessed_trace())
+        .collect::<Vec<_>>();
+    let has_preprocessed = preprocessed.iter().map(Option::is_some).collect::<Vec<_>>();
+    let (preprocessed_commit, preprocessed_data) =
+        pcs.commit_batches(preprocessed.into_iter().flatten().collect());
+    challenger.observe(preprocessed_commit);
+    let mut preprocessed_ldes = pcs.get_ldes(&preprocessed_data).into_iter();
+
+    let mains = chips
+        .iter()
+        .map(|chip| {
+            let mut main = chip.generate_main_trace(machine);
+            chip.pad_trace(machine, &mut main);
+            main
+        })
+        .collect::<Vec<_>>();
+    let log_degrees = mains
+        .iter()
+        .map(|main| log2_strict_usize(main.height()))
+        .collect::<Vec<_>>();
+    let (main_commit, main_data) = pcs.commit_batches(mains.clone());
+    let main_ldes = pcs.get_ldes(&main_data);
+
+    let perm_challenges: Vec<SC::Challenge> = machine.derive_permutation_challenges(
+        &mut challenger,
+        &[main_commit.clone()],
+        num_permutation_challenges(machine, dyn_chips.iter().copied()),
+        permutation_challenge_field(dyn_chips.iter().copied()),
+    );
+    let dependencies = chips
+        .iter()
+        .map(|chip| chip.cumulative_sum_dependencies())
+        .collect::<Vec<_>>();
+    let (perms, cumulative_sums): (Vec<_>, Vec<_>) = generate_dependent_permutation_traces(
+        machine,
+        &dyn_chips,
+        &mains,
+        &perm_challenges,
+        &dependencies,
+    )
+    .into_iter()
+    .unzip();
+    let (perm_commit, perm_data) =
+        pcs.commit_batches(perms.iter().map(|perm| perm.flatten_to_base()).collect());
+    challenger.observe(perm_commit.clone());
+    let perm_ldes = pcs.get_ldes(&perm_data);
+
+    let alpha: SC::Challenge = challenger.sample_ext_element();
+    let quotients = chips
+        .iter()
+        .zip(main_ldes.into_iter().zip(perm_ldes))
+        .enumerate()
+        .map(|(i, (chip, (main_lde, perm_lde)))| {
+            let preprocessed_lde = has_preprocessed[i]
+                .then(|| preprocessed_ldes.next())
+                .flatten();
+            quotient(
+                machine,
+                config,
+                chip,
+                log_degrees[i],
+                preprocessed_lde,
+                main_lde,
+                perm_lde,
+                cumulative_sums[i],
+                &perm_challenges,
+                alpha,
+            )
+        })
+        .collect::<Vec<_>>();
+    let coset_shifts = log_quotient_degrees
+        .iter()
+        .map(|&log_quotient_degree| pcs.coset_shift().exp_power_of_2(log_quotient_degree))
+        .collect::<Vec<_>>();
+    let (quotient_commit, quotient_data) = pcs.commit_shifted_batches(quotients, &coset_shifts);
+    challenger.observe(quotient_commit.clone());
+
+    let zeta: SC::Challenge = challenger.sample_ext_element();
+    let zeta_and_next = log_degrees
+        .iter()
+        .map(|&log_degree| vec![zeta, zeta * SC::Val::two_adic_generator(log_degree)])
+        .collect::<Vec<_>>();
+    let zeta_exp_quotient_degree = log_quotient_degrees
+        .iter()
+        .map(|&log_quotient_degree| vec![zeta.exp_power_of_2(log_quotient_degree)])
+        .collect::<Vec<_>>();
+    let preprocessed_zeta_and_next = zeta_and_next
+        .iter()
+        .zip(&has_preprocessed)
+        .filter(|&(_, &has_preprocessed)| has_preprocessed)
+        .map(|(points, _)| points.clone())
+        .collect::<Vec<_>>();
+    let mut rounds = vec![
+        (&main_data, zeta_and_next.as_slice()),
+        (&perm_data, zeta_and_next.as_slice()),
+        (&quotient_data, zeta_exp_quotient_degree.as_slice()),
+    ];
+    if !preprocessed_zeta_and_next.is_empty() {
+        rounds.push((&preprocessed_data, preprocessed_zeta_and_next.as_slice()));
+    }
+    let (mut openings, opening_proof) = pcs.open_multi_batches(&rounds, &mut challenger);
+
+    let mut preprocessed_openings = if preprocessed_zeta_and_next.is_empty() {
+        vec![]
+    } else {
+        openings.pop().expect("Should have a preprocessed round")
+    }
+    .into_iter();
+    let [main, perm, quotient_chunks]: [Vec<Vec<Vec<SC::Challenge>>>; 3] = openings
+        .try_into()
+        .unwrap_or_else(|_| panic!("Should have 3 rounds of openings"));
+    let mut chip_proofs = vec![];
+    for (i, ((main, perm), quotient_chunks)) in
+        main.into_iter().zip(perm).zip(quotient_chunks).enumerate()
+    {
+        let [preprocessed_local, preprocessed_next] = if has_preprocessed[i] {
+            preprocessed_openings
+                .next()
+                .expect("Should have 1 preprocessed trace per chip with one")
+                .try_into()
+                .expect("Should have 2 openings")
+        } else {
+            [vec![], vec![]]
+        };
+        let [trace_local, trace_next] = main.try_into().expect("Should have 2 openings");
+        let [permutation_local, permutation_next] =
+            perm.try_into().expect("Should have 2 openings");
+        let [quotient_chunks] = quotient_chunks.try_into().expect("Should have 1 opening");
+        chip_proofs.push(ChipProof {
+            log_degree: log_degrees[i],
+            opened_values: OpenedValues {
+                preprocessed_local,
+                preprocessed_next,
+                trace_local,
+                trace_next,
+                permutation_local,
+                permutation_next,
+                quotient_chunks,
+            },
+            cumulative_sum: cumulative_sums[i],
+        });
+    }
+    assert_eq!(
+        chip_proofs.len(),
+        chips.len(),
+        "Should have 1 matrix per chip"
+    );
+
+    MachineProof {
+        commitments: Commitments {
+            main_trace: main_commit,
+            perm_trace: perm_commit,
+            quotient_chunks: quotient_commit,
+        },
+        opening_proof,
+        chip_proofs,
+    }
+}
+
+/// Verify a proof of `chip` from `prove_chip`, see `verify_chips`.
+pub fn verify_chip<M, C, SC>(
+    config: &SC,
+    machine: &M,
+    chip: &C,
+    proof: &MachineProof<SC>,
+) -> Result<(), VerificationError>
+where
+    M: Machine<SC::Val>,
+    C: Chip<M, SC>,
+    SC: StarkConfig,
+{
+    verify_chips(config, machine, core::slice::from_ref(chip), proof)
+}
+
+/// Verify a proof of `chips` from `prove_chips`: replay its transcript, check the openings against
+/// the commitments and each chip's constraints at the out-of-domain point. The cumulative sums are
+/// not checked, since the chips need not balance their global buses; see
+/// `verify_aggregate_cumulative_sum` to check them.
+pub fn verify_chips<M, C, SC>(
+    config: &SC,
+    machine: &M,
+    chips: &[C],
+    proof: &MachineProof<SC>,
+) -> Result<(), VerificationError>
+where
+    M: Machine<SC::Val>,
+    C: Chip<M, SC>,
+    SC: StarkConfig,
+{
+    let pcs = config.pcs();
+    let mut challenger = config.challenger();
+    if proof.chip_proofs.len() != chips.len() {
+        return Err(VerificationError::InvalidProofShape(
+            ProofShapeError::MainTrace,
+        ));
+    }
+    let dyn_chips = chips
+        .iter()
+        .map(|chip| chip as &dyn Chip<M, SC>)
+        .collect::<Vec<_>>();
+    let log_quotient_degrees = chips
+        .iter()
+        .map(|chip| get_chip_log_quotient_degree::<M, SC, C>(machine, chip))
+        .collect::<Vec<_>>();
+    let Commitments {
+        main_trace,
+        perm_trace,
+        quotient_chunks,
+    } = &proof.commitments;
+
+    let preprocessed = chips
+        .iter()
+        .map(|chip| chip.preprocessed_trace())
+        .collect::<Vec<_>>();
+    let preprocessed_widths = preprocessed
+        .iter()
+        .map(|trace| trace.as_ref().map(|trace| trace.width()))
+        .collect::<Vec<_>>();
+    let (preprocessed_commit, _) = pcs.commit_batches(preprocessed.into_iter().flatten().collect());
+    challenger.observe(preprocessed_commit.clone());
+
+    let perm_challenges: Vec<SC::Challenge> = machine.derive_permutation_challenges(
+        &mut challenger,
+        &[main_trace.clone()],
+        num_permutation_challenges(machine, dyn_chips.iter().copied()),
+        permutation_challenge_field(dyn_chips.iter().copied()),
+    );
+    challenger.observe(perm_trace.clone());
+    let alpha: SC::Challenge = challenger.sample_ext_element();
+    challenger.observe(quotient_chunks.clone());
+
+    let zeta: SC::Challenge = challenger.sample_ext_element();
+    let gs = proof
+        .chip_proofs
+        .iter()
+        .map(|chip_proof| SC::Val::two_adic_generator(chip_proof.log_degree))
+        .collect::<Vec<_>>();
+    let zeta_and_next = gs.iter().map(|&g| vec![zeta, zeta * g]).collect::<Vec<_>>();
+    let zeta_exp_quotient_degree = log_quotient_degrees
+        .iter()
+        .map(|&log_quotient_degree| vec![zeta.exp_power_of_2(log_quotient_degree)])
+        .collect::<Vec<_>>();
+    let preprocessed_zeta_and_next = zeta_and_next
+        .iter()
+        .zip(&preprocessed_widths)
+        .filter(|(_, width)| width.is_some())
+        .map(|(points, _)| points.clone())
+        .collect::<Vec<_>>();
+    let mut rounds = vec![
+        (main_trace.clone(), zeta_and_next.as_slice()),
+        (perm_trace.clone(), zeta_and_next.as_slice()),
+        (quotient_chunks.clone(), zeta_exp_quotient_degree.as_slice()),
+    ];
+    let dimensions = |widths: Vec<usize>| {
+        widths
+            .into_iter()
+            .zip(&proof.chip_proofs)
+            .map(|(width, chip_proof)| Dimensions {
+                width,
+                height: 1 << chip_proof.log_degree,
+            })
+            .collect::<Vec<_>>()
+    };
+    let mut dims = vec![
+        dimensions(chips.iter().map(|chip| chip.trace_width()).collect()),
+        dimensions(
+            chips
+                .iter()
+                .map(|chip| permutation_trace_width::<M, SC, _>(machine, chip) * SC::Challenge::D)
+                .collect(),
+        ),
+        dimensions(
+            log_quotient_degrees
+                .iter()
+                .map(|&log_quotient_degree| (1 << log_quotient_degree) * SC::Challenge::D)
+                .collect(),
+        ),
+    ];
+    let opened_values = proof
+        .chip_proofs
+        .iter()
+        .map(|chip_proof| &chip_proof.opened_values)
+        .collect::<Vec<_>>();
+    let mut values = vec![
+        opened_values
+            .iter()
+            .map(|opened| vec![opened.trace_local.clone(), opened.trace_next.clone()])
+            .collect::<Vec<_>>(),
+        opened_values
+            .iter()
+            .map(|opened| {
+                vec![
+                    opened.permutation_local.clone(),
+                    opened.permutation_next.clone(),
+                ]
+            })
+            .collect(),
+        opened_values
+            .iter()
+            .map(|opened| vec![opened.quotient_chunks.clone()])
+            .collect(),
+    ];
+    if !preprocessed_zeta_and_next.is_empty() {
+        rounds.push((preprocessed_commit, preprocessed_zeta_and_next.as_slice()));
+        dims.push(
+            preprocessed_widths
+                .iter()
+                .zip(&proof.chip_proofs)
+                .filter_map(|(width, chip_proof)| {
+                    width.map(|width| Dimensions {
+                        width,
+                        height: 1 << chip_proof.log_degree,
+                    })
+                })
+                .collect(),
+        );
+        values.push(
+            preprocessed_widths
+                .iter()
+                .zip(&opened_values)
+                .filter(|(width, _)| width.is_some())
+                .map(|(_, opened)| {
+                    vec![
+                        opened.preprocessed_local.clone(),
+                        opened.preprocessed_next.clone(),
+                    ]
+                })
+                .collect(),
+        );
+    }
+    pcs.verify_multi_batches(
+        &rounds,
+        &dims,
+        values,
+        &proof.opening_proof,
+        &mut challenger,
+    )
+    .map_err(|_| VerificationError::InvalidOpenningArgument)?;
+
+    for ((chip, chip_proof), g) in chips.iter().zip(&proof.chip_proofs).zip(gs) {
+        verify_constraints::<M, C, SC>(
+            machine,
+            chip,
+            &chip_proof.opened_values,
+            chip_proof.cumulative_sum,
+            chip_proof.log_degree,
+            g,
+            zeta,
+            alpha,
+            &perm_challenges,
+        )
+        .map_err(|_| VerificationError::OodEvaluationMismatch)?;
+    }
+    Ok(())
+}
+
+/// A transcript observing fingerprints of main traces, which samples each challenge by hashing
+/// everything observed so far. It is deterministic, but offers none of the soundness of a
+/// `StarkConfig` challenger, see `interaction_fingerprint`.
+#[derive(Default)]
+struct FingerprintTranscript {
+    observed: Vec<u8>,
+    samples: u32,
+}
+
+impl<F: PrimeField32> Transcript<F, [u8; 32]> for FingerprintTranscript {
+    fn observe_commitment(&mut self, commitment: [u8; 32]) {
+        self.observed.extend(commitment);
+    }
+
+    fn observe_public_values(&mut self, values: &[F]) {
+        for value in values {
+            self.observed.extend(value.as_canonical_u32().to_le_bytes());
+        }
+    }
+
+    fn sample_base_element(&mut self) -> F {
+        let coefficient = self.coefficient(0);
+        self.samples += 1;
+        coefficient
+    }
+
+    fn sample_ext_element<EF: AbstractExtensionField<F>>(&mut self) -> EF {
+        let coefficients = (0..EF::D).map(|i| self.coefficient(i)).collect::<Vec<_>>();
+        self.samples += 1;
+        EF::from_base_slice(&coefficients)
+    }
+}
+
+impl FingerprintTranscript {
+    /// The `i`th coefficient of the next sample, hashed from everything observed so far.
+    fn coefficient<F: PrimeField32>(&self, i: usize) -> F {
+        let mut hasher = Fingerprinter::new();
+        hasher.write_bytes(&self.observed);
+        hasher.write(self.samples);
+        hasher.write(i as u32);
+        let digest = hasher.finish();
+        F::from_wrapped_u32(u32::from_le_bytes([
+            digest[0], digest[1], digest[2], digest[3],
+        ]))
+    }
+}
+
+fn trace_fingerprint<F: PrimeField32>(trace: &RowMajorMatrix<F>) -> [u8; 32] {
+    let mut hasher = Fingerprinter::new();
+    hasher.write(trace.width() as u32);
+    for value in &trace.values {
+        hasher.write(value.as_canonical_u32());
+    }
+    hasher.finish()
+}
+
+/// A deterministic stream of challenges, so that tests and benchmarks pass the same
+/// `random_elements` on every run.
+///
+/// For tests and benchmarks only: a seeded source knows nothing of the traces, so a prover using
+/// it could choose its traces knowing the challenges. Challenges for a proof must be derived from
+/// the transcript with `Machine::derive_permutation_challenges`.
+pub trait ChallengeSource<F: Field> {
+    fn from_seed(seed: u64) -> Self;
+
+    fn sample<EF: AbstractExtensionField<F>>(&mut self) -> EF;
+}
+
+/// A `ChallengeSource` built on the SplitMix64 generator, drawing each coefficient of a challenge
+/// from the high bits of its next output.
+pub struct SeededChallenges {
+    rng: SplitMix64,
+}
+
+impl<F: PrimeField32> ChallengeSource<F> for SeededChallenges {
+    fn from_seed(seed: u64) -> Self {
+        Self {
+            rng: SplitMix64(seed),
+        }
+    }
+
+    fn sample<EF: AbstractExtensionField<F>>(&mut self) -> EF {
+        let coefficients = (0..EF::D)
+            .map(|_| F::from_wrapped_u32(self.rng.next_u32()))
+            .collect::<Vec<_>>();
+        EF::from_base_slice(&coefficients)
+    }
+}
+
+/// The next `n` challenges of `source`, e.g. the `random_elements` of a chip, see
+/// `InteractionAir::num_permutation_challenges`.
+pub fn sample_permutation_challenges<F, EF, S>(source: &mut S, n: usize) -> Vec<EF>
+where
+    F: Field,
+    EF: AbstractExtensionField<F>,
+    S: ChallengeSource<F>,
+{
+    (0..n).map(|_| source.sample()).collect()
+}
@@ -0,0 +1,188 @@
+//! Caching of generated main traces on disk, see `generate_or_load_trace`.
+//!
+//! A cached trace is stored with the key it was generated under, and is only reused while the key
+//! still matches. The key covers the machine's `Machine::trace_cache_state`, the chip type, its
+//! interaction fingerprint and its trace width, so a changed program, input or chip interface
+//! causes a regeneration. Anything else a chip's trace depends on must be part of the machine's
+//! state.
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::any::type_name;
+use core::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use crate::config::StarkConfig;
+use crate::fingerprint::Fingerprinter;
+use crate::{Chip, Machine};
+use p3_field::PrimeField32;
+use p3_matrix::dense::RowMajorMatrix;
+use p3_matrix::Matrix;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "tracing")]
+use tracing::warn;
+
+/// Bumped whenever the cache format or key derivation changes, so old caches are ignored.
+const TRACE_CACHE_VERSION: u32 = 1;
+
+/// A trace as stored on disk, with its values in canonical form.
+#[derive(Serialize, Deserialize)]
+struct CachedTrace {
+    key: [u8; 32],
+    width: usize,
+    values: Vec<u32>,
+}
+
+#[derive(Debug)]
+pub enum TraceCacheError {
+    Io(std::io::Error),
+    /// The cache file could not be encoded or decoded.
+    Encoding(String),
+    /// The trace was cached under a different key.
+    StaleKey,
+    /// The cached values do not form a trace over the field.
+    InvalidTrace,
+}
+
+impl Display for TraceCacheError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            TraceCacheError::Io(err) => write!(f, "trace cache I/O error: {}", err),
+            TraceCacheError::Encoding(err) => write!(f, "trace cache encoding error: {}", err),
+            TraceCacheError::StaleKey => write!(f, "cached trace has a stale key"),
+            TraceCacheError::InvalidTrace => write!(f, "cached trace is invalid"),
+        }
+    }
+}
+
+impl From<std::io::Error> for TraceCacheError {
+    fn from(err: std::io::Error) -> Self {
+        TraceCacheError::Io(err)
+    }
+}
+
+/// The key a chip's trace is cached under, or `None` if the machine does not report its state.
+pub fn trace_cache_key<M, SC, C>(machine: &M, chip: &C) -> Option<[u8; 32]>
+where
+    M: Machine<SC::Val>,
+    SC: StarkConfig,
+    C: Chip<M, SC> + ?Sized,
+{
+    let state = machine.trace_cache_state()?;
+    let mut hasher = Fingerprinter::new();
+    hasher.write(TRACE_CACHE_VERSION);
+    hasher.write_bytes(&state);
+    hasher.write_bytes(type_name::<C>().as_bytes());
+    hasher.write_bytes(&chip.interaction_fingerprint(machine));
+    hasher.write(chip.trace_width() as u32);
+    Some(hasher.finish())
+}
+
+/// Serialize a trace with the key it was generated under.
+pub fn write_trace<F: PrimeField32, W: Write>(
+    trace: &RowMajorMatrix<F>,
+    key: [u8; 32],
+    writer: W,
+) -> Result<(), TraceCacheError> {
+    let cached = CachedTrace {
+        key,
+        width: trace.width(),
+        values: trace.values.iter().map(|x| x.as_canonical_u32()).collect(),
+    };
+    ciborium::into_writer(&cached, writer)
+        .map_err(|err| TraceCacheError::Encoding(format!("{:?}", err)))
+}
+
+/// Deserialize a trace written by `write_trace`, checking that it was written under `key`.
+pub fn read_trace<F: PrimeField32, R: Read>(
+    reader: R,
+    key: [u8; 32],
+) -> Result<RowMajorMatrix<F>, TraceCacheError> {
+    let cached: CachedTrace = ciborium::from_reader(reader)
+        .map_err(|err| TraceCacheError::Encoding(format!("{:?}", err)))?;
+    if cached.key != key {
+        return Err(TraceCacheError::StaleKey);
+    }
+    if cached.width == 0
+        || cached.values.len() % cached.width != 0
+        || cached.values.iter().any(|&x| x >= F::ORDER_U32)
+    {
+        return Err(TraceCacheError::InvalidTrace);
+    }
+    let values = cached
+        .values
+        .into_iter()
+        .map(F::from_canonical_u32)
+        .collect();
+    Ok(RowMajorMatrix::new(values, cached.width))
+}
+
+/// Load a chip's main trace from `path` if it was cached there under the current
+/// `trace_cache_key`, and otherwise generate it and cache it at `path`. A machine without a
+/// `Machine::trace_cache_state` always generates. Failing to write the cache is logged, not
+/// returned, since the generated trace is still good.
+pub fn generate_or_load_trace<M, SC, C>(
+    machine: &M,
+    chip: &C,
+    path: &Path,
+) -> RowMajorMatrix<SC::Val>
+where
+    M: Machine<SC::Val>,
+    SC: StarkConfig,
+    C: Chip<M, SC> + ?Sized,
+{
+    let Some(key) = trace_cache_key::<M, SC, C>(machine, chip) else {
+        return chip.generate_main_trace(machine);
+    };
+    let cached = File::open(path)
+        .map_err(TraceCacheError::from)
+        .and_then(|file| read_trace(BufReader::new(file), key));
+    if let Ok(trace) = cached {
+        return trace;
+    }
+
+    let trace = chip.generate_main_trace(machine);
+    let written = File::create(path)
+        .map_err(TraceCacheError::from)
+        .and_then(|file| {
+            let mut writer = BufWriter::new(file);
+            write_trace(&trace, key, &mut writer)?;
+            Ok(writer.flush()?)
+        });
+    #[cfg(feature = "tracing")]
+    if let Err(err) = written {
+        warn!("failed to cache trace at {}: {}", path.display(), err);
+    }
+    #[cfg(not(feature = "tracing"))]
+    let _ = written;
+    trace
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use p3_field::AbstractField;
+
+    type F = p3_baby_bear::BabyBear;
+
+    #[test]
+    fn trace_round_trip() {
+        let trace = RowMajorMatrix::new(
+            (0..12).map(|x| F::from_canonical_u32(x * 1000)).collect(),
+            3,
+        );
+        let mut bytes = Vec::new();
+        write_trace(&trace, [7; 32], &mut bytes).unwrap();
+
+        let read: RowMajorMatrix<F> = read_trace(bytes.as_slice(), [7; 32]).unwrap();
+        assert_eq!(read.width(), 3);
+        assert_eq!(read.values, trace.values);
+        assert!(matches!(
+            read_trace::<F, _>(bytes.as_slice(), [8; 32]),
+            Err(TraceCacheError::StaleKey)
+        ));
+    }
+}
@@ -0,0 +1,38 @@
+use p3_challenger::{CanObserve, CanSample, FieldChallenger};
+use p3_field::{AbstractExtensionField, Field};
+
+/// The Fiat-Shamir transcript the permutation challenges are drawn from, see
+/// `Machine::derive_permutation_challenges`. Every challenger of a `StarkConfig` is one.
+pub trait Transcript<F: Field, Commitment> {
+    fn observe_commitment(&mut self, commitment: Commitment);
+
+    fn observe_public_values(&mut self, values: &[F]);
+
+    fn sample_base_element(&mut self) -> F;
+
+    fn sample_ext_element<EF: AbstractExtensionField<F>>(&mut self) -> EF;
+}
+
+impl<F, Commitment, C> Transcript<F, Commitment> for C
+where
+    F: Field,
+    C: FieldChallenger<F> + CanObserve<Commitment>,
+{
+    fn observe_commitment(&mut self, commitment: Commitment) {
+        CanObserve::<Commitment>::observe(self, commitment);
+    }
+
+    fn observe_public_values(&mut self, values: &[F]) {
+        for &value in values {
+            CanObserve::<F>::observe(self, value);
+        }
+    }
+
+    fn sample_base_element(&mut self) -> F {
+        CanSample::<F>::sample(self)
+    }
+
+    fn sample_ext_element<EF: AbstractExtensionField<F>>(&mut self) -> EF {
+        FieldChallenger::sample_ext_element(self)
+    }
+}
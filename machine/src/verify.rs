@@ -5,7 +5,8 @@ use p3_util::reverse_slice_index_bits;
 
 use crate::folding_builder::VerifierConstraintFolder;
 use crate::{
-    eval_permutation_constraints, Chip, Machine, OodEvaluationMismatch, OpenedValues, StarkConfig,
+    eval_permutation_constraints, permutation_trace_width, Chip, Machine, OodEvaluationMismatch,
+    OpenedValues, StarkConfig,
 };
 
 pub fn verify_constraints<M, C, SC>(
@@ -105,3 +106,61 @@ where
         false => Err(OodEvaluationMismatch),
     }
 }
+
+/// Check the claimed cumulative sum of every chip at once against its permutation trace opened
+/// at the last row, whose final entry is the running sum. Returns the indices of the chips whose
+/// opened row has the wrong width or whose running sum differs from the claim.
+///
+/// `opened_perms[i]` holds the extension elements of chip `i`'s last permutation row.
+pub fn verify_cumulative_sums<M, SC>(
+    chips: &[&dyn Chip<M, SC>],
+    machine: &M,
+    claimed: &[SC::Challenge],
+    opened_perms: &[Vec<SC::Challenge>],
+) -> Result<(), Vec<usize>>
+where
+    M: Machine<SC::Val>,
+    SC: StarkConfig,
+{
+    assert_eq!(chips.len(), claimed.len());
+    assert_eq!(chips.len(), opened_perms.len());
+
+    let mismatches = chips
+        .iter()
+        .zip(claimed.iter().zip(opened_perms))
+        .enumerate()
+        .filter(|(_, (chip, (claimed, perm)))| {
+            perm.len() != permutation_trace_width::<M, SC, _>(machine, **chip)
+                || perm.last() != Some(claimed)
+        })
+        .map(|(i, _)| i)
+        .collect::<Vec<_>>();
+    if mismatches.is_empty() {
+        Ok(())
+    } else {
+        Err(mismatches)
+    }
+}
+
+/// Check the soundness condition of global lookups: the cumulative sums of all chips, combined by
+/// `Machine::aggregate_cumulative_sum`, must net to zero. The boundary constraints of each chip
+/// only bind its running sum to its own claimed cumulative sum, so a send on a global bus is
+/// matched by a receive in another chip through this check alone. Unlike `check_global_balance`,
+/// which compares multiplicities, it works on the challenge dependent sums the verifier sees.
+///
+/// The sums in `per_chip` must be in the sends positive convention, see `SignConvention::orient`.
+///
+/// Returns the nonzero total if the check fails.
+pub fn verify_aggregate_cumulative_sum<M, F, EF>(machine: &M, per_chip: &[EF]) -> Result<(), EF>
+where
+    M: Machine<F>,
+    F: Field,
+    EF: Field,
+{
+    let sum = machine.aggregate_cumulative_sum(per_chip);
+    if sum.is_zero() {
+        Ok(())
+    } else {
+        Err(sum)
+    }
+}
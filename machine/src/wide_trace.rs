@@ -0,0 +1,216 @@
+//! Batching of equal-height main traces into one wide matrix, so that several chips can share a
+//! single commitment.
+//!
+//! Every trace must have the same height. The columns of the `i`th trace start at `offsets[i]` in
+//! the wide matrix, and `Interaction::rebased` shifts an interaction's main columns accordingly.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::Interaction;
+use p3_air::{PairCol, VirtualPairCol};
+use p3_field::Field;
+use p3_matrix::dense::RowMajorMatrix;
+use p3_matrix::{Matrix, MatrixRowSlices};
+
+/// Concatenate equal-height main traces horizontally. Returns the wide matrix and the column
+/// offset of each trace in it.
+pub fn concat_main_traces<F: Field>(
+    traces: &[RowMajorMatrix<F>],
+) -> (RowMajorMatrix<F>, Vec<usize>) {
+    let height = traces.first().map_or(0, |trace| trace.height());
+    assert!(
+        traces.iter().all(|trace| trace.height() == height),
+        "batched main traces must have equal heights"
+    );
+
+    let mut offsets = Vec::with_capacity(traces.len());
+    let mut width = 0;
+    for trace in traces {
+        offsets.push(width);
+        width += trace.width();
+    }
+
+    let mut values = Vec::with_capacity(height * width);
+    for n in 0..height {
+        for trace in traces {
+            values.extend_from_slice(trace.row_slice(n));
+        }
+    }
+    (RowMajorMatrix::new(values, width), offsets)
+}
+
+/// The nonzero weight of each column `column` depends on, preprocessed columns first and each in
+/// column order, and its constant term.
+///
+/// `VirtualPairCol` is affine, so its weights are recovered by evaluating it on unit rows. Weights
+/// of a repeated column are thereby merged, and those which cancel out are dropped.
+pub fn column_terms<F: Field>(
+    column: &VirtualPairCol<F>,
+    preprocessed_width: usize,
+    main_width: usize,
+) -> (Vec<(PairCol, F)>, F) {
+    let mut preprocessed_row = vec![F::zero(); preprocessed_width];
+    let mut main_row = vec![F::zero(); main_width];
+    let constant = column.apply::<F, F>(&preprocessed_row, &main_row);
+
+    let mut column_weights = Vec::new();
+    for i in 0..preprocessed_width {
+        preprocessed_row[i] = F::one();
+        let weight = column.apply::<F, F>(&preprocessed_row, &main_row) - constant;
+        preprocessed_row[i] = F::zero();
+        if !weight.is_zero() {
+            column_weights.push((PairCol::Preprocessed(i), weight));
+        }
+    }
+    for j in 0..main_width {
+        main_row[j] = F::one();
+        let weight = column.apply::<F, F>(&preprocessed_row, &main_row) - constant;
+        main_row[j] = F::zero();
+        if !weight.is_zero() {
+            column_weights.push((PairCol::Main(j), weight));
+        }
+    }
+    (column_weights, constant)
+}
+
+/// `column` with one nonzero weight per column it depends on and a single constant, see
+/// `column_terms`. It evaluates like `column` on rows of the given widths, with fewer terms for
+/// the permutation constraints to fold when `column` repeats columns or has zero weights.
+pub fn simplify_column<F: Field>(
+    column: &VirtualPairCol<F>,
+    preprocessed_width: usize,
+    main_width: usize,
+) -> VirtualPairCol<F> {
+    rebase_column(column, preprocessed_width, main_width, 0)
+}
+
+/// Shift the main columns of `column` by `main_offset`, leaving preprocessed columns in place.
+/// The result is simplified as by `simplify_column`.
+pub fn rebase_column<F: Field>(
+    column: &VirtualPairCol<F>,
+    preprocessed_width: usize,
+    main_width: usize,
+    main_offset: usize,
+) -> VirtualPairCol<F> {
+    let (mut column_weights, constant) = column_terms(column, preprocessed_width, main_width);
+    for (col, _) in &mut column_weights {
+        if let PairCol::Main(j) = col {
+            *j += main_offset;
+        }
+    }
+    VirtualPairCol::new(column_weights, constant)
+}
+
+impl<F: Field> Interaction<F> {
+    /// This interaction with its main columns shifted by `main_offset`, for a chip whose main
+    /// trace was batched by `concat_main_traces`.
+    pub fn rebased(
+        &self,
+        preprocessed_width: usize,
+        main_width: usize,
+        main_offset: usize,
+    ) -> Self {
+        let rebase = |column| rebase_column(column, preprocessed_width, main_width, main_offset);
+        Self {
+            fields: self.fields.iter().map(rebase).collect(),
+            count: rebase(&self.count),
+            argument_index: self.argument_index,
+            rotations: self.rotations.clone(),
+            count_bound: self.count_bound,
+            is_real: self.is_real.as_ref().map(rebase),
+            limb_order: self.limb_order,
+        }
+    }
+
+    /// This interaction with every column simplified, see `simplify_column`.
+    pub fn simplified(&self, preprocessed_width: usize, main_width: usize) -> Self {
+        self.rebased(preprocessed_width, main_width, 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BusArgument, CountBound};
+    use p3_field::AbstractField;
+
+    type F = p3_baby_bear::BabyBear;
+
+    #[test]
+    fn concat_two_chips() {
+        let a = RowMajorMatrix::new((0..24).map(F::from_canonical_u32).collect(), 3);
+        let b = RowMajorMatrix::new((100..132).map(F::from_canonical_u32).collect(), 4);
+        let (wide, offsets) = concat_main_traces(&[a, b.clone()]);
+        assert_eq!(wide.width(), 7);
+        assert_eq!(wide.height(), 8);
+        assert_eq!(offsets, vec![0, 3]);
+
+        let interaction = Interaction::<F> {
+            fields: vec![
+                VirtualPairCol::single_main(1),
+                VirtualPairCol::new_main(vec![(0, F::two()), (3, F::one())], F::one()),
+            ],
+            count: VirtualPairCol::single_main(2),
+            argument_index: BusArgument::Global(0),
+            rotations: vec![],
+            count_bound: CountBound::Unbounded,
+            is_real: None,
+            limb_order: None,
+        };
+        let rebased = interaction.rebased(0, b.width(), offsets[1]);
+
+        for n in 0..8 {
+            let eval = |interaction: &Interaction<F>, row: &[F]| {
+                interaction
+                    .fields
+                    .iter()
+                    .chain([&interaction.count])
+                    .map(|column| column.apply::<F, F>(&[], row))
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(
+                eval(&rebased, wide.row_slice(n)),
+                eval(&interaction, b.row_slice(n))
+            );
+        }
+    }
+
+    #[test]
+    fn simplified_columns_evaluate_alike() {
+        let column = VirtualPairCol::new(
+            vec![
+                (PairCol::Main(0), F::two()),
+                (PairCol::Main(1), F::zero()),
+                (PairCol::Preprocessed(1), F::one()),
+                (PairCol::Main(0), F::from_canonical_u32(3)),
+                (PairCol::Main(2), F::one()),
+                (PairCol::Main(3), F::one()),
+                (PairCol::Main(3), -F::one()),
+                (PairCol::Preprocessed(1), F::two()),
+            ],
+            F::from_canonical_u32(7),
+        );
+        let simplified = simplify_column(&column, 2, 4);
+
+        let (column_weights, constant) = column_terms(&simplified, 2, 4);
+        assert_eq!(column_weights.len(), 3);
+        assert!(column_weights.iter().all(|(_, weight)| !weight.is_zero()));
+        assert_eq!(constant, F::from_canonical_u32(7));
+
+        // Pseudorandom rows.
+        let mut x = F::from_canonical_u32(0x1234_5678);
+        let mut next = || {
+            x = x * x + F::from_canonical_u32(0x9e37_79b9);
+            x
+        };
+        for _ in 0..64 {
+            let preprocessed_row = [next(), next()];
+            let main_row = [next(), next(), next(), next()];
+            assert_eq!(
+                simplified.apply::<F, F>(&preprocessed_row, &main_row),
+                column.apply::<F, F>(&preprocessed_row, &main_row)
+            );
+        }
+    }
+}
@@ -0,0 +1,152 @@
+use alloc::vec::Vec;
+
+use crate::bundle::bundle_degree_budget;
+use crate::chip::{
+    beta_powers, expect_independent_challenges, generate_permutation_trace_of,
+    generate_rlc_elements, reduce_row,
+};
+use crate::config::StarkConfig;
+use crate::lookup_field::LookupField;
+use crate::{Chip, Interaction, InteractionType, Machine};
+use p3_field::{ExtensionField, Field};
+use p3_matrix::dense::RowMajorMatrix;
+use p3_matrix::{Matrix, MatrixRowSlices};
+
+/// How permutation trace generation treats a reciprocal denominator
+/// `\alpha + \sum_j \beta^j f_j` which is zero.
+///
+/// No reciprocal of zero exists, so a trace with a zero denominator cannot satisfy the reciprocal
+/// constraint whichever policy is chosen. The policies differ in how the failure surfaces. Neither
+/// changes the challenges, which the verifier derives from the transcript on its own, so a zero
+/// denominator can only be avoided by a different main trace.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum ZeroPolicy {
+    /// Fail with the row and interaction of the first zero denominator.
+    Panic,
+    /// Invert with `batch_multiplicative_inverse_allowing_zero`, leaving the reciprocal zero, as
+    /// `generate_permutation_trace` does. Its debug reciprocal check, which panics on the zero, is
+    /// skipped, so the trace is returned in debug builds too and fails at verification instead.
+    #[default]
+    SkipAsZero,
+}
+
+/// Generate a chip's permutation trace, handling zero denominators according to `policy`. Only
+/// the default reciprocal construction of `generate_permutation_trace` is covered, so chips with a
+/// base field lookup or with bundled reciprocals panic, and a `Chip::generate_permutation_trace`
+/// override is not consulted.
+pub fn generate_permutation_trace_with_zero_policy<M, SC>(
+    machine: &M,
+    chip: &dyn Chip<M, SC>,
+    main: &RowMajorMatrix<SC::Val>,
+    random_elements: Vec<SC::Challenge>,
+    policy: ZeroPolicy,
+) -> RowMajorMatrix<SC::Challenge>
+where
+    M: Machine<SC::Val>,
+    SC: StarkConfig,
+{
+    assert!(
+        chip.lookup_field() == LookupField::Extension
+            && bundle_degree_budget::<M, SC, _>(machine, chip).is_none(),
+        "zero policies only cover one reciprocal column per interaction"
+    );
+    expect_independent_challenges(&random_elements);
+    let layout = chip.interaction_layout(machine);
+    if policy == ZeroPolicy::Panic {
+        let (alphas_local, alphas_global) =
+            generate_rlc_elements::<M, SC, _>(machine, chip, &random_elements);
+        let zero = find_zero_denominator(
+            &layout.interactions,
+            chip.preprocessed_trace().as_ref(),
+            main,
+            &alphas_local,
+            &alphas_global,
+            &beta_powers(random_elements[2], &layout.interactions),
+        );
+        if let Some((row, m)) = zero {
+            panic!("interaction {} has a zero denominator on row {}", m, row);
+        }
+    }
+
+    generate_permutation_trace_of(
+        machine,
+        chip,
+        main,
+        random_elements,
+        &layout,
+        cfg!(feature = "packed"),
+        policy == ZeroPolicy::SkipAsZero,
+    )
+}
+
+/// The `(row, interaction)` of the first zero reciprocal denominator, if any.
+pub(crate) fn find_zero_denominator<F, EF>(
+    interactions: &[(Interaction<F>, InteractionType)],
+    preprocessed: Option<&RowMajorMatrix<F>>,
+    main: &RowMajorMatrix<F>,
+    alphas_local: &[EF],
+    alphas_global: &[EF],
+    betas: &[EF],
+) -> Option<(usize, usize)>
+where
+    F: Field,
+    EF: ExtensionField<F>,
+{
+    let height = main.height();
+    for n in 0..height {
+        let n_next = (n + 1) % height;
+        let main_rows = [main.row_slice(n), main.row_slice(n_next)];
+        let preprocessed_rows = match preprocessed {
+            Some(preprocessed) => [preprocessed.row_slice(n), preprocessed.row_slice(n_next)],
+            None => [&[][..], &[][..]],
+        };
+        for (m, (interaction, _)) in interactions.iter().enumerate() {
+            let alpha = if interaction.is_local() {
+                alphas_local[interaction.argument_index()]
+            } else {
+                alphas_global[interaction.argument_index()]
+            };
+            let rlc = reduce_row(&main_rows, &preprocessed_rows, interaction, alpha, betas);
+            if rlc.is_zero() {
+                return Some((n, m));
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BusArgument, CountBound};
+    use alloc::vec;
+    use p3_air::VirtualPairCol;
+    use p3_field::AbstractField;
+
+    type F = p3_baby_bear::BabyBear;
+    type EF = p3_field::extension::BinomialExtensionField<F, 4>;
+
+    #[test]
+    fn zero_denominator() {
+        let interactions = vec![(
+            Interaction::<F> {
+                fields: vec![VirtualPairCol::single_main(0)],
+                count: VirtualPairCol::one(),
+                argument_index: BusArgument::Local(0),
+                rotations: vec![],
+                count_bound: CountBound::Unbounded,
+                is_real: None,
+                limb_order: None,
+            },
+            InteractionType::LocalSend,
+        )];
+        let main = RowMajorMatrix::new([1, 2, 3, 4].map(F::from_canonical_u32).to_vec(), 1);
+        let find = |alpha: EF| {
+            find_zero_denominator(&interactions, None, &main, &[alpha], &[], &[EF::one()])
+        };
+
+        // The denominator on row 2 is `3 - 3`, and no row makes `9 + f` vanish.
+        assert_eq!(find(-EF::from_canonical_u32(3)), Some((2, 0)));
+        assert_eq!(find(EF::from_canonical_u32(9)), None);
+    }
+}
@@ -17,10 +17,11 @@ use p3_matrix::dense::RowMajorMatrix;
 use p3_maybe_rayon::prelude::*;
 use valida_bus::MachineWithMemBus;
 use valida_machine::StarkConfig;
-use valida_machine::{Chip, Interaction, Machine, Word};
+use valida_machine::{Chip, CountBound, Interaction, InteractionAir, Machine, Word};
 use valida_util::batch_multiplicative_inverse_allowing_zero;
 
 pub mod columns;
+pub mod sorted;
 pub mod stark;
 
 #[derive(Copy, Clone, Debug)]
@@ -135,6 +136,55 @@ impl MemoryChip {
     }
 }
 
+impl<M, SC> InteractionAir<M, SC> for MemoryChip
+where
+    M: MachineWithMemBus<SC::Val>,
+    SC: StarkConfig,
+{
+    fn local_sends(&self) -> Vec<Interaction<SC::Val>> {
+        return vec![]; // TODO
+                       // let sends = Interaction {
+                       //     fields: vec![VirtualPairCol::single_main(MEM_COL_MAP.diff)],
+                       //     count: VirtualPairCol::one(),
+                       //     argument_index: BusArgument::Local(0),
+                       // };
+                       // vec![sends]
+    }
+
+    fn local_receives(&self) -> Vec<Interaction<SC::Val>> {
+        return vec![]; // TODO
+                       // let receives = Interaction {
+                       //     fields: vec![VirtualPairCol::single_main(MEM_COL_MAP.counter)],
+                       //     count: VirtualPairCol::single_main(MEM_COL_MAP.counter_mult),
+                       //     argument_index: BusArgument::Local(0),
+                       // };
+                       // vec![receives]
+    }
+
+    fn global_receives(&self, machine: &M) -> Vec<Interaction<SC::Val>> {
+        let is_read: VirtualPairCol<SC::Val> = VirtualPairCol::single_main(MEM_COL_MAP.is_read);
+        let clk = VirtualPairCol::single_main(MEM_COL_MAP.clk);
+        let addr = VirtualPairCol::single_main(MEM_COL_MAP.addr);
+        let is_static_initial = VirtualPairCol::single_main(MEM_COL_MAP.is_static_initial);
+        let value = MEM_COL_MAP.value.0.map(VirtualPairCol::single_main);
+
+        let mut fields = vec![is_read, clk, addr, is_static_initial];
+        fields.extend(value);
+
+        let is_real = VirtualPairCol::sum_main(vec![MEM_COL_MAP.is_read, MEM_COL_MAP.is_write]);
+        let receive = Interaction {
+            fields,
+            count: is_real,
+            argument_index: machine.mem_bus(),
+            rotations: vec![],
+            count_bound: CountBound::Unbounded,
+            is_real: None,
+            limb_order: None,
+        };
+        vec![receive]
+    }
+}
+
 impl<M, SC> Chip<M, SC> for MemoryChip
 where
     M: MachineWithMemBus<SC::Val>,
@@ -192,45 +242,6 @@ where
 
         trace
     }
-
-    fn local_sends(&self) -> Vec<Interaction<SC::Val>> {
-        return vec![]; // TODO
-                       // let sends = Interaction {
-                       //     fields: vec![VirtualPairCol::single_main(MEM_COL_MAP.diff)],
-                       //     count: VirtualPairCol::one(),
-                       //     argument_index: BusArgument::Local(0),
-                       // };
-                       // vec![sends]
-    }
-
-    fn local_receives(&self) -> Vec<Interaction<SC::Val>> {
-        return vec![]; // TODO
-                       // let receives = Interaction {
-                       //     fields: vec![VirtualPairCol::single_main(MEM_COL_MAP.counter)],
-                       //     count: VirtualPairCol::single_main(MEM_COL_MAP.counter_mult),
-                       //     argument_index: BusArgument::Local(0),
-                       // };
-                       // vec![receives]
-    }
-
-    fn global_receives(&self, machine: &M) -> Vec<Interaction<SC::Val>> {
-        let is_read: VirtualPairCol<SC::Val> = VirtualPairCol::single_main(MEM_COL_MAP.is_read);
-        let clk = VirtualPairCol::single_main(MEM_COL_MAP.clk);
-        let addr = VirtualPairCol::single_main(MEM_COL_MAP.addr);
-        let is_static_initial = VirtualPairCol::single_main(MEM_COL_MAP.is_static_initial);
-        let value = MEM_COL_MAP.value.0.map(VirtualPairCol::single_main);
-
-        let mut fields = vec![is_read, clk, addr, is_static_initial];
-        fields.extend(value);
-
-        let is_real = VirtualPairCol::sum_main(vec![MEM_COL_MAP.is_read, MEM_COL_MAP.is_write]);
-        let receive = Interaction {
-            fields,
-            count: is_real,
-            argument_index: machine.mem_bus(),
-        };
-        vec![receive]
-    }
 }
 
 impl MemoryChip {
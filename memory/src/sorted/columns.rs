@@ -0,0 +1,42 @@
+use core::borrow::{Borrow, BorrowMut};
+use core::mem::{size_of, transmute};
+use valida_derive::AlignedBorrow;
+use valida_util::indices_arr;
+
+#[derive(AlignedBorrow, Default, Debug)]
+pub struct SortedMemoryCols<T> {
+    /// Memory address
+    pub addr: T,
+
+    /// Timestamp of the access
+    pub timestamp: T,
+
+    /// Value read or written
+    pub value: T,
+
+    /// Whether the row is an access rather than padding
+    pub is_real: T,
+
+    /// Whether the access is a write
+    pub is_write: T,
+
+    /// Whether the previous row accesses the same address
+    pub same_addr: T,
+
+    /// Either timestamp - timestamp_prev - 1 (if the address is unchanged), or
+    /// addr - addr_prev - 1 (if it is changed). Range checked against `counter`.
+    pub gap: T,
+
+    /// Increment-by-one counter for local range check
+    pub counter: T,
+    /// Multiplicities for local range check
+    pub counter_mult: T,
+}
+
+pub const NUM_SORTED_MEM_COLS: usize = size_of::<SortedMemoryCols<u8>>();
+pub const SORTED_MEM_COL_MAP: SortedMemoryCols<usize> = make_col_map();
+
+const fn make_col_map() -> SortedMemoryCols<usize> {
+    let indices_arr = indices_arr::<NUM_SORTED_MEM_COLS>();
+    unsafe { transmute::<[usize; NUM_SORTED_MEM_COLS], SortedMemoryCols<usize>>(indices_arr) }
+}
@@ -0,0 +1,155 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use columns::{SortedMemoryCols, NUM_SORTED_MEM_COLS, SORTED_MEM_COL_MAP};
+use core::borrow::BorrowMut;
+use p3_air::VirtualPairCol;
+use p3_field::AbstractField;
+use p3_matrix::dense::RowMajorMatrix;
+use valida_machine::StarkConfig;
+use valida_machine::{BusArgument, Chip, CountBound, Interaction, InteractionAir, Machine};
+
+pub mod columns;
+pub mod stark;
+
+/// A read or write of one memory cell.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Access {
+    pub addr: u32,
+    pub timestamp: u32,
+    pub value: u32,
+    pub is_write: bool,
+}
+
+/// An offline memory checking argument over address-sorted accesses.
+///
+/// The chips performing memory accesses send `(addr, timestamp, value, is_write)` on `bus` in
+/// execution order, e.g. with `Interaction::send_columns`, and this chip receives the same tuples
+/// sorted by address, then timestamp. The global bus balances exactly when both sides hold the
+/// same accesses, and the sorted order lets the local constraints check consistency row by row:
+/// the first access to each address is a write, a read returns the value of the access before it,
+/// and timestamps of an address strictly increase. The address or timestamp step between adjacent
+/// rows is range checked on a local bus against a counter column, so the trace is at least as
+/// tall as the largest step.
+pub struct SortedMemoryChip {
+    pub bus: BusArgument,
+    pub accesses: Vec<Access>,
+}
+
+impl SortedMemoryChip {
+    pub fn new(bus: BusArgument) -> Self {
+        Self {
+            bus,
+            accesses: Vec::new(),
+        }
+    }
+
+    /// The accesses in trace order, each with the range checked step from the access before it.
+    fn sorted_accesses(&self) -> Vec<(Access, u32)> {
+        let mut accesses = self.accesses.clone();
+        accesses.sort_by_key(|access| (access.addr, access.timestamp));
+        let mut prev: Option<Access> = None;
+        accesses
+            .into_iter()
+            .map(|access| {
+                let gap = match prev {
+                    None => 0,
+                    Some(prev) if prev.addr == access.addr => access
+                        .timestamp
+                        .checked_sub(prev.timestamp + 1)
+                        .unwrap_or_else(|| {
+                            panic!(
+                                "two accesses to address {} at timestamp {}",
+                                access.addr, access.timestamp
+                            )
+                        }),
+                    Some(prev) => access.addr - prev.addr - 1,
+                };
+                prev = Some(access);
+                (access, gap)
+            })
+            .collect()
+    }
+}
+
+impl<M, SC> InteractionAir<M, SC> for SortedMemoryChip
+where
+    M: Machine<SC::Val>,
+    SC: StarkConfig,
+{
+    fn local_sends(&self) -> Vec<Interaction<SC::Val>> {
+        vec![Interaction::range_check(
+            BusArgument::Local(0),
+            SORTED_MEM_COL_MAP.gap,
+            VirtualPairCol::single_main(SORTED_MEM_COL_MAP.is_real),
+        )]
+    }
+
+    fn local_receives(&self) -> Vec<Interaction<SC::Val>> {
+        vec![Interaction::with_multiplicity_col(
+            vec![VirtualPairCol::single_main(SORTED_MEM_COL_MAP.counter)],
+            SORTED_MEM_COL_MAP.counter_mult,
+            BusArgument::Local(0),
+        )]
+    }
+
+    fn global_receives(&self, _machine: &M) -> Vec<Interaction<SC::Val>> {
+        let fields = [
+            SORTED_MEM_COL_MAP.addr,
+            SORTED_MEM_COL_MAP.timestamp,
+            SORTED_MEM_COL_MAP.value,
+            SORTED_MEM_COL_MAP.is_write,
+        ]
+        .map(VirtualPairCol::single_main);
+        let receive = Interaction {
+            fields: fields.into(),
+            count: VirtualPairCol::single_main(SORTED_MEM_COL_MAP.is_real),
+            argument_index: self.bus,
+            rotations: vec![],
+            count_bound: CountBound::Unbounded,
+            is_real: None,
+            limb_order: None,
+        };
+        vec![receive]
+    }
+}
+
+impl<M, SC> Chip<M, SC> for SortedMemoryChip
+where
+    M: Machine<SC::Val>,
+    SC: StarkConfig,
+{
+    fn generate_trace(&self, _machine: &M) -> RowMajorMatrix<SC::Val> {
+        let accesses = self.sorted_accesses();
+        let max_gap = accesses.iter().map(|&(_, gap)| gap as usize).max();
+        let height = accesses
+            .len()
+            .max(max_gap.map_or(1, |gap| gap + 1))
+            .next_power_of_two();
+        let mut values = vec![SC::Val::zero(); height * NUM_SORTED_MEM_COLS];
+
+        for i in 0..height {
+            let row = &mut values[i * NUM_SORTED_MEM_COLS..(i + 1) * NUM_SORTED_MEM_COLS];
+            let cols: &mut SortedMemoryCols<SC::Val> = row.borrow_mut();
+            cols.counter = SC::Val::from_canonical_usize(i);
+        }
+
+        let mut prev_addr = None;
+        for (i, &(access, gap)) in accesses.iter().enumerate() {
+            let row = &mut values[i * NUM_SORTED_MEM_COLS..(i + 1) * NUM_SORTED_MEM_COLS];
+            let cols: &mut SortedMemoryCols<SC::Val> = row.borrow_mut();
+            cols.addr = SC::Val::from_canonical_u32(access.addr);
+            cols.timestamp = SC::Val::from_canonical_u32(access.timestamp);
+            cols.value = SC::Val::from_canonical_u32(access.value);
+            cols.is_real = SC::Val::one();
+            cols.is_write = SC::Val::from_bool(access.is_write);
+            cols.same_addr = SC::Val::from_bool(prev_addr == Some(access.addr));
+            cols.gap = SC::Val::from_canonical_u32(gap);
+            prev_addr = Some(access.addr);
+
+            values[gap as usize * NUM_SORTED_MEM_COLS + SORTED_MEM_COL_MAP.counter_mult] +=
+                SC::Val::one();
+        }
+
+        RowMajorMatrix::new(values, NUM_SORTED_MEM_COLS)
+    }
+}
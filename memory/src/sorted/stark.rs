@@ -0,0 +1,75 @@
+use super::columns::{SortedMemoryCols, NUM_SORTED_MEM_COLS};
+use super::SortedMemoryChip;
+use core::borrow::Borrow;
+
+use p3_air::{Air, AirBuilder, BaseAir};
+use p3_field::AbstractField;
+use p3_matrix::MatrixRowSlices;
+
+impl<F> BaseAir<F> for SortedMemoryChip {
+    fn width(&self) -> usize {
+        NUM_SORTED_MEM_COLS
+    }
+}
+
+impl<AB> Air<AB> for SortedMemoryChip
+where
+    AB: AirBuilder,
+{
+    fn eval(&self, builder: &mut AB) {
+        let main = builder.main();
+        let local: &SortedMemoryCols<AB::Var> = main.row_slice(0).borrow();
+        let next: &SortedMemoryCols<AB::Var> = main.row_slice(1).borrow();
+
+        // Flags should be boolean, and only set on real rows.
+        builder.assert_bool(local.is_real);
+        builder.assert_bool(local.is_write);
+        builder.assert_bool(local.same_addr);
+        builder.when(local.is_write).assert_one(local.is_real);
+        builder.when(local.same_addr).assert_one(local.is_real);
+
+        // Real rows come first.
+        builder
+            .when_transition()
+            .when(AB::Expr::one() - local.is_real)
+            .assert_zero(next.is_real);
+
+        // The first access to each address is a write.
+        builder.when_first_row().assert_zero(local.same_addr);
+        builder
+            .when_first_row()
+            .when(local.is_real)
+            .assert_one(local.is_write);
+        builder
+            .when_transition()
+            .when(next.is_real - next.same_addr)
+            .assert_one(next.is_write);
+
+        // same_addr is set only if the address is unchanged, and then the timestamp increases;
+        // otherwise the address increases. The step less one is range checked.
+        builder
+            .when_transition()
+            .when(next.same_addr)
+            .assert_eq(next.addr, local.addr);
+        let addr_step = next.addr - local.addr;
+        let step =
+            addr_step.clone() + next.same_addr * (next.timestamp - local.timestamp - addr_step);
+        builder
+            .when_transition()
+            .when(next.is_real)
+            .assert_eq(next.gap + AB::Expr::one(), step);
+
+        // A read returns the value of the previous access to its address.
+        builder
+            .when_transition()
+            .when(next.same_addr)
+            .when(AB::Expr::one() - next.is_write)
+            .assert_eq(next.value, local.value);
+
+        // Counter increments from zero.
+        builder.when_first_row().assert_zero(local.counter);
+        builder
+            .when_transition()
+            .assert_eq(next.counter, local.counter + AB::Expr::one());
+    }
+}
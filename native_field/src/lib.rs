@@ -8,7 +8,9 @@ use columns::{NativeFieldCols, COL_MAP, NUM_NATIVE_FIELD_COLS};
 use core::mem::transmute;
 use valida_bus::{MachineWithGeneralBus, MachineWithRangeBus8};
 use valida_cpu::MachineWithCpuChip;
-use valida_machine::{instructions, Chip, Instruction, Interaction, Operands, Word};
+use valida_machine::{
+    instructions, Chip, CountBound, Instruction, Interaction, InteractionAir, Operands, Word,
+};
 use valida_opcodes::{ADD, MUL, SUB};
 use valida_range::MachineWithRangeChip;
 use valida_util::pad_to_power_of_two;
@@ -33,42 +35,19 @@ pub struct NativeFieldChip {
     operations: Vec<Operation>,
 }
 
-impl<M, SC> Chip<M, SC> for NativeFieldChip
+impl<M, SC> InteractionAir<M, SC> for NativeFieldChip
 where
     M: MachineWithGeneralBus<SC::Val> + MachineWithRangeBus8<SC::Val>,
     SC: StarkConfig,
 {
-    fn generate_trace(&self, _machine: &M) -> RowMajorMatrix<SC::Val> {
-        let rows = self
-            .operations
-            .par_iter()
-            .map(|op| self.op_to_row(op))
-            .collect::<Vec<_>>();
-
-        let mut trace = RowMajorMatrix::new(
-            rows.into_iter().flatten().collect::<Vec<_>>(),
-            NUM_NATIVE_FIELD_COLS,
-        );
-
-        pad_to_power_of_two::<NUM_NATIVE_FIELD_COLS, SC::Val>(&mut trace.values);
-
-        trace
-    }
-
     fn global_sends(&self, machine: &M) -> Vec<Interaction<SC::Val>> {
         let sends = COL_MAP
             .output
             .0
             .map(|field| {
-                let output = VirtualPairCol::single_main(field);
                 let is_real =
                     VirtualPairCol::sum_main(vec![COL_MAP.is_add, COL_MAP.is_sub, COL_MAP.is_mul]);
-
-                Interaction {
-                    fields: vec![output],
-                    count: is_real,
-                    argument_index: machine.range_bus(),
-                }
+                Interaction::range_check(machine.range_bus(), field, is_real)
             })
             .into_iter()
             .collect::<Vec<_>>();
@@ -100,11 +79,38 @@ where
             fields,
             count: is_real,
             argument_index: machine.general_bus(),
+            rotations: vec![],
+            count_bound: CountBound::Unbounded,
+            is_real: None,
+            limb_order: None,
         };
         vec![receive]
     }
 }
 
+impl<M, SC> Chip<M, SC> for NativeFieldChip
+where
+    M: MachineWithGeneralBus<SC::Val> + MachineWithRangeBus8<SC::Val>,
+    SC: StarkConfig,
+{
+    fn generate_trace(&self, _machine: &M) -> RowMajorMatrix<SC::Val> {
+        let rows = self
+            .operations
+            .par_iter()
+            .map(|op| self.op_to_row(op))
+            .collect::<Vec<_>>();
+
+        let mut trace = RowMajorMatrix::new(
+            rows.into_iter().flatten().collect::<Vec<_>>(),
+            NUM_NATIVE_FIELD_COLS,
+        );
+
+        pad_to_power_of_two::<NUM_NATIVE_FIELD_COLS, SC::Val>(&mut trace.values);
+
+        trace
+    }
+}
+
 impl NativeFieldChip {
     fn op_to_row<F>(&self, op: &Operation) -> [F; NUM_NATIVE_FIELD_COLS]
     where
@@ -4,7 +4,8 @@ use core::mem::transmute;
 use valida_bus::MachineWithGeneralBus;
 use valida_cpu::MachineWithCpuChip;
 use valida_machine::{
-    instructions, Chip, Instruction, Interaction, Operands, CPU_MEMORY_CHANNELS, MEMORY_CELL_BYTES,
+    instructions, Chip, CountBound, Instruction, Interaction, InteractionAir, Operands,
+    CPU_MEMORY_CHANNELS, MEMORY_CELL_BYTES,
 };
 use valida_opcodes::WRITE;
 
@@ -29,6 +30,55 @@ impl OutputChip {
     }
 }
 
+impl<M, SC> InteractionAir<M, SC> for OutputChip
+where
+    M: MachineWithGeneralBus<SC::Val>,
+    SC: StarkConfig,
+{
+    //fn local_sends(&self) -> Vec<Interaction<SC::Val>> {
+    //    let sends = Interaction {
+    //        fields: vec![VirtualPairCol::single_main(OUTPUT_COL_MAP.diff)],
+    //        count: VirtualPairCol::one(),
+    //        argument_index: BusArgument::Local(0),
+    //    };
+    //    vec![sends]
+    //}
+
+    //fn local_receives(&self) -> Vec<Interaction<SC::Val>> {
+    //    let receives = Interaction {
+    //        fields: vec![VirtualPairCol::single_main(OUTPUT_COL_MAP.counter)],
+    //        count: VirtualPairCol::single_main(OUTPUT_COL_MAP.counter_mult),
+    //        argument_index: BusArgument::Local(0),
+    //    };
+    //    vec![receives]
+    //}
+
+    fn global_receives(&self, machine: &M) -> Vec<Interaction<SC::Val>> {
+        let opcode = VirtualPairCol::single_main(OUTPUT_COL_MAP.opcode);
+        let clk = VirtualPairCol::single_main(OUTPUT_COL_MAP.clk);
+
+        let mut values = (0..CPU_MEMORY_CHANNELS * MEMORY_CELL_BYTES)
+            .map(|_| VirtualPairCol::constant(SC::Val::zero()))
+            .collect::<Vec<_>>();
+        values[MEMORY_CELL_BYTES - 1] = VirtualPairCol::single_main(OUTPUT_COL_MAP.value);
+
+        let mut fields = vec![opcode];
+        fields.extend(values);
+        fields.push(clk);
+
+        let receive = Interaction {
+            fields,
+            count: VirtualPairCol::single_main(OUTPUT_COL_MAP.is_real),
+            argument_index: machine.general_bus(),
+            rotations: vec![],
+            count_bound: CountBound::Unbounded,
+            is_real: None,
+            limb_order: None,
+        };
+        vec![receive]
+    }
+}
+
 impl<M, SC> Chip<M, SC> for OutputChip
 where
     M: MachineWithGeneralBus<SC::Val>,
@@ -95,45 +145,6 @@ where
         pad_to_power_of_two::<NUM_OUTPUT_COLS, SC::Val>(&mut values);
         RowMajorMatrix::new(values, NUM_OUTPUT_COLS)
     }
-
-    //fn local_sends(&self) -> Vec<Interaction<SC::Val>> {
-    //    let sends = Interaction {
-    //        fields: vec![VirtualPairCol::single_main(OUTPUT_COL_MAP.diff)],
-    //        count: VirtualPairCol::one(),
-    //        argument_index: BusArgument::Local(0),
-    //    };
-    //    vec![sends]
-    //}
-
-    //fn local_receives(&self) -> Vec<Interaction<SC::Val>> {
-    //    let receives = Interaction {
-    //        fields: vec![VirtualPairCol::single_main(OUTPUT_COL_MAP.counter)],
-    //        count: VirtualPairCol::single_main(OUTPUT_COL_MAP.counter_mult),
-    //        argument_index: BusArgument::Local(0),
-    //    };
-    //    vec![receives]
-    //}
-
-    fn global_receives(&self, machine: &M) -> Vec<Interaction<SC::Val>> {
-        let opcode = VirtualPairCol::single_main(OUTPUT_COL_MAP.opcode);
-        let clk = VirtualPairCol::single_main(OUTPUT_COL_MAP.clk);
-
-        let mut values = (0..CPU_MEMORY_CHANNELS * MEMORY_CELL_BYTES)
-            .map(|_| VirtualPairCol::constant(SC::Val::zero()))
-            .collect::<Vec<_>>();
-        values[MEMORY_CELL_BYTES - 1] = VirtualPairCol::single_main(OUTPUT_COL_MAP.value);
-
-        let mut fields = vec![opcode];
-        fields.extend(values);
-        fields.push(clk);
-
-        let receive = Interaction {
-            fields,
-            count: VirtualPairCol::single_main(OUTPUT_COL_MAP.is_real),
-            argument_index: machine.general_bus(),
-        };
-        vec![receive]
-    }
 }
 
 pub trait MachineWithOutputChip<F: Field>: MachineWithCpuChip<F> {
@@ -6,7 +6,7 @@ use crate::columns::NUM_PROGRAM_COLS;
 use alloc::vec;
 use alloc::vec::Vec;
 use valida_bus::MachineWithProgramBus;
-use valida_machine::{Chip, Interaction, Machine, ProgramROM};
+use valida_machine::{Chip, Interaction, InteractionAir, Machine, ProgramROM};
 use valida_util::pad_to_power_of_two;
 
 use p3_field::{AbstractField, Field};
@@ -30,23 +30,11 @@ impl ProgramChip {
     }
 }
 
-impl<M, SC> Chip<M, SC> for ProgramChip
+impl<M, SC> InteractionAir<M, SC> for ProgramChip
 where
     M: MachineWithProgramBus<SC::Val>,
     SC: StarkConfig,
 {
-    fn generate_trace(&self, _machine: &M) -> RowMajorMatrix<SC::Val> {
-        let mut values = self
-            .counts
-            .iter()
-            .map(|c| SC::Val::from_canonical_u32(*c))
-            .collect();
-
-        pad_to_power_of_two::<NUM_PROGRAM_COLS, SC::Val>(&mut values);
-
-        RowMajorMatrix::new(values, NUM_PROGRAM_COLS)
-    }
-
     fn global_receives(&self, _machine: &M) -> Vec<Interaction<SC::Val>> {
         // let pc = VirtualPairCol::single_preprocessed(PREPROCESSED_COL_MAP.pc);
         // let opcode = VirtualPairCol::single_preprocessed(PREPROCESSED_COL_MAP.opcode);
@@ -68,6 +56,24 @@ where
     }
 }
 
+impl<M, SC> Chip<M, SC> for ProgramChip
+where
+    M: MachineWithProgramBus<SC::Val>,
+    SC: StarkConfig,
+{
+    fn generate_trace(&self, _machine: &M) -> RowMajorMatrix<SC::Val> {
+        let mut values = self
+            .counts
+            .iter()
+            .map(|c| SC::Val::from_canonical_u32(*c))
+            .collect();
+
+        pad_to_power_of_two::<NUM_PROGRAM_COLS, SC::Val>(&mut values);
+
+        RowMajorMatrix::new(values, NUM_PROGRAM_COLS)
+    }
+}
+
 pub trait MachineWithProgramChip<F: Field>: Machine<F> {
     fn program(&self) -> &ProgramChip;
 
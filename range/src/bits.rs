@@ -0,0 +1,102 @@
+use alloc::collections::BTreeMap;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use p3_air::{Air, AirBuilder, BaseAir, VirtualPairCol};
+use p3_field::{AbstractField, Field, PrimeField32};
+use p3_matrix::dense::RowMajorMatrix;
+use valida_machine::{
+    global_send_multiplicities, BusArgument, Chip, Interaction, InteractionAir, Machine,
+    StarkConfig,
+};
+
+/// A range check table of all values in `0..2^BITS`, held in a preprocessed column, which
+/// receives every lookup on its global bus. Chips register a value with `range_send`, and
+/// `record_sends` fills in the multiplicity column from the sends of all chips. A value outside
+/// the range matches no row of the table, so its send leaves the bus unbalanced.
+pub struct RangeCheckChip<const BITS: usize> {
+    pub bus: BusArgument,
+    /// The number of lookups of each value.
+    pub count: BTreeMap<u32, u32>,
+}
+
+/// A send of column `col` to the range check table on `bus`, on every row.
+pub fn range_send<F: Field>(bus: BusArgument, col: usize) -> Interaction<F> {
+    Interaction::range_check(bus, col, VirtualPairCol::one())
+}
+
+impl<const BITS: usize> RangeCheckChip<BITS> {
+    pub fn new(bus: usize) -> Self {
+        Self {
+            bus: BusArgument::Global(bus),
+            count: BTreeMap::new(),
+        }
+    }
+
+    /// Set the multiplicity of every value to the number of times `chips` send it on this chip's
+    /// bus. Sends of values outside the range are not recorded.
+    pub fn record_sends<M, SC>(&mut self, machine: &M, chips: &[&dyn Chip<M, SC>])
+    where
+        M: Machine<SC::Val>,
+        SC: StarkConfig,
+    {
+        let BusArgument::Global(bus) = self.bus else {
+            panic!("a range check table must be on a global bus");
+        };
+        self.count = global_send_multiplicities(machine, chips, bus)
+            .into_iter()
+            .filter_map(|(fields, count)| match fields[..] {
+                [value] if (value as usize) < 1 << BITS => Some((value, count.as_canonical_u32())),
+                _ => None,
+            })
+            .collect();
+    }
+}
+
+impl<F: Field, const BITS: usize> BaseAir<F> for RangeCheckChip<BITS> {
+    fn width(&self) -> usize {
+        1
+    }
+
+    fn preprocessed_trace(&self) -> Option<RowMajorMatrix<F>> {
+        Some(RowMajorMatrix::new_col(
+            (0..1 << BITS).map(F::from_canonical_usize).collect(),
+        ))
+    }
+}
+
+impl<AB: AirBuilder, const BITS: usize> Air<AB> for RangeCheckChip<BITS> {
+    fn eval(&self, _builder: &mut AB) {}
+}
+
+impl<M, SC, const BITS: usize> InteractionAir<M, SC> for RangeCheckChip<BITS>
+where
+    M: Machine<SC::Val>,
+    SC: StarkConfig,
+{
+    fn global_receives(&self, _machine: &M) -> Vec<Interaction<SC::Val>> {
+        vec![Interaction::with_multiplicity_col(
+            vec![VirtualPairCol::single_preprocessed(0)],
+            0,
+            self.bus,
+        )]
+    }
+}
+
+impl<M, SC, const BITS: usize> Chip<M, SC> for RangeCheckChip<BITS>
+where
+    M: Machine<SC::Val>,
+    SC: StarkConfig,
+{
+    fn generate_trace(&self, _machine: &M) -> RowMajorMatrix<SC::Val> {
+        let mut mult = vec![SC::Val::zero(); 1 << BITS];
+        for (&value, &count) in &self.count {
+            mult[value as usize] = SC::Val::from_canonical_u32(count);
+        }
+        RowMajorMatrix::new_col(mult)
+    }
+
+    fn provides_once(&self) -> bool {
+        true
+    }
+}
@@ -9,13 +9,14 @@ use columns::{RangeCols, NUM_RANGE_COLS, RANGE_COL_MAP};
 use core::mem::transmute;
 use valida_bus::MachineWithRangeBus8;
 use valida_machine::Interaction;
-use valida_machine::{Chip, Machine, Word};
+use valida_machine::{Chip, InteractionAir, Machine, Word};
 
 use p3_air::VirtualPairCol;
 use p3_field::{AbstractField, Field};
 use p3_matrix::dense::RowMajorMatrix;
 use valida_machine::StarkConfig;
 
+pub mod bits;
 pub mod columns;
 pub mod stark;
 
@@ -24,6 +25,23 @@ pub struct RangeCheckerChip<const MAX: u32> {
     pub count: BTreeMap<u32, u32>,
 }
 
+impl<M, SC, const MAX: u32> InteractionAir<M, SC> for RangeCheckerChip<MAX>
+where
+    M: MachineWithRangeBus8<SC::Val>,
+    SC: StarkConfig,
+{
+    fn global_receives(&self, machine: &M) -> Vec<Interaction<SC::Val>> {
+        let input = VirtualPairCol::single_main(RANGE_COL_MAP.counter);
+
+        let receive = Interaction::with_multiplicity_col(
+            vec![input],
+            RANGE_COL_MAP.mult,
+            machine.range_bus(),
+        );
+        vec![receive]
+    }
+}
+
 impl<M, SC, const MAX: u32> Chip<M, SC> for RangeCheckerChip<MAX>
 where
     M: MachineWithRangeBus8<SC::Val>,
@@ -43,15 +61,8 @@ where
         RowMajorMatrix::new(rows.concat(), NUM_RANGE_COLS)
     }
 
-    fn global_receives(&self, machine: &M) -> Vec<Interaction<SC::Val>> {
-        let input = VirtualPairCol::single_main(RANGE_COL_MAP.counter);
-
-        let receive = Interaction {
-            fields: vec![input],
-            count: VirtualPairCol::single_main(RANGE_COL_MAP.mult),
-            argument_index: machine.range_bus(),
-        };
-        vec![receive]
+    fn provides_once(&self) -> bool {
+        true
     }
 }
 
@@ -11,7 +11,7 @@ use p3_air::VirtualPairCol;
 use p3_field::{AbstractField, Field};
 use p3_matrix::dense::RowMajorMatrix;
 use valida_bus::MachineWithMemBus;
-use valida_machine::{Chip, Interaction, StarkConfig, Word};
+use valida_machine::{Chip, CountBound, Interaction, InteractionAir, StarkConfig, Word};
 use valida_memory::MachineWithMemoryChip;
 
 pub mod columns;
@@ -52,6 +52,33 @@ impl StaticDataChip {
     }
 }
 
+impl<M, SC> InteractionAir<M, SC> for StaticDataChip
+where
+    M: MachineWithMemBus<SC::Val>,
+    SC: StarkConfig,
+{
+    fn global_sends(&self, machine: &M) -> Vec<Interaction<SC::Val>> {
+        let addr = VirtualPairCol::single_main(STATIC_DATA_COL_MAP.addr);
+        let value = STATIC_DATA_COL_MAP.value.0.map(VirtualPairCol::single_main);
+        let is_read = VirtualPairCol::constant(SC::Val::zero());
+        let is_real = VirtualPairCol::single_main(STATIC_DATA_COL_MAP.is_real);
+        let is_static_initial = VirtualPairCol::constant(SC::Val::one());
+        let clk = VirtualPairCol::constant(SC::Val::zero());
+        let mut fields = vec![is_read, clk, addr, is_static_initial];
+        fields.extend(value);
+        let send = Interaction {
+            fields,
+            count: is_real,
+            argument_index: machine.mem_bus(),
+            rotations: vec![],
+            count_bound: CountBound::Unbounded,
+            is_real: None,
+            limb_order: None,
+        };
+        vec![send]
+    }
+}
+
 impl<M, SC> Chip<M, SC> for StaticDataChip
 where
     M: MachineWithMemBus<SC::Val>,
@@ -77,21 +104,4 @@ where
         );
         RowMajorMatrix::new(rows, NUM_STATIC_DATA_COLS)
     }
-
-    fn global_sends(&self, machine: &M) -> Vec<Interaction<SC::Val>> {
-        let addr = VirtualPairCol::single_main(STATIC_DATA_COL_MAP.addr);
-        let value = STATIC_DATA_COL_MAP.value.0.map(VirtualPairCol::single_main);
-        let is_read = VirtualPairCol::constant(SC::Val::zero());
-        let is_real = VirtualPairCol::single_main(STATIC_DATA_COL_MAP.is_real);
-        let is_static_initial = VirtualPairCol::constant(SC::Val::one());
-        let clk = VirtualPairCol::constant(SC::Val::zero());
-        let mut fields = vec![is_read, clk, addr, is_static_initial];
-        fields.extend(value);
-        let send = Interaction {
-            fields,
-            count: is_real,
-            argument_index: machine.mem_bus(),
-        };
-        vec![send]
-    }
 }
@@ -4,6 +4,7 @@ extern crate alloc;
 
 use alloc::vec::Vec;
 use p3_field::Field;
+use p3_maybe_rayon::prelude::*;
 
 /// Returns `[0, ..., N - 1]`.
 pub const fn indices_arr<const N: usize>() -> [usize; N] {
@@ -18,32 +19,194 @@ pub const fn indices_arr<const N: usize>() -> [usize; N] {
 
 /// Calculates and returns the multiplicative inverses of each field element, with zero
 /// values remaining unchanged.
+///
+/// A zero can arise legitimately, e.g. when a reduced interaction row cancels, so zeros are
+/// skipped rather than poisoning the running product: every nonzero value is inverted with a
+/// single field inversion of the product of the nonzero values, and each zero maps to zero. The
+/// result is the same as inverting each nonzero element on its own.
+///
+/// With the `parallel` feature, large inputs are inverted by `batch_multiplicative_inverse_chunked`.
 pub fn batch_multiplicative_inverse_allowing_zero<F: Field>(values: Vec<F>) -> Vec<F> {
-    // Check if values are zero, and construct a new vector with only nonzero values
-    let mut nonzero_values = Vec::with_capacity(values.len());
-    let mut indices = Vec::with_capacity(values.len());
-    for (i, value) in values.iter().cloned().enumerate() {
-        if value.is_zero() {
-            continue;
-        }
-        nonzero_values.push(value);
-        indices.push(i);
+    if cfg!(feature = "parallel") && values.len() > BATCH_INVERSE_CHUNK_SIZE {
+        return batch_multiplicative_inverse_chunked(values, BATCH_INVERSE_CHUNK_SIZE);
     }
+    let (prefix_products, product) = prefix_products_of_nonzero(&values);
+    let mut result = values;
+    invert_from_products(&mut result, prefix_products, product.inverse());
+    result
+}
 
-    // Compute the multiplicative inverse of nonzero values
-    let inverse_nonzero_values = p3_field::batch_multiplicative_inverse(&nonzero_values);
+/// The chunk size `batch_multiplicative_inverse_allowing_zero` splits large inputs into with the
+/// `parallel` feature.
+const BATCH_INVERSE_CHUNK_SIZE: usize = 1 << 12;
 
-    // Reconstruct the original vector
-    let mut result = values.clone();
-    for (i, index) in indices.into_iter().enumerate() {
-        result[index] = inverse_nonzero_values[i];
-    }
+/// Like `batch_multiplicative_inverse_allowing_zero`, but splits `values` into chunks of
+/// `chunk_size`, which are processed in parallel with the `parallel` feature. Each chunk takes its
+/// own prefix products; the chunk products are then inverted together, so there is still a single
+/// field inversion, and each chunk walks back from the inverse of its own product. The result is
+/// the same for every chunk size.
+pub fn batch_multiplicative_inverse_chunked<F: Field>(values: Vec<F>, chunk_size: usize) -> Vec<F> {
+    assert!(chunk_size > 0, "chunk size must be positive");
+    let (prefix_products, chunk_products): (Vec<_>, Vec<_>) = values
+        .par_chunks(chunk_size)
+        .map(prefix_products_of_nonzero)
+        .unzip();
+
+    // Each chunk product is a product of nonzero values, so none is zero.
+    let (chunk_prefix_products, product) = prefix_products_of_nonzero(&chunk_products);
+    let mut chunk_inverses = chunk_products;
+    invert_from_products(
+        &mut chunk_inverses,
+        chunk_prefix_products,
+        product.inverse(),
+    );
 
+    let mut result = values;
+    result
+        .par_chunks_mut(chunk_size)
+        .zip(prefix_products)
+        .zip(chunk_inverses)
+        .for_each(|((chunk, prefix_products), inverse)| {
+            invert_from_products(chunk, prefix_products, inverse)
+        });
     result
 }
 
+/// The product of the nonzero values before each position, and the product of all of them.
+fn prefix_products_of_nonzero<F: Field>(values: &[F]) -> (Vec<F>, F) {
+    let mut prefix_products = Vec::with_capacity(values.len());
+    let mut product = F::one();
+    for &value in values {
+        prefix_products.push(product);
+        if !value.is_zero() {
+            product *= value;
+        }
+    }
+    (prefix_products, product)
+}
+
+/// Replace each nonzero value by its inverse, given the prefix products of
+/// `prefix_products_of_nonzero` and the inverse of the full product.
+fn invert_from_products<F: Field>(values: &mut [F], prefix_products: Vec<F>, mut inverse: F) {
+    // Walk back from the inverse of the full product, peeling off one nonzero value at a time.
+    for (value, prefix_product) in values.iter_mut().zip(prefix_products).rev() {
+        if value.is_zero() {
+            continue;
+        }
+        let next_inverse = inverse * *value;
+        *value = inverse * prefix_product;
+        inverse = next_inverse;
+    }
+}
+
+/// Like `batch_multiplicative_inverse_allowing_zero`, but inverts several groups of values with a
+/// single field inversion across all of them, returning the inverses grouped as in the input.
+pub fn batch_multiplicative_inverse_many<F: Field>(groups: Vec<Vec<F>>) -> Vec<Vec<F>> {
+    let lengths = groups.iter().map(|group| group.len()).collect::<Vec<_>>();
+    let values = groups.into_iter().flatten().collect::<Vec<_>>();
+    let mut inverses = batch_multiplicative_inverse_allowing_zero(values).into_iter();
+    lengths
+        .into_iter()
+        .map(|len| inverses.by_ref().take(len).collect())
+        .collect()
+}
+
 pub fn pad_to_power_of_two<const N: usize, T: Clone + Default>(values: &mut Vec<T>) {
+    pad_to_min_height::<N, T>(values, 1);
+}
+
+/// Like `pad_to_power_of_two`, but pads to at least `min_height` rows before rounding up, for
+/// chips declaring a `Chip::min_height`.
+pub fn pad_to_min_height<const N: usize, T: Clone + Default>(
+    values: &mut Vec<T>,
+    min_height: usize,
+) {
     debug_assert!(values.len() % N == 0);
     let n_real_rows = values.len() / N;
-    values.resize(n_real_rows.next_power_of_two() * N, T::default());
+    let height = n_real_rows.max(min_height).next_power_of_two();
+    values.resize(height * N, T::default());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+    use p3_field::AbstractField;
+    use rand::Rng;
+
+    type F = p3_baby_bear::BabyBear;
+
+    fn naive_inverse(values: &[F]) -> Vec<F> {
+        values
+            .iter()
+            .map(|x| x.try_inverse().unwrap_or(F::zero()))
+            .collect()
+    }
+
+    #[test]
+    fn batch_inverse_with_zeros() {
+        let cases = [
+            vec![0, 3, 0, 0, 7, 1, 0, 12],
+            vec![0, 0, 0],
+            vec![2, 5, 9, 11],
+            vec![],
+        ];
+        for case in cases {
+            let values = case
+                .into_iter()
+                .map(F::from_canonical_u32)
+                .collect::<Vec<_>>();
+            assert_eq!(
+                batch_multiplicative_inverse_allowing_zero(values.clone()),
+                naive_inverse(&values)
+            );
+        }
+    }
+
+    #[test]
+    fn chunked_batch_inverse_matches_naive() {
+        let mut rng = rand::thread_rng();
+        for chunk_size in [1, 7, 1 << 12, 1 << 20] {
+            // Roughly one in ten values is zero, including whole runs in the small chunks.
+            let values = (0..100_000)
+                .map(|_| {
+                    if rng.gen_bool(0.1) {
+                        F::zero()
+                    } else {
+                        rng.gen()
+                    }
+                })
+                .collect::<Vec<F>>();
+            let expected = naive_inverse(&values);
+            assert_eq!(
+                batch_multiplicative_inverse_chunked(values.clone(), chunk_size),
+                expected
+            );
+            assert_eq!(batch_multiplicative_inverse_allowing_zero(values), expected);
+        }
+    }
+
+    #[test]
+    fn grouped_batch_inverse_matches_per_group() {
+        let groups = [vec![4, 0, 9], vec![], vec![0, 0, 17, 2, 5]]
+            .map(|group| {
+                group
+                    .into_iter()
+                    .map(F::from_canonical_u32)
+                    .collect::<Vec<_>>()
+            })
+            .to_vec();
+        let per_group = groups
+            .iter()
+            .map(|group| batch_multiplicative_inverse_allowing_zero(group.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(batch_multiplicative_inverse_many(groups), per_group);
+    }
+
+    #[test]
+    fn pad_single_row_to_min_height() {
+        let mut values = vec![1u32, 2, 3];
+        pad_to_min_height::<3, u32>(&mut values, 2);
+        assert_eq!(values, vec![1, 2, 3, 0, 0, 0]);
+    }
 }